@@ -11,12 +11,10 @@ use std::time::Duration;
 use graph::prelude::{LinkResolver as LinkResolverTrait, *};
 use serde_json::Value;
 
-// Environment variable for limiting the `ipfs.map` file size limit.
+// Environment variable for limiting the `ipfs.map` file size limit. Since
+// `ipfs.map` streams the file, its size is not limited by default.
 const MAX_IPFS_MAP_FILE_SIZE_VAR: &'static str = "GRAPH_MAX_IPFS_MAP_FILE_SIZE";
 
-// The default file size limit for `ipfs.map` is 256MiB.
-const DEFAULT_MAX_IPFS_MAP_FILE_SIZE: u64 = 256 * 1024 * 1024;
-
 // Environment variable for limiting the `ipfs.cat` file size limit.
 const MAX_IPFS_FILE_SIZE_VAR: &'static str = "GRAPH_MAX_IPFS_FILE_BYTES";
 
@@ -188,75 +186,113 @@ impl LinkResolverTrait for LinkResolver {
     ) -> Box<dyn Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
         // Discard the `/ipfs/` prefix (if present) to get the hash.
         let path = link.link.trim_start_matches("/ipfs/").to_owned();
-        let mut stream = self.client.cat(&path).fuse();
-        let mut buf = BytesMut::with_capacity(1024);
-        // Count the number of lines we've already successfully deserialized.
-        // We need that to adjust the line number in error messages from serde_json
-        // to translate from line numbers in the snippet we are deserializing
-        // to the line number in the overall file
-        let mut count = 0;
-
-        let stream: JsonValueStream = Box::new(poll_fn(
-            move || -> Poll<Option<JsonStreamValue>, failure::Error> {
-                loop {
-                    if let Some(offset) = buf.iter().position(|b| *b == b'\n') {
-                        let line_bytes = buf.split_to(offset + 1);
-                        count += 1;
-                        if line_bytes.len() > 1 {
-                            let line = std::str::from_utf8(&line_bytes)?;
-                            let res = match serde_json::from_str::<Value>(line) {
-                                Ok(v) => Ok(Async::Ready(Some(JsonStreamValue {
-                                    value: v,
-                                    line: count,
-                                }))),
-                                Err(e) => {
-                                    // Adjust the line number in the serde error. This
-                                    // is fun because we can only get at the full error
-                                    // message, and not the error message without line number
-                                    let msg = e.to_string();
-                                    let msg = msg.split(" at line ").next().unwrap();
-                                    Err(format_err!(
-                                        "{} at line {} column {}: '{}'",
-                                        msg,
-                                        e.line() + count - 1,
-                                        e.column(),
-                                        line
-                                    ))
-                                }
-                            };
-                            return res;
-                        }
-                    } else {
-                        // We only get here if there is no complete line in buf, and
-                        // it is therefore ok to immediately pass an Async::NotReady
-                        // from stream through.
-                        // If we get a None from poll, but still have something in buf,
-                        // that means the input was not terminated with a newline. We
-                        // add that so that the last line gets picked up in the next
-                        // run through the loop.
-                        match try_ready!(stream.poll()) {
-                            Some(b) => buf.extend_from_slice(&b),
-                            None if buf.len() > 0 => buf.extend_from_slice(&[b'\n']),
-                            None => return Ok(Async::Ready(None)),
-                        }
+
+        let stream: JsonValueStream = Box::new(line_bytes_stream(&self.client, &path).and_then(
+            |(count, line_bytes)| {
+                let line = std::str::from_utf8(&line_bytes)?;
+                match serde_json::from_str::<Value>(line) {
+                    Ok(v) => Ok(JsonStreamValue {
+                        value: v,
+                        line: count,
+                    }),
+                    Err(e) => {
+                        // Adjust the line number in the serde error. This
+                        // is fun because we can only get at the full error
+                        // message, and not the error message without line number
+                        let msg = e.to_string();
+                        let msg = msg.split(" at line ").next().unwrap();
+                        Err(format_err!(
+                            "{} at line {} column {}: '{}'",
+                            msg,
+                            e.line() + count - 1,
+                            e.column(),
+                            line
+                        ))
                     }
                 }
             },
         ));
 
-        let max_file_size =
-            read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).unwrap_or(DEFAULT_MAX_IPFS_MAP_FILE_SIZE);
+        restrict_file_size(
+            &self.client,
+            path,
+            self.timeout,
+            read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR),
+            Box::new(future::ok(stream)),
+        )
+    }
+
+    fn line_stream(
+        &self,
+        link: &Link,
+    ) -> Box<dyn Future<Item = LineValueStream, Error = failure::Error> + Send + 'static> {
+        // Discard the `/ipfs/` prefix (if present) to get the hash.
+        let path = link.link.trim_start_matches("/ipfs/").to_owned();
+
+        let stream: LineValueStream = Box::new(
+            line_bytes_stream(&self.client, &path)
+                .and_then(|(count, line_bytes)| {
+                    let line = std::str::from_utf8(&line_bytes)
+                        .map_err(|e| format_err!("{} at line {}", e, count))?;
+                    Ok(LineStreamValue {
+                        value: line.trim_end_matches(|c| c == '\n' || c == '\r').to_owned(),
+                        line: count,
+                    })
+                })
+                // Lines that only consist of a `\r\n` are empty, too
+                .filter(|line| !line.value.is_empty()),
+        );
 
         restrict_file_size(
             &self.client,
             path,
             self.timeout,
-            Some(max_file_size),
+            read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR),
             Box::new(future::ok(stream)),
         )
     }
 }
 
+/// Split the contents of the IPFS file at `path` into lines as they are
+/// downloaded, without holding more than the current line in memory. The
+/// stream produces the line number and the bytes of each line, including
+/// the terminating newline, and skips empty lines.
+fn line_bytes_stream(
+    client: &ipfs_api::IpfsClient,
+    path: &str,
+) -> impl Stream<Item = (usize, BytesMut), Error = failure::Error> + Send + 'static {
+    let mut stream = client.cat(path).fuse();
+    let mut buf = BytesMut::with_capacity(1024);
+    // Count the number of lines we've already read. We need that to
+    // report line numbers in the overall file
+    let mut count = 0;
+
+    poll_fn(move || -> Poll<Option<(usize, BytesMut)>, failure::Error> {
+        loop {
+            if let Some(offset) = buf.iter().position(|b| *b == b'\n') {
+                let line_bytes = buf.split_to(offset + 1);
+                count += 1;
+                if line_bytes.len() > 1 {
+                    return Ok(Async::Ready(Some((count, line_bytes))));
+                }
+            } else {
+                // We only get here if there is no complete line in buf, and
+                // it is therefore ok to immediately pass an Async::NotReady
+                // from stream through.
+                // If we get a None from poll, but still have something in buf,
+                // that means the input was not terminated with a newline. We
+                // add that so that the last line gets picked up in the next
+                // run through the loop.
+                match try_ready!(stream.poll()) {
+                    Some(b) => buf.extend_from_slice(&b),
+                    None if buf.len() > 0 => buf.extend_from_slice(&[b'\n']),
+                    None => return Ok(Async::Ready(None)),
+                }
+            }
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,6 +356,33 @@ mod tests {
         );
     }
 
+    fn line_round_trip(text: &'static str) -> Result<Vec<String>, failure::Error> {
+        let client = ipfs_api::IpfsClient::default();
+        let resolver = super::LinkResolver::from(client.clone());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let link = runtime.block_on(client.add(text.as_bytes())).unwrap().hash;
+        runtime.block_on(
+            LinkResolver::line_stream(&resolver, &Link { link: link.clone() })
+                .and_then(|stream| stream.map(|line| line.value).collect()),
+        )
+    }
+
+    #[test]
+    fn read_line_stream() {
+        let lines = line_round_trip("one\ntwo\n");
+        assert_eq!(vec!["one", "two"], lines.unwrap());
+
+        let lines = line_round_trip("without newline");
+        assert_eq!(vec!["without newline"], lines.unwrap());
+
+        let lines = line_round_trip("windows\r\nline endings\r\n\r\n");
+        assert_eq!(vec!["windows", "line endings"], lines.unwrap());
+
+        let lines = line_round_trip("a,b\n\n\"c\",d");
+        assert_eq!(vec!["a,b", "\"c\",d"], lines.unwrap());
+    }
+
     #[test]
     fn ipfs_map_file_size() {
         let file = "\"small test string that trips the size restriction\"";
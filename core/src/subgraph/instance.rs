@@ -150,7 +150,7 @@ where
     ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
         let logger = logger.to_owned();
         match trigger {
-            EthereumTrigger::Log(log) => {
+            EthereumTrigger::Log(log, receipt) => {
                 let transaction = block
                     .transaction_for_log(&log)
                     .map(Arc::new)
@@ -167,6 +167,7 @@ where
                             block.clone(),
                             transaction.clone(),
                             log.clone(),
+                            receipt.clone(),
                             state,
                         )
                     })
@@ -1,8 +1,11 @@
 use futures::future::{loop_fn, Loop};
 use futures::sync::mpsc::{channel, Receiver, Sender};
+use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use graph::data::subgraph::schema::{
@@ -12,32 +15,84 @@ use graph::prelude::{SubgraphInstance as SubgraphInstanceTrait, *};
 
 use super::SubgraphInstance;
 
+lazy_static! {
+    /// The longest time to wait before restarting a subgraph that failed with
+    /// a non-deterministic error.
+    static ref SUBGRAPH_ERROR_RETRY_CEIL: Duration =
+        env::var("GRAPH_SUBGRAPH_ERROR_RETRY_CEIL_SECS")
+            .ok()
+            .map(|s| u64::from_str(&s).unwrap_or_else(|_| {
+                panic!("failed to parse env var GRAPH_SUBGRAPH_ERROR_RETRY_CEIL_SECS")
+            }))
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30 * 60));
+}
+
+/// The time to wait before restarting a subgraph after its first
+/// non-deterministic failure; the delay doubles with every further failure.
+const SUBGRAPH_ERROR_RETRY_BASE: Duration = Duration::from_secs(10);
+
+/// The time to wait before restarting a subgraph that has failed with a
+/// non-deterministic error `failures` times in a row.
+fn subgraph_error_retry_delay(failures: u64) -> Duration {
+    1u32.checked_shl(failures.min(32) as u32)
+        .and_then(|factor| SUBGRAPH_ERROR_RETRY_BASE.checked_mul(factor))
+        .map_or(*SUBGRAPH_ERROR_RETRY_CEIL, |delay| {
+            delay.min(*SUBGRAPH_ERROR_RETRY_CEIL)
+        })
+}
+
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<SubgraphDeploymentId, CancelGuard>>>;
 
-struct IndexingInputs<B, S> {
+struct IndexingInputs<B, T, S> {
     deployment_id: SubgraphDeploymentId,
     network_name: String,
     start_blocks: Vec<u64>,
     store: Arc<S>,
     eth_adapter: Arc<dyn EthereumAdapter>,
     stream_builder: B,
+    host_builder: T,
     templates_use_calls: bool,
+    confirmations: Option<u64>,
     top_level_templates: Vec<DataSourceTemplate>,
 }
 
+impl<B: Clone, T: Clone, S> Clone for IndexingInputs<B, T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            deployment_id: self.deployment_id.clone(),
+            network_name: self.network_name.clone(),
+            start_blocks: self.start_blocks.clone(),
+            store: self.store.clone(),
+            eth_adapter: self.eth_adapter.clone(),
+            stream_builder: self.stream_builder.clone(),
+            host_builder: self.host_builder.clone(),
+            templates_use_calls: self.templates_use_calls,
+            confirmations: self.confirmations,
+            top_level_templates: self.top_level_templates.clone(),
+        }
+    }
+}
+
 struct IndexingState<T: RuntimeHostBuilder> {
     logger: Logger,
     instance: SubgraphInstance<T>,
     instances: SharedInstanceKeepAliveMap,
+    /// The manifest of the subgraph, including all dynamic data sources
+    /// created so far.
+    manifest: SubgraphManifest,
     log_filter: EthereumLogFilter,
     call_filter: EthereumCallFilter,
     block_filter: EthereumBlockFilter,
     restarts: u64,
+    /// The number of non-deterministic failures since the subgraph last
+    /// made progress.
+    failures: u64,
 }
 
 struct IndexingContext<B, T: RuntimeHostBuilder, S> {
     /// Read only inputs that are needed while indexing a subgraph.
-    pub inputs: IndexingInputs<B, S>,
+    pub inputs: IndexingInputs<B, T, S>,
 
     /// Mutable state that may be modified while indexing a subgraph.
     pub state: IndexingState<T>,
@@ -54,6 +109,84 @@ struct IndexingContext<B, T: RuntimeHostBuilder, S> {
     pub block_stream_metrics: Arc<BlockStreamMetrics>,
 }
 
+impl<B, T, S> IndexingContext<B, T, S>
+where
+    B: BlockStreamBuilder,
+    T: RuntimeHostBuilder,
+{
+    /// Captures everything needed to build this context again from scratch.
+    fn seed(&self) -> IndexingSeed<B, T, S> {
+        IndexingSeed {
+            inputs: self.inputs.clone(),
+            logger: self.state.logger.clone(),
+            instances: self.state.instances.clone(),
+            manifest: self.state.manifest.clone(),
+            restarts: self.state.restarts,
+            failures: self.state.failures,
+            subgraph_metrics: self.subgraph_metrics.clone(),
+            host_metrics: self.host_metrics.clone(),
+            ethrpc_metrics: self.ethrpc_metrics.clone(),
+            block_stream_metrics: self.block_stream_metrics.clone(),
+        }
+    }
+}
+
+/// The parts of an `IndexingContext` that do not depend on the blocks that
+/// have been processed. Used to start a subgraph, and to start it over after
+/// a non-deterministic failure left the context in an unknown state.
+struct IndexingSeed<B, T, S> {
+    inputs: IndexingInputs<B, T, S>,
+    logger: Logger,
+    instances: SharedInstanceKeepAliveMap,
+    manifest: SubgraphManifest,
+    restarts: u64,
+    failures: u64,
+    subgraph_metrics: Arc<SubgraphInstanceMetrics>,
+    host_metrics: Arc<HostMetrics>,
+    ethrpc_metrics: Arc<SubgraphEthRpcMetrics>,
+    block_stream_metrics: Arc<BlockStreamMetrics>,
+}
+
+impl<B, T, S> IndexingSeed<B, T, S>
+where
+    B: BlockStreamBuilder,
+    T: RuntimeHostBuilder,
+{
+    fn into_context(self) -> Result<IndexingContext<B, T, S>, Error> {
+        // Obtain filters from the manifest
+        let log_filter = EthereumLogFilter::from_data_sources(&self.manifest.data_sources);
+        let call_filter = EthereumCallFilter::from_data_sources(&self.manifest.data_sources);
+        let block_filter = EthereumBlockFilter::from_data_sources(&self.manifest.data_sources);
+
+        // Create a subgraph instance from the manifest
+        let instance = SubgraphInstance::from_manifest(
+            &self.logger,
+            self.manifest.clone(),
+            self.inputs.host_builder.clone(),
+            self.host_metrics.clone(),
+        )?;
+
+        Ok(IndexingContext {
+            inputs: self.inputs,
+            state: IndexingState {
+                logger: self.logger,
+                instance,
+                instances: self.instances,
+                manifest: self.manifest,
+                log_filter,
+                call_filter,
+                block_filter,
+                restarts: self.restarts,
+                failures: self.failures,
+            },
+            subgraph_metrics: self.subgraph_metrics,
+            host_metrics: self.host_metrics,
+            ethrpc_metrics: self.ethrpc_metrics,
+            block_stream_metrics: self.block_stream_metrics,
+        })
+    }
+}
+
 pub struct SubgraphInstanceManager {
     logger: Logger,
     input: Sender<SubgraphAssignmentProviderEvent>,
@@ -324,11 +457,6 @@ impl SubgraphInstanceManager {
         // Clone the deployment ID for later
         let deployment_id = manifest.id.clone();
         let network_name = manifest.network_name()?;
-
-        // Obtain filters from the manifest
-        let log_filter = EthereumLogFilter::from_data_sources(&manifest.data_sources);
-        let call_filter = EthereumCallFilter::from_data_sources(&manifest.data_sources);
-        let block_filter = EthereumBlockFilter::from_data_sources(&manifest.data_sources);
         let start_blocks = manifest.start_blocks();
 
         // Identify whether there are templates with call handlers or
@@ -340,9 +468,8 @@ impl SubgraphInstanceManager {
         });
 
         let top_level_templates = manifest.templates.clone();
+        let confirmations = manifest.confirmations;
 
-        // Create a subgraph instance from the manifest; this moves
-        // ownership of the manifest and host builder into the new instance
         let subgraph_metrics = Arc::new(SubgraphInstanceMetrics::new(
             registry.clone(),
             deployment_id.clone().to_string(),
@@ -361,11 +488,9 @@ impl SubgraphInstanceManager {
             ethrpc_metrics.clone(),
             deployment_id.clone(),
         ));
-        let instance =
-            SubgraphInstance::from_manifest(&logger, manifest, host_builder, host_metrics.clone())?;
 
         // The subgraph state tracks the state of the subgraph instance over time
-        let ctx = IndexingContext {
+        let ctx = IndexingSeed {
             inputs: IndexingInputs {
                 deployment_id: deployment_id.clone(),
                 network_name,
@@ -373,29 +498,29 @@ impl SubgraphInstanceManager {
                 store,
                 eth_adapter,
                 stream_builder,
+                host_builder,
                 templates_use_calls,
+                confirmations,
                 top_level_templates,
             },
-            state: IndexingState {
-                logger,
-                instance,
-                instances,
-                log_filter,
-                call_filter,
-                block_filter,
-                restarts: 0,
-            },
+            logger,
+            instances,
+            manifest,
+            restarts: 0,
+            failures: 0,
             subgraph_metrics,
             host_metrics,
             ethrpc_metrics,
             block_stream_metrics,
-        };
+        }
+        .into_context()?;
 
         // Keep restarting the subgraph until it terminates. The subgraph
         // will usually only run once, but is restarted whenever a block
         // creates dynamic data sources. This allows us to recreate the
         // block stream and include events for the new data sources going
         // forward; this is easier than updating the existing block stream.
+        // It is also restarted after failing with a non-deterministic error.
         //
         // This task has many calls to the store, so mark it as `blocking`.
         let subgraph_runner =
@@ -447,8 +572,13 @@ where
     let logger_for_err = logger.clone();
     let logger_for_block_stream_errors = logger.clone();
 
+    // Remember how to build the context from scratch, in case processing a
+    // block fails and the subgraph has to be started over
+    let mut seed = ctx.seed();
+
     let block_stream_canceler = CancelGuard::new();
     let block_stream_cancel_handle = block_stream_canceler.handle();
+    let cancel_handle_for_err = block_stream_cancel_handle.clone();
     let block_stream = ctx
         .inputs
         .stream_builder
@@ -461,6 +591,7 @@ where
             ctx.state.call_filter.clone(),
             ctx.state.block_filter.clone(),
             ctx.inputs.templates_use_calls,
+            ctx.inputs.confirmations,
             ctx.block_stream_metrics.clone(),
         )
         .from_err()
@@ -477,9 +608,10 @@ where
     debug!(logger, "Starting block stream");
 
     // The processing stream may be end due to an error or for restarting to
-    // account for new data sources.
+    // account for new data sources. Errors carry the block that failed and
+    // how many times in a row the subgraph had failed before that block.
     enum StreamEnd<B: BlockStreamBuilder, T: RuntimeHostBuilder, S> {
-        Error(CancelableError<Error>),
+        Error(CancelableError<Error>, EthereumBlockPointer, u64),
         NeedsRestart(IndexingContext<B, T, S>),
     }
 
@@ -501,6 +633,8 @@ where
         // Process blocks from the stream as long as no restart is needed
         .fold(ctx, move |ctx, block| {
             let subgraph_metrics = ctx.subgraph_metrics.clone();
+            let block_ptr = EthereumBlockPointer::from(&block.ethereum_block);
+            let failures = ctx.state.failures;
             let start = Instant::now();
             if block.triggers.len() > 0 {
                 subgraph_metrics
@@ -514,7 +648,7 @@ where
                 block_stream_cancel_handle.clone(),
                 block,
             )
-            .map_err(|e| StreamEnd::Error(e))
+            .map_err(move |e| StreamEnd::Error(e, block_ptr, failures))
             .and_then(|(ctx, needs_restart)| match needs_restart {
                 false => Ok(ctx),
                 true => Err(StreamEnd::NeedsRestart(ctx)),
@@ -525,57 +659,123 @@ where
                 res
             })
         })
-        .then(move |res| match res {
-            Ok(_) => unreachable!("block stream finished without error"),
-            Err(StreamEnd::NeedsRestart(mut ctx)) => {
-                // Increase the restart counter
-                ctx.state.restarts += 1;
-
-                // Cancel the stream for real
-                ctx.state
-                    .instances
-                    .write()
-                    .unwrap()
-                    .remove(&ctx.inputs.deployment_id);
-
-                // And restart the subgraph
-                Ok(Loop::Continue(ctx))
-            }
+        .then(move |res| -> Box<dyn Future<Item = _, Error = _> + Send> {
+            match res {
+                Ok(_) => unreachable!("block stream finished without error"),
+                Err(StreamEnd::NeedsRestart(mut ctx)) => {
+                    // Increase the restart counter
+                    ctx.state.restarts += 1;
+
+                    // Cancel the stream for real
+                    ctx.state
+                        .instances
+                        .write()
+                        .unwrap()
+                        .remove(&ctx.inputs.deployment_id);
+
+                    // And restart the subgraph
+                    Box::new(future::ok(Loop::Continue(ctx)))
+                }
 
-            Err(StreamEnd::Error(CancelableError::Cancel)) => {
-                debug!(
-                    logger_for_err,
-                    "Subgraph block stream shut down cleanly";
-                    "id" => id_for_err.to_string(),
-                );
-                Err(())
-            }
+                Err(StreamEnd::Error(CancelableError::Cancel, _, _)) => {
+                    debug!(
+                        logger_for_err,
+                        "Subgraph block stream shut down cleanly";
+                        "id" => id_for_err.to_string(),
+                    );
+                    Box::new(future::err(()))
+                }
 
-            // Handle unexpected stream errors by marking the subgraph as failed.
-            Err(StreamEnd::Error(CancelableError::Error(e))) => {
-                error!(
-                    logger_for_err,
-                    "Subgraph instance failed to run: {}", e;
-                    "id" => id_for_err.to_string(),
-                    "code" => LogCode::SubgraphSyncingFailure
-                );
+                // Handle unexpected stream errors by marking the subgraph as failed;
+                // unless the error is deterministic, start it over after a while.
+                Err(StreamEnd::Error(CancelableError::Error(e), block_ptr, failures)) => {
+                    let deterministic = DeterministicError::is_deterministic(&e);
 
-                // Set subgraph status to Failed
-                let status_ops =
-                    SubgraphDeploymentEntity::update_failed_operations(&id_for_err, true);
-                if let Err(e) = store_for_err.apply_metadata_operations(status_ops) {
                     error!(
                         logger_for_err,
-                        "Failed to set subgraph status to Failed: {}", e;
+                        "Subgraph instance failed to run: {}", e;
                         "id" => id_for_err.to_string(),
-                        "code" => LogCode::SubgraphSyncingFailureNotRecorded
+                        "deterministic" => deterministic,
+                        "code" => LogCode::SubgraphSyncingFailure
                     );
+
+                    // Set subgraph status to Failed and record why
+                    let status_ops = SubgraphDeploymentEntity::update_failure_operations(
+                        &id_for_err,
+                        e.to_string(),
+                        Some(block_ptr),
+                    );
+                    if let Err(e) = store_for_err.apply_metadata_operations(status_ops) {
+                        error!(
+                            logger_for_err,
+                            "Failed to set subgraph status to Failed: {}", e;
+                            "id" => id_for_err.to_string(),
+                            "code" => LogCode::SubgraphSyncingFailureNotRecorded
+                        );
+                    }
+
+                    // Processing the block again would fail in the same way
+                    if deterministic {
+                        return Box::new(future::err(()));
+                    }
+
+                    // The seed still has the failures from when the subgraph
+                    // was started; it may have recovered since then
+                    seed.failures = failures;
+                    retry_subgraph(logger_for_err, seed, cancel_handle_for_err)
                 }
-                Err(())
             }
         })
 }
 
+/// Starts a subgraph over from `seed` after it failed with a non-deterministic
+/// error, waiting longer the more often it has failed in a row.
+fn retry_subgraph<B, T, S>(
+    logger: Logger,
+    mut seed: IndexingSeed<B, T, S>,
+    cancel_handle: CancelHandle,
+) -> Box<dyn Future<Item = Loop<(), IndexingContext<B, T, S>>, Error = ()> + Send>
+where
+    B: BlockStreamBuilder,
+    T: RuntimeHostBuilder,
+    S: ChainStore + Store + EthereumCallCache + SubgraphDeploymentStore,
+{
+    let delay = subgraph_error_retry_delay(seed.failures);
+    seed.failures += 1;
+
+    info!(
+        logger,
+        "Restarting subgraph after a non-deterministic failure";
+        "delay_secs" => delay.as_secs(),
+        "failures" => seed.failures,
+    );
+
+    Box::new(
+        // Timer errors are harmless. Treat them as if the delay had passed.
+        tokio_timer::Delay::new(Instant::now() + delay).then(move |_| {
+            // The subgraph may have been stopped while we were waiting
+            if cancel_handle.is_canceled() {
+                debug!(logger, "Subgraph was stopped before it could be restarted");
+                return Err(());
+            }
+
+            // Drop the cancel guard of the failed block stream
+            seed.instances
+                .write()
+                .unwrap()
+                .remove(&seed.inputs.deployment_id);
+
+            seed.into_context().map(Loop::Continue).map_err(|e| {
+                error!(
+                    logger,
+                    "Failed to restart subgraph: {}", e;
+                    "code" => LogCode::SubgraphStartFailure
+                );
+            })
+        }),
+    )
+}
+
 /// Processes a block and returns the updated context and a boolean flag indicating
 /// whether new dynamic data sources have been added to the subgraph.
 fn process_block<B, T: RuntimeHostBuilder, S>(
@@ -725,7 +925,7 @@ where
         .from_err()
     })
     // Apply entity operations and advance the stream
-    .and_then(move |(ctx, block_state, needs_restart)| {
+    .and_then(move |(mut ctx, block_state, needs_restart)| {
         // Avoid writing to store if block stream has been canceled
         if block_stream_cancel_handle.is_canceled() {
             return Err(CancelableError::Cancel);
@@ -760,6 +960,24 @@ where
                         &block_ptr_after,
                     );
                 }
+
+                // The subgraph is making progress again after failing
+                if ctx.state.failures > 0 {
+                    info!(logger1, "Subgraph recovered from failure");
+
+                    let status_ops = SubgraphDeploymentEntity::update_failed_operations(
+                        &ctx.inputs.deployment_id,
+                        false,
+                    );
+                    match ctx.inputs.store.apply_metadata_operations(status_ops) {
+                        Ok(()) => ctx.state.failures = 0,
+                        Err(e) => error!(
+                            logger1,
+                            "Failed to clear the failed status of the subgraph: {}", e
+                        ),
+                    }
+                }
+
                 (ctx, needs_restart)
             })
             .map_err(|e| {
@@ -785,7 +1003,7 @@ where
             let block = block.clone();
             let subgraph_metrics = ctx.subgraph_metrics.clone();
             let trigger_type = match trigger {
-                EthereumTrigger::Log(_) => TriggerType::Event,
+                EthereumTrigger::Log(..) => TriggerType::Event,
                 EthereumTrigger::Call(_) => TriggerType::Call,
                 EthereumTrigger::Block(..) => TriggerType::Block,
             };
@@ -798,7 +1016,7 @@ where
                     subgraph_metrics.observe_trigger_processing_duration(elapsed, trigger_type);
                     (ctx, block_state)
                 })
-                .map_err(|e| format_err!("Failed to process trigger: {}", e))
+                .map_err(|e| DeterministicError::with_context(e, "Failed to process trigger"))
         })
 }
 
@@ -817,7 +1035,7 @@ where
 
     for info in created_data_sources {
         // Try to instantiate a data source from the template
-        let data_source = DataSource::try_from_template(info.template, &info.params)?;
+        let data_source = DataSource::try_from_template(info.template, &info.params, info.context)?;
         let host_metrics = host_metrics.clone();
 
         // Try to create a runtime host for the data source
@@ -880,4 +1098,7 @@ fn persist_dynamic_data_sources<B, T: RuntimeHostBuilder, S>(
     ctx.state
         .block_filter
         .extend(EthereumBlockFilter::from_data_sources(&data_sources));
+
+    // Remember the data sources in case the subgraph has to be started over
+    ctx.state.manifest.data_sources.extend(data_sources);
 }
@@ -76,6 +76,7 @@ where
                           eventHandlers { event handler }
                         }
                       }
+                      context
                     }
                   }
                 }
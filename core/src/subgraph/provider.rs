@@ -2,7 +2,9 @@ use futures::sync::mpsc::{channel, Receiver, Sender};
 use std::collections::HashSet;
 use std::sync::Mutex;
 
-use graph::data::subgraph::schema::attribute_index_definitions;
+use graph::data::subgraph::schema::{
+    attribute_index_definitions, SubgraphDeploymentAssignmentEntity,
+};
 use graph::prelude::{
     DataSourceLoader as _, GraphQlRunner,
     SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait, *,
@@ -83,6 +85,22 @@ where
         &self,
         id: SubgraphDeploymentId,
     ) -> Box<dyn Future<Item = (), Error = SubgraphAssignmentProviderError> + Send + 'static> {
+        // Paused deployments stay stopped until they are resumed
+        match self
+            .store
+            .get(SubgraphDeploymentAssignmentEntity::key(id.clone()))
+        {
+            Ok(Some(assignment)) if SubgraphDeploymentAssignmentEntity::is_paused(&assignment) => {
+                return Box::new(future::err(SubgraphAssignmentProviderError::Paused(id)));
+            }
+            Ok(_) => (),
+            Err(e) => {
+                return Box::new(future::err(SubgraphAssignmentProviderError::from(
+                    Error::from(e),
+                )))
+            }
+        }
+
         let self_clone = self.clone();
         let store = self.store.clone();
         let subgraph_id = id.clone();
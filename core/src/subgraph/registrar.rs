@@ -16,12 +16,13 @@ lazy_static! {
 use super::validation;
 use graph::data::subgraph::schema::{
     generate_entity_id, SubgraphDeploymentAssignmentEntity, SubgraphDeploymentEntity,
-    SubgraphEntity, SubgraphVersionEntity, TypedEntity,
+    SubgraphEntity, SubgraphPersistedQueryEntity, SubgraphVersionEntity, TypedEntity,
 };
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
     SubgraphRegistrar as SubgraphRegistrarTrait, *,
 };
+use graph_graphql::graphql_parser::parse_query;
 
 pub struct SubgraphRegistrar<L, P, S, CS> {
     logger: Logger,
@@ -183,6 +184,9 @@ where
                                         if let Some(entity) = entity_opt {
                                             if entity.get("nodeId")
                                                 == Some(&node_id.to_string().into())
+                                                && !SubgraphDeploymentAssignmentEntity::is_paused(
+                                                    &entity,
+                                                )
                                             {
                                                 // Start subgraph on this node
                                                 Box::new(stream::once(Ok(AssignmentEvent::Add {
@@ -190,7 +194,8 @@ where
                                                     node_id: node_id.clone(),
                                                 })))
                                             } else {
-                                                // Ensure it is removed from this node
+                                                // Ensure it is removed from this node, or
+                                                // stopped if it was paused
                                                 Box::new(stream::once(Ok(
                                                     AssignmentEvent::Remove {
                                                         subgraph_id: subgraph_hash,
@@ -234,6 +239,9 @@ where
             .and_then(move |assignment_entities| {
                 assignment_entities
                     .into_iter()
+                    .filter(|assignment_entity| {
+                        !SubgraphDeploymentAssignmentEntity::is_paused(assignment_entity)
+                    })
                     .map(|assignment_entity| {
                         // Parse as subgraph hash
                         assignment_entity.id().and_then(|id| {
@@ -385,6 +393,124 @@ where
             node_id,
         )))
     }
+
+    fn pause_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        Box::new(future::result(set_paused(
+            self.store.clone(),
+            &hash,
+            &self.node_id,
+            true,
+        )))
+    }
+
+    fn resume_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        Box::new(future::result(set_paused(
+            self.store.clone(),
+            &hash,
+            &self.node_id,
+            false,
+        )))
+    }
+
+    fn rewind_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+        block_ptr: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        let store = self.store.clone();
+        let provider = self.provider.clone();
+        let resolver = self.resolver.clone();
+        let chain_stores = self.chain_stores.clone();
+        let logger = self.logger_factory.subgraph_logger(&hash);
+
+        Box::new(
+            future::result(check_assignment(store.clone(), &hash, &self.node_id))
+                .and_then(move |()| {
+                    SubgraphManifest::resolve(hash.to_ipfs_link(), resolver, logger.clone())
+                        .map_err(SubgraphRegistrarError::ResolveError)
+                        .map(move |manifest| (manifest, hash, logger))
+                })
+                .and_then(move |(manifest, hash, logger)| {
+                    let network_name = manifest.network_name()?;
+                    chain_stores
+                        .get(&network_name)
+                        .cloned()
+                        .ok_or(SubgraphRegistrarError::NetworkNotSupported(network_name))
+                        .map(|chain_store| (chain_store, hash, logger))
+                })
+                .and_then(move |(chain_store, hash, logger)| {
+                    rewind_subgraph(logger, store, chain_store, provider, hash, block_ptr)
+                }),
+        )
+    }
+
+    fn persist_query(
+        &self,
+        name: SubgraphName,
+        query: String,
+    ) -> Box<dyn Future<Item = String, Error = SubgraphRegistrarError> + Send + 'static> {
+        Box::new(future::result(persist_query(
+            &self.logger,
+            self.store.clone(),
+            name,
+            query,
+        )))
+    }
+
+    fn remove_persisted_query(
+        &self,
+        name: SubgraphName,
+        query_hash: String,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        Box::new(future::result(remove_persisted_query(
+            &self.logger,
+            self.store.clone(),
+            name,
+            query_hash,
+        )))
+    }
+}
+
+/// Makes sure that the deployment is assigned to this node, since only the
+/// node that indexes a deployment can stop and restart it.
+fn check_assignment(
+    store: Arc<impl Store>,
+    hash: &SubgraphDeploymentId,
+    node_id: &NodeId,
+) -> Result<(), SubgraphRegistrarError> {
+    let assignment = store
+        .get(SubgraphDeploymentAssignmentEntity::key(hash.clone()))?
+        .ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.to_string()))?;
+
+    if assignment.get("nodeId") == Some(&node_id.to_string().into()) {
+        Ok(())
+    } else {
+        Err(SubgraphRegistrarError::DeploymentNotAssigned(
+            hash.to_string(),
+        ))
+    }
+}
+
+/// Records that the deployment is paused or not on its assignment. Changing
+/// the assignment stops or starts the deployment through the assignment
+/// events of this node.
+fn set_paused(
+    store: Arc<impl Store>,
+    hash: &SubgraphDeploymentId,
+    node_id: &NodeId,
+    paused: bool,
+) -> Result<(), SubgraphRegistrarError> {
+    check_assignment(store.clone(), hash, node_id)?;
+    store.apply_metadata_operations(SubgraphDeploymentAssignmentEntity::paused_operations(
+        hash, paused,
+    ))?;
+    Ok(())
 }
 
 fn handle_assignment_event<P>(
@@ -446,6 +572,14 @@ fn start_subgraph<P: SubgraphAssignmentProviderTrait>(
             match result {
                 Ok(()) => Ok(()),
                 Err(SubgraphAssignmentProviderError::AlreadyRunning(_)) => Ok(()),
+                Err(SubgraphAssignmentProviderError::Paused(_)) => {
+                    info!(
+                        logger,
+                        "Not starting paused subgraph";
+                        "subgraph_id" => subgraph_id.to_string()
+                    );
+                    Ok(())
+                }
                 Err(e) => {
                     // Errors here are likely an issue with the subgraph.
                     error!(
@@ -522,10 +656,12 @@ fn resolve_subgraph_chain_blocks(
         // If the minimum start block is 0 (i.e. the genesis block),
         // return `None` to start indexing from the genesis block. Otherwise
         // return a block pointer for the block with number `min_start_block - 1`.
+        // Grafted subgraphs start indexing right after the graft block.
         match manifest
-            .start_blocks()
-            .into_iter()
-            .min()
+            .graft
+            .as_ref()
+            .map(|graft| graft.block + 1)
+            .or_else(|| manifest.start_blocks().into_iter().min())
             .expect("cannot identify minimum start block because there are no data sources")
         {
             0 => Box::new(future::ok(None)) as Box<dyn Future<Item = _, Error = _> + Send>,
@@ -551,6 +687,32 @@ fn resolve_subgraph_chain_blocks(
     )
 }
 
+/// Checks that the graft base of a subgraph exists and has indexed the
+/// graft block, so that the new deployment can start from its data
+fn validate_graft(store: Arc<impl Store>, graft: &Graft) -> Result<(), SubgraphRegistrarError> {
+    let invalid = |reason: String| {
+        SubgraphRegistrarError::ManifestValidationError(vec![
+            SubgraphManifestValidationError::GraftBaseInvalid(reason),
+        ])
+    };
+
+    match store.block_ptr(graft.base.clone()) {
+        Err(e) => Err(invalid(format!(
+            "failed to read deployment {}: {}",
+            graft.base, e
+        ))),
+        Ok(None) => Err(invalid(format!(
+            "deployment {} has not indexed any blocks yet",
+            graft.base
+        ))),
+        Ok(Some(ptr)) if ptr.number < graft.block => Err(invalid(format!(
+            "deployment {} has only indexed up to block {}, but the graft block is {}",
+            graft.base, ptr.number, graft.block
+        ))),
+        Ok(Some(_)) => Ok(()),
+    }
+}
+
 struct SubraphVersionUpdatingMetadata {
     subgraph_entity_id: String,
     version_entity_id: String,
@@ -752,6 +914,12 @@ fn create_subgraph_version(
     let store = store.clone();
     let deployment_store = store.clone();
 
+    if let Some(graft) = &manifest.graft {
+        if let Err(e) = validate_graft(store.clone(), graft) {
+            return Box::new(future::err(e));
+        }
+    }
+
     Box::new(
         future::result(get_version_ids_and_summaries(
             logger.clone(),
@@ -895,7 +1063,7 @@ fn create_subgraph_version(
                         .create_operations(&manifest.id),
                     );
                     deployment_store
-                        .create_subgraph_deployment(&manifest.schema, ops)
+                        .create_subgraph_deployment(&manifest.schema, ops, manifest.graft.as_ref())
                         .map_err(|e| SubgraphRegistrarError::SubgraphDeploymentError(e))
                 }
             })
@@ -967,6 +1135,19 @@ fn remove_subgraph(
             .map(|op| op.into()),
     );
 
+    // Remove the queries persisted for the subgraph
+    let persisted_queries = store.find(SubgraphPersistedQueryEntity::query().filter(
+        EntityFilter::new_equal("subgraph", subgraph_entity.id().unwrap()),
+    ))?;
+    ops.extend(
+        persisted_queries
+            .iter()
+            .map(|entity| MetadataOperation::Remove {
+                entity: SubgraphPersistedQueryEntity::TYPENAME.to_owned(),
+                id: entity.id().unwrap(),
+            }),
+    );
+
     // Remove the subgraph entity
     ops.push(MetadataOperation::Remove {
         entity: SubgraphEntity::TYPENAME.to_owned(),
@@ -1058,6 +1239,103 @@ fn remove_subgraph_versions(
     Ok(ops)
 }
 
+/// Rewind a subgraph deployment to an ancestor of its latest block by reverting
+/// all blocks after it in one transaction, and restart it if it was running.
+fn rewind_subgraph<S, CS, P>(
+    logger: Logger,
+    store: Arc<S>,
+    chain_store: Arc<CS>,
+    provider: Arc<P>,
+    hash: SubgraphDeploymentId,
+    block_ptr: EthereumBlockPointer,
+) -> impl Future<Item = (), Error = SubgraphRegistrarError> + Send
+where
+    S: Store,
+    CS: ChainStore,
+    P: SubgraphAssignmentProviderTrait,
+{
+    // Stop the deployment if it is running so that its block pointer can't
+    // change while we rewind it; a paused deployment stays paused after
+    // rewinding it
+    provider
+        .stop(hash.clone())
+        .then(|result| match result {
+            Ok(()) => Ok(true),
+            Err(SubgraphAssignmentProviderError::NotRunning(_)) => Ok(false),
+            Err(e) => Err(SubgraphRegistrarError::from(e)),
+        })
+        .and_then(move |was_running| {
+            let result = rewind_stopped_subgraph(
+                &logger,
+                store.as_ref(),
+                chain_store.as_ref(),
+                &hash,
+                block_ptr,
+            );
+
+            // Restart the deployment even if rewinding it failed
+            let restart: Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send> =
+                if was_running {
+                    Box::new(provider.start(hash).from_err())
+                } else {
+                    Box::new(future::ok(()))
+                };
+            restart.and_then(move |()| result)
+        })
+}
+
+fn rewind_stopped_subgraph(
+    logger: &Logger,
+    store: &impl Store,
+    chain_store: &impl ChainStore,
+    hash: &SubgraphDeploymentId,
+    block_ptr: EthereumBlockPointer,
+) -> Result<(), SubgraphRegistrarError> {
+    let current_ptr = store.block_ptr(hash.clone())?.ok_or_else(|| {
+        SubgraphRegistrarError::RewindError(format!(
+            "deployment {} has not processed any blocks yet",
+            hash
+        ))
+    })?;
+    if block_ptr.number > current_ptr.number {
+        return Err(SubgraphRegistrarError::RewindError(format!(
+            "block {} is after the latest block {} of deployment {}",
+            block_ptr.number, current_ptr.number, hash
+        )));
+    }
+
+    // Make sure the block we rewind to is an ancestor of the latest block
+    // of the deployment before changing anything
+    let ancestor = chain_store
+        .ancestor_block(current_ptr, current_ptr.number - block_ptr.number)?
+        .ok_or_else(|| {
+            SubgraphRegistrarError::RewindError(format!(
+                "the chain store is missing blocks between block {} and the latest \
+                 block {} of deployment {}",
+                block_ptr.number, current_ptr.number, hash
+            ))
+        })?;
+    if EthereumBlockPointer::from(ancestor) != block_ptr {
+        return Err(SubgraphRegistrarError::RewindError(format!(
+            "block {} ({:x}) is not an ancestor of the latest block of deployment {}",
+            block_ptr.number, block_ptr.hash, hash
+        )));
+    }
+
+    info!(
+        logger,
+        "Rewind subgraph";
+        "blocks" => current_ptr.number - block_ptr.number,
+        "block_number" => block_ptr.number,
+        "block_hash" => format!("{:x}", block_ptr.hash),
+    );
+
+    // Revert all blocks at once so that a failure leaves the deployment
+    // where it was
+    store.rewind_block_operations(hash.clone(), current_ptr, block_ptr)?;
+    Ok(())
+}
+
 /// Reassign a subgraph deployment to a different node.
 ///
 /// Reassigning to a nodeId that does not match any reachable graph-nodes will effectively pause the
@@ -1107,3 +1385,69 @@ fn reassign_subgraph(
 
     Ok(())
 }
+
+fn persist_query(
+    logger: &Logger,
+    store: Arc<impl Store>,
+    name: SubgraphName,
+    query: String,
+) -> Result<String, SubgraphRegistrarError> {
+    let subgraph_id = subgraph_entity_id(store.as_ref(), &name)?;
+
+    // Only accept queries that will at least parse when clients run them
+    parse_query(&query).map_err(|e| SubgraphRegistrarError::InvalidQuery(e.to_string()))?;
+
+    let query_hash = SubgraphPersistedQueryEntity::hash(&query);
+    store.apply_metadata_operations(
+        SubgraphPersistedQueryEntity::new(subgraph_id, query).write_operations(),
+    )?;
+
+    debug!(
+        logger,
+        "Persisted query";
+        "subgraph_name" => name.to_string(),
+        "query_hash" => &query_hash,
+    );
+
+    Ok(query_hash)
+}
+
+fn remove_persisted_query(
+    logger: &Logger,
+    store: Arc<impl Store>,
+    name: SubgraphName,
+    query_hash: String,
+) -> Result<(), SubgraphRegistrarError> {
+    let subgraph_id = subgraph_entity_id(store.as_ref(), &name)?;
+
+    let id = SubgraphPersistedQueryEntity::id(&subgraph_id, &query_hash);
+    if store.get(SubgraphPersistedQueryEntity::key(id))?.is_none() {
+        return Err(SubgraphRegistrarError::PersistedQueryNotFound(query_hash));
+    }
+
+    store.apply_metadata_operations(SubgraphPersistedQueryEntity::remove_operations(
+        &subgraph_id,
+        &query_hash,
+    ))?;
+
+    debug!(
+        logger,
+        "Removed persisted query";
+        "subgraph_name" => name.to_string(),
+        "query_hash" => &query_hash,
+    );
+
+    Ok(())
+}
+
+/// Look up the id of the `Subgraph` entity for the subgraph `name`
+fn subgraph_entity_id(
+    store: &impl Store,
+    name: &SubgraphName,
+) -> Result<String, SubgraphRegistrarError> {
+    let subgraph_entity = store
+        .find_one(SubgraphEntity::query().filter(EntityFilter::new_equal("name", name.to_string())))
+        .map_err(|e| format_err!("query execution error: {}", e))?
+        .ok_or_else(|| SubgraphRegistrarError::NameNotFound(name.to_string()))?;
+    Ok(subgraph_entity.id()?)
+}
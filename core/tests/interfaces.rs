@@ -23,6 +23,8 @@ fn insert_and_query(
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        graft: None,
+        confirmations: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
@@ -32,7 +34,9 @@ fn insert_and_query(
         .into_iter()
         .map(|op| op.into())
         .collect();
-    STORE.create_subgraph_deployment(&schema, ops).unwrap();
+    STORE
+        .create_subgraph_deployment(&schema, ops, None)
+        .unwrap();
 
     let insert_ops = entities
         .into_iter()
@@ -89,6 +89,7 @@ fn multiple_data_sources_per_subgraph() {
             _: Arc<LightEthereumBlock>,
             _: Arc<Transaction>,
             _: Arc<Log>,
+            _: Option<Arc<TransactionReceipt>>,
             _: BlockState,
         ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
             unimplemented!();
@@ -0,0 +1,871 @@
+use ethabi::Token;
+use futures::future::{self, Loop};
+use futures::prelude::*;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
+use graph::prelude::*;
+use web3::types::*;
+
+lazy_static! {
+    /// How long a request may take before it is retried with another provider of the same
+    /// network. Requests that only one provider can serve are never cut short.
+    static ref FAILOVER_TIMEOUT: Duration = Duration::from_secs(
+        std::env::var("GRAPH_ETHEREUM_FAILOVER_TIMEOUT")
+            .unwrap_or("180".into())
+            .parse::<u64>()
+            .expect("invalid GRAPH_ETHEREUM_FAILOVER_TIMEOUT env var")
+    );
+
+    /// How many times a request goes around all the providers that can serve it before it
+    /// fails with the error of the last provider.
+    static ref FAILOVER_ROUNDS: usize = std::env::var("GRAPH_ETHEREUM_FAILOVER_ROUNDS")
+        .unwrap_or("5".into())
+        .parse::<usize>()
+        .expect("invalid GRAPH_ETHEREUM_FAILOVER_ROUNDS env var");
+}
+
+const NO_CAPABILITIES: NodeCapabilities = NodeCapabilities {
+    archive: false,
+    traces: false,
+};
+
+const ARCHIVE: NodeCapabilities = NodeCapabilities {
+    archive: true,
+    traces: false,
+};
+
+const TRACES: NodeCapabilities = NodeCapabilities {
+    archive: false,
+    traces: true,
+};
+
+/// An Ethereum node that serves requests for a network.
+#[derive(Clone)]
+pub struct EthereumProvider {
+    /// Identifies the provider in logs and metrics
+    pub name: String,
+    pub capabilities: NodeCapabilities,
+    pub adapter: Arc<dyn EthereumAdapterTrait>,
+    /// The metrics that `adapter` records its requests in
+    pub metrics: Arc<ProviderEthRpcMetrics>,
+}
+
+/// An `EthereumAdapter` that spreads requests across several providers for the same network.
+///
+/// Requests go to the healthy providers in turn. When a request to a provider fails or times
+/// out, the error is recorded in the provider's metrics and the request is retried with the
+/// next provider, going around all of them a few times until one succeeds; providers whose
+/// requests keep failing are avoided for a while. Requests that need traces only go to providers tagged with
+/// the `traces` capability, and contract calls prefer providers tagged with `archive`.
+///
+/// The adapters of a pool with several providers should give up on requests after a few
+/// attempts; a pool with just one provider leaves retrying requests to its adapter.
+pub struct EthereumAdapterPool {
+    network: String,
+    providers: Vec<EthereumProvider>,
+    next: AtomicUsize,
+}
+
+impl EthereumAdapterPool {
+    pub fn new(network: String, providers: Vec<EthereumProvider>) -> Result<Self, Error> {
+        if providers.is_empty() {
+            return Err(format_err!(
+                "no Ethereum providers for network `{}`",
+                network
+            ));
+        }
+        Ok(EthereumAdapterPool {
+            network,
+            providers,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// The providers that have the `required` capabilities, healthy ones first. Healthy
+    /// providers take turns being first, which spreads requests across them.
+    fn providers(&self, required: &NodeCapabilities) -> Vec<EthereumProvider> {
+        let (mut healthy, unhealthy) = self.partition_by_health(required);
+        if !healthy.is_empty() {
+            let first = self.next.fetch_add(1, Ordering::Relaxed) % healthy.len();
+            healthy.rotate_left(first);
+        }
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// The providers to ask about the chain head, in the order in which they were
+    /// configured, healthy ones first. Different providers are rarely at exactly the same
+    /// block, and asking them in turn would make the chain head jump back and forth.
+    fn head_providers(&self) -> Vec<EthereumProvider> {
+        let (mut healthy, unhealthy) = self.partition_by_health(&NO_CAPABILITIES);
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    fn partition_by_health(
+        &self,
+        required: &NodeCapabilities,
+    ) -> (Vec<EthereumProvider>, Vec<EthereumProvider>) {
+        self.providers
+            .iter()
+            .filter(|provider| provider.capabilities.supports(required))
+            .cloned()
+            .partition(|provider| provider.metrics.is_healthy())
+    }
+
+    /// Like `providers`, but fails if no provider has the `required` capabilities.
+    fn capable_providers(
+        &self,
+        required: &NodeCapabilities,
+    ) -> Result<Vec<EthereumProvider>, Error> {
+        let providers = self.providers(required);
+        if providers.is_empty() {
+            Err(format_err!(
+                "none of the Ethereum providers for network `{}` has the capabilities `{}`, \
+                 which call handlers and block handlers with a call filter need",
+                self.network,
+                required
+            ))
+        } else {
+            Ok(providers)
+        }
+    }
+
+    /// Finding triggers needs traces if the subgraph has call handlers or
+    /// block handlers with a call filter.
+    fn capable_providers_for_triggers(
+        &self,
+        call_filter: &EthereumCallFilter,
+        block_filter: &EthereumBlockFilter,
+    ) -> Result<Vec<EthereumProvider>, Error> {
+        if call_filter.is_empty() && block_filter.contract_addresses.is_empty() {
+            Ok(self.providers(&NO_CAPABILITIES))
+        } else {
+            self.capable_providers(&TRACES)
+        }
+    }
+
+    /// Send `request` to the `providers` in order until one of them succeeds, starting over
+    /// with the first one after the last one failed, for at most `FAILOVER_ROUNDS` rounds.
+    /// Each attempt may take at most `FAILOVER_TIMEOUT` if there is another provider to try.
+    fn with_failover<T, E, F>(
+        &self,
+        logger: &Logger,
+        providers: Vec<EthereumProvider>,
+        method: &'static str,
+        request: F,
+    ) -> Box<dyn Future<Item = T, Error = E> + Send>
+    where
+        T: Send + 'static,
+        E: RequestError,
+        F: Fn(&Arc<dyn EthereumAdapterTrait>) -> Box<dyn Future<Item = T, Error = E> + Send>
+            + Send
+            + 'static,
+    {
+        if self.providers.len() == 1 {
+            // The adapter retries the request itself
+            let provider = providers[0].clone();
+            return Box::new(request(&provider.adapter).then(move |result| {
+                match &result {
+                    Ok(_) => provider.metrics.add_success(),
+                    Err(e) if e.is_provider_error() => provider.metrics.add_error(method),
+                    Err(_) => (),
+                }
+                result
+            }));
+        }
+
+        let logger = logger.clone();
+        Box::new(future::loop_fn(0, move |attempt| {
+            let providers_len = providers.len();
+            let provider: EthereumProvider = providers[attempt % providers_len].clone();
+            let round_complete = (attempt + 1) % providers_len == 0;
+            let logger = logger.clone();
+
+            let response = request(&provider.adapter);
+            let response: Box<dyn Future<Item = T, Error = E> + Send> = if providers_len > 1 {
+                Box::new(
+                    tokio_timer::Timeout::new(response, *FAILOVER_TIMEOUT)
+                        .map_err(move |e| e.into_inner().unwrap_or_else(|| E::timeout(method))),
+                )
+            } else {
+                response
+            };
+
+            response.then(
+                move |result| -> Box<dyn Future<Item = _, Error = E> + Send> {
+                    match result {
+                        Ok(value) => {
+                            provider.metrics.add_success();
+                            Box::new(future::ok(Loop::Break(value)))
+                        }
+                        Err(e) => {
+                            if !e.is_provider_error() {
+                                return Box::new(future::err(e));
+                            }
+                            provider.metrics.add_error(method);
+                            let rounds = (attempt + 1) / providers_len;
+                            if round_complete && rounds >= *FAILOVER_ROUNDS {
+                                warn!(
+                                    logger, "Every Ethereum provider failed, giving up";
+                                    "method" => method,
+                                    "rounds" => rounds,
+                                    "error" => e.to_string(),
+                                );
+                                return Box::new(future::err(e));
+                            }
+                            warn!(
+                                logger, "Ethereum provider failed, trying the next one";
+                                "provider" => &provider.name,
+                                "method" => method,
+                                "error" => e.to_string(),
+                            );
+                            let next = future::ok(Loop::Continue(attempt + 1));
+                            if round_complete {
+                                // Every provider failed; give them some time to recover
+                                Box::new(
+                                    tokio_timer::Delay::new(
+                                        Instant::now() + round_delay(rounds - 1),
+                                    )
+                                    .then(|_| next),
+                                )
+                            } else {
+                                Box::new(next)
+                            }
+                        }
+                    }
+                },
+            )
+        }))
+    }
+
+    /// Stream the items `request` returns for `keys` from the `providers`, like
+    /// `with_failover` does for futures. When a provider fails, the next one is asked only
+    /// for the keys whose items have not been returned yet; `key_of` tells which key an
+    /// item belongs to.
+    fn stream_with_failover<K, T, F, G>(
+        &self,
+        logger: &Logger,
+        providers: Vec<EthereumProvider>,
+        method: &'static str,
+        keys: Vec<K>,
+        key_of: G,
+        request: F,
+    ) -> Box<dyn Stream<Item = T, Error = Error> + Send>
+    where
+        K: Clone + Eq + Hash + Send + 'static,
+        T: Send + 'static,
+        F: Fn(
+                &Arc<dyn EthereumAdapterTrait>,
+                Vec<K>,
+            ) -> Box<dyn Stream<Item = T, Error = Error> + Send>
+            + Send
+            + Sync
+            + 'static,
+        G: Fn(&T) -> K + Send + 'static,
+    {
+        if self.providers.len() == 1 {
+            // The adapter retries the requests itself
+            let metrics = providers[0].metrics.clone();
+            return Box::new(request(&providers[0].adapter, keys).map_err(move |e| {
+                if e.is_provider_error() {
+                    metrics.add_error(method);
+                }
+                e
+            }));
+        }
+
+        let mut stream = FailoverStream {
+            logger: logger.clone(),
+            providers,
+            method,
+            keys,
+            returned: HashSet::new(),
+            key_of,
+            request: Arc::new(request),
+            attempt: 0,
+            current: Box::new(stream::empty()),
+        };
+        stream.request_missing();
+        Box::new(stream)
+    }
+}
+
+/// The errors of `EthereumAdapter` requests, as seen by the pool.
+trait RequestError: fmt::Display + Send + 'static {
+    /// Whether the provider is to blame for the error. Other errors, like reverted contract
+    /// calls, would be returned by every provider and are therefore not retried.
+    fn is_provider_error(&self) -> bool;
+
+    /// The error for a request to `method` that took too long.
+    fn timeout(method: &'static str) -> Self;
+}
+
+impl RequestError for Error {
+    /// Only errors of the JSON-RPC transport, errors the node responded with and requests
+    /// that took too long are the provider's fault. Other errors, like those of the chain
+    /// store that some requests use, would happen with every provider.
+    fn is_provider_error(&self) -> bool {
+        self.iter_chain().any(|cause| {
+            cause.downcast_ref::<web3::Error>().is_some()
+                || cause.downcast_ref::<EthereumTimeoutError>().is_some()
+        })
+    }
+
+    fn timeout(method: &'static str) -> Self {
+        EthereumTimeoutError(format!("respond to {}", method)).into()
+    }
+}
+
+impl RequestError for EthereumAdapterError {
+    /// A missing block is most likely the result of a reorg and not the provider's fault.
+    fn is_provider_error(&self) -> bool {
+        match self {
+            EthereumAdapterError::BlockUnavailable(_) => false,
+            EthereumAdapterError::Unknown(e) => e.is_provider_error(),
+        }
+    }
+
+    fn timeout(method: &'static str) -> Self {
+        EthereumAdapterError::Unknown(Error::timeout(method))
+    }
+}
+
+impl RequestError for EthereumContractCallError {
+    /// Calls that revert or do not match the ABI fail the same way with every provider.
+    fn is_provider_error(&self) -> bool {
+        match self {
+            EthereumContractCallError::Web3Error(_) | EthereumContractCallError::Timeout => true,
+            EthereumContractCallError::ABIError(_)
+            | EthereumContractCallError::TypeError(_, _)
+            | EthereumContractCallError::Revert(_) => false,
+        }
+    }
+
+    fn timeout(_: &'static str) -> Self {
+        EthereumContractCallError::Timeout
+    }
+}
+
+/// How long to wait before going around the providers once more after all of them failed
+/// `rounds` times.
+fn round_delay(rounds: usize) -> Duration {
+    Duration::from_millis(500 << rounds.min(6))
+}
+
+/// Fail `stream` if it takes longer than `FAILOVER_TIMEOUT` to produce its next item.
+fn timed_stream<T: Send + 'static>(
+    stream: Box<dyn Stream<Item = T, Error = Error> + Send>,
+    method: &'static str,
+) -> Box<dyn Stream<Item = T, Error = Error> + Send> {
+    Box::new(
+        tokio_timer::Timeout::new(stream, *FAILOVER_TIMEOUT)
+            .map_err(move |e| e.into_inner().unwrap_or_else(|| Error::timeout(method))),
+    )
+}
+
+/// The stream returned by `EthereumAdapterPool::stream_with_failover`.
+struct FailoverStream<K, T, F, G> {
+    logger: Logger,
+    providers: Vec<EthereumProvider>,
+    method: &'static str,
+    /// All keys, in the order in which they were requested
+    keys: Vec<K>,
+    /// The keys whose items were returned already
+    returned: HashSet<K>,
+    key_of: G,
+    request: Arc<F>,
+    attempt: usize,
+    current: Box<dyn Stream<Item = T, Error = Error> + Send>,
+}
+
+impl<K, T, F, G> FailoverStream<K, T, F, G>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Send + 'static,
+    F: Fn(
+            &Arc<dyn EthereumAdapterTrait>,
+            Vec<K>,
+        ) -> Box<dyn Stream<Item = T, Error = Error> + Send>
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Ask the provider for the current attempt for the keys that are still missing.
+    fn request_missing(&mut self) {
+        let provider = self.providers[self.attempt % self.providers.len()].clone();
+        let missing: Vec<K> = self
+            .keys
+            .iter()
+            .filter(|key| !self.returned.contains(key))
+            .cloned()
+            .collect();
+        let request = self.request.clone();
+        let method = self.method;
+        let timed = self.providers.len() > 1;
+        let stream = move || {
+            let stream = request(&provider.adapter, missing);
+            if timed {
+                timed_stream(stream, method)
+            } else {
+                stream
+            }
+        };
+
+        self.current = if self.attempt > 0 && self.attempt % self.providers.len() == 0 {
+            // Every provider failed; give them some time to recover
+            let delay = round_delay(self.attempt / self.providers.len() - 1);
+            Box::new(
+                tokio_timer::Delay::new(Instant::now() + delay)
+                    .map_err(Error::from)
+                    .map(move |_| stream())
+                    .flatten_stream(),
+            )
+        } else {
+            stream()
+        };
+    }
+}
+
+impl<K, T, F, G> Stream for FailoverStream<K, T, F, G>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Send + 'static,
+    F: Fn(
+            &Arc<dyn EthereumAdapterTrait>,
+            Vec<K>,
+        ) -> Box<dyn Stream<Item = T, Error = Error> + Send>
+        + Send
+        + Sync
+        + 'static,
+    G: Fn(&T) -> K,
+{
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        loop {
+            let provider = self.providers[self.attempt % self.providers.len()].clone();
+            match self.current.poll() {
+                Ok(Async::Ready(Some(item))) => {
+                    self.returned.insert((self.key_of)(&item));
+                    return Ok(Async::Ready(Some(item)));
+                }
+                Ok(Async::Ready(None)) => {
+                    provider.metrics.add_success();
+                    return Ok(Async::Ready(None));
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    if !e.is_provider_error() {
+                        return Err(e);
+                    }
+                    provider.metrics.add_error(self.method);
+                    self.attempt += 1;
+                    let rounds = self.attempt / self.providers.len();
+                    if self.attempt % self.providers.len() == 0 && rounds >= *FAILOVER_ROUNDS {
+                        warn!(
+                            self.logger, "Every Ethereum provider failed, giving up";
+                            "method" => self.method,
+                            "rounds" => rounds,
+                            "error" => e.to_string(),
+                        );
+                        return Err(e);
+                    }
+                    warn!(
+                        self.logger, "Ethereum provider failed, trying the next one";
+                        "provider" => &provider.name,
+                        "method" => self.method,
+                        "error" => e.to_string(),
+                    );
+                    self.request_missing();
+                }
+            }
+        }
+    }
+}
+
+impl EthereumAdapterTrait for EthereumAdapterPool {
+    fn net_identifiers(
+        &self,
+        logger: &Logger,
+    ) -> Box<dyn Future<Item = EthereumNetworkIdentifier, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "net_identifiers",
+            move |adapter| adapter.net_identifiers(&logger_for_request),
+        )
+    }
+
+    fn latest_block(
+        &self,
+        logger: &Logger,
+    ) -> Box<dyn Future<Item = LightEthereumBlock, Error = EthereumAdapterError> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.head_providers(),
+            "latest_block",
+            move |adapter| adapter.latest_block(&logger_for_request),
+        )
+    }
+
+    fn subscribe_new_heads(
+        &self,
+        logger: &Logger,
+    ) -> Box<
+        dyn Future<
+                Item = Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>,
+                Error = Error,
+            > + Send,
+    > {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.head_providers(),
+            "subscribe_new_heads",
+            move |adapter| adapter.subscribe_new_heads(&logger_for_request),
+        )
+    }
+
+    fn load_block(
+        &self,
+        logger: &Logger,
+        block_hash: H256,
+    ) -> Box<dyn Future<Item = LightEthereumBlock, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "load_block",
+            move |adapter| adapter.load_block(&logger_for_request, block_hash),
+        )
+    }
+
+    fn load_blocks(
+        &self,
+        logger: Logger,
+        chain_store: Arc<dyn ChainStore>,
+        block_hashes: HashSet<H256>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.stream_with_failover(
+            &logger,
+            self.providers(&NO_CAPABILITIES),
+            "load_blocks",
+            block_hashes.into_iter().collect(),
+            |block: &LightEthereumBlock| block.hash.unwrap(),
+            move |adapter, block_hashes| {
+                adapter.load_blocks(
+                    logger_for_request.clone(),
+                    chain_store.clone(),
+                    block_hashes.into_iter().collect(),
+                )
+            },
+        )
+    }
+
+    fn blocks_by_numbers(
+        &self,
+        logger: &Logger,
+        block_numbers: Vec<u64>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.stream_with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "blocks_by_numbers",
+            block_numbers,
+            |block: &LightEthereumBlock| block.number.unwrap().as_u64(),
+            move |adapter, block_numbers| {
+                adapter.blocks_by_numbers(&logger_for_request, block_numbers)
+            },
+        )
+    }
+
+    fn block_range_to_ptrs(
+        &self,
+        logger: Logger,
+        from: u64,
+        to: u64,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            &logger,
+            self.providers(&NO_CAPABILITIES),
+            "block_range_to_ptrs",
+            move |adapter| adapter.block_range_to_ptrs(logger_for_request.clone(), from, to),
+        )
+    }
+
+    fn block_by_hash(
+        &self,
+        logger: &Logger,
+        block_hash: H256,
+    ) -> Box<dyn Future<Item = Option<LightEthereumBlock>, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "block_by_hash",
+            move |adapter| adapter.block_by_hash(&logger_for_request, block_hash),
+        )
+    }
+
+    fn load_full_block(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        block: LightEthereumBlock,
+    ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "load_full_block",
+            move |adapter| {
+                adapter.load_full_block(&logger_for_request, chain_store.clone(), block.clone())
+            },
+        )
+    }
+
+    fn load_transaction_receipts(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        block_hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Box<dyn Future<Item = Vec<TransactionReceipt>, Error = EthereumAdapterError> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "load_transaction_receipts",
+            move |adapter| {
+                adapter.load_transaction_receipts(
+                    &logger_for_request,
+                    chain_store.clone(),
+                    block_hash,
+                    transaction_hashes.clone(),
+                )
+            },
+        )
+    }
+
+    fn block_pointer_from_number(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+    ) -> Box<dyn Future<Item = EthereumBlockPointer, Error = EthereumAdapterError> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "block_pointer_from_number",
+            move |adapter| adapter.block_pointer_from_number(&logger_for_request, block_number),
+        )
+    }
+
+    fn block_hash_by_block_number(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+    ) -> Box<dyn Future<Item = Option<H256>, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "block_hash_by_block_number",
+            move |adapter| adapter.block_hash_by_block_number(&logger_for_request, block_number),
+        )
+    }
+
+    fn is_on_main_chain(
+        &self,
+        logger: &Logger,
+        metrics: Arc<SubgraphEthRpcMetrics>,
+        block_ptr: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "is_on_main_chain",
+            move |adapter| {
+                adapter.is_on_main_chain(&logger_for_request, metrics.clone(), block_ptr)
+            },
+        )
+    }
+
+    fn calls_in_block(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        block_number: u64,
+        block_hash: H256,
+    ) -> Box<dyn Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+        let providers = match self.capable_providers(&TRACES) {
+            Ok(providers) => providers,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let logger_for_request = logger.clone();
+        self.with_failover(logger, providers, "calls_in_block", move |adapter| {
+            adapter.calls_in_block(
+                &logger_for_request,
+                chain_store.clone(),
+                subgraph_metrics.clone(),
+                block_number,
+                block_hash,
+            )
+        })
+    }
+
+    fn blocks_with_triggers(
+        self: Arc<Self>,
+        logger: Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+        call_filter: EthereumCallFilter,
+        block_filter: EthereumBlockFilter,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockWithTriggers>, Error = Error> + Send> {
+        let providers = match self.capable_providers_for_triggers(&call_filter, &block_filter) {
+            Ok(providers) => providers,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let logger_for_request = logger.clone();
+        self.with_failover(&logger, providers, "blocks_with_triggers", move |adapter| {
+            adapter.clone().blocks_with_triggers(
+                logger_for_request.clone(),
+                chain_store.clone(),
+                subgraph_metrics.clone(),
+                from,
+                to,
+                log_filter.clone(),
+                call_filter.clone(),
+                block_filter.clone(),
+            )
+        })
+    }
+
+    fn logs_in_block_range(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+    ) -> Box<dyn Future<Item = Vec<Log>, Error = Error> + Send> {
+        let logger_for_request = logger.clone();
+        self.with_failover(
+            logger,
+            self.providers(&NO_CAPABILITIES),
+            "logs_in_block_range",
+            move |adapter| {
+                adapter.logs_in_block_range(
+                    &logger_for_request,
+                    chain_store.clone(),
+                    subgraph_metrics.clone(),
+                    from,
+                    to,
+                    log_filter.clone(),
+                )
+            },
+        )
+    }
+
+    fn calls_in_block_range(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        call_filter: EthereumCallFilter,
+    ) -> Box<dyn Stream<Item = EthereumCall, Error = Error> + Send> {
+        let providers = match self.capable_providers(&TRACES) {
+            Ok(providers) => providers,
+            Err(e) => return Box::new(stream::once(Err(e))),
+        };
+        // Calls do not say which part of the range they came from; if a provider fails,
+        // the next one has to trace the whole range again
+        let logger_for_request = logger.clone();
+        let calls = self.with_failover(logger, providers, "calls_in_block_range", move |adapter| {
+            Box::new(
+                adapter
+                    .calls_in_block_range(
+                        &logger_for_request,
+                        chain_store.clone(),
+                        subgraph_metrics.clone(),
+                        from,
+                        to,
+                        call_filter.clone(),
+                    )
+                    .collect(),
+            )
+        });
+        Box::new(calls.map(stream::iter_ok).flatten_stream())
+    }
+
+    fn contract_call(
+        &self,
+        logger: &Logger,
+        call: EthereumContractCall,
+        cache: Arc<dyn EthereumCallCache>,
+    ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
+        // Calls at old blocks need an archive node; other nodes are only
+        // used when no archive node is available
+        let mut providers = self.providers(&ARCHIVE);
+        providers.extend(
+            self.providers(&NO_CAPABILITIES)
+                .into_iter()
+                .filter(|provider| !provider.capabilities.archive),
+        );
+
+        let logger_for_request = logger.clone();
+        self.with_failover(logger, providers, "contract_call", move |adapter| {
+            adapter.contract_call(&logger_for_request, call.clone(), cache.clone())
+        })
+    }
+
+    fn triggers_in_block(
+        self: Arc<Self>,
+        logger: Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        log_filter: EthereumLogFilter,
+        call_filter: EthereumCallFilter,
+        block_filter: EthereumBlockFilter,
+        ethereum_block: BlockFinality,
+    ) -> Box<dyn Future<Item = EthereumBlockWithTriggers, Error = Error> + Send> {
+        let providers = match self.capable_providers_for_triggers(&call_filter, &block_filter) {
+            Ok(providers) => providers,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let logger_for_request = logger.clone();
+        self.with_failover(&logger, providers, "triggers_in_block", move |adapter| {
+            adapter.clone().triggers_in_block(
+                logger_for_request.clone(),
+                chain_store.clone(),
+                subgraph_metrics.clone(),
+                log_filter.clone(),
+                call_filter.clone(),
+                block_filter.clone(),
+                ethereum_block.clone(),
+            )
+        })
+    }
+}
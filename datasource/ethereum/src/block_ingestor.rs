@@ -1,9 +1,41 @@
 use std::time::Duration;
 use std::time::Instant;
 
+use lazy_static::lazy_static;
+
 use graph::prelude::*;
 use web3::types::*;
 
+lazy_static! {
+    /// If no new chain head arrives through the `newHeads` subscription for this many seconds,
+    /// the subscription is considered lost.
+    static ref NEW_HEADS_TIMEOUT: Duration = std::env::var("ETHEREUM_NEW_HEADS_TIMEOUT")
+        .ok()
+        .map(|s| Duration::from_secs(
+            s.parse::<u64>()
+                .expect("invalid ETHEREUM_NEW_HEADS_TIMEOUT env var")
+        ))
+        .unwrap_or(Duration::from_secs(120));
+
+    /// How long to poll for new blocks after failing to subscribe to new chain heads or losing
+    /// the subscription, before trying to subscribe again.
+    static ref RESUBSCRIBE_INTERVAL: Duration = std::env::var("ETHEREUM_RESUBSCRIBE_INTERVAL")
+        .ok()
+        .map(|s| Duration::from_secs(
+            s.parse::<u64>()
+                .expect("invalid ETHEREUM_RESUBSCRIBE_INTERVAL env var")
+        ))
+        .unwrap_or(Duration::from_secs(60));
+}
+
+/// If the latest block is more than this many blocks ahead of the chain head in the store, the
+/// gap is backfilled by requesting blocks by number in parallel batches, instead of walking back
+/// through missing parents one block at a time.
+const BACKFILL_THRESHOLD: u64 = 10;
+
+/// Maximum number of backfilled blocks for which to load receipts in parallel.
+const BACKFILL_PARALLEL_BLOCKS: usize = 20;
+
 pub struct BlockIngestor<S>
 where
     S: ChainStore,
@@ -14,6 +46,7 @@ where
     network_name: String,
     logger: Logger,
     polling_interval: Duration,
+    can_subscribe: bool,
 }
 
 impl<S> BlockIngestor<S>
@@ -27,6 +60,7 @@ where
         network_name: String,
         logger_factory: &LoggerFactory,
         polling_interval: Duration,
+        can_subscribe: bool,
     ) -> Result<BlockIngestor<S>, Error> {
         let logger = logger_factory.component_logger(
             "BlockIngestor",
@@ -44,39 +78,73 @@ where
             network_name,
             logger,
             polling_interval,
+            can_subscribe,
         })
     }
 
-    pub fn into_polling_stream(self) -> impl Future<Item = (), Error = ()> {
+    /// Ingest blocks whenever the Ethereum node announces a new chain head through a `newHeads`
+    /// subscription. If the transport can't subscribe, which is the case for JSON-RPC over HTTP,
+    /// poll for new blocks at the polling interval instead. If subscribing fails or the
+    /// subscription is lost, poll for `ETHEREUM_RESUBSCRIBE_INTERVAL` seconds before subscribing
+    /// again.
+    pub fn into_stream(self) -> Box<dyn Future<Item = (), Error = ()> + Send> {
         // Currently, there is no way to stop block ingestion, so just leak self
         let static_self: &'static _ = Box::leak(Box::new(self));
 
-        // Create stream that emits at polling interval
-        tokio::timer::Interval::new(Instant::now(), static_self.polling_interval)
-            .map_err(move |e| {
-                error!(static_self.logger, "timer::Interval failed: {:?}", e);
-            })
-            .for_each(move |_| {
-                // Attempt to poll
-                static_self.do_poll().then(move |result| {
-                    if let Err(err) = result {
-                        // Some polls will fail due to transient issues
-                        match err {
-                            EthereumAdapterError::BlockUnavailable(_) => {
-                                trace!(
-                                    static_self.logger,
-                                    "Trying again after block polling failed: {}",
-                                    err
-                                );
+        if !static_self.can_subscribe {
+            info!(
+                static_self.logger,
+                "Polling for new blocks, since the transport can't subscribe to new chain heads"
+            );
+            return Box::new(static_self.poll(None));
+        }
+
+        Box::new(future::loop_fn((), move |()| {
+            static_self
+                .eth_adapter
+                .subscribe_new_heads(&static_self.logger)
+                .then(
+                    move |result| -> Box<dyn Future<Item = _, Error = _> + Send> {
+                        match result {
+                            Ok(new_heads) => {
+                                info!(static_self.logger, "Subscribed to new chain heads");
+                                Box::new(static_self.follow_new_heads(new_heads))
                             }
-                            EthereumAdapterError::Unknown(inner_err) => {
+                            Err(e) => {
                                 warn!(
                                     static_self.logger,
-                                    "Trying again after block polling failed: {}", inner_err
+                                    "Failed to subscribe to new chain heads";
+                                    "error" => e.to_string(),
                                 );
+                                Box::new(future::ok(()))
                             }
                         }
-                    }
+                    },
+                )
+                .and_then(move |()| {
+                    info!(
+                        static_self.logger,
+                        "Polling for new blocks until subscribing to new chain heads again";
+                        "retry_delay_s" => RESUBSCRIBE_INTERVAL.as_secs(),
+                    );
+                    static_self.poll(Some(Instant::now() + *RESUBSCRIBE_INTERVAL))
+                })
+                .map(|()| future::Loop::Continue(()))
+        }))
+    }
+
+    /// Poll for new blocks at the polling interval, forever or until `until`.
+    fn poll(&'static self, until: Option<Instant>) -> impl Future<Item = (), Error = ()> + Send {
+        // Create stream that emits at polling interval
+        tokio::timer::Interval::new(Instant::now(), self.polling_interval)
+            .map_err(move |e| {
+                error!(self.logger, "timer::Interval failed: {:?}", e);
+            })
+            .take_while(move |_| Ok(until.map_or(true, |until| Instant::now() < until)))
+            .for_each(move |_| {
+                // Attempt to poll
+                self.do_poll().then(move |result| {
+                    self.log_poll_result(result);
 
                     // Continue polling even if polling failed
                     future::ok(())
@@ -84,6 +152,75 @@ where
             })
     }
 
+    /// Ingest blocks for every new chain head until the subscription fails or ends, or until
+    /// no new chain head has been received for `ETHEREUM_NEW_HEADS_TIMEOUT` seconds.
+    fn follow_new_heads(
+        &'static self,
+        new_heads: Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>,
+    ) -> impl Future<Item = (), Error = ()> + Send {
+        let new_heads = tokio::timer::Timeout::new(new_heads, *NEW_HEADS_TIMEOUT).map_err(|e| {
+            e.into_inner().unwrap_or_else(|| {
+                format_err!(
+                    "no new chain head received for {}s",
+                    NEW_HEADS_TIMEOUT.as_secs()
+                )
+            })
+        });
+
+        // Poll once right away to pick up blocks produced before subscribing
+        stream::once(Ok(None))
+            .chain(new_heads.map(Some))
+            .for_each(move |head_ptr: Option<EthereumBlockPointer>| {
+                if let Some(head_ptr) = head_ptr {
+                    trace!(
+                        self.logger,
+                        "Received new chain head";
+                        "block_number" => head_ptr.number,
+                        "block_hash" => format!("{:x}", head_ptr.hash),
+                    );
+                }
+
+                // The new head is only a trigger; blocks are ingested the same way as when
+                // polling, so reorgs are handled through the `ChainStore`
+                self.do_poll().then(move |result| {
+                    self.log_poll_result(result);
+                    future::ok(())
+                })
+            })
+            .then(move |result| {
+                match result {
+                    Ok(()) => warn!(self.logger, "Subscription to new chain heads ended"),
+                    Err(e) => warn!(
+                        self.logger,
+                        "Subscription to new chain heads failed";
+                        "error" => e.to_string(),
+                    ),
+                }
+                future::ok(())
+            })
+    }
+
+    fn log_poll_result(&self, result: Result<(), EthereumAdapterError>) {
+        if let Err(err) = result {
+            // Some polls will fail due to transient issues
+            match err {
+                EthereumAdapterError::BlockUnavailable(_) => {
+                    trace!(
+                        self.logger,
+                        "Trying again after block polling failed: {}",
+                        err
+                    );
+                }
+                EthereumAdapterError::Unknown(inner_err) => {
+                    warn!(
+                        self.logger,
+                        "Trying again after block polling failed: {}", inner_err
+                    );
+                }
+            }
+        }
+    }
+
     fn do_poll<'a>(&'a self) -> impl Future<Item = (), Error = EthereumAdapterError> + 'a {
         trace!(self.logger, "BlockIngestor::do_poll");
         let network_name = self.network_name.clone();
@@ -137,13 +274,17 @@ where
                         }
 
                         Box::new(
-                            self.eth_adapter.load_full_block(&self.logger, latest_block)
-                            .and_then(move |latest_block: EthereumBlock| {
-                                // Store latest block in block store.
+                            self.backfill_blocks(&latest_block, head_block_ptr_opt)
+                            .join(self.eth_adapter.load_full_block(&self.logger, self.chain_store.clone(), latest_block))
+                            .and_then(move |(backfilled_blocks, latest_block): (Vec<EthereumBlock>, EthereumBlock)| {
+                                // Store latest block in block store, together with any blocks
+                                // that were backfilled.
                                 // Might be a no-op if latest block is one that we have seen.
                                 // ingest_blocks will return a (potentially incomplete) list of blocks that are
                                 // missing.
-                                self.ingest_blocks(stream::once(Ok(latest_block)))
+                                self.ingest_blocks(stream::iter_ok(
+                                    std::iter::once(latest_block).chain(backfilled_blocks),
+                                ))
                             }).and_then(move |missing_block_hashes| {
                                 // Repeatedly fetch missing parent blocks, and ingest them.
                                 // ingest_blocks will continue to tell us about more missing parent
@@ -183,6 +324,76 @@ where
             })
     }
 
+    /// If the gap between the chain head in the store and the latest block is large, load the
+    /// blocks in `[latest - ancestor_count, latest)` that are missing from the store by number,
+    /// in parallel batches. Only the blocks that form an unbroken chain of parent hashes leading
+    /// up to the latest block are returned; anything else is left to the missing parent loop.
+    fn backfill_blocks<'a>(
+        &'a self,
+        latest_block: &LightEthereumBlock,
+        head_block_ptr_opt: Option<EthereumBlockPointer>,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlock>, Error = EthereumAdapterError> + Send + 'a> {
+        let latest_number = latest_block.number.unwrap().as_u64();
+        let oldest_number = latest_number.saturating_sub(self.ancestor_count);
+        let first_number = match head_block_ptr_opt {
+            None => oldest_number,
+            Some(head_block_ptr) => {
+                if head_block_ptr.number + BACKFILL_THRESHOLD >= latest_number {
+                    // Small gaps are cheaper to fill in through the missing parents
+                    return Box::new(future::ok(vec![]));
+                }
+                (head_block_ptr.number + 1).max(oldest_number)
+            }
+        };
+        if first_number >= latest_number {
+            return Box::new(future::ok(vec![]));
+        }
+
+        debug!(
+            self.logger,
+            "Backfilling blocks";
+            "from" => first_number,
+            "to" => latest_number - 1,
+        );
+
+        let logger = self.logger.clone();
+        let eth_adapter = self.eth_adapter.clone();
+        let chain_store: Arc<dyn ChainStore> = self.chain_store.clone();
+        let latest_parent_hash = latest_block.parent_hash;
+
+        Box::new(
+            self.eth_adapter
+                .blocks_by_numbers(&self.logger, (first_number..latest_number).collect())
+                .collect()
+                .from_err()
+                .and_then(move |blocks| {
+                    // Blocks near the head may have been reorged while they were being
+                    // requested. Walk back from the latest block and keep only the blocks that
+                    // are linked to it through their parent hashes.
+                    let mut expected_hash = latest_parent_hash;
+                    let linked_blocks = blocks
+                        .into_iter()
+                        .rev()
+                        .take_while(|block| {
+                            if block.hash == Some(expected_hash) {
+                                expected_hash = block.parent_hash;
+                                true
+                            } else {
+                                false
+                            }
+                        })
+                        .collect::<Vec<_>>();
+
+                    stream::iter_ok(linked_blocks)
+                        .map(move |block| {
+                            eth_adapter.load_full_block(&logger, chain_store.clone(), block)
+                        })
+                        .buffer_unordered(BACKFILL_PARALLEL_BLOCKS)
+                        .collect()
+                }),
+        )
+    }
+
     /// Put some blocks into the block store (if they are not there already), and try to update the
     /// head block pointer. If missing blocks prevent such an update, return a Vec with at least
     /// one of the missing blocks' hashes.
@@ -211,10 +422,12 @@ where
     ) -> Box<dyn Stream<Item = EthereumBlock, Error = EthereumAdapterError> + Send + 'a> {
         let logger = self.logger.clone();
         let eth_adapter = self.eth_adapter.clone();
+        let chain_store: Arc<dyn ChainStore> = self.chain_store.clone();
 
         let block_futures = block_hashes.iter().map(move |&block_hash| {
             let logger = logger.clone();
             let eth_adapter = eth_adapter.clone();
+            let chain_store = chain_store.clone();
 
             eth_adapter
                 .block_by_hash(&logger, block_hash)
@@ -222,9 +435,276 @@ where
                 .and_then(move |block_opt| {
                     block_opt.ok_or_else(|| EthereumAdapterError::BlockUnavailable(block_hash))
                 })
-                .and_then(move |block| eth_adapter.load_full_block(&logger, block))
+                .and_then(move |block| eth_adapter.load_full_block(&logger, chain_store, block))
         });
 
         Box::new(stream::futures_unordered(block_futures))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::util::ethereum::string_to_h256;
+    use mock::{MockEthereumAdapter, MockNewHeads, MockStore};
+
+    fn block_hash(number: u64) -> H256 {
+        string_to_h256(&format!("block{}", number))
+    }
+
+    fn block(number: u64) -> LightEthereumBlock {
+        let mut block = LightEthereumBlock::default();
+        block.number = Some(number.into());
+        block.hash = Some(block_hash(number));
+        if number > 0 {
+            block.parent_hash = block_hash(number - 1);
+        }
+        block
+    }
+
+    /// A block that replaced `block(number)` in a reorg.
+    fn fork_block(number: u64) -> LightEthereumBlock {
+        let mut block = block(number);
+        block.hash = Some(string_to_h256(&format!("fork{}", number)));
+        block
+    }
+
+    /// A mock adapter with `blocks`. Of several blocks with the same number, the Ethereum node
+    /// returns the first one when asked for that number.
+    fn adapter(blocks: impl IntoIterator<Item = LightEthereumBlock>) -> Arc<MockEthereumAdapter> {
+        let adapter = Arc::new(MockEthereumAdapter::default());
+        for block in blocks {
+            adapter.add_block(block);
+        }
+        adapter
+    }
+
+    /// A store whose chain head is `head`, with all blocks up to it.
+    fn store(head: u64) -> Arc<MockStore> {
+        let store = Arc::new(MockStore::new(vec![]));
+        store
+            .upsert_light_blocks((0..=head).map(block).collect())
+            .unwrap();
+        store.attempt_chain_head_update(head).unwrap();
+        store
+    }
+
+    fn ingestor(
+        adapter: &Arc<MockEthereumAdapter>,
+        store: &Arc<MockStore>,
+        ancestor_count: u64,
+        can_subscribe: bool,
+    ) -> BlockIngestor<MockStore> {
+        // Keep tests of lost subscriptions short
+        std::env::set_var("ETHEREUM_NEW_HEADS_TIMEOUT", "1");
+        std::env::set_var("ETHEREUM_RESUBSCRIBE_INTERVAL", "1");
+
+        let logger_factory = LoggerFactory::new(Logger::root(slog::Discard, o!()), None);
+        BlockIngestor::new(
+            store.clone(),
+            adapter.clone(),
+            ancestor_count,
+            "mainnet".to_owned(),
+            &logger_factory,
+            Duration::from_millis(10),
+            can_subscribe,
+        )
+        .unwrap()
+    }
+
+    /// Run `ingestor` in the background for `duration`.
+    fn ingest_for(ingestor: BlockIngestor<MockStore>, duration: Duration) {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ingestor.into_stream());
+        std::thread::sleep(duration);
+        runtime.shutdown_now().wait().unwrap();
+    }
+
+    fn head_ptr(number: u64) -> Option<EthereumBlockPointer> {
+        Some(EthereumBlockPointer::from(&block(number)))
+    }
+
+    fn backfill(
+        ingestor: &BlockIngestor<MockStore>,
+        latest_block: LightEthereumBlock,
+        head_block_ptr_opt: Option<EthereumBlockPointer>,
+    ) -> Vec<u64> {
+        let mut numbers = ingestor
+            .backfill_blocks(&latest_block, head_block_ptr_opt)
+            .wait()
+            .unwrap()
+            .iter()
+            .map(|block| block.block.number())
+            .collect::<Vec<_>>();
+        numbers.sort();
+        numbers
+    }
+
+    fn request_count(adapter: &MockEthereumAdapter, method: &str) -> usize {
+        adapter
+            .requests()
+            .iter()
+            .filter(|request| *request == method)
+            .count()
+    }
+
+    #[test]
+    fn backfill_blocks_linked_to_latest_block() {
+        let adapter = adapter((0..=20).map(block));
+
+        // Without a chain head, `ancestor_count` blocks are backfilled
+        let without_head = ingestor(&adapter, &store(0), 10, true);
+        assert_eq!(
+            backfill(&without_head, block(20), None),
+            (10..20).collect::<Vec<_>>()
+        );
+
+        // Otherwise, only the blocks after the chain head
+        let with_head = ingestor(&adapter, &store(4), 30, true);
+        assert_eq!(
+            backfill(&with_head, block(20), head_ptr(4)),
+            (5..20).collect::<Vec<_>>()
+        );
+
+        // Blocks that are not linked to the latest block are not backfilled
+        let mut latest_on_fork = block(20);
+        latest_on_fork.parent_hash = fork_block(19).hash.unwrap();
+        assert!(backfill(&with_head, latest_on_fork, head_ptr(4)).is_empty());
+    }
+
+    #[test]
+    fn backfill_only_beyond_threshold() {
+        let adapter = adapter((0..=20).map(block));
+        let ingestor = ingestor(&adapter, &store(4), 30, true);
+
+        // Small gaps are left to the missing parents
+        assert!(backfill(&ingestor, block(20), head_ptr(20 - BACKFILL_THRESHOLD)).is_empty());
+        assert_eq!(request_count(&adapter, "blocks_by_numbers"), 0);
+
+        assert_eq!(
+            backfill(&ingestor, block(20), head_ptr(20 - BACKFILL_THRESHOLD - 1)),
+            (20 - BACKFILL_THRESHOLD..20).collect::<Vec<_>>()
+        );
+        assert_eq!(request_count(&adapter, "blocks_by_numbers"), 1);
+    }
+
+    #[test]
+    fn backfill_stops_at_reorged_block() {
+        // The node returns block 15 of a fork for its number, while block 16 is still the
+        // child of the block 15 that is now only found by its hash
+        let adapter = adapter(
+            (0..15)
+                .map(block)
+                .chain(std::iter::once(fork_block(15)))
+                .chain((16..=20).map(block))
+                .chain(std::iter::once(block(15))),
+        );
+        let ingestor = ingestor(&adapter, &store(4), 30, true);
+
+        // Only the blocks after the reorged block are linked to the latest block
+        assert_eq!(
+            backfill(&ingestor, block(20), head_ptr(4)),
+            (16..20).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn poll_ingests_backfilled_blocks() {
+        let adapter = adapter((0..=20).map(block));
+        let store = store(4);
+        let ingestor = ingestor(&adapter, &store, 30, true);
+
+        ingestor.do_poll().wait().unwrap();
+
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(20));
+        assert_eq!(request_count(&adapter, "blocks_by_numbers"), 1);
+        assert_eq!(request_count(&adapter, "block_by_hash"), 0);
+    }
+
+    #[test]
+    fn poll_loads_parents_of_reorged_blocks_by_hash() {
+        let adapter = adapter(
+            (0..15)
+                .map(block)
+                .chain(std::iter::once(fork_block(15)))
+                .chain((16..=20).map(block))
+                .chain(std::iter::once(block(15))),
+        );
+        let store = store(4);
+        let ingestor = ingestor(&adapter, &store, 30, true);
+
+        ingestor.do_poll().wait().unwrap();
+
+        // Blocks 5 to 15 are not linked to the backfilled blocks and are loaded one at a time
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(20));
+        assert_eq!(request_count(&adapter, "block_by_hash"), 11);
+        let stored = store
+            .blocks(vec![block_hash(15), fork_block(15).hash.unwrap()])
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].hash, Some(block_hash(15)));
+    }
+
+    #[test]
+    fn poll_when_transport_cannot_subscribe() {
+        let adapter = adapter((0..=5).map(block));
+        let store = Arc::new(MockStore::new(vec![]));
+
+        ingest_for(
+            ingestor(&adapter, &store, 5, false),
+            Duration::from_millis(500),
+        );
+
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(5));
+        assert_eq!(request_count(&adapter, "subscribe_new_heads"), 0);
+        assert!(request_count(&adapter, "latest_block") > 1);
+    }
+
+    #[test]
+    fn resubscribe_after_failing_to_subscribe() {
+        let adapter = adapter((0..=5).map(block));
+        adapter.new_heads(MockNewHeads::Fail);
+        let store = Arc::new(MockStore::new(vec![]));
+
+        // Blocks are polled for until subscribing again after a second
+        ingest_for(
+            ingestor(&adapter, &store, 5, true),
+            Duration::from_millis(1500),
+        );
+
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(5));
+        assert_eq!(request_count(&adapter, "subscribe_new_heads"), 2);
+    }
+
+    #[test]
+    fn resubscribe_after_subscription_ends() {
+        let adapter = adapter((0..=5).map(block));
+        adapter.new_heads(MockNewHeads::End);
+        let store = Arc::new(MockStore::new(vec![]));
+
+        ingest_for(
+            ingestor(&adapter, &store, 5, true),
+            Duration::from_millis(1500),
+        );
+
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(5));
+        assert_eq!(request_count(&adapter, "subscribe_new_heads"), 2);
+    }
+
+    #[test]
+    fn resubscribe_after_subscription_times_out() {
+        let adapter = adapter((0..=5).map(block));
+        adapter.new_heads(MockNewHeads::Silent);
+        let store = Arc::new(MockStore::new(vec![]));
+
+        // The subscription is lost after a second without a new chain head, and blocks are
+        // polled for another second before subscribing again
+        ingest_for(
+            ingestor(&adapter, &store, 5, true),
+            Duration::from_millis(2500),
+        );
+
+        assert_eq!(store.chain_head_ptr().unwrap(), head_ptr(5));
+        assert_eq!(request_count(&adapter, "subscribe_new_heads"), 2);
+    }
+}
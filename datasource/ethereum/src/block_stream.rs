@@ -99,6 +99,7 @@ struct BlockStreamContext<S, C> {
     node_id: NodeId,
     subgraph_id: SubgraphDeploymentId,
     reorg_threshold: u64,
+    confirmations: u64,
     log_filter: EthereumLogFilter,
     call_filter: EthereumCallFilter,
     block_filter: EthereumBlockFilter,
@@ -117,6 +118,7 @@ impl<S, C> Clone for BlockStreamContext<S, C> {
             node_id: self.node_id.clone(),
             subgraph_id: self.subgraph_id.clone(),
             reorg_threshold: self.reorg_threshold,
+            confirmations: self.confirmations,
             log_filter: self.log_filter.clone(),
             call_filter: self.call_filter.clone(),
             block_filter: self.block_filter.clone(),
@@ -152,6 +154,7 @@ where
         start_blocks: Vec<u64>,
         templates_use_calls: bool,
         reorg_threshold: u64,
+        confirmations: u64,
         logger: Logger,
         metrics: Arc<BlockStreamMetrics>,
     ) -> Self {
@@ -166,6 +169,7 @@ where
                 node_id,
                 subgraph_id,
                 reorg_threshold,
+                confirmations,
                 logger,
                 log_filter,
                 call_filter,
@@ -262,14 +266,24 @@ where
             "number" => subgraph_ptr.map(|block| block.number),
         );
 
-        // Only continue if the subgraph block ptr is behind the head block ptr.
+        // Blocks with fewer than `confirmations` confirmations are not processed yet; with the
+        // default of zero confirmations, this is the head block.
+        let confirmed_number = match head_ptr.number.checked_sub(ctx.confirmations) {
+            Some(number) => number,
+            None => {
+                return Box::new(future::ok(ReconciliationStep::Done))
+                    as Box<dyn Future<Item = _, Error = _> + Send>;
+            }
+        };
+
+        // Only continue if the subgraph block ptr is behind the last confirmed block.
         // subgraph_ptr > head_ptr shouldn't happen, but if it does, it's safest to just stop.
         if let Some(ptr) = subgraph_ptr {
             self.metrics
                 .blocks_behind
                 .set((head_ptr.number - ptr.number) as f64);
 
-            if ptr.number >= head_ptr.number {
+            if ptr.number >= confirmed_number {
                 return Box::new(future::ok(ReconciliationStep::Done))
                     as Box<dyn Future<Item = _, Error = _> + Send>;
             }
@@ -371,9 +385,12 @@ where
 
                             // End either just before the the next data source start_block or
                             // just prior to the reorg threshold. It isn't safe to go any farther
-                            // due to race conditions.
-                            let to_limit =
-                                cmp::min(head_ptr.number - reorg_threshold, next_start_block - 1);
+                            // due to race conditions. Blocks without enough confirmations are
+                            // left for later.
+                            let to_limit = cmp::min(
+                                cmp::min(head_ptr.number - reorg_threshold, confirmed_number),
+                                next_start_block - 1,
+                            );
 
                             // The range should not be too small, due to the overhead of finding
                             // triggers for each range, neither too large, so that progress is
@@ -467,6 +484,7 @@ where
                                 ctx.eth_adapter
                                     .calls_in_block(
                                         &logger,
+                                        ctx.chain_store.clone(),
                                         ctx.metrics.ethrpc_metrics.clone(),
                                         head_ancestor.block.number.unwrap().as_u64(),
                                         head_ancestor.block.hash.unwrap(),
@@ -727,9 +745,22 @@ where
         match head_ptr_opt {
             None => Ok(()),
             Some(head_ptr) => {
+                // Blocks are final once they are beyond the reorg threshold, or
+                // have as many confirmations as this subgraph asks for. The final
+                // block is unknown while its ancestors are missing from the store.
+                let final_depth = cmp::max(self.reorg_threshold, self.confirmations);
+                let final_ptr = if head_ptr.number >= final_depth {
+                    self.chain_store
+                        .ancestor_block(head_ptr, final_depth)?
+                        .map(|block| EthereumBlockPointer::from(&block))
+                } else {
+                    None
+                };
+
                 let ops = SubgraphDeploymentEntity::update_ethereum_head_block_operations(
                     &self.subgraph_id,
                     head_ptr,
+                    final_ptr,
                 );
                 self.subgraph_store
                     .apply_metadata_operations(ops)
@@ -906,6 +937,8 @@ pub struct BlockStreamBuilder<S, C, M> {
     eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
     node_id: NodeId,
     reorg_threshold: u64,
+    ancestor_count: u64,
+    confirmations: HashMap<String, u64>,
     metrics_registry: Arc<M>,
 }
 
@@ -917,6 +950,8 @@ impl<S, C, M> Clone for BlockStreamBuilder<S, C, M> {
             eth_adapters: self.eth_adapters.clone(),
             node_id: self.node_id.clone(),
             reorg_threshold: self.reorg_threshold,
+            ancestor_count: self.ancestor_count,
+            confirmations: self.confirmations.clone(),
             metrics_registry: self.metrics_registry.clone(),
         }
     }
@@ -934,6 +969,8 @@ where
         eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>>,
         node_id: NodeId,
         reorg_threshold: u64,
+        ancestor_count: u64,
+        confirmations: HashMap<String, u64>,
         metrics_registry: Arc<M>,
     ) -> Self {
         BlockStreamBuilder {
@@ -942,6 +979,8 @@ where
             eth_adapters,
             node_id,
             reorg_threshold,
+            ancestor_count,
+            confirmations,
             metrics_registry,
         }
     }
@@ -965,6 +1004,7 @@ where
         call_filter: EthereumCallFilter,
         block_filter: EthereumBlockFilter,
         templates_use_calls: bool,
+        confirmations: Option<u64>,
         metrics: Arc<BlockStreamMetrics>,
     ) -> Self::Stream {
         let logger = logger.new(o!(
@@ -988,6 +1028,29 @@ where
             ))
             .clone();
 
+        // Wait for whichever is more confirmations: those the subgraph asks
+        // for or those the node requires for the network
+        let network_confirmations = self.confirmations.get(&network_name).cloned().unwrap_or(0);
+        let confirmations = cmp::max(confirmations.unwrap_or(0), network_confirmations);
+
+        // The block ingestor keeps the head block and its `ancestor_count`
+        // most recent ancestors, or more if the network requires more
+        // confirmations. Waiting for more confirmations than that would leave
+        // the final block of the subgraph outside of the chain store
+        let max_confirmations = cmp::max(self.ancestor_count, network_confirmations);
+        let confirmations = if confirmations > max_confirmations {
+            warn!(
+                logger,
+                "Subgraph asks for more confirmations than this node keeps blocks for, \
+                 waiting for as many as it keeps";
+                "confirmations" => confirmations,
+                "max_confirmations" => max_confirmations,
+            );
+            max_confirmations
+        } else {
+            confirmations
+        };
+
         // Create the actual subgraph-specific block stream
         BlockStream::new(
             self.subgraph_store.clone(),
@@ -1001,6 +1064,7 @@ where
             start_blocks,
             templates_use_calls,
             self.reorg_threshold,
+            confirmations,
             logger,
             metrics,
         )
@@ -2,13 +2,14 @@ use ethabi::Token;
 use futures::future;
 use futures::prelude::*;
 use lazy_static::lazy_static;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 
 use ethabi::ParamType;
 use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
 use graph::prelude::*;
+use graph::util::futures::RetryConfig;
 use web3;
 use web3::api::Web3;
 use web3::transports::batch::Batch;
@@ -18,6 +19,9 @@ use web3::types::{Filter, *};
 pub struct EthereumAdapter<T: web3::Transport> {
     web3: Arc<Web3<T>>,
     metrics: Arc<ProviderEthRpcMetrics>,
+    /// How many attempts to make at requests that are retried until they
+    /// succeed if this is `None`
+    retry_limit: Option<usize>,
 }
 
 lazy_static! {
@@ -47,12 +51,69 @@ lazy_static! {
             .parse::<usize>()
             .expect("invalid ETHEREUM_BLOCK_BATCH_SIZE env var");
 
+    /// Maximum number of batches of blocks to request in parallel when loading blocks by number.
+    static ref PARALLEL_BLOCK_BATCHES: usize = std::env::var("ETHEREUM_PARALLEL_BLOCK_BATCHES")
+            .unwrap_or("5".into())
+            .parse::<usize>()
+            .expect("invalid ETHEREUM_PARALLEL_BLOCK_BATCHES env var");
+
     /// This should not be too large that it causes requests to timeout without us catching it, nor
     /// too small that it causes us to timeout requests that would've succeeded.
     static ref JSON_RPC_TIMEOUT: u64 = std::env::var("GRAPH_ETHEREUM_JSON_RPC_TIMEOUT")
             .unwrap_or("120".into())
             .parse::<u64>()
             .expect("invalid GRAPH_ETHEREUM_JSON_RPC_TIMEOUT env var");
+
+    /// Keep full blocks, transaction receipts, traces and the results of range scans in the
+    /// chain store and look them up there before asking the Ethereum node, so that
+    /// reindexing does not fetch them again.
+    static ref BLOCK_CACHE: bool = std::env::var("GRAPH_ETHEREUM_BLOCK_CACHE")
+            .map(|s| s == "true")
+            .unwrap_or(false);
+}
+
+/// Retry `operation_name` until it succeeds, or at most `limit` times if there is a limit.
+fn retry_up_to<I: Send, E: Send>(
+    limit: Option<usize>,
+    operation_name: impl ToString,
+    logger: &Logger,
+) -> RetryConfig<I, E> {
+    let retry = retry(operation_name, logger);
+    match limit {
+        Some(limit) => retry.limit(limit),
+        None => retry.no_limit(),
+    }
+}
+
+/// Identifies the `eth_getLogs` requests made for `filter` in the block cache.
+fn log_scan_filter(filter: &EthGetLogsFilter) -> String {
+    let mut contracts: Vec<String> = filter
+        .contracts
+        .iter()
+        .map(|address| format!("{:x}", address))
+        .collect();
+    let mut events: Vec<String> = filter
+        .event_signatures
+        .iter()
+        .map(|sig| format!("{:x}", sig))
+        .collect();
+    contracts.sort();
+    events.sort();
+    format!(
+        "eth_getLogs address=[{}] topic0=[{}]",
+        contracts.join(","),
+        events.join(",")
+    )
+}
+
+/// Identifies the `trace_filter` requests made for calls to `addresses` in the block cache.
+fn trace_scan_filter(addresses: &[H160]) -> String {
+    let mut addresses: Vec<String> = addresses
+        .iter()
+        .map(|address| format!("{:x}", address))
+        .collect();
+    addresses.sort();
+    format!("trace_filter toAddress=[{}]", addresses.join(","))
 }
 
 impl<T> EthereumAdapter<T>
@@ -65,9 +126,18 @@ where
         EthereumAdapter {
             web3: Arc::new(Web3::new(transport)),
             metrics: provider_metrics,
+            retry_limit: None,
         }
     }
 
+    /// Give up on requests after `limit` attempts instead of retrying them until they
+    /// succeed. Adapters that share a network with other providers use this so that
+    /// the adapter pool can send the request to another provider.
+    pub fn with_retry_limit(mut self, limit: usize) -> Self {
+        self.retry_limit = Some(limit);
+        self
+    }
+
     fn traces(
         &self,
         logger: &Logger,
@@ -79,8 +149,7 @@ where
         let eth = self.clone();
         let logger = logger.to_owned();
 
-        retry("trace_filter RPC call", &logger)
-            .no_limit()
+        retry_up_to(self.retry_limit, "trace_filter RPC call", &logger)
             .timeout_secs(*JSON_RPC_TIMEOUT)
             .run(move || {
                 let trace_filter: TraceFilter = match addresses.len() {
@@ -145,12 +214,11 @@ where
             })
             .map_err(move |e| {
                 e.into_inner().unwrap_or_else(move || {
-                    format_err!(
-                        "Ethereum node took too long to respond to trace_filter \
-                         (from block {}, to block {})",
-                        from,
-                        to
-                    )
+                    EthereumTimeoutError(format!(
+                        "respond to trace_filter (from block {}, to block {})",
+                        from, to
+                    ))
+                    .into()
                 })
             })
     }
@@ -166,14 +234,13 @@ where
     ) -> impl Future<Item = Vec<Log>, Error = tokio_timer::timeout::Error<web3::error::Error>> {
         let eth_adapter = self.clone();
 
-        retry("eth_getLogs RPC call", &logger)
+        retry_up_to(self.retry_limit, "eth_getLogs RPC call", &logger)
             .when(move |res: &Result<_, web3::error::Error>| match res {
                 Ok(_) => false,
                 Err(e) => !too_many_logs_fingerprints
                     .iter()
                     .any(|f| e.to_string().contains(f)),
             })
-            .no_limit()
             .timeout_secs(*JSON_RPC_TIMEOUT)
             .run(move || {
                 let start = Instant::now();
@@ -327,7 +394,13 @@ where
                                 Ok((vec![], (start, new_step)))
                             } else {
                                 warn!(logger, "Unexpected RPC error"; "error" => &string_err);
-                                Err(err_msg(string_err))
+                                Err(e.into_inner().map(Error::from).unwrap_or_else(|| {
+                                    EthereumTimeoutError(format!(
+                                        "return logs for blocks [{}, {}]",
+                                        start, to
+                                    ))
+                                    .into()
+                                }))
                             }
                         }
                         Ok(logs) => Ok((logs, (low, step))),
@@ -337,6 +410,39 @@ where
         .concat2()
     }
 
+    /// Like `log_stream`, but serves the logs from the block cache if the same scan was
+    /// made before, and adds them to the cache otherwise.
+    fn cached_log_stream(
+        &self,
+        logger: Logger,
+        chain_store: Arc<dyn ChainStore>,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: u64,
+        to: u64,
+        filter: EthGetLogsFilter,
+    ) -> Box<dyn Future<Item = Vec<Log>, Error = Error> + Send> {
+        if !*BLOCK_CACHE {
+            return Box::new(self.log_stream(logger, subgraph_metrics, from, to, filter));
+        }
+
+        let scan_filter = log_scan_filter(&filter);
+        match chain_store.range_logs(&scan_filter, from, to) {
+            Ok(Some(logs)) => return Box::new(future::ok(logs)),
+            Ok(None) => (),
+            Err(e) => error!(logger, "Error accessing block cache {}", e),
+        }
+
+        Box::new(
+            self.log_stream(logger.clone(), subgraph_metrics, from, to, filter)
+                .map(move |logs| {
+                    if let Err(e) = chain_store.set_range_logs(&scan_filter, from, to, &logs) {
+                        error!(logger, "Error writing to block cache {}", e);
+                    }
+                    logs
+                }),
+        )
+    }
+
     fn call(
         &self,
         logger: &Logger,
@@ -346,6 +452,7 @@ where
     ) -> impl Future<Item = Bytes, Error = EthereumContractCallError> + Send {
         let web3 = self.web3.clone();
         let logger = logger.clone();
+        let retry_limit = self.retry_limit;
 
         // Outer retry used only for 0-byte responses,
         // where we can't guarantee the problem is temporary.
@@ -368,12 +475,11 @@ where
                 let web3 = web3.clone();
                 let call_data = call_data.clone();
 
-                retry("eth_call RPC call", &logger)
+                retry_up_to(retry_limit, "eth_call RPC call", &logger)
                     .when(|result| match result {
                         Ok(_) | Err(EthereumContractCallError::Revert(_)) => false,
                         Err(_) => true,
                     })
-                    .no_limit()
                     .timeout_secs(*JSON_RPC_TIMEOUT)
                     .run(move || {
                         let req = CallRequest {
@@ -483,6 +589,113 @@ where
             })
     }
 
+    /// Request the receipts of transactions in the block with hash `block_hash` through
+    /// JSON-RPC.
+    fn load_transaction_receipts_rpc(
+        &self,
+        logger: &Logger,
+        block_hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Box<dyn Future<Item = Vec<TransactionReceipt>, Error = EthereumAdapterError> + Send> {
+        let logger = logger.clone();
+
+        // The early return is necessary for correctness, otherwise we'll
+        // request an empty batch which is not valid in JSON-RPC.
+        if transaction_hashes.is_empty() {
+            return Box::new(future::ok(Vec::new()));
+        }
+        let web3 = self.web3.clone();
+
+        // Retry, but eventually give up.
+        // A receipt might be missing because the block was uncled, and the
+        // transaction never made it back into the main chain.
+        Box::new(
+            retry("batch eth_getTransactionReceipt RPC call", &logger)
+                .limit(16)
+                .no_logging()
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || {
+                    let batching_web3 = Web3::new(Batch::new(web3.transport().clone()));
+
+                    let receipt_futures = transaction_hashes
+                        .iter()
+                        .map(|&tx_hash| {
+                            let logger = logger.clone();
+                            batching_web3
+                                .eth()
+                                .transaction_receipt(tx_hash)
+                                .from_err()
+                                .map_err(EthereumAdapterError::Unknown)
+                                .and_then(move |receipt_opt| {
+                                    receipt_opt.ok_or_else(move || {
+                                        // No receipt was returned.
+                                        //
+                                        // This can be because the Ethereum node no longer
+                                        // considers this block to be part of the main chain,
+                                        // and so the transaction is no longer in the main
+                                        // chain.  Nothing we can do from here except give up
+                                        // trying to ingest this block.
+                                        //
+                                        // This could also be because the receipt is simply not
+                                        // available yet.  For that case, we should retry until
+                                        // it becomes available.
+                                        EthereumAdapterError::BlockUnavailable(block_hash)
+                                    })
+                                })
+                                .and_then(move |receipt| {
+                                    // Parity nodes seem to return receipts with no block hash
+                                    // when a transaction is no longer in the main chain, so
+                                    // treat that case the same as a receipt being absent
+                                    // entirely.
+                                    let receipt_block_hash =
+                                        receipt.block_hash.ok_or_else(|| {
+                                            EthereumAdapterError::BlockUnavailable(block_hash)
+                                        })?;
+
+                                    // Check if receipt is for the right block
+                                    if receipt_block_hash != block_hash {
+                                        trace!(
+                                            logger, "receipt block mismatch";
+                                            "receipt_block_hash" =>
+                                                receipt_block_hash.to_string(),
+                                            "block_hash" =>
+                                                block_hash.to_string(),
+                                            "tx_hash" => tx_hash.to_string(),
+                                        );
+
+                                        // If the receipt came from a different block, then the
+                                        // Ethereum node no longer considers this block to be
+                                        // in the main chain.  Nothing we can do from here
+                                        // except give up trying to ingest this block.
+                                        // There is no way to get the transaction receipt from
+                                        // this block.
+                                        Err(EthereumAdapterError::BlockUnavailable(block_hash))
+                                    } else {
+                                        Ok(receipt)
+                                    }
+                                })
+                        })
+                        .collect::<Vec<_>>();
+
+                    batching_web3
+                        .transport()
+                        .submit_batch()
+                        .from_err()
+                        .map_err(EthereumAdapterError::Unknown)
+                        .and_then(move |_| stream::futures_ordered(receipt_futures).collect())
+                })
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        Error::from(EthereumTimeoutError(format!(
+                            "return receipts for block {}",
+                            block_hash
+                        )))
+                        .into()
+                    })
+                }),
+        )
+    }
+
     /// Request blocks by hash through JSON-RPC.
     fn load_blocks_rpc(
         &self,
@@ -490,11 +703,11 @@ where
         ids: Vec<H256>,
     ) -> impl Stream<Item = LightEthereumBlock, Error = Error> + Send {
         let web3 = self.web3.clone();
+        let retry_limit = self.retry_limit;
 
         stream::iter_ok::<_, Error>(ids.into_iter().map(move |hash| {
             let web3 = web3.clone();
-            retry(format!("load block {}", hash), &logger)
-                .no_limit()
+            retry_up_to(retry_limit, format!("load block {}", hash), &logger)
                 .timeout_secs(*JSON_RPC_TIMEOUT)
                 .run(move || {
                     web3.eth()
@@ -507,7 +720,11 @@ where
                             })
                         })
                 })
-                .from_err()
+                .map_err(move |e| {
+                    e.into_inner().map(|e| e.into_inner()).unwrap_or_else(|| {
+                        EthereumTimeoutError(format!("return block {:?}", hash)).into()
+                    })
+                })
         }))
         .buffered(*BLOCK_BATCH_SIZE)
     }
@@ -521,25 +738,32 @@ where
         block_nums: Vec<u64>,
     ) -> impl Stream<Item = EthereumBlockPointer, Error = Error> + Send {
         let web3 = self.web3.clone();
+        let retry_limit = self.retry_limit;
 
         stream::iter_ok::<_, Error>(block_nums.into_iter().map(move |block_num| {
             let web3 = web3.clone();
-            retry(format!("load block ptr {}", block_num), &logger)
-                .no_limit()
-                .timeout_secs(*JSON_RPC_TIMEOUT)
-                .run(move || {
-                    web3.eth()
-                        .block(BlockId::Number(BlockNumber::Number(block_num)))
-                        .from_err::<Error>()
-                        .map_err(|e| e.compat())
-                        .and_then(move |block| {
-                            block.ok_or_else(|| {
-                                format_err!("Ethereum node did not find block {:?}", block_num)
-                                    .compat()
-                            })
+            retry_up_to(
+                retry_limit,
+                format!("load block ptr {}", block_num),
+                &logger,
+            )
+            .timeout_secs(*JSON_RPC_TIMEOUT)
+            .run(move || {
+                web3.eth()
+                    .block(BlockId::Number(BlockNumber::Number(block_num)))
+                    .from_err::<Error>()
+                    .map_err(|e| e.compat())
+                    .and_then(move |block| {
+                        block.ok_or_else(|| {
+                            format_err!("Ethereum node did not find block {:?}", block_num).compat()
                         })
+                    })
+            })
+            .map_err(move |e| {
+                e.into_inner().map(|e| e.into_inner()).unwrap_or_else(|| {
+                    EthereumTimeoutError(format!("return block #{}", block_num)).into()
                 })
-                .from_err()
+            })
         }))
         .buffered(*BLOCK_BATCH_SIZE)
         .map(|b| b.into())
@@ -548,9 +772,10 @@ where
 
 impl<T> EthereumAdapterTrait for EthereumAdapter<T>
 where
-    T: web3::BatchTransport + Send + Sync + 'static,
+    T: web3::BatchTransport + web3::DuplexTransport + Send + Sync + 'static,
     T::Batch: Send,
     T::Out: Send,
+    T::NotificationStream: Send,
 {
     fn net_identifiers(
         &self,
@@ -559,29 +784,31 @@ where
         let logger = logger.clone();
 
         let web3 = self.web3.clone();
-        let net_version_future = retry("net_version RPC call", &logger)
-            .no_limit()
+        let net_version_future = retry_up_to(self.retry_limit, "net_version RPC call", &logger)
             .timeout_secs(20)
             .run(move || web3.net().version().from_err());
 
         let web3 = self.web3.clone();
-        let gen_block_hash_future = retry("eth_getBlockByNumber(0, false) RPC call", &logger)
-            .no_limit()
-            .timeout_secs(30)
-            .run(move || {
-                web3.eth()
-                    .block(BlockNumber::Earliest.into())
-                    .from_err()
-                    .and_then(|gen_block_opt| {
-                        future::result(
-                            gen_block_opt
-                                .ok_or_else(|| {
-                                    format_err!("Ethereum node could not find genesis block")
-                                })
-                                .map(|gen_block| gen_block.hash.unwrap()),
-                        )
-                    })
-            });
+        let gen_block_hash_future = retry_up_to(
+            self.retry_limit,
+            "eth_getBlockByNumber(0, false) RPC call",
+            &logger,
+        )
+        .timeout_secs(30)
+        .run(move || {
+            web3.eth()
+                .block(BlockNumber::Earliest.into())
+                .from_err()
+                .and_then(|gen_block_opt| {
+                    future::result(
+                        gen_block_opt
+                            .ok_or_else(|| {
+                                format_err!("Ethereum node could not find genesis block")
+                            })
+                            .map(|gen_block| gen_block.hash.unwrap()),
+                    )
+                })
+        });
 
         Box::new(
             net_version_future
@@ -594,7 +821,7 @@ where
                 )
                 .map_err(|e| {
                     e.into_inner().unwrap_or_else(|| {
-                        format_err!("Ethereum node took too long to read network identifiers")
+                        EthereumTimeoutError("read network identifiers".to_owned()).into()
                     })
                 }),
         )
@@ -607,24 +834,73 @@ where
         let web3 = self.web3.clone();
 
         Box::new(
-            retry("eth_getBlockByNumber(latest) RPC call", logger)
-                .no_limit()
-                .timeout_secs(*JSON_RPC_TIMEOUT)
-                .run(move || {
-                    web3.eth()
-                        .block_with_txs(BlockNumber::Latest.into())
-                        .map_err(|e| format_err!("could not get latest block from Ethereum: {}", e))
-                        .from_err()
-                        .and_then(|block_opt| {
-                            block_opt.ok_or_else(|| {
-                                format_err!("no latest block returned from Ethereum").into()
-                            })
+            retry_up_to(
+                self.retry_limit,
+                "eth_getBlockByNumber(latest) RPC call",
+                logger,
+            )
+            .timeout_secs(*JSON_RPC_TIMEOUT)
+            .run(move || {
+                web3.eth()
+                    .block_with_txs(BlockNumber::Latest.into())
+                    .map_err(|e| {
+                        let message = format!("could not get latest block from Ethereum: {}", e);
+                        Error::from(e.context(message))
+                    })
+                    .from_err()
+                    .and_then(|block_opt| {
+                        block_opt.ok_or_else(|| {
+                            format_err!("no latest block returned from Ethereum").into()
                         })
-                })
-                .map_err(move |e| {
-                    e.into_inner().unwrap_or_else(move || {
-                        format_err!("Ethereum node took too long to return latest block").into()
                     })
+            })
+            .map_err(move |e| {
+                e.into_inner().unwrap_or_else(move || {
+                    Error::from(EthereumTimeoutError("return latest block".to_owned())).into()
+                })
+            }),
+        )
+    }
+
+    fn subscribe_new_heads(
+        &self,
+        logger: &Logger,
+    ) -> Box<
+        dyn Future<
+                Item = Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>,
+                Error = Error,
+            > + Send,
+    > {
+        let logger = logger.clone();
+
+        Box::new(
+            self.web3
+                .eth_subscribe()
+                .subscribe_new_heads()
+                .map_err(|e| {
+                    let message = format!("failed to subscribe to new heads: {}", e);
+                    Error::from(e.context(message))
+                })
+                .map(move |subscription| {
+                    Box::new(
+                        subscription
+                            .map_err(|e| {
+                                let message = format!("new heads subscription failed: {}", e);
+                                Error::from(e.context(message))
+                            })
+                            .filter_map(move |header: BlockHeader| {
+                                match (header.hash, header.number) {
+                                    (Some(hash), Some(number)) => {
+                                        Some(EthereumBlockPointer::from((hash, number.as_u64())))
+                                    }
+                                    _ => {
+                                        debug!(logger, "Ignoring new head without hash or number");
+                                        None
+                                    }
+                                }
+                            }),
+                    )
+                        as Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>
                 }),
         )
     }
@@ -656,8 +932,7 @@ where
         let logger = logger.clone();
 
         Box::new(
-            retry("eth_getBlockByHash RPC call", &logger)
-                .no_limit()
+            retry_up_to(self.retry_limit, "eth_getBlockByHash RPC call", &logger)
                 .timeout_secs(*JSON_RPC_TIMEOUT)
                 .run(move || {
                     web3.eth()
@@ -666,7 +941,7 @@ where
                 })
                 .map_err(move |e| {
                     e.into_inner().unwrap_or_else(move || {
-                        format_err!("Ethereum node took too long to return block {}", block_hash)
+                        EthereumTimeoutError(format!("return block {}", block_hash)).into()
                     })
                 }),
         )
@@ -675,119 +950,112 @@ where
     fn load_full_block(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         block: LightEthereumBlock,
     ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
-        let logger = logger.clone();
         let block_hash = block.hash.expect("block is missing block hash");
 
-        // The early return is necessary for correctness, otherwise we'll
-        // request an empty batch which is not valid in JSON-RPC.
+        if *BLOCK_CACHE {
+            match chain_store.full_blocks(vec![block_hash]) {
+                Ok(mut blocks) => {
+                    if let Some(block) = blocks.pop() {
+                        return Box::new(future::ok(block));
+                    }
+                }
+                Err(e) => error!(logger, "Error accessing block cache {}", e),
+            }
+        }
+
         if block.transactions.is_empty() {
             info!(logger, "Block {} contains no transactions", block_hash);
-            return Box::new(future::ok(EthereumBlock {
-                block,
-                transaction_receipts: Vec::new(),
-            }));
         }
-        let web3 = self.web3.clone();
 
-        // Retry, but eventually give up.
-        // A receipt might be missing because the block was uncled, and the
-        // transaction never made it back into the main chain.
+        let logger = logger.clone();
+        let transaction_hashes = block.transactions.iter().map(|tx| tx.hash).collect();
         Box::new(
-            retry("batch eth_getTransactionReceipt RPC call", &logger)
-                .limit(16)
-                .no_logging()
-                .timeout_secs(*JSON_RPC_TIMEOUT)
-                .run(move || {
-                    let block = block.clone();
-                    let batching_web3 = Web3::new(Batch::new(web3.transport().clone()));
-
-                    let receipt_futures = block
-                        .transactions
-                        .iter()
-                        .map(|tx| {
-                            let logger = logger.clone();
-                            let tx_hash = tx.hash;
-
-                            batching_web3
-                                .eth()
-                                .transaction_receipt(tx_hash)
-                                .from_err()
-                                .map_err(EthereumAdapterError::Unknown)
-                                .and_then(move |receipt_opt| {
-                                    receipt_opt.ok_or_else(move || {
-                                        // No receipt was returned.
-                                        //
-                                        // This can be because the Ethereum node no longer
-                                        // considers this block to be part of the main chain,
-                                        // and so the transaction is no longer in the main
-                                        // chain.  Nothing we can do from here except give up
-                                        // trying to ingest this block.
-                                        //
-                                        // This could also be because the receipt is simply not
-                                        // available yet.  For that case, we should retry until
-                                        // it becomes available.
-                                        EthereumAdapterError::BlockUnavailable(block_hash)
-                                    })
-                                })
-                                .and_then(move |receipt| {
-                                    // Parity nodes seem to return receipts with no block hash
-                                    // when a transaction is no longer in the main chain, so
-                                    // treat that case the same as a receipt being absent
-                                    // entirely.
-                                    let receipt_block_hash =
-                                        receipt.block_hash.ok_or_else(|| {
-                                            EthereumAdapterError::BlockUnavailable(block_hash)
-                                        })?;
+            self.load_transaction_receipts_rpc(&logger, block_hash, transaction_hashes)
+                .map(move |transaction_receipts| {
+                    let block = EthereumBlock {
+                        block,
+                        transaction_receipts,
+                    };
+                    if *BLOCK_CACHE {
+                        if let Err(e) = chain_store.upsert_full_blocks(vec![block.clone()]) {
+                            error!(logger, "Error writing to block cache {}", e);
+                        }
+                    }
+                    block
+                }),
+        )
+    }
 
-                                    // Check if receipt is for the right block
-                                    if receipt_block_hash != block_hash {
-                                        trace!(
-                                            logger, "receipt block mismatch";
-                                            "receipt_block_hash" =>
-                                                receipt_block_hash.to_string(),
-                                            "block_hash" =>
-                                                block_hash.to_string(),
-                                            "tx_hash" => tx_hash.to_string(),
-                                        );
+    fn load_transaction_receipts(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        block_hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Box<dyn Future<Item = Vec<TransactionReceipt>, Error = EthereumAdapterError> + Send> {
+        if !*BLOCK_CACHE {
+            return self.load_transaction_receipts_rpc(logger, block_hash, transaction_hashes);
+        }
 
-                                        // If the receipt came from a different block, then the
-                                        // Ethereum node no longer considers this block to be
-                                        // in the main chain.  Nothing we can do from here
-                                        // except give up trying to ingest this block.
-                                        // There is no way to get the transaction receipt from
-                                        // this block.
-                                        Err(EthereumAdapterError::BlockUnavailable(block_hash))
-                                    } else {
-                                        Ok(receipt)
-                                    }
-                                })
-                        })
-                        .collect::<Vec<_>>();
+        // Look the receipts up in full blocks and among the receipts that
+        // were stored on their own, and only ask the node for the rest
+        let mut receipts: HashMap<H256, TransactionReceipt> = HashMap::new();
+        match chain_store.full_blocks(vec![block_hash]) {
+            Ok(blocks) => receipts.extend(
+                blocks
+                    .into_iter()
+                    .flat_map(|block| block.transaction_receipts)
+                    .map(|receipt| (receipt.transaction_hash, receipt)),
+            ),
+            Err(e) => error!(logger, "Error accessing block cache {}", e),
+        }
+        let missing: Vec<H256> = transaction_hashes
+            .iter()
+            .filter(|hash| !receipts.contains_key(hash))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            match chain_store.transaction_receipts(block_hash, missing) {
+                Ok(stored) => receipts.extend(
+                    stored
+                        .into_iter()
+                        .map(|receipt| (receipt.transaction_hash, receipt)),
+                ),
+                Err(e) => error!(logger, "Error accessing block cache {}", e),
+            }
+        }
+        let missing: Vec<H256> = transaction_hashes
+            .iter()
+            .filter(|hash| !receipts.contains_key(hash))
+            .cloned()
+            .collect();
 
-                    batching_web3
-                        .transport()
-                        .submit_batch()
-                        .from_err()
-                        .map_err(EthereumAdapterError::Unknown)
-                        .and_then(move |_| {
-                            stream::futures_ordered(receipt_futures).collect().map(
-                                move |transaction_receipts| EthereumBlock {
-                                    block,
-                                    transaction_receipts,
-                                },
-                            )
+        let logger = logger.clone();
+        Box::new(
+            self.load_transaction_receipts_rpc(&logger, block_hash, missing)
+                .and_then(move |loaded| {
+                    if !loaded.is_empty() {
+                        if let Err(e) = chain_store.upsert_transaction_receipts(&loaded) {
+                            error!(logger, "Error writing to block cache {}", e);
+                        }
+                    }
+                    receipts.extend(
+                        loaded
+                            .into_iter()
+                            .map(|receipt| (receipt.transaction_hash, receipt)),
+                    );
+                    transaction_hashes
+                        .into_iter()
+                        .map(|hash| {
+                            receipts
+                                .get(&hash)
+                                .cloned()
+                                .ok_or(EthereumAdapterError::BlockUnavailable(block_hash))
                         })
-                })
-                .map_err(move |e| {
-                    e.into_inner().unwrap_or_else(move || {
-                        format_err!(
-                            "Ethereum node took too long to return receipts for block {}",
-                            block_hash
-                        )
-                        .into()
-                    })
+                        .collect::<Result<Vec<_>, _>>()
                 }),
         )
     }
@@ -823,8 +1091,7 @@ where
         let web3 = self.web3.clone();
 
         Box::new(
-            retry("eth_getBlockByNumber RPC call", &logger)
-                .no_limit()
+            retry_up_to(self.retry_limit, "eth_getBlockByNumber RPC call", &logger)
                 .timeout_secs(*JSON_RPC_TIMEOUT)
                 .run(move || {
                     web3.eth()
@@ -834,10 +1101,8 @@ where
                 })
                 .map_err(move |e| {
                     e.into_inner().unwrap_or_else(move || {
-                        format_err!(
-                            "Ethereum node took too long to return data for block #{}",
-                            block_number
-                        )
+                        EthereumTimeoutError(format!("return data for block #{}", block_number))
+                            .into()
                     })
                 }),
         )
@@ -864,10 +1129,20 @@ where
     fn calls_in_block(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         block_number: u64,
         block_hash: H256,
     ) -> Box<dyn Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+        if *BLOCK_CACHE {
+            match chain_store.block_calls(block_hash) {
+                Ok(Some(calls)) => return Box::new(future::ok(calls)),
+                Ok(None) => (),
+                Err(e) => error!(logger, "Error accessing block cache {}", e),
+            }
+        }
+
+        let logger = logger.clone();
         let eth = self.clone();
         let addresses = Vec::new();
         let calls = eth
@@ -905,10 +1180,17 @@ where
                 future::ok(traces)
             })
             .map(move |traces| {
-                traces
+                let calls: Vec<EthereumCall> = traces
                     .iter()
                     .filter_map(EthereumCall::try_from_trace)
-                    .collect()
+                    .collect();
+                if *BLOCK_CACHE {
+                    let block_ptr = EthereumBlockPointer::from((block_hash, block_number));
+                    if let Err(e) = chain_store.set_block_calls(block_ptr, &calls) {
+                        error!(logger, "Error writing to block cache {}", e);
+                    }
+                }
+                calls
             });
         Box::new(calls)
     }
@@ -916,6 +1198,7 @@ where
     fn logs_in_block_range(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
@@ -925,7 +1208,14 @@ where
         let logger = logger.clone();
         Box::new(
             stream::iter_ok(log_filter.eth_get_logs_filters().map(move |filter| {
-                eth.log_stream(logger.clone(), subgraph_metrics.clone(), from, to, filter)
+                eth.cached_log_stream(
+                    logger.clone(),
+                    chain_store.clone(),
+                    subgraph_metrics.clone(),
+                    from,
+                    to,
+                    filter,
+                )
             }))
             .buffered(*LOG_STREAM_PARALLEL_CHUNKS as usize)
             .concat2(),
@@ -935,6 +1225,7 @@ where
     fn calls_in_block_range(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
@@ -950,17 +1241,55 @@ where
             .collect::<HashSet<H160>>()
             .into_iter()
             .collect::<Vec<H160>>();
-        Box::new(
-            eth.trace_stream(&logger, subgraph_metrics, from, to, addresses)
-                .filter_map(|trace| EthereumCall::try_from_trace(&trace))
-                .filter(move |call| {
-                    // `trace_filter` can only filter by calls `to` an address and
-                    // a block range. Since subgraphs are subscribing to calls
-                    // for a specific contract function an additional filter needs
-                    // to be applied
-                    call_filter.matches(&call)
-                }),
-        )
+
+        let scan_filter = trace_scan_filter(&addresses);
+        let cached = if *BLOCK_CACHE {
+            chain_store
+                .range_calls(&scan_filter, from, to)
+                .unwrap_or_else(|e| {
+                    error!(logger, "Error accessing block cache {}", e);
+                    None
+                })
+        } else {
+            None
+        };
+
+        let calls: Box<dyn Stream<Item = EthereumCall, Error = Error> + Send> = match cached {
+            Some(calls) => Box::new(stream::iter_ok(calls)),
+            None => {
+                let calls = eth
+                    .trace_stream(&logger, subgraph_metrics, from, to, addresses)
+                    .filter_map(|trace| EthereumCall::try_from_trace(&trace));
+                if *BLOCK_CACHE {
+                    // Remember all calls to the addresses, not just the ones that match
+                    // the filter, since that is what `trace_filter` returns
+                    let logger = logger.clone();
+                    Box::new(
+                        calls
+                            .collect()
+                            .map(move |calls| {
+                                if let Err(e) =
+                                    chain_store.set_range_calls(&scan_filter, from, to, &calls)
+                                {
+                                    error!(logger, "Error writing to block cache {}", e);
+                                }
+                                stream::iter_ok(calls)
+                            })
+                            .flatten_stream(),
+                    )
+                } else {
+                    Box::new(calls)
+                }
+            }
+        };
+
+        Box::new(calls.filter(move |call| {
+            // `trace_filter` can only filter by calls `to` an address and
+            // a block range. Since subgraphs are subscribing to calls
+            // for a specific contract function an additional filter needs
+            // to be applied
+            call_filter.matches(&call)
+        }))
     }
 
     fn contract_call(
@@ -1116,6 +1445,69 @@ where
         )
     }
 
+    fn blocks_by_numbers(
+        &self,
+        logger: &Logger,
+        block_numbers: Vec<u64>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
+        let web3 = self.web3.clone();
+        let logger = logger.clone();
+        let retry_limit = self.retry_limit;
+        let batches: Vec<Vec<u64>> = block_numbers
+            .chunks(*BLOCK_BATCH_SIZE)
+            .map(|batch| batch.to_vec())
+            .collect();
+
+        Box::new(
+            stream::iter_ok::<_, Error>(batches.into_iter().map(move |batch| {
+                let web3 = web3.clone();
+                let first = batch[0];
+                let last = batch[batch.len() - 1];
+                retry_up_to(
+                    retry_limit,
+                    format!(
+                        "batch eth_getBlockByNumber RPC call for blocks [{}, {}]",
+                        first, last
+                    ),
+                    &logger,
+                )
+                .timeout_secs(*JSON_RPC_TIMEOUT)
+                .run(move || {
+                    let batching_web3 = Web3::new(Batch::new(web3.transport().clone()));
+
+                    let block_futures = batch
+                        .iter()
+                        .map(|&number| {
+                            batching_web3
+                                .eth()
+                                .block_with_txs(BlockId::Number(BlockNumber::Number(number)))
+                                .from_err::<Error>()
+                                .and_then(move |block| {
+                                    block.ok_or_else(|| {
+                                        format_err!("Ethereum node did not find block #{}", number)
+                                    })
+                                })
+                        })
+                        .collect::<Vec<_>>();
+
+                    batching_web3
+                        .transport()
+                        .submit_batch()
+                        .from_err()
+                        .and_then(move |_| stream::futures_ordered(block_futures).collect())
+                })
+                .map_err(move |e| {
+                    e.into_inner().unwrap_or_else(move || {
+                        EthereumTimeoutError(format!("return blocks [{}, {}]", first, last)).into()
+                    })
+                })
+            }))
+            .buffered(*PARALLEL_BLOCK_BATCHES)
+            .map(stream::iter_ok)
+            .flatten(),
+        )
+    }
+
     /// Reorg safety: `to` must be a final block.
     fn block_range_to_ptrs(
         &self,
@@ -1142,11 +1534,17 @@ fn parse_log_triggers(
         .iter()
         .flat_map(move |receipt| {
             let log_filter = log_filter.clone();
-            receipt
-                .logs
-                .iter()
-                .filter(move |log| log_filter.matches(log))
-                .map(move |log| EthereumTrigger::Log(log.clone()))
+            receipt.logs.iter().filter_map(move |log| {
+                if !log_filter.matches(log) {
+                    return None;
+                }
+                let receipt = if log_filter.requires_receipt(log) {
+                    Some(Arc::new(receipt.clone()))
+                } else {
+                    None
+                };
+                Some(EthereumTrigger::Log(log.clone(), receipt))
+            })
         })
         .collect()
 }
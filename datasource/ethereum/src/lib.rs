@@ -5,11 +5,13 @@ extern crate jsonrpc_core;
 #[macro_use]
 extern crate lazy_static;
 
+mod adapter_pool;
 mod block_ingestor;
 mod block_stream;
 mod ethereum_adapter;
 mod transport;
 
+pub use self::adapter_pool::{EthereumAdapterPool, EthereumProvider};
 pub use self::block_ingestor::BlockIngestor;
 pub use self::block_stream::{BlockStream, BlockStreamBuilder};
 pub use self::ethereum_adapter::EthereumAdapter;
@@ -3,8 +3,9 @@ use jsonrpc_core::types::Call;
 use serde_json::Value;
 use std::env;
 
+use web3::api::SubscriptionId;
 use web3::transports::{http, ipc, ws};
-use web3::RequestId;
+use web3::{DuplexTransport, RequestId};
 
 pub use web3::transports::EventLoopHandle;
 
@@ -83,3 +84,25 @@ impl web3::BatchTransport for Transport {
         }
     }
 }
+
+impl web3::DuplexTransport for Transport {
+    type NotificationStream = Box<dyn Stream<Item = Value, Error = web3::error::Error> + Send>;
+
+    fn subscribe(&self, id: &SubscriptionId) -> Self::NotificationStream {
+        match self {
+            Transport::RPC(_) => Box::new(stream::once(Err(web3::error::Error::Transport(
+                "subscriptions are not supported over JSON-RPC over HTTP".into(),
+            )))),
+            Transport::IPC(ipc) => Box::new(ipc.subscribe(id)),
+            Transport::WS(ws) => Box::new(ws.subscribe(id)),
+        }
+    }
+
+    fn unsubscribe(&self, id: &SubscriptionId) {
+        match self {
+            Transport::RPC(_) => (),
+            Transport::IPC(ipc) => ipc.unsubscribe(id),
+            Transport::WS(ws) => ws.unsubscribe(id),
+        }
+    }
+}
@@ -11,9 +11,10 @@ use graph::prelude::EthereumAdapter as EthereumAdapterTrait;
 use graph::prelude::*;
 use graph_datasource_ethereum::EthereumAdapter;
 use mock::MockMetricsRegistry;
+use web3::api::SubscriptionId;
 use web3::helpers::*;
 use web3::types::*;
-use web3::{BatchTransport, RequestId, Transport};
+use web3::{BatchTransport, DuplexTransport, RequestId, Transport};
 
 fn mock_block() -> Block<U256> {
     Block {
@@ -91,6 +92,17 @@ impl BatchTransport for TestTransport {
     }
 }
 
+impl DuplexTransport for TestTransport {
+    type NotificationStream =
+        Box<dyn Stream<Item = jsonrpc_core::Value, Error = web3::Error> + Send + 'static>;
+
+    fn subscribe(&self, _: &SubscriptionId) -> Self::NotificationStream {
+        Box::new(stream::empty())
+    }
+
+    fn unsubscribe(&self, _: &SubscriptionId) {}
+}
+
 impl TestTransport {
     pub fn set_response(&mut self, value: jsonrpc_core::Value) {
         *self.response.lock().unwrap() = vec![value].into();
@@ -169,7 +181,7 @@ fn contract_call() {
 
     let logger = Logger::root(slog::Discard, o!());
 
-    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone()));
+    let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry.clone(), "test"));
 
     let adapter = EthereumAdapter::new(transport, provider_metrics);
     let balance_of = Function {
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethabi::Function;
+use graph::components::ethereum::EthereumContractCall;
+use graph::prelude::EthereumAdapter as EthereumAdapterTrait;
+use graph::prelude::*;
+use graph::util::ethereum::string_to_h256;
+use graph_datasource_ethereum::{EthereumAdapterPool, EthereumProvider};
+use mock::{FakeStore, MockEthereumAdapter, MockFailure, MockMetricsRegistry};
+use web3::types::*;
+
+fn logger() -> Logger {
+    Logger::root(slog::Discard, o!())
+}
+
+fn block_hash(number: u64) -> H256 {
+    string_to_h256(&format!("block{}", number))
+}
+
+fn block(number: u64) -> LightEthereumBlock {
+    let mut block = LightEthereumBlock::default();
+    block.number = Some(number.into());
+    block.hash = Some(block_hash(number));
+    block
+}
+
+/// A mock adapter with blocks `0..=head`.
+fn adapter(head: u64) -> Arc<MockEthereumAdapter> {
+    let adapter = Arc::new(MockEthereumAdapter::default());
+    for number in 0..=head {
+        adapter.add_block(block(number));
+    }
+    adapter
+}
+
+/// A provider with the given comma-separated `capabilities`, which may be empty.
+fn provider(
+    name: &str,
+    capabilities: &str,
+    adapter: &Arc<MockEthereumAdapter>,
+) -> EthereumProvider {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let capabilities = if capabilities.is_empty() {
+        NodeCapabilities {
+            archive: false,
+            traces: false,
+        }
+    } else {
+        NodeCapabilities::from_str(capabilities).unwrap()
+    };
+    EthereumProvider {
+        name: name.to_owned(),
+        capabilities,
+        adapter: adapter.clone(),
+        metrics: Arc::new(ProviderEthRpcMetrics::new(registry, name)),
+    }
+}
+
+fn pool(providers: Vec<EthereumProvider>) -> EthereumAdapterPool {
+    // Keep tests that wait for a hanging provider or for every provider to
+    // fail short
+    std::env::set_var("GRAPH_ETHEREUM_FAILOVER_TIMEOUT", "1");
+    std::env::set_var("GRAPH_ETHEREUM_FAILOVER_ROUNDS", "2");
+    EthereumAdapterPool::new("mainnet".to_owned(), providers).unwrap()
+}
+
+fn run<F: Future + Send + 'static>(future: F) -> Result<F::Item, F::Error>
+where
+    F::Item: Send + 'static,
+    F::Error: Send + 'static,
+{
+    tokio::runtime::Runtime::new().unwrap().block_on(future)
+}
+
+fn no_calls() -> EthereumCallFilter {
+    EthereumCallFilter {
+        contract_addresses_function_signatures: HashMap::new(),
+    }
+}
+
+struct FakeEthereumCallCache;
+
+impl EthereumCallCache for FakeEthereumCallCache {
+    fn get_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        unimplemented!()
+    }
+
+    fn set_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+        _: &[u8],
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
+}
+
+#[test]
+fn parse_node_capabilities() {
+    let both = NodeCapabilities::from_str("archive,traces").unwrap();
+    assert!(both.archive && both.traces);
+    assert_eq!(both.to_string(), "archive,traces");
+
+    let traces = NodeCapabilities::from_str(" traces ").unwrap();
+    assert!(!traces.archive && traces.traces);
+    assert!(both.supports(&traces));
+    assert!(!traces.supports(&both));
+
+    let none = NodeCapabilities {
+        archive: false,
+        traces: false,
+    };
+    assert_eq!(none.to_string(), "none");
+    assert!(traces.supports(&none));
+
+    assert!(NodeCapabilities::from_str("archive,light").is_err());
+}
+
+#[test]
+fn requests_rotate_across_providers() {
+    let a = adapter(5);
+    let b = adapter(5);
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    for _ in 0..4 {
+        let hash = run(pool.block_hash_by_block_number(&logger(), 3)).unwrap();
+        assert_eq!(hash, Some(block_hash(3)));
+    }
+
+    assert_eq!(a.requests().len(), 2);
+    assert_eq!(b.requests().len(), 2);
+}
+
+#[test]
+fn failing_provider_is_avoided() {
+    let a = adapter(5);
+    let b = adapter(5);
+    a.fail_with(Some(MockFailure::Error));
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    // Every other request goes to `a` first and fails over to `b`; after
+    // three errors in a row, `a` is no longer asked
+    for _ in 0..10 {
+        let block = run(pool.load_block(&logger(), block_hash(2))).unwrap();
+        assert_eq!(block.hash, Some(block_hash(2)));
+    }
+
+    assert_eq!(a.requests().len(), 3);
+    assert_eq!(b.requests().len(), 10);
+}
+
+#[test]
+fn hanging_provider_times_out() {
+    let a = adapter(5);
+    let b = adapter(5);
+    a.fail_with(Some(MockFailure::Hang));
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    for _ in 0..2 {
+        let block = run(pool.block_by_hash(&logger(), block_hash(4))).unwrap();
+        assert_eq!(block.and_then(|block| block.hash), Some(block_hash(4)));
+    }
+
+    assert_eq!(a.requests().len(), 1);
+    assert_eq!(b.requests().len(), 2);
+}
+
+#[test]
+fn requests_fail_after_some_rounds() {
+    let a = adapter(5);
+    let b = adapter(5);
+    a.fail_with(Some(MockFailure::Error));
+    b.fail_with(Some(MockFailure::Error));
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    let error = run(pool.load_block(&logger(), block_hash(2)))
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("mocked failure"),
+        "unexpected error: {}",
+        error
+    );
+    assert_eq!(a.requests().len(), 2);
+    assert_eq!(b.requests().len(), 2);
+}
+
+#[test]
+fn store_errors_are_not_retried() {
+    let a = adapter(5);
+    let b = adapter(5);
+    a.fail_with(Some(MockFailure::StoreError));
+    b.fail_with(Some(MockFailure::StoreError));
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    assert!(run(pool.load_block(&logger(), block_hash(2))).is_err());
+    assert_eq!(a.requests().len() + b.requests().len(), 1);
+}
+
+#[test]
+fn reverted_calls_are_not_retried() {
+    let a = adapter(5);
+    let b = adapter(5);
+    let address = Address::from_str("eF7FfF64389B814A946f3E92105513705CA6B990").unwrap();
+    for adapter in &[&a, &b] {
+        adapter.mock_contract_call(address, "totalSupply", vec![], None);
+    }
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    let call = EthereumContractCall {
+        address,
+        block_ptr: EthereumBlockPointer::from((block_hash(5), 5 as i64)),
+        function: Function {
+            name: "totalSupply".to_owned(),
+            inputs: vec![],
+            outputs: vec![],
+            constant: true,
+        },
+        args: vec![],
+    };
+    let result = run(pool.contract_call(&logger(), call, Arc::new(FakeEthereumCallCache)));
+
+    match result {
+        Err(EthereumContractCallError::Revert(_)) => (),
+        Err(e) => panic!("expected a revert, got {}", e),
+        Ok(tokens) => panic!("expected a revert, got {:?}", tokens),
+    }
+    assert_eq!(a.requests().len() + b.requests().len(), 1);
+}
+
+#[test]
+fn block_streams_resume_with_missing_blocks() {
+    let a = adapter(9);
+    let b = adapter(9);
+    a.fail_with(Some(MockFailure::AfterBlocks(4)));
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    // The first request goes to `a`, which fails after four blocks; `b` is
+    // only asked for the rest
+    let mut numbers: Vec<u64> = run(pool
+        .blocks_by_numbers(&logger(), (1..=8).collect())
+        .map(|block| block.number.unwrap().as_u64())
+        .collect())
+    .unwrap();
+    numbers.sort();
+
+    assert_eq!(numbers, (1..=8).collect::<Vec<_>>());
+    assert_eq!(a.requests(), vec!["blocks_by_numbers"]);
+    assert_eq!(b.requests(), vec!["blocks_by_numbers"]);
+}
+
+#[test]
+fn chain_head_comes_from_one_provider() {
+    let a = adapter(10);
+    let b = adapter(11);
+    let pool = pool(vec![provider("a", "", &a), provider("b", "", &b)]);
+
+    for _ in 0..4 {
+        let head = run(pool.latest_block(&logger())).unwrap();
+        assert_eq!(head.number.unwrap().as_u64(), 10);
+    }
+    assert!(b.requests().is_empty());
+
+    // Only once `a` fails is `b` asked
+    a.fail_with(Some(MockFailure::Error));
+    let head = run(pool.latest_block(&logger())).unwrap();
+    assert_eq!(head.number.unwrap().as_u64(), 11);
+}
+
+#[test]
+fn calls_need_provider_with_traces() {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let metrics = Arc::new(SubgraphEthRpcMetrics::new(registry, "test".to_owned()));
+    let store: Arc<dyn ChainStore> = Arc::new(FakeStore);
+
+    let a = adapter(5);
+    let pool_without_traces = pool(vec![provider("a", "archive", &a)]);
+    let result = run(pool_without_traces
+        .calls_in_block_range(&logger(), store.clone(), metrics.clone(), 1, 5, no_calls())
+        .collect());
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("`traces`"), "unexpected error: {}", error);
+    assert!(a.requests().is_empty());
+
+    let b = adapter(5);
+    let pool_with_traces = pool(vec![
+        provider("a", "archive", &a),
+        provider("b", "traces", &b),
+    ]);
+    for _ in 0..2 {
+        run(pool_with_traces
+            .calls_in_block_range(&logger(), store.clone(), metrics.clone(), 1, 5, no_calls())
+            .collect())
+        .unwrap();
+    }
+    assert!(a.requests().is_empty());
+    assert_eq!(b.requests().len(), 2);
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use graph::data::subgraph::schema::SubgraphDeploymentEntity;
+use graph::prelude::BlockStreamBuilder as BlockStreamBuilderTrait;
+use graph::prelude::*;
+use graph::util::ethereum::string_to_h256;
+use graph_datasource_ethereum::BlockStreamBuilder;
+use mock::{MockEthereumAdapter, MockMetricsRegistry, MockStore};
+use web3::types::*;
+
+fn logger() -> Logger {
+    Logger::root(slog::Discard, o!())
+}
+
+fn deployment_id() -> SubgraphDeploymentId {
+    SubgraphDeploymentId::new("blockStreamTest").unwrap()
+}
+
+fn block_hash(number: u64) -> H256 {
+    string_to_h256(&format!("block{}", number))
+}
+
+fn block(number: u64) -> LightEthereumBlock {
+    let mut block = LightEthereumBlock::default();
+    block.number = Some(number.into());
+    block.hash = Some(block_hash(number));
+    if number > 0 {
+        block.parent_hash = block_hash(number - 1);
+    }
+    block
+}
+
+/// A mock adapter with blocks `0..=head`, and a store whose chain store,
+/// like the one the block ingestor fills, has the head block and its
+/// `ancestor_count` most recent ancestors.
+fn chain(head: u64, ancestor_count: u64) -> (Arc<MockEthereumAdapter>, Arc<MockStore>) {
+    let adapter = Arc::new(MockEthereumAdapter::default());
+    for number in 0..=head {
+        adapter.add_block(block(number));
+    }
+
+    let store = Arc::new(MockStore::new(vec![]));
+    store
+        .upsert_light_blocks((head - ancestor_count..=head).map(block).collect())
+        .unwrap();
+    assert!(store
+        .attempt_chain_head_update(ancestor_count)
+        .unwrap()
+        .is_empty());
+
+    // The block stream updates the deployment entity with the head and final blocks
+    store
+        .apply_metadata_operations(vec![MetadataOperation::Set {
+            entity: SubgraphDeploymentEntity::TYPENAME.to_owned(),
+            id: deployment_id().to_string(),
+            data: Entity::new(),
+        }])
+        .unwrap();
+
+    (adapter, store)
+}
+
+fn block_stream(
+    adapter: Arc<MockEthereumAdapter>,
+    store: Arc<MockStore>,
+    reorg_threshold: u64,
+    ancestor_count: u64,
+    confirmations: Option<u64>,
+) -> impl Stream<Item = EthereumBlockWithTriggers, Error = Error> {
+    let registry = Arc::new(MockMetricsRegistry::new());
+    let mut chain_stores = HashMap::new();
+    chain_stores.insert("mainnet".to_owned(), store.clone());
+    let mut eth_adapters: HashMap<String, Arc<dyn EthereumAdapter>> = HashMap::new();
+    eth_adapters.insert("mainnet".to_owned(), adapter);
+
+    let builder = BlockStreamBuilder::new(
+        store,
+        chain_stores,
+        eth_adapters,
+        NodeId::new("test").unwrap(),
+        reorg_threshold,
+        ancestor_count,
+        HashMap::new(),
+        registry.clone(),
+    );
+    let ethrpc_metrics = Arc::new(SubgraphEthRpcMetrics::new(
+        registry.clone(),
+        deployment_id().to_string(),
+    ));
+    let metrics = Arc::new(BlockStreamMetrics::new(
+        registry,
+        ethrpc_metrics,
+        deployment_id(),
+    ));
+
+    builder.build(
+        logger(),
+        deployment_id(),
+        "mainnet".to_owned(),
+        vec![],
+        EthereumLogFilter::default(),
+        EthereumCallFilter {
+            contract_addresses_function_signatures: HashMap::new(),
+        },
+        EthereumBlockFilter::default(),
+        false,
+        confirmations,
+        metrics,
+    )
+}
+
+/// Process the blocks the stream yields, like the subgraph instance manager
+/// does, until the stream waits for the chain head to change. Returns the
+/// numbers of the processed blocks.
+fn process_blocks(
+    stream: &mut impl Stream<Item = EthereumBlockWithTriggers, Error = Error>,
+    store: &MockStore,
+) -> Vec<u64> {
+    let mut numbers = vec![];
+    future::poll_fn(|| loop {
+        match stream.poll()? {
+            Async::Ready(Some(block)) => {
+                let block_ptr = EthereumBlockPointer::from(&block.ethereum_block);
+                store
+                    .transact_block_operations(deployment_id(), block_ptr, vec![])
+                    .unwrap();
+                numbers.push(block_ptr.number);
+            }
+            Async::Ready(None) => panic!("block stream ended"),
+            Async::NotReady => return Ok::<_, Error>(Async::Ready(())),
+        }
+    })
+    .wait()
+    .unwrap();
+    numbers
+}
+
+fn final_block_number(store: &MockStore) -> Option<Value> {
+    store
+        .get(MetadataOperation::entity_key(
+            SubgraphDeploymentEntity::TYPENAME.to_owned(),
+            deployment_id().to_string(),
+        ))
+        .unwrap()
+        .unwrap()
+        .get("finalEthereumBlockNumber")
+        .cloned()
+}
+
+#[test]
+fn stream_stops_confirmations_behind_head() {
+    let (adapter, store) = chain(10, 6);
+    let mut stream = block_stream(adapter.clone(), store.clone(), 5, 6, Some(3));
+
+    // Block 5 is found with a scan of final blocks, blocks 6 and 7 one at a
+    // time from the chain store; blocks 8 to 10 don't have enough
+    // confirmations yet
+    assert_eq!(process_blocks(&mut stream, &store), vec![5, 6, 7]);
+    assert_eq!(final_block_number(&store), Some(Value::from(5u64)));
+
+    // Each new head confirms one more block
+    adapter.add_block(block(11));
+    store.upsert_light_blocks(vec![block(11)]).unwrap();
+    store.attempt_chain_head_update(6).unwrap();
+    assert_eq!(process_blocks(&mut stream, &store), vec![8]);
+    assert_eq!(final_block_number(&store), Some(Value::from(6u64)));
+}
+
+#[test]
+fn confirmations_are_capped_at_ancestor_count() {
+    // The subgraph asks for more confirmations than the chain store keeps
+    // blocks for, so it only waits for as many as there are ancestors
+    let (adapter, store) = chain(10, 4);
+    let mut stream = block_stream(adapter, store.clone(), 2, 4, Some(8));
+
+    assert_eq!(process_blocks(&mut stream, &store), vec![6]);
+    assert_eq!(final_block_number(&store), Some(Value::from(6u64)));
+}
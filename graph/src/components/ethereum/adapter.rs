@@ -1,10 +1,14 @@
 use ethabi::{Bytes, Error as ABIError, Function, ParamType, Token};
 use failure::SyncFailure;
 use futures::Future;
+use lazy_static::lazy_static;
 use petgraph::graphmap::GraphMap;
 use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tiny_keccak::keccak256;
 use web3::types::*;
 
@@ -12,6 +16,16 @@ use super::types::*;
 use crate::components::metrics::{CounterVec, GaugeVec, HistogramVec};
 use crate::prelude::*;
 
+lazy_static! {
+    /// Maximum number of blocks whose transaction receipts are loaded in
+    /// parallel when turning logs into triggers
+    static ref PARALLEL_RECEIPT_BLOCKS: usize =
+        std::env::var("GRAPH_ETHEREUM_PARALLEL_RECEIPT_BLOCKS")
+            .unwrap_or("10".into())
+            .parse::<usize>()
+            .expect("invalid GRAPH_ETHEREUM_PARALLEL_RECEIPT_BLOCKS env var");
+}
+
 pub type EventSignature = H256;
 
 /// A collection of attributes that (kind of) uniquely identify an Ethereum blockchain.
@@ -20,6 +34,65 @@ pub struct EthereumNetworkIdentifier {
     pub genesis_block_hash: H256,
 }
 
+/// The features an Ethereum node supports beyond the standard JSON-RPC API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeCapabilities {
+    /// The node keeps the state of old blocks, which contract calls at
+    /// blocks that are not recent need
+    pub archive: bool,
+    /// The node supports `trace_filter`, which call handlers and block
+    /// handlers with a call filter need
+    pub traces: bool,
+}
+
+impl NodeCapabilities {
+    /// Check if a node with these capabilities has everything in `required`.
+    pub fn supports(&self, required: &NodeCapabilities) -> bool {
+        (self.archive || !required.archive) && (self.traces || !required.traces)
+    }
+}
+
+impl FromStr for NodeCapabilities {
+    type Err = Error;
+
+    /// Parses a comma-separated list like `archive,traces`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut capabilities = NodeCapabilities {
+            archive: false,
+            traces: false,
+        };
+        for capability in s.split(',').map(str::trim) {
+            match capability {
+                "archive" => capabilities.archive = true,
+                "traces" => capabilities.traces = true,
+                _ => {
+                    return Err(format_err!(
+                        "unknown Ethereum node capability `{}`, \
+                         expected `archive` or `traces`",
+                        capability
+                    ))
+                }
+            }
+        }
+        Ok(capabilities)
+    }
+}
+
+impl fmt::Display for NodeCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let capabilities = [("archive", self.archive), ("traces", self.traces)]
+            .iter()
+            .filter(|(_, supported)| *supported)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        if capabilities.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", capabilities.join(","))
+        }
+    }
+}
+
 /// A request for the state of a contract at a specific block hash and address.
 pub struct EthereumContractStateRequest {
     pub address: Address,
@@ -88,6 +161,12 @@ impl From<Error> for EthereumAdapterError {
     }
 }
 
+/// An Ethereum node took too long to respond to a request. The request is
+/// described by what the node took too long to do, e.g. `return block #1`.
+#[derive(Fail, Debug)]
+#[fail(display = "Ethereum node took too long to {}", _0)]
+pub struct EthereumTimeoutError(pub String);
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
 enum LogFilterNode {
     Contract(Address),
@@ -132,6 +211,11 @@ pub struct EthereumLogFilter {
 
     // Event sigs with no associated address, matching on all addresses.
     wildcard_events: HashSet<EventSignature>,
+
+    // The (contract, event) pairs whose handlers need the receipt of the
+    // transaction that emitted the event. A contract of `None` stands for
+    // all contracts, like `wildcard_events`.
+    receipt_events: HashSet<(Option<Address>, EventSignature)>,
 }
 
 impl EthereumLogFilter {
@@ -165,10 +249,23 @@ impl EthereumLogFilter {
         }
     }
 
+    /// Check if a handler for the specified `Log` needs the receipt of the
+    /// transaction that emitted it.
+    pub fn requires_receipt(&self, log: &Log) -> bool {
+        log.topics.first().map_or(false, |sig| {
+            self.receipt_events.contains(&(Some(log.address), *sig))
+                || self.receipt_events.contains(&(None, *sig))
+        })
+    }
+
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
         let mut this = EthereumLogFilter::default();
         for ds in iter {
-            for event_sig in ds.mapping.event_handlers.iter().map(|e| e.topic0()) {
+            for event_handler in ds.mapping.event_handlers.iter() {
+                let event_sig = event_handler.topic0();
+                if event_handler.receipt {
+                    this.receipt_events.insert((ds.source.address, event_sig));
+                }
                 match ds.source.address {
                     Some(contract) => {
                         this.contracts_and_events_graph.add_edge(
@@ -192,11 +289,13 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            receipt_events,
         } = other;
         for (s, t, ()) in contracts_and_events_graph.all_edges() {
             self.contracts_and_events_graph.add_edge(s, t, ());
         }
         self.wildcard_events.extend(wildcard_events);
+        self.receipt_events.extend(receipt_events);
     }
 
     /// An empty filter is one that never matches.
@@ -205,6 +304,7 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            receipt_events: _,
         } = self;
         contracts_and_events_graph.edge_count() == 0 && wildcard_events.is_empty()
     }
@@ -469,19 +569,36 @@ impl EthereumBlockFilter {
     }
 }
 
+/// A provider is considered unhealthy after this many errors in a row.
+const PROVIDER_UNHEALTHY_AFTER_ERRORS: usize = 3;
+
+/// How long an unhealthy provider is avoided before it gets another chance.
+const PROVIDER_UNHEALTHY_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+struct ProviderHealth {
+    consecutive_errors: usize,
+    last_error: Option<Instant>,
+}
+
 #[derive(Clone)]
 pub struct ProviderEthRpcMetrics {
     request_duration: Box<HistogramVec>,
     errors: Box<CounterVec>,
+    health: Arc<Mutex<ProviderHealth>>,
 }
 
 impl ProviderEthRpcMetrics {
-    pub fn new<M: MetricsRegistry>(registry: Arc<M>) -> Self {
+    /// Metrics for the provider with the given `provider` name, which is
+    /// added as a label to all metrics.
+    pub fn new<M: MetricsRegistry>(registry: Arc<M>, provider: &str) -> Self {
+        let mut const_labels = HashMap::new();
+        const_labels.insert(String::from("provider"), provider.to_owned());
         let request_duration = registry
             .new_histogram_vec(
                 String::from("eth_rpc_request_duration"),
                 String::from("Measures eth rpc request duration"),
-                HashMap::new(),
+                const_labels.clone(),
                 vec![String::from("method")],
                 vec![0.05, 0.2, 0.5, 1.0, 3.0, 5.0],
             )
@@ -490,13 +607,14 @@ impl ProviderEthRpcMetrics {
             .new_counter_vec(
                 String::from("eth_rpc_errors"),
                 String::from("Counts eth rpc request errors"),
-                HashMap::new(),
+                const_labels,
                 vec![String::from("method")],
             )
             .unwrap();
         Self {
             request_duration,
             errors,
+            health: Arc::new(Mutex::new(ProviderHealth::default())),
         }
     }
 
@@ -506,8 +624,28 @@ impl ProviderEthRpcMetrics {
             .observe(duration);
     }
 
+    /// Record a failed or timed out request.
     pub fn add_error(&self, method: &str) {
         self.errors.with_label_values(vec![method].as_slice()).inc();
+
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_errors += 1;
+        health.last_error = Some(Instant::now());
+    }
+
+    /// Record a successful request, which makes the provider healthy again.
+    pub fn add_success(&self) {
+        self.health.lock().unwrap().consecutive_errors = 0;
+    }
+
+    /// A provider is healthy unless its last few requests all failed. Unhealthy
+    /// providers become healthy again after a while so that they are retried.
+    pub fn is_healthy(&self) -> bool {
+        let health = self.health.lock().unwrap();
+        health.consecutive_errors < PROVIDER_UNHEALTHY_AFTER_ERRORS
+            || health.last_error.map_or(true, |last_error| {
+                last_error.elapsed() >= PROVIDER_UNHEALTHY_BACKOFF
+            })
     }
 }
 
@@ -607,6 +745,19 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         logger: &Logger,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = EthereumAdapterError> + Send>;
 
+    /// Subscribe to new chain heads with `eth_subscribe("newHeads")`. The returned future fails
+    /// if the transport does not support subscriptions, and the stream fails or ends when the
+    /// connection to the Ethereum node is lost.
+    fn subscribe_new_heads(
+        &self,
+        logger: &Logger,
+    ) -> Box<
+        dyn Future<
+                Item = Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>,
+                Error = Error,
+            > + Send,
+    >;
+
     fn load_block(
         &self,
         logger: &Logger,
@@ -622,6 +773,17 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         block_hashes: HashSet<H256>,
     ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send>;
 
+    /// Load the blocks with the given numbers, in the order of `block_numbers`. The blocks are
+    /// requested in batches, several of which are sent in parallel.
+    ///
+    /// Careful: the blocks for numbers that are not final can change at any time, so
+    /// consecutive blocks returned by this function are not necessarily each other's parent.
+    fn blocks_by_numbers(
+        &self,
+        logger: &Logger,
+        block_numbers: Vec<u64>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send>;
+
     /// Reorg safety: `to` must be a final block.
     fn block_range_to_ptrs(
         &self,
@@ -638,12 +800,24 @@ pub trait EthereumAdapter: Send + Sync + 'static {
     ) -> Box<dyn Future<Item = Option<LightEthereumBlock>, Error = Error> + Send>;
 
     /// Load full information for the specified `block` (in particular, transaction receipts).
+    /// May use the `chain_store` as a cache.
     fn load_full_block(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         block: LightEthereumBlock,
     ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send>;
 
+    /// Load the receipts of the transactions with the given hashes, all of which must be part of
+    /// the block with hash `block_hash`. May use the `chain_store` as a cache.
+    fn load_transaction_receipts(
+        &self,
+        logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
+        block_hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Box<dyn Future<Item = Vec<TransactionReceipt>, Error = EthereumAdapterError> + Send>;
+
     /// Load block pointer for the specified `block number`.
     fn block_pointer_from_number(
         &self,
@@ -683,9 +857,11 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         block_ptr: EthereumBlockPointer,
     ) -> Box<dyn Future<Item = bool, Error = Error> + Send>;
 
+    /// Find all calls made in a block. May use the `chain_store` as a cache.
     fn calls_in_block(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         block_number: u64,
         block_hash: H256,
@@ -725,17 +901,42 @@ pub trait EthereumAdapter: Send + Sync + 'static {
 
         // Scan the block range from triggers to find relevant blocks
         if !log_filter.is_empty() {
+            let eth = eth.clone();
+            let logger = logger.clone();
+            let chain_store = chain_store.clone();
             trigger_futs.push(Box::new(
-                eth.logs_in_block_range(&logger, subgraph_metrics.clone(), from, to, log_filter)
-                    .map(|logs: Vec<Log>| logs.into_iter().map(EthereumTrigger::Log).collect()),
+                eth.logs_in_block_range(
+                    &logger,
+                    chain_store.clone(),
+                    subgraph_metrics.clone(),
+                    from,
+                    to,
+                    log_filter.clone(),
+                )
+                .and_then(move |logs: Vec<Log>| {
+                    log_triggers_with_receipts(
+                        eth.as_ref(),
+                        &logger,
+                        chain_store,
+                        &log_filter,
+                        logs,
+                    )
+                }),
             ))
         }
 
         if !call_filter.is_empty() {
             trigger_futs.push(Box::new(
-                eth.calls_in_block_range(&logger, subgraph_metrics.clone(), from, to, call_filter)
-                    .map(EthereumTrigger::Call)
-                    .collect(),
+                eth.calls_in_block_range(
+                    &logger,
+                    chain_store.clone(),
+                    subgraph_metrics.clone(),
+                    from,
+                    to,
+                    call_filter,
+                )
+                .map(EthereumTrigger::Call)
+                .collect(),
             ));
         }
 
@@ -754,14 +955,21 @@ pub trait EthereumAdapter: Send + Sync + 'static {
             // a `call_filter` and run `blocks_with_calls`
             let call_filter = EthereumCallFilter::from(block_filter);
             trigger_futs.push(Box::new(
-                eth.calls_in_block_range(&logger, subgraph_metrics.clone(), from, to, call_filter)
-                    .map(|call| {
-                        EthereumTrigger::Block(
-                            EthereumBlockPointer::from(&call),
-                            EthereumBlockTriggerType::WithCallTo(call.to),
-                        )
-                    })
-                    .collect(),
+                eth.calls_in_block_range(
+                    &logger,
+                    chain_store.clone(),
+                    subgraph_metrics.clone(),
+                    from,
+                    to,
+                    call_filter,
+                )
+                .map(|call| {
+                    EthereumTrigger::Block(
+                        EthereumBlockPointer::from(&call),
+                        EthereumBlockTriggerType::WithCallTo(call.to),
+                    )
+                })
+                .collect(),
             ));
         }
 
@@ -809,6 +1017,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
     fn logs_in_block_range(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
@@ -818,6 +1027,7 @@ pub trait EthereumAdapter: Send + Sync + 'static {
     fn calls_in_block_range(
         &self,
         logger: &Logger,
+        chain_store: Arc<dyn ChainStore>,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: u64,
         to: u64,
@@ -843,3 +1053,58 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         ethereum_block: BlockFinality,
     ) -> Box<dyn Future<Item = EthereumBlockWithTriggers, Error = Error> + Send>;
 }
+
+/// Turn `logs` into triggers, loading the receipts of the transactions that emitted logs whose
+/// handlers need them.
+fn log_triggers_with_receipts<A: EthereumAdapter + ?Sized>(
+    eth: &A,
+    logger: &Logger,
+    chain_store: Arc<dyn ChainStore>,
+    log_filter: &EthereumLogFilter,
+    logs: Vec<Log>,
+) -> Box<dyn Future<Item = Vec<EthereumTrigger>, Error = Error> + Send> {
+    // Receipts are loaded per block, which lets the adapter check that
+    // they are still part of the block the logs came from
+    let mut transactions_by_block: HashMap<H256, HashSet<H256>> = HashMap::new();
+    for log in logs.iter().filter(|log| log_filter.requires_receipt(log)) {
+        if let (Some(block_hash), Some(transaction_hash)) = (log.block_hash, log.transaction_hash) {
+            transactions_by_block
+                .entry(block_hash)
+                .or_default()
+                .insert(transaction_hash);
+        }
+    }
+
+    let receipt_futures = transactions_by_block
+        .into_iter()
+        .map(|(block_hash, transaction_hashes)| {
+            eth.load_transaction_receipts(
+                logger,
+                chain_store.clone(),
+                block_hash,
+                transaction_hashes.into_iter().collect(),
+            )
+            .map_err(Error::from)
+        })
+        .collect::<Vec<_>>();
+
+    let receipts = stream::iter_ok::<_, Error>(receipt_futures)
+        .buffered(*PARALLEL_RECEIPT_BLOCKS)
+        .collect();
+
+    Box::new(receipts.map(move |receipts| {
+        let receipts: HashMap<H256, Arc<TransactionReceipt>> = receipts
+            .into_iter()
+            .flatten()
+            .map(|receipt| (receipt.transaction_hash, Arc::new(receipt)))
+            .collect();
+        logs.into_iter()
+            .map(|log| {
+                let receipt = log
+                    .transaction_hash
+                    .and_then(|hash| receipts.get(&hash).cloned());
+                EthereumTrigger::Log(log, receipt)
+            })
+            .collect()
+    }))
+}
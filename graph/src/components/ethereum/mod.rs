@@ -7,13 +7,14 @@ pub use self::adapter::{
     BlockStreamMetrics, EthGetLogsFilter, EthereumAdapter, EthereumAdapterError,
     EthereumBlockFilter, EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
     EthereumContractState, EthereumContractStateError, EthereumContractStateRequest,
-    EthereumLogFilter, EthereumNetworkIdentifier, ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
+    EthereumLogFilter, EthereumNetworkIdentifier, EthereumTimeoutError, NodeCapabilities,
+    ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
 };
 pub use self::listener::{ChainHeadUpdate, ChainHeadUpdateListener, ChainHeadUpdateStream};
 pub use self::stream::{BlockStream, BlockStreamBuilder};
 pub use self::types::{
     BlockFinality, EthereumBlock, EthereumBlockData, EthereumBlockPointer,
     EthereumBlockTriggerType, EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall,
-    EthereumCallData, EthereumEventData, EthereumTransactionData, EthereumTrigger,
-    LightEthereumBlock, LightEthereumBlockExt,
+    EthereumCallData, EthereumEventData, EthereumLogData, EthereumTransactionData,
+    EthereumTransactionReceiptData, EthereumTrigger, LightEthereumBlock, LightEthereumBlockExt,
 };
@@ -18,6 +18,7 @@ pub trait BlockStreamBuilder: Clone + Send + Sync + 'static {
         call_filter: EthereumCallFilter,
         block_filter: EthereumBlockFilter,
         templates_use_calls: bool,
+        confirmations: Option<u64>,
         ethrpc_metrics: Arc<BlockStreamMetrics>,
     ) -> Self::Stream;
 }
@@ -1,6 +1,7 @@
 use ethabi::LogParam;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::sync::Arc;
 use web3::types::*;
 
 pub type LightEthereumBlock = Block<Transaction>;
@@ -111,7 +112,7 @@ pub struct EthereumBlock {
     pub transaction_receipts: Vec<TransactionReceipt>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct EthereumCall {
     pub from: Address,
     pub to: Address,
@@ -161,13 +162,33 @@ impl EthereumCall {
             transaction_index,
         })
     }
+
+    /// The top-level call made by a transaction that is included in a
+    /// block, returning `output`. Returns `None` for transactions that are
+    /// not in a block or that create a contract.
+    pub fn from_transaction(transaction: &Transaction, output: Bytes) -> Option<Self> {
+        Some(EthereumCall {
+            from: transaction.from,
+            to: transaction.to?,
+            value: transaction.value,
+            gas_used: transaction.gas,
+            input: transaction.input.clone(),
+            output,
+            block_number: transaction.block_number?.as_u64(),
+            block_hash: transaction.block_hash?,
+            transaction_hash: Some(transaction.hash),
+            transaction_index: transaction.transaction_index?.as_u64(),
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum EthereumTrigger {
     Block(EthereumBlockPointer, EthereumBlockTriggerType),
     Call(EthereumCall),
-    Log(Log),
+    /// A log, together with the receipt of the transaction that emitted it
+    /// if one of the handlers for the log needs it
+    Log(Log, Option<Arc<TransactionReceipt>>),
 }
 
 #[derive(Clone, Debug)]
@@ -180,7 +201,7 @@ impl EthereumTrigger {
     fn transaction_index(&self) -> Option<u64> {
         match self {
             // We only handle logs that are in a block and therefore have a `transaction_index`.
-            EthereumTrigger::Log(log) => Some(log.transaction_index.unwrap().as_u64()),
+            EthereumTrigger::Log(log, _) => Some(log.transaction_index.unwrap().as_u64()),
             EthereumTrigger::Call(call) => Some(call.transaction_index),
             EthereumTrigger::Block(_, _) => None,
         }
@@ -190,7 +211,7 @@ impl EthereumTrigger {
         match self {
             EthereumTrigger::Block(block_ptr, _) => block_ptr.number,
             EthereumTrigger::Call(call) => call.block_number,
-            EthereumTrigger::Log(log) => log.block_number.unwrap().as_u64(),
+            EthereumTrigger::Log(log, _) => log.block_number.unwrap().as_u64(),
         }
     }
 
@@ -198,7 +219,7 @@ impl EthereumTrigger {
         match self {
             EthereumTrigger::Block(block_ptr, _) => block_ptr.hash,
             EthereumTrigger::Call(call) => call.block_hash,
-            EthereumTrigger::Log(log) => log.block_hash.unwrap(),
+            EthereumTrigger::Log(log, _) => log.block_hash.unwrap(),
         }
     }
 }
@@ -271,6 +292,51 @@ impl<'a> From<&'a Transaction> for EthereumTransactionData {
     }
 }
 
+/// An Ethereum log that is part of a transaction receipt.
+#[derive(Clone, Debug)]
+pub struct EthereumLogData {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+    pub log_index: U256,
+    pub transaction_log_index: U256,
+    pub log_type: Option<String>,
+}
+
+impl<'a> From<&'a Log> for EthereumLogData {
+    fn from(log: &'a Log) -> EthereumLogData {
+        EthereumLogData {
+            address: log.address,
+            topics: log.topics.clone(),
+            data: log.data.clone(),
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+        }
+    }
+}
+
+/// Ethereum transaction receipt data.
+#[derive(Clone, Debug)]
+pub struct EthereumTransactionReceiptData {
+    /// `None` for transactions from before the Byzantium fork
+    pub status: Option<U256>,
+    pub gas_used: U256,
+    pub cumulative_gas_used: U256,
+    pub logs: Vec<EthereumLogData>,
+}
+
+impl<'a> From<&'a TransactionReceipt> for EthereumTransactionReceiptData {
+    fn from(receipt: &'a TransactionReceipt) -> EthereumTransactionReceiptData {
+        EthereumTransactionReceiptData {
+            status: receipt.status.map(|status| U256::from(status.as_u64())),
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            logs: receipt.logs.iter().map(EthereumLogData::from).collect(),
+        }
+    }
+}
+
 /// An Ethereum event logged from a specific contract address and block.
 #[derive(Debug)]
 pub struct EthereumEventData {
@@ -281,6 +347,8 @@ pub struct EthereumEventData {
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
     pub params: Vec<LogParam>,
+    /// Only set for handlers that asked for the receipt in the manifest
+    pub receipt: Option<EthereumTransactionReceiptData>,
 }
 
 impl Clone for EthereumEventData {
@@ -300,6 +368,7 @@ impl Clone for EthereumEventData {
                     value: log_param.value.clone(),
                 })
                 .collect(),
+            receipt: self.receipt.clone(),
         }
     }
 }
@@ -17,6 +17,17 @@ pub struct JsonStreamValue {
 pub type JsonValueStream =
     Box<dyn Stream<Item = JsonStreamValue, Error = failure::Error> + Send + 'static>;
 
+/// The values that `line_stream` returns. The struct contains the text of a
+/// line from the input stream, without the line terminator, together with
+/// the line number from which it was read.
+pub struct LineStreamValue {
+    pub value: String,
+    pub line: usize,
+}
+
+pub type LineValueStream =
+    Box<dyn Stream<Item = LineStreamValue, Error = failure::Error> + Send + 'static>;
+
 /// Resolves links to subgraph manifests and resources referenced by them.
 pub trait LinkResolver: Send + Sync + 'static {
     /// Updates the timeout used by the resolver.
@@ -44,4 +55,11 @@ pub trait LinkResolver: Send + Sync + 'static {
         &self,
         link: &Link,
     ) -> Box<dyn Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static>;
+
+    /// Read the contents of `link` and split them into a stream of lines.
+    /// Empty lines are skipped, but still counted for the line numbers.
+    fn line_stream(
+        &self,
+        link: &Link,
+    ) -> Box<dyn Future<Item = LineValueStream, Error = failure::Error> + Send + 'static>;
 }
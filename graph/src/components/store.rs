@@ -3,13 +3,13 @@ use futures::stream::poll_fn;
 use futures::{Async, Future, Poll, Stream};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use web3::types::H256;
+use web3::types::{Log, TransactionReceipt, H256};
 
 use crate::data::store::*;
 use crate::data::subgraph::schema::*;
@@ -24,8 +24,30 @@ lazy_static! {
             )))
             .map(|millis| Duration::from_millis(millis))
             .unwrap_or(Duration::from_millis(1000));
+
+    /// Whether to reject queries that have not been persisted for the
+    /// subgraph they are sent to
+    pub static ref PERSISTED_QUERIES_ONLY: bool = env::var("GRAPH_GRAPHQL_PERSISTED_QUERIES_ONLY")
+        .ok()
+        .map(|s| bool::from_str(&s).unwrap_or_else(|_| {
+            panic!("failed to parse env var GRAPH_GRAPHQL_PERSISTED_QUERIES_ONLY")
+        }))
+        .unwrap_or(false);
 }
 
+/// Looks up the text of a persisted query by its hex-encoded SHA-256 hash,
+/// usually with `Store::persisted_query` for a fixed deployment.
+pub type PersistedQueryLookup = Box<dyn Fn(&str) -> Result<Option<String>, Error> + Send>;
+
+/// The type we use for block numbers. This has to be a signed integer type
+/// since Postgres does not support unsigned integer types. But 2G ought to
+/// be enough for everybody
+pub type BlockNumber = i32;
+
+/// The largest block number we support; queries at this block always see
+/// the latest version of every entity
+pub const BLOCK_NUMBER_MAX: BlockNumber = std::i32::MAX;
+
 /// Key by which an individual entity in the store can be accessed.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EntityKey {
@@ -58,6 +80,9 @@ pub enum EntityFilter {
     NotStartsWith(Attribute, Value),
     EndsWith(Attribute, Value),
     NotEndsWith(Attribute, Value),
+    /// Matches entities whose fulltext search field `Attribute`, declared
+    /// with `@fulltext`, matches the search text in `Value`
+    Fulltext(Attribute, Value),
 }
 
 // Define some convenience methods
@@ -87,14 +112,42 @@ pub enum EntityOrder {
     Descending,
 }
 
-/// How many entities to return, how many to skip etc.
+/// A position in the result of a query, identified by the value of the
+/// attribute the query is ordered by and the id of the entity at that
+/// position. Queries are always ordered by `id` last, which makes that
+/// position unique.
 #[derive(Clone, Debug, PartialEq)]
+pub struct EntityCursor {
+    /// The value of the attribute the query is ordered by; `Value::Null`
+    /// if the entity does not have a value for it, or if the query is only
+    /// ordered by `id`
+    pub value: Value,
+
+    /// The id of the entity at the position
+    pub id: String,
+}
+
+/// How many entities to return, how many to skip etc.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct EntityRange {
     /// Limit on how many entities to return.
     pub first: Option<u32>,
 
     /// How many entities to skip.
     pub skip: u32,
+
+    /// Only return entities that come strictly after this position in the
+    /// order of the query.
+    pub after: Option<EntityCursor>,
+
+    /// Only return entities that come strictly before this position in the
+    /// order of the query.
+    pub before: Option<EntityCursor>,
+
+    /// Return entities in the reverse of the order of the query, so that
+    /// `first` and `skip` count from the end of the result. Cursors still
+    /// refer to positions in the order of the query.
+    pub reverse: bool,
 }
 
 impl EntityRange {
@@ -103,8 +156,16 @@ impl EntityRange {
         Self {
             first: Some(n),
             skip: 0,
+            after: None,
+            before: None,
+            reverse: false,
         }
     }
+
+    /// Return `true` if the range restricts the result with cursors.
+    pub fn has_cursor(&self) -> bool {
+        self.after.is_some() || self.before.is_some()
+    }
 }
 
 /// A query for entities in a store.
@@ -128,6 +189,11 @@ pub struct EntityQuery {
 
     /// A range to limit the size of the result.
     pub range: EntityRange,
+
+    /// The block at which to run the query; entities are returned in the
+    /// version they had at that block. Use `BLOCK_NUMBER_MAX` to query
+    /// the latest version of entities.
+    pub block: BlockNumber,
 }
 
 impl EntityQuery {
@@ -143,6 +209,7 @@ impl EntityQuery {
             order_by: None,
             order_direction: None,
             range,
+            block: BLOCK_NUMBER_MAX,
         }
     }
 
@@ -161,6 +228,165 @@ impl EntityQuery {
         self.range = range;
         self
     }
+
+    pub fn block(mut self, block: BlockNumber) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Restrict this query to the entities that belong to the parent with
+    /// id `parent_id` in `window`
+    pub fn for_parent(&self, window: &EntityWindow, parent_id: &str) -> Self {
+        let parent_filter = window.filter_for(parent_id);
+        let mut query = self.clone();
+        query.filter = Some(match query.filter {
+            Some(EntityFilter::And(mut filters)) => {
+                filters.push(parent_filter);
+                EntityFilter::And(filters)
+            }
+            Some(filter) => EntityFilter::And(vec![filter, parent_filter]),
+            None => parent_filter,
+        });
+        query
+    }
+}
+
+/// A value computed over all the entities that match a query. Sums have
+/// type `BigInt` for `Int` and `BigInt` attributes and type `BigDecimal`
+/// for `BigDecimal` attributes; averages are always `BigDecimal`, and
+/// minimum and maximum have the type of the attribute. All aggregates
+/// except `Count` are `Value::Null` if no entity has a value for the
+/// attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityAggregate {
+    /// The number of entities, as a `BigInt`
+    Count,
+    Sum(Attribute),
+    Avg(Attribute),
+    Min(Attribute),
+    Max(Attribute),
+}
+
+impl EntityAggregate {
+    /// Compute the aggregate over `entities` in memory. The aggregated
+    /// attribute must be numeric.
+    pub fn compute(&self, entities: &[Entity]) -> Value {
+        fn to_big_decimal(value: &Value) -> Option<BigDecimal> {
+            match value {
+                Value::Int(i) => Some(BigDecimal::from(*i)),
+                Value::BigInt(n) => Some(n.clone().to_big_decimal(0.into())),
+                Value::BigDecimal(d) => Some(d.clone()),
+                _ => None,
+            }
+        }
+
+        fn values<'a>(entities: &'a [Entity], attribute: &Attribute) -> Vec<&'a Value> {
+            entities
+                .iter()
+                .filter_map(|entity| match entity.get(attribute) {
+                    None | Some(Value::Null) => None,
+                    Some(value) => Some(value),
+                })
+                .collect()
+        }
+
+        fn extreme(entities: &[Entity], attribute: &Attribute, keep: std::cmp::Ordering) -> Value {
+            values(entities, attribute)
+                .into_iter()
+                .fold(None, |best: Option<&Value>, value| match best {
+                    Some(best) if to_big_decimal(value).cmp(&to_big_decimal(best)) != keep => {
+                        Some(best)
+                    }
+                    _ => Some(value),
+                })
+                .cloned()
+                .unwrap_or(Value::Null)
+        }
+
+        match self {
+            EntityAggregate::Count => Value::BigInt(BigInt::from(entities.len() as u64)),
+            EntityAggregate::Sum(attribute) => {
+                values(entities, attribute)
+                    .into_iter()
+                    .fold(Value::Null, |sum, value| match (sum, value) {
+                        (Value::Null, Value::Int(i)) => Value::BigInt(BigInt::from(*i)),
+                        (Value::Null, value) => value.clone(),
+                        (Value::BigInt(sum), Value::Int(i)) => {
+                            Value::BigInt(sum + BigInt::from(*i))
+                        }
+                        (Value::BigInt(sum), Value::BigInt(n)) => Value::BigInt(sum + n.clone()),
+                        (Value::BigDecimal(sum), Value::BigDecimal(d)) => {
+                            Value::BigDecimal(sum + d.clone())
+                        }
+                        (sum, _) => sum,
+                    })
+            }
+            EntityAggregate::Avg(attribute) => {
+                let values: Vec<_> = values(entities, attribute)
+                    .into_iter()
+                    .filter_map(to_big_decimal)
+                    .collect();
+                if values.is_empty() {
+                    Value::Null
+                } else {
+                    let count = BigDecimal::from(values.len() as u64);
+                    let sum = values
+                        .into_iter()
+                        .fold(BigDecimal::from(0), |sum, value| sum + value);
+                    Value::BigDecimal(sum / count)
+                }
+            }
+            EntityAggregate::Min(attribute) => {
+                extreme(entities, attribute, std::cmp::Ordering::Less)
+            }
+            EntityAggregate::Max(attribute) => {
+                extreme(entities, attribute, std::cmp::Ordering::Greater)
+            }
+        }
+    }
+}
+
+/// The attribute through which the entities returned by a windowed query
+/// refer to their parent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowAttribute {
+    /// The attribute holds the id of a single parent.
+    Scalar(Attribute),
+    /// The attribute holds a list of parent ids.
+    List(Attribute),
+}
+
+impl WindowAttribute {
+    pub fn name(&self) -> &str {
+        match self {
+            WindowAttribute::Scalar(name) | WindowAttribute::List(name) => name,
+        }
+    }
+}
+
+/// Groups the results of a query by parent: an entity belongs to every
+/// parent in `ids` that it refers to through `link`, and the range of the
+/// query applies to the entities of each parent separately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityWindow {
+    /// The attribute that links entities to their parents.
+    pub link: WindowAttribute,
+
+    /// The ids of the parents.
+    pub ids: Vec<String>,
+}
+
+impl EntityWindow {
+    /// The filter that matches the entities belonging to the parent with
+    /// id `parent_id`.
+    pub fn filter_for(&self, parent_id: &str) -> EntityFilter {
+        match &self.link {
+            WindowAttribute::Scalar(name) => EntityFilter::Equal(name.clone(), parent_id.into()),
+            WindowAttribute::List(name) => {
+                EntityFilter::Contains(name.clone(), Value::List(vec![parent_id.into()]))
+            }
+        }
+    }
 }
 
 /// Operation types that lead to entity changes.
@@ -606,6 +832,55 @@ pub trait Store: Send + Sync + 'static {
     /// Queries the store for entities that match the store query.
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError>;
 
+    /// Like `find`, but string ids are compared byte by byte, the way Rust
+    /// compares `String`s, rather than with the rules of the store, which
+    /// might depend on its locale. Stores that compare ids like Rust can
+    /// use the default implementation.
+    fn find_in_byte_order(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.find(query)
+    }
+
+    /// Queries the store for the entities matching `query` that belong to
+    /// each of the parents in `window`, as if `query` had been run once for
+    /// every parent. Returns pairs of parent id and entity; the entities of
+    /// each parent are ordered and limited as requested by `query`.
+    ///
+    /// The default implementation runs one query per parent; stores should
+    /// override it with something more efficient.
+    fn find_windowed(
+        &self,
+        query: EntityQuery,
+        window: EntityWindow,
+    ) -> Result<Vec<(String, Entity)>, QueryExecutionError> {
+        let mut entities = vec![];
+        for parent_id in &window.ids {
+            for entity in self.find(query.for_parent(&window, parent_id))? {
+                entities.push((parent_id.clone(), entity));
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Computes `aggregates` over all entities that match `query`,
+    /// disregarding its order and range. Returns one value per aggregate.
+    ///
+    /// The default implementation loads all matching entities; stores should
+    /// override it with something more efficient.
+    fn aggregate(
+        &self,
+        mut query: EntityQuery,
+        aggregates: &[EntityAggregate],
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        query.order_by = None;
+        query.order_direction = None;
+        query.range = EntityRange::default();
+        let entities = self.find(query)?;
+        Ok(aggregates
+            .iter()
+            .map(|aggregate| aggregate.compute(&entities))
+            .collect())
+    }
+
     /// Queries the store for a single entity matching the store query.
     fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError>;
 
@@ -613,6 +888,15 @@ pub trait Store: Send + Sync + 'static {
     /// rainbow table.
     fn find_ens_name(&self, _hash: &str) -> Result<Option<String>, QueryExecutionError>;
 
+    /// Look up the number of the block with the given hash on the network
+    /// that the subgraph `subgraph_id` indexes. Returns `None` if the store
+    /// does not know about a block with that hash.
+    fn block_number(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block_hash: H256,
+    ) -> Result<Option<BlockNumber>, StoreError>;
+
     /// Transact the entity changes from a single block atomically into the store, and update the
     /// subgraph block pointer to `block_ptr_to`.
     ///
@@ -652,6 +936,18 @@ pub trait Store: Send + Sync + 'static {
         block_ptr_to: EthereumBlockPointer,
     ) -> Result<(), StoreError>;
 
+    /// Revert the entity changes from all blocks after `block_ptr_to` atomically in the
+    /// store, and update the subgraph block pointer to `block_ptr_to`.
+    ///
+    /// `block_ptr_from` must match the current value of the subgraph block pointer, and
+    /// `block_ptr_to` must point to one of its ancestors.
+    fn rewind_block_operations(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        block_ptr_from: EthereumBlockPointer,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError>;
+
     /// Subscribe to changes for specific subgraphs and entities.
     ///
     /// Returns a stream of store events that match the input arguments.
@@ -721,6 +1017,19 @@ pub trait Store: Send + Sync + 'static {
             .map(Some)
     }
 
+    /// Find the text of the query with the hex-encoded SHA-256 hash `hash`
+    /// among the queries persisted for the subgraphs that `deployment` is a
+    /// version of. Since the text for a hash never changes, implementations
+    /// may cache queries they found until a persisted query or a version is
+    /// removed
+    fn persisted_query(
+        &self,
+        deployment: &SubgraphDeploymentId,
+        hash: &str,
+    ) -> Result<Option<String>, Error> {
+        find_persisted_query(self, deployment, hash)
+    }
+
     /// Read all version entities pointing to the specified deployment IDs and
     /// determine whether they are current or pending in order to produce
     /// `SubgraphVersionSummary`s.
@@ -937,11 +1246,13 @@ pub trait Store: Send + Sync + 'static {
     /// Create a new subgraph deployment. The deployment must not exist yet. `ops`
     /// needs to contain all the operations on subgraphs and subgraph deployments to
     /// create the deployment, including any assignments as a current or pending
-    /// version
+    /// version. If `graft` is given, the new deployment starts out with the
+    /// entities of the graft base as of the graft block
     fn create_subgraph_deployment(
         &self,
         schema: &Schema,
         ops: Vec<MetadataOperation>,
+        graft: Option<&Graft>,
     ) -> Result<(), StoreError>;
 
     /// Start an existing subgraph deployment. This will reset the state of
@@ -969,6 +1280,37 @@ pub trait Store: Send + Sync + 'static {
     );
 }
 
+/// Look up a persisted query for `Store::persisted_query` without any
+/// caching
+pub fn find_persisted_query<S: Store + ?Sized>(
+    store: &S,
+    deployment: &SubgraphDeploymentId,
+    hash: &str,
+) -> Result<Option<String>, Error> {
+    let versions = store
+        .find(
+            SubgraphVersionEntity::query().filter(EntityFilter::new_equal(
+                "deployment",
+                deployment.to_string(),
+            )),
+        )
+        .map_err(QueryError::from)?;
+
+    for version in versions {
+        let subgraph = version
+            .get("subgraph")
+            .and_then(|subgraph| subgraph.clone().as_string())
+            .ok_or_else(|| format_err!("SubgraphVersion entity without `subgraph`"))?;
+        let key =
+            SubgraphPersistedQueryEntity::key(SubgraphPersistedQueryEntity::id(&subgraph, hash));
+        let entity = store.get(key).map_err(QueryError::from)?;
+        if let Some(Value::String(query)) = entity.as_ref().and_then(|e| e.get("query")) {
+            return Ok(Some(query.to_owned()));
+        }
+    }
+    Ok(None)
+}
+
 pub trait SubgraphDeploymentStore: Send + Sync + 'static {
     /// Return the GraphQL schema supplied by the user
     fn input_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<Arc<Schema>, Error>;
@@ -1029,9 +1371,65 @@ pub trait ChainStore: Send + Sync + 'static {
     /// The head block pointer will be None on initial set up.
     fn chain_head_ptr(&self) -> Result<Option<EthereumBlockPointer>, Error>;
 
+    /// Insert blocks together with the receipts of all their transactions, replacing blocks
+    /// that are already present.
+    fn upsert_full_blocks(&self, blocks: Vec<EthereumBlock>) -> Result<(), Error>;
+
     /// Returns the blocks present in the store.
     fn blocks(&self, hashes: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error>;
 
+    /// Returns the blocks present in the store that were stored with the receipts of all their
+    /// transactions.
+    fn full_blocks(&self, hashes: Vec<H256>) -> Result<Vec<EthereumBlock>, Error>;
+
+    /// Returns all calls made in the block with hash `block_hash`, if they are in the store.
+    fn block_calls(&self, block_hash: H256) -> Result<Option<Vec<EthereumCall>>, Error>;
+
+    /// Store all calls made in the block `block_ptr` points to.
+    fn set_block_calls(
+        &self,
+        block_ptr: EthereumBlockPointer,
+        calls: &[EthereumCall],
+    ) -> Result<(), Error>;
+
+    /// Returns the receipts of those transactions with hashes in `transaction_hashes` that are
+    /// in the store for the block with hash `block_hash`, in no particular order.
+    fn transaction_receipts(
+        &self,
+        block_hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Result<Vec<TransactionReceipt>, Error>;
+
+    /// Store transaction receipts without the rest of their blocks.
+    fn upsert_transaction_receipts(&self, receipts: &[TransactionReceipt]) -> Result<(), Error>;
+
+    /// Returns the logs in blocks `from` to `to` that an earlier scan with the `eth_getLogs`
+    /// filter identified by `filter` found, if a scan of all of these blocks is in the store.
+    fn range_logs(&self, filter: &str, from: u64, to: u64) -> Result<Option<Vec<Log>>, Error>;
+
+    /// Store the logs that a scan of blocks `from` to `to` with the `eth_getLogs` filter
+    /// identified by `filter` found.
+    fn set_range_logs(&self, filter: &str, from: u64, to: u64, logs: &[Log]) -> Result<(), Error>;
+
+    /// Returns the calls in blocks `from` to `to` that an earlier scan with the `trace_filter`
+    /// filter identified by `filter` found, if a scan of all of these blocks is in the store.
+    fn range_calls(
+        &self,
+        filter: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Option<Vec<EthereumCall>>, Error>;
+
+    /// Store the calls that a scan of blocks `from` to `to` with the `trace_filter` filter
+    /// identified by `filter` found.
+    fn set_range_calls(
+        &self,
+        filter: &str,
+        from: u64,
+        to: u64,
+        calls: &[EthereumCall],
+    ) -> Result<(), Error>;
+
     /// Get the `offset`th ancestor of `block_hash`, where offset=0 means the block matching
     /// `block_hash` and offset=1 means its parent. Returns None if unable to complete due to
     /// missing blocks in the chain store.
@@ -1151,6 +1549,101 @@ impl EntityCache {
         }
     }
 
+    /// Find up to `limit` entities of type `entity_type` whose `attribute`
+    /// is equal to `value`, ordered by ID. Like `get`, this sees the
+    /// entities as they are after the changes made through the cache.
+    ///
+    /// IDs are compared byte by byte so that the order does not depend on
+    /// how the store is set up.
+    pub fn find(
+        &mut self,
+        store: &(impl Store + ?Sized),
+        subgraph_id: &SubgraphDeploymentId,
+        entity_type: &str,
+        attribute: &str,
+        value: &Value,
+        limit: u32,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        let updated = self
+            .updates
+            .keys()
+            .filter(|key| key.subgraph_id == *subgraph_id && key.entity_type == entity_type)
+            .cloned()
+            .collect::<Vec<_>>();
+        let entity_key = |entity: &Entity| -> Result<EntityKey, QueryExecutionError> {
+            Ok(EntityKey {
+                subgraph_id: subgraph_id.clone(),
+                entity_type: entity_type.to_owned(),
+                entity_id: entity.id().map_err(QueryExecutionError::StoreError)?,
+            })
+        };
+        let ids = |keys: &[EntityKey]| {
+            keys.iter()
+                .map(|key| Value::from(key.entity_id.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // Entities we changed are looked at below; from the store, we only
+        // need the first `limit` of the ones we didn't change
+        let mut filter = EntityFilter::Equal(attribute.to_owned(), value.clone());
+        if !updated.is_empty() {
+            filter = EntityFilter::And(vec![
+                filter,
+                EntityFilter::NotIn("id".to_owned(), ids(&updated)),
+            ]);
+        }
+        let query = EntityQuery::new(
+            subgraph_id.clone(),
+            vec![entity_type.to_owned()],
+            EntityRange::first(limit),
+        )
+        .filter(filter)
+        .order_by(("id".to_owned(), ValueType::ID), EntityOrder::Ascending);
+
+        let mut found = BTreeMap::new();
+        for entity in store.find_in_byte_order(query)? {
+            let key = entity_key(&entity)?;
+            found.insert(key.entity_id.clone(), entity.clone());
+            self.current.entry(key).or_insert(Some(entity));
+        }
+
+        // Load the entities we changed but haven't seen in the store yet
+        // with one query
+        let missing = updated
+            .iter()
+            .filter(|key| !self.current.contains_key(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let query = EntityQuery::new(
+                subgraph_id.clone(),
+                vec![entity_type.to_owned()],
+                EntityRange::first(missing.len() as u32),
+            )
+            .filter(EntityFilter::In("id".to_owned(), ids(&missing)));
+            for entity in store.find(query)? {
+                self.current.insert(entity_key(&entity)?, Some(entity));
+            }
+            for key in missing {
+                self.current.entry(key).or_insert(None);
+            }
+        }
+
+        for key in updated {
+            if let Some(entity) = self.get(store, &key)? {
+                if entity.get(attribute) == Some(value) {
+                    found.insert(key.entity_id, entity);
+                }
+            }
+        }
+
+        Ok(found
+            .into_iter()
+            .map(|(_, entity)| entity)
+            .take(limit as usize)
+            .collect())
+    }
+
     pub fn remove(&mut self, key: EntityKey) {
         self.updates.insert(key, None);
     }
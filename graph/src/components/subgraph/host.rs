@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use crate::components::metrics::HistogramVec;
 use crate::prelude::*;
-use web3::types::{Log, Transaction};
+use web3::types::{Log, Transaction, TransactionReceipt};
 
 /// Common trait for runtime host implementations.
 pub trait RuntimeHost: Send + Sync + Debug + 'static {
@@ -21,12 +21,14 @@ pub trait RuntimeHost: Send + Sync + Debug + 'static {
     fn matches_block(&self, call: EthereumBlockTriggerType, block_number: u64) -> bool;
 
     /// Process an Ethereum event and return a vector of entity operations.
+    /// The `receipt` is only passed to handlers that asked for it.
     fn process_log(
         &self,
         logger: Logger,
         block: Arc<LightEthereumBlock>,
         transaction: Arc<Transaction>,
         log: Arc<Log>,
+        receipt: Option<Arc<TransactionReceipt>>,
         state: BlockState,
     ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send>;
 
@@ -53,6 +55,7 @@ pub trait RuntimeHost: Send + Sync + Debug + 'static {
 pub struct HostMetrics {
     handler_execution_time: Box<HistogramVec>,
     host_fn_execution_time: Box<HistogramVec>,
+    handler_gas_used: Box<HistogramVec>,
 }
 
 impl fmt::Debug for HostMetrics {
@@ -82,9 +85,19 @@ impl HostMetrics {
                 vec![0.025, 0.05, 0.2, 2.0, 8.0, 20.0],
             )
             .expect("failed to create `subgraph_host_fn_execution_time` histogram");
+        let handler_gas_used = registry
+            .new_histogram_vec(
+                format!("subgraph_handler_gas_used_{}", subgraph_hash),
+                String::from("Measures the gas used by handlers"),
+                HashMap::new(),
+                vec![String::from("handler")],
+                vec![1e4, 1e5, 1e6, 1e7, 1e8, 1e9],
+            )
+            .expect("failed to create `subgraph_handler_gas_used` histogram");
         Self {
             handler_execution_time,
             host_fn_execution_time,
+            handler_gas_used,
         }
     }
 
@@ -94,6 +107,12 @@ impl HostMetrics {
             .observe(duration);
     }
 
+    pub fn observe_handler_gas_used(&self, gas: f64, handler: String) {
+        self.handler_gas_used
+            .with_label_values(vec![handler.as_ref()].as_slice())
+            .observe(gas);
+    }
+
     pub fn observe_host_fn_execution_time(&self, duration: f64, fn_name: String) {
         self.host_fn_execution_time
             .with_label_values(vec![fn_name.as_ref()].as_slice())
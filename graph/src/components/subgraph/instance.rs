@@ -6,6 +6,7 @@ pub struct DataSourceTemplateInfo {
     pub data_source: String,
     pub template: DataSourceTemplate,
     pub params: Vec<String>,
+    pub context: Option<DataSourceContext>,
 }
 
 #[derive(Debug, Default)]
@@ -14,6 +15,32 @@ pub struct BlockState {
     pub created_data_sources: Vec<DataSourceTemplateInfo>,
 }
 
+/// An error that will happen again every time the same block is processed,
+/// such as a mapping that aborts or Ethereum data that can't be decoded with
+/// the ABI of a data source. Subgraphs that fail with any other error are
+/// retried.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", _0)]
+pub struct DeterministicError(pub String);
+
+impl DeterministicError {
+    /// Returns `true` if `error` is a `DeterministicError`.
+    pub fn is_deterministic(error: &Error) -> bool {
+        error.downcast_ref::<DeterministicError>().is_some()
+    }
+
+    /// Prefixes the message of `error` with `context`, preserving whether
+    /// `error` is deterministic.
+    pub fn with_context(error: Error, context: &str) -> Error {
+        let message = format!("{}: {}", context, error);
+        if Self::is_deterministic(&error) {
+            DeterministicError(message).into()
+        } else {
+            err_msg(message)
+        }
+    }
+}
+
 /// Represents a loaded instance of a subgraph.
 pub trait SubgraphInstance<H: RuntimeHost> {
     /// Returns true if the subgraph has a handler for an Ethereum event.
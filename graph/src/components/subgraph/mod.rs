@@ -8,7 +8,9 @@ mod registrar;
 pub use crate::prelude::Entity;
 
 pub use self::host::{HostMetrics, RuntimeHost, RuntimeHostBuilder};
-pub use self::instance::{BlockState, DataSourceTemplateInfo, SubgraphInstance};
+pub use self::instance::{
+    BlockState, DataSourceTemplateInfo, DeterministicError, SubgraphInstance,
+};
 pub use self::instance_manager::SubgraphInstanceManager;
 pub use self::loader::DataSourceLoader;
 pub use self::provider::SubgraphAssignmentProvider;
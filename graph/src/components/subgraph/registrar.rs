@@ -40,4 +40,44 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         hash: SubgraphDeploymentId,
         node_id: NodeId,
     ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    /// Stops indexing a deployment that is assigned to this node without
+    /// changing its assignment. The deployment stays paused, also across
+    /// restarts of the node, until it is resumed.
+    fn pause_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    /// Starts indexing a paused deployment again.
+    fn resume_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    /// Reverts all changes a deployment made after `block_ptr`, which must
+    /// be an ancestor of the block the deployment has processed last. A
+    /// running deployment is stopped first and restarted afterwards.
+    fn rewind_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+        block_ptr: EthereumBlockPointer,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    /// Registers `query` as a persisted query for the subgraph `name` and
+    /// returns the hex-encoded SHA-256 hash under which clients can run it
+    /// against any version of the subgraph.
+    fn persist_query(
+        &self,
+        name: SubgraphName,
+        query: String,
+    ) -> Box<dyn Future<Item = String, Error = SubgraphRegistrarError> + Send + 'static>;
+
+    /// Removes the persisted query with the SHA-256 hash `query_hash` from
+    /// the subgraph `name`.
+    fn remove_persisted_query(
+        &self,
+        name: SubgraphName,
+        query_hash: String,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
 }
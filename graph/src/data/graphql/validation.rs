@@ -1,3 +1,4 @@
+use crate::data::schema::{FulltextDefinition, IndexDefinition};
 use crate::prelude::Fail;
 use graphql_parser::schema::*;
 use serde::{Deserialize, Serialize};
@@ -33,12 +34,18 @@ pub enum SchemaValidationError {
         _1, _0, _2
     )]
     DerivedFromInvalid(String, String, String), // (type, field, reason)
+    #[fail(display = "Type `{}` has invalid @fulltext: {}", _0, _1)]
+    FulltextInvalid(String, String), // (type, reason)
+    #[fail(display = "Type `{}` has invalid @index: {}", _0, _1)]
+    IndexInvalid(String, String), // (type, reason)
 }
 
 /// Validates whether a GraphQL schema is compatible with The Graph.
 pub(crate) fn validate_schema(schema: &Document) -> Result<(), SchemaValidationError> {
     validate_schema_types(schema)?;
-    validate_derived_from(schema)
+    validate_derived_from(schema)?;
+    validate_fulltext(schema)?;
+    validate_indexes(schema)
 }
 
 /// Validates whether all object types in the schema are declared with an @entity directive.
@@ -282,6 +289,51 @@ fn validate_derived_from(schema: &Document) -> Result<(), SchemaValidationError>
     Ok(())
 }
 
+/// Check that all `@fulltext` directives are well-formed and that the
+/// names of the search fields they declare are unique across the schema
+fn validate_fulltext(schema: &Document) -> Result<(), SchemaValidationError> {
+    let mut names = HashMap::new();
+    for object_type in get_object_type_definitions(schema) {
+        let definitions = FulltextDefinition::for_object_type(object_type).map_err(|reason| {
+            SchemaValidationError::FulltextInvalid(object_type.name.to_owned(), reason)
+        })?;
+        for definition in definitions {
+            if let Some(other) = names.insert(definition.name.clone(), &object_type.name) {
+                return Err(SchemaValidationError::FulltextInvalid(
+                    object_type.name.to_owned(),
+                    format!(
+                        "the name `{}` is already used for a fulltext search on `{}`",
+                        definition.name, other
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that all `@index` directives are well-formed and that no type
+/// declares the same index twice
+fn validate_indexes(schema: &Document) -> Result<(), SchemaValidationError> {
+    for object_type in get_object_type_definitions(schema) {
+        let definitions = IndexDefinition::for_object_type(object_type).map_err(|reason| {
+            SchemaValidationError::IndexInvalid(object_type.name.to_owned(), reason)
+        })?;
+        for (i, definition) in definitions.iter().enumerate() {
+            if definitions[..i].contains(definition) {
+                return Err(SchemaValidationError::IndexInvalid(
+                    object_type.name.to_owned(),
+                    format!(
+                        "the index on `{}` is declared more than once",
+                        definition.fields.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[test]
 fn test_derived_from_validation() {
     const OTHER_TYPES: &str = "
@@ -346,3 +398,125 @@ type Account implements Address @entity { id: ID!, txn: Transaction! @derivedFro
     );
     validate("j: B @derivedFrom(field: \"id\")", "ok");
 }
+
+#[test]
+fn test_fulltext_validation() {
+    const FIELDS: &str = "id: ID!, name: String!, symbol: String, count: Int, tags: [String!]";
+
+    fn validate(directive: &str, errmsg: &str) {
+        let raw = format!(
+            "type A @entity {} {{ {} }}\ntype B @entity @fulltext(name: \"bSearch\", fields: [\"name\"]) {{ {} }}",
+            directive, FIELDS, FIELDS
+        );
+
+        let document = graphql_parser::parse_schema(&raw).expect("Failed to parse raw schema");
+        match validate_fulltext(&document) {
+            Err(SchemaValidationError::FulltextInvalid(_, msg)) => assert_eq!(errmsg, msg),
+            Err(_) => panic!("expected variant SchemaValidationError::FulltextInvalid"),
+            Ok(_) => {
+                if errmsg != "ok" {
+                    panic!("expected validation for `{}` to fail", directive)
+                }
+            }
+        }
+    }
+
+    validate(
+        "@fulltext(name: \"aSearch\", language: \"english\", fields: [\"name\", \"symbol\"])",
+        "ok",
+    );
+    validate("@fulltext(name: \"aSearch\", fields: [\"name\"])", "ok");
+    validate(
+        "@fulltext(fields: [\"name\"])",
+        "the directive must have a `name` argument",
+    );
+    validate(
+        "@fulltext(name: \"name\", fields: [\"name\"])",
+        "the name `name` is already used for a field of the type",
+    );
+    validate(
+        "@fulltext(name: \"bSearch\", fields: [\"name\"])",
+        "the name `bSearch` is already used for a fulltext search on `A`",
+    );
+    validate(
+        "@fulltext(name: \"aSearch\", language: \"klingon\", fields: [\"name\"])",
+        "unsupported language `klingon`; the language must be one of simple, danish, dutch, \
+         english, finnish, french, german, hungarian, italian, norwegian, portuguese, \
+         romanian, russian, spanish, swedish, turkish",
+    );
+    validate(
+        "@fulltext(name: \"aSearch\", fields: [])",
+        "the `fields` argument must be a non-empty list",
+    );
+    validate(
+        "@fulltext(name: \"aSearch\", fields: [\"owner\"])",
+        "field `owner` does not exist on the type",
+    );
+    validate(
+        "@fulltext(name: \"aSearch\", fields: [\"count\"])",
+        "field `count` must have type String or String!",
+    );
+    validate(
+        "@fulltext(name: \"aSearch\", fields: [\"tags\"])",
+        "field `tags` must have type String or String!",
+    );
+}
+
+#[test]
+fn test_index_validation() {
+    const FIELDS: &str = "id: ID!, name: String!, owner: B!, count: Int, tags: [String!], \
+                          parts: [B!]! @derivedFrom(field: \"owner\")";
+
+    fn validate(directive: &str, errmsg: &str) {
+        let raw = format!(
+            "type A @entity {} {{ {} }}\ntype B @entity {{ id: ID!, owner: A! }}",
+            directive, FIELDS
+        );
+
+        let document = graphql_parser::parse_schema(&raw).expect("Failed to parse raw schema");
+        match validate_indexes(&document) {
+            Err(SchemaValidationError::IndexInvalid(_, msg)) => assert_eq!(errmsg, msg),
+            Err(_) => panic!("expected variant SchemaValidationError::IndexInvalid"),
+            Ok(_) => {
+                if errmsg != "ok" {
+                    panic!("expected validation for `{}` to fail", directive)
+                }
+            }
+        }
+    }
+
+    validate("@index(fields: [\"owner\", \"count\"])", "ok");
+    validate(
+        "@index(fields: [\"owner\"]) @index(fields: [\"name\", \"count\"])",
+        "ok",
+    );
+    validate("@index", "the `fields` argument must be a non-empty list");
+    validate(
+        "@index(fields: [])",
+        "the `fields` argument must be a non-empty list",
+    );
+    validate(
+        "@index(fields: [\"owner\", 1])",
+        "the `fields` argument must be a list of strings",
+    );
+    validate(
+        "@index(fields: [\"size\"])",
+        "field `size` does not exist on the type",
+    );
+    validate(
+        "@index(fields: [\"name\", \"name\"])",
+        "field `name` is listed more than once",
+    );
+    validate(
+        "@index(fields: [\"tags\"])",
+        "field `tags` is a list and can not be part of an index",
+    );
+    validate(
+        "@index(fields: [\"parts\"])",
+        "field `parts` is derived and is not stored with the type",
+    );
+    validate(
+        "@index(fields: [\"owner\", \"count\"]) @index(fields: [\"owner\", \"count\"])",
+        "the index on `owner, count` is declared more than once",
+    );
+}
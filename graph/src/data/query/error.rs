@@ -8,7 +8,7 @@ use std::error::Error;
 use std::fmt;
 use std::string::FromUtf8Error;
 
-use crate::components::store::StoreError;
+use crate::components::store::{BlockNumber, StoreError};
 use crate::data::subgraph::*;
 
 /// Error caused while executing a [Query](struct.Query.html).
@@ -53,6 +53,9 @@ pub enum QueryExecutionError {
     ScalarCoercionError(Pos, String, q::Value, String),
     TooComplex(u64, u64), // (complexity, max_complexity)
     TooDeep(u8),          // max_depth
+    BlockNotFound(String),
+    BlockNotIndexed(BlockNumber, Option<u64>), // (requested block, latest indexed block)
+    InvalidCursor(String, String),
 }
 
 impl Error for QueryExecutionError {
@@ -134,7 +137,7 @@ impl fmt::Display for QueryExecutionError {
             RangeArgumentsError(args, first_limit) => {
                 let msg = args.into_iter().map(|arg| {
                     match *arg {
-                        "first" => format!("Value of \"first\" must be between 1 and {}", first_limit),
+                        "first" | "last" => format!("Value of \"{}\" must be between 1 and {}", arg, first_limit),
                         "skip" => format!("Value of \"skip\" must be greater than 0"),
                         _ => format!("Value of \"{}\" is must be an integer", arg),
                     }
@@ -191,7 +194,17 @@ impl fmt::Display for QueryExecutionError {
                            of the query, querying fewer relationships or using `first` to \
                            return smaller collections", complexity, max_complexity)
             }
-            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth)
+            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth),
+            BlockNotFound(block) => write!(f, "Block `{}` not found", block),
+            BlockNotIndexed(block, Some(latest)) => {
+                write!(f, "subgraph has only indexed up to block number {} \
+                           and data for block number {} is therefore not yet available", latest, block)
+            }
+            BlockNotIndexed(block, None) => {
+                write!(f, "subgraph has not indexed any blocks yet \
+                           and data for block number {} is therefore not yet available", block)
+            }
+            InvalidCursor(cursor, e) => write!(f, "Invalid cursor `{}`: {}", cursor, e),
         }
     }
 }
@@ -13,6 +13,211 @@ use graphql_parser::{
 use std::collections::BTreeMap;
 use std::iter::FromIterator;
 
+/// The text search configurations that can be used as the `language` of a
+/// `@fulltext` directive; these are the configurations that come with
+/// every Postgres installation
+pub const FULLTEXT_LANGUAGES: &[&str] = &[
+    "simple",
+    "danish",
+    "dutch",
+    "english",
+    "finnish",
+    "french",
+    "german",
+    "hungarian",
+    "italian",
+    "norwegian",
+    "portuguese",
+    "romanian",
+    "russian",
+    "spanish",
+    "swedish",
+    "turkish",
+];
+
+/// A fulltext search declared on an entity type with a directive like
+/// `@fulltext(name: "tokenSearch", language: "english", fields: ["name", "description"])`.
+/// The search becomes a field `name` on the `Query` type that returns the
+/// entities whose `fields` match a search text, best matches first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FulltextDefinition {
+    /// The name of the search field
+    pub name: String,
+    /// The text search configuration used to turn text into search tokens;
+    /// one of `FULLTEXT_LANGUAGES`
+    pub language: String,
+    /// The `String` fields of the entity type whose text gets searched
+    pub fields: Vec<String>,
+}
+
+impl FulltextDefinition {
+    /// Return all fulltext searches declared on `object_type`, or a
+    /// description of what is wrong with one of its `@fulltext` directives
+    pub fn for_object_type(object_type: &ObjectType) -> Result<Vec<Self>, String> {
+        object_type
+            .directives
+            .iter()
+            .filter(|directive| directive.name == "fulltext")
+            .map(|directive| Self::from_directive(object_type, directive))
+            .collect()
+    }
+
+    fn from_directive(
+        object_type: &ObjectType,
+        directive: &schema::Directive,
+    ) -> Result<Self, String> {
+        let argument = |name: &str| {
+            directive
+                .arguments
+                .iter()
+                .find(|(arg, _)| arg == name)
+                .map(|(_, value)| value)
+        };
+
+        let name = match argument("name") {
+            Some(schema::Value::String(name)) => name.to_owned(),
+            Some(_) => return Err("the `name` argument must be a string".to_owned()),
+            None => return Err("the directive must have a `name` argument".to_owned()),
+        };
+        if object_type.fields.iter().any(|field| field.name == name) {
+            return Err(format!(
+                "the name `{}` is already used for a field of the type",
+                name
+            ));
+        }
+
+        let language = match argument("language") {
+            Some(schema::Value::String(language)) => language.to_owned(),
+            Some(_) => return Err("the `language` argument must be a string".to_owned()),
+            None => "simple".to_owned(),
+        };
+        if !FULLTEXT_LANGUAGES.contains(&language.as_str()) {
+            return Err(format!(
+                "unsupported language `{}`; the language must be one of {}",
+                language,
+                FULLTEXT_LANGUAGES.join(", ")
+            ));
+        }
+
+        let fields = match argument("fields") {
+            Some(schema::Value::List(values)) if !values.is_empty() => values
+                .iter()
+                .map(|value| match value {
+                    schema::Value::String(field) => Ok(field.to_owned()),
+                    _ => Err("the `fields` argument must be a list of strings".to_owned()),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("the `fields` argument must be a non-empty list".to_owned()),
+        };
+        fn is_string(field_type: &schema::Type) -> bool {
+            match field_type {
+                schema::Type::NamedType(name) => name == "String",
+                schema::Type::NonNullType(inner) => is_string(inner),
+                schema::Type::ListType(_) => false,
+            }
+        }
+        for field_name in &fields {
+            let field = object_type
+                .fields
+                .iter()
+                .find(|field| &field.name == field_name)
+                .ok_or_else(|| format!("field `{}` does not exist on the type", field_name))?;
+            if !is_string(&field.field_type) {
+                return Err(format!(
+                    "field `{}` must have type String or String!",
+                    field_name
+                ));
+            }
+        }
+
+        Ok(FulltextDefinition {
+            name,
+            language,
+            fields,
+        })
+    }
+}
+
+/// A composite index declared on an entity type with a directive like
+/// `@index(fields: ["owner", "createdAt"])`. Queries that filter or sort
+/// on a prefix of `fields` can use the index instead of the single
+/// attribute indexes that every attribute gets
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexDefinition {
+    /// The fields of the entity type that get indexed, in the order in
+    /// which they appear in the index
+    pub fields: Vec<String>,
+}
+
+impl IndexDefinition {
+    /// Return all composite indexes declared on `object_type`, or a
+    /// description of what is wrong with one of its `@index` directives
+    pub fn for_object_type(object_type: &ObjectType) -> Result<Vec<Self>, String> {
+        object_type
+            .directives
+            .iter()
+            .filter(|directive| directive.name == "index")
+            .map(|directive| Self::from_directive(object_type, directive))
+            .collect()
+    }
+
+    fn from_directive(
+        object_type: &ObjectType,
+        directive: &schema::Directive,
+    ) -> Result<Self, String> {
+        let fields = match directive
+            .arguments
+            .iter()
+            .find(|(arg, _)| arg == "fields")
+            .map(|(_, value)| value)
+        {
+            Some(schema::Value::List(values)) if !values.is_empty() => values
+                .iter()
+                .map(|value| match value {
+                    schema::Value::String(field) => Ok(field.to_owned()),
+                    _ => Err("the `fields` argument must be a list of strings".to_owned()),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("the `fields` argument must be a non-empty list".to_owned()),
+        };
+        fn is_list(field_type: &schema::Type) -> bool {
+            match field_type {
+                schema::Type::NamedType(_) => false,
+                schema::Type::NonNullType(inner) => is_list(inner),
+                schema::Type::ListType(_) => true,
+            }
+        }
+        for (i, field_name) in fields.iter().enumerate() {
+            if fields[..i].contains(field_name) {
+                return Err(format!("field `{}` is listed more than once", field_name));
+            }
+            let field = object_type
+                .fields
+                .iter()
+                .find(|field| &field.name == field_name)
+                .ok_or_else(|| format!("field `{}` does not exist on the type", field_name))?;
+            if field
+                .directives
+                .iter()
+                .any(|directive| directive.name == "derivedFrom")
+            {
+                return Err(format!(
+                    "field `{}` is derived and is not stored with the type",
+                    field_name
+                ));
+            }
+            if is_list(&field.field_type) {
+                return Err(format!(
+                    "field `{}` is a list and can not be part of an index",
+                    field_name
+                ));
+            }
+        }
+
+        Ok(IndexDefinition { fields })
+    }
+}
+
 /// A validated and preprocessed GraphQL schema for a subgraph.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Schema {
@@ -19,6 +19,7 @@ use crate::components::link_resolver::LinkResolver;
 use crate::components::store::StoreError;
 use crate::data::query::QueryExecutionError;
 use crate::data::schema::Schema;
+use crate::data::store::Entity;
 use crate::data::subgraph::schema::{
     EthereumBlockHandlerEntity, EthereumCallHandlerEntity, EthereumContractAbiEntity,
     EthereumContractDataSourceEntity, EthereumContractDataSourceTemplateEntity,
@@ -239,6 +240,16 @@ pub enum SubgraphRegistrarError {
     DeploymentNotFound(String),
     #[fail(display = "deployment assignment unchanged: {}", _0)]
     DeploymentAssignmentUnchanged(String),
+    #[fail(display = "deployment not assigned to this node: {}", _0)]
+    DeploymentNotAssigned(String),
+    #[fail(display = "cannot rewind deployment: {}", _0)]
+    RewindError(String),
+    #[fail(display = "invalid query: {}", _0)]
+    InvalidQuery(String),
+    #[fail(display = "persisted query not found: {}", _0)]
+    PersistedQueryNotFound(String),
+    #[fail(display = "subgraph assignment provider error: {}", _0)]
+    AssignmentProviderError(SubgraphAssignmentProviderError),
     #[fail(display = "subgraph registrar internal query error: {}", _0)]
     QueryExecutionError(QueryExecutionError),
     #[fail(display = "subgraph registrar error with store: {}", _0)]
@@ -269,6 +280,12 @@ impl From<Error> for SubgraphRegistrarError {
     }
 }
 
+impl From<SubgraphAssignmentProviderError> for SubgraphRegistrarError {
+    fn from(e: SubgraphAssignmentProviderError) -> Self {
+        SubgraphRegistrarError::AssignmentProviderError(e)
+    }
+}
+
 impl From<SubgraphManifestValidationError> for SubgraphRegistrarError {
     fn from(e: SubgraphManifestValidationError) -> Self {
         SubgraphRegistrarError::ManifestValidationError(vec![e])
@@ -286,6 +303,8 @@ pub enum SubgraphAssignmentProviderError {
     AlreadyRunning(SubgraphDeploymentId),
     #[fail(display = "Subgraph with ID {} is not running", _0)]
     NotRunning(SubgraphDeploymentId),
+    #[fail(display = "Subgraph with ID {} is paused", _0)]
+    Paused(SubgraphDeploymentId),
     /// Occurs when a subgraph's GraphQL schema is invalid.
     #[fail(display = "GraphQL schema error: {}", _0)]
     SchemaValidationError(failure::Error),
@@ -333,6 +352,8 @@ pub enum SubgraphManifestValidationError {
     DataSourceBlockHandlerLimitExceeded,
     #[fail(display = "the specified block must exist on the Ethereum network")]
     BlockNotFound(String),
+    #[fail(display = "the graft base is invalid: {}", _0)]
+    GraftBaseInvalid(String),
 }
 
 #[derive(Fail, Debug)]
@@ -507,6 +528,8 @@ pub struct MappingEventHandler {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    #[serde(default)]
+    pub receipt: bool,
 }
 
 impl MappingEventHandler {
@@ -522,6 +545,7 @@ impl From<EthereumContractEventHandlerEntity> for MappingEventHandler {
             event: entity.event,
             topic0: entity.topic0,
             handler: entity.handler,
+            receipt: entity.receipt,
         }
     }
 }
@@ -619,7 +643,12 @@ impl From<EthereumContractMappingEntity> for UnresolvedMapping {
     }
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+/// Key/value pairs that a mapping attaches to a data source it creates
+/// from a template with `dataSource.createWithContext`; the mapping of the
+/// new data source can read them back with `dataSource.context()`
+pub type DataSourceContext = Entity;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct BaseDataSource<M, T> {
     pub kind: String,
     pub network: Option<String>,
@@ -628,6 +657,9 @@ pub struct BaseDataSource<M, T> {
     pub mapping: M,
     #[serde(default)]
     pub templates: Vec<T>, // Deprecated in manifest spec version 0.0.2
+    /// Only data sources created from templates can have a context
+    #[serde(skip)]
+    pub context: Option<DataSourceContext>,
 }
 
 pub type UnresolvedDataSource = BaseDataSource<UnresolvedMapping, UnresolvedDataSourceTemplate>;
@@ -646,6 +678,7 @@ impl UnresolvedDataSource {
             source,
             mapping,
             templates,
+            context,
         } = self;
 
         info!(logger, "Resolve data source"; "name" => &name, "source" => &source.start_block);
@@ -666,6 +699,7 @@ impl UnresolvedDataSource {
                 source,
                 mapping,
                 templates,
+                context,
             })
     }
 }
@@ -674,6 +708,7 @@ impl DataSource {
     pub fn try_from_template(
         template: DataSourceTemplate,
         params: &Vec<String>,
+        context: Option<DataSourceContext>,
     ) -> Result<Self, failure::Error> {
         // Obtain the address from the parameters
         let string = params
@@ -705,6 +740,7 @@ impl DataSource {
             },
             mapping: template.mapping,
             templates: Vec::new(),
+            context,
         })
     }
 }
@@ -718,6 +754,7 @@ impl From<EthereumContractDataSourceEntity> for UnresolvedDataSource {
             source: entity.source.into(),
             mapping: entity.mapping.into(),
             templates: entity.templates.into_iter().map(Into::into).collect(),
+            context: entity.context,
         }
     }
 }
@@ -791,6 +828,15 @@ impl DataSourceTemplate {
     }
 }
 
+/// Tells a new deployment to start from the data of an existing deployment
+/// `base` as of `block` rather than from its start blocks; the deployment
+/// starts indexing at the block after `block`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Graft {
+    pub base: SubgraphDeploymentId,
+    pub block: u64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BaseSubgraphManifest<S, D, T> {
@@ -803,6 +849,13 @@ pub struct BaseSubgraphManifest<S, D, T> {
     pub data_sources: Vec<D>,
     #[serde(default)]
     pub templates: Vec<T>,
+    #[serde(default)]
+    pub graft: Option<Graft>,
+    /// Only process blocks once they have at least this many confirmations,
+    /// so that the subgraph never contains data from blocks that are later
+    /// reverted by a chain reorganization.
+    #[serde(default)]
+    pub confirmations: Option<u64>,
 }
 
 /// Consider two subgraphs to be equal if they come from the same IPLD link.
@@ -907,6 +960,8 @@ impl UnresolvedSubgraphManifest {
             schema,
             data_sources,
             templates,
+            graft,
+            confirmations,
         } = self;
 
         match semver::Version::parse(&spec_version) {
@@ -954,6 +1009,8 @@ impl UnresolvedSubgraphManifest {
                     schema,
                     data_sources,
                     templates,
+                    graft,
+                    confirmations,
                 }),
         )
     }
@@ -17,6 +17,7 @@ use hex;
 use lazy_static::lazy_static;
 use rand::rngs::OsRng;
 use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::str::FromStr;
 use web3::types::*;
@@ -29,7 +30,7 @@ use crate::components::store::{
 };
 use crate::data::graphql::{TryFromValue, ValueMap};
 use crate::data::store::{Entity, NodeId, SubgraphEntityPair, Value, ValueType};
-use crate::data::subgraph::{SubgraphManifest, SubgraphName};
+use crate::data::subgraph::{DataSourceContext, SubgraphManifest, SubgraphName};
 use crate::prelude::*;
 
 lazy_static! {
@@ -47,6 +48,9 @@ pub trait TypedEntity {
         let range = EntityRange {
             first: None,
             skip: 0,
+            after: None,
+            before: None,
+            reverse: false,
         };
         EntityQuery::new(SUBGRAPHS_ID.clone(), vec![Self::TYPENAME.to_owned()], range)
     }
@@ -324,14 +328,21 @@ impl SubgraphDeploymentEntity {
         )]
     }
 
+    /// Update the chain head block and, if it is known, the most recent
+    /// block that is considered final for the deployment.
     pub fn update_ethereum_head_block_operations(
         id: &SubgraphDeploymentId,
         block_ptr: EthereumBlockPointer,
+        final_block_ptr: Option<EthereumBlockPointer>,
     ) -> Vec<MetadataOperation> {
         let mut entity = Entity::new();
         entity.set("totalEthereumBlocksCount", block_ptr.number);
         entity.set("ethereumHeadBlockHash", block_ptr.hash_hex());
         entity.set("ethereumHeadBlockNumber", block_ptr.number);
+        if let Some(final_block_ptr) = final_block_ptr {
+            entity.set("finalEthereumBlockHash", final_block_ptr.hash_hex());
+            entity.set("finalEthereumBlockNumber", final_block_ptr.number);
+        }
 
         vec![update_metadata_operation(
             Self::TYPENAME,
@@ -347,6 +358,40 @@ impl SubgraphDeploymentEntity {
         let mut entity = Entity::new();
         entity.set("failed", failed);
 
+        // The reason for an earlier failure no longer applies once the
+        // deployment is running again
+        if !failed {
+            entity.set("failureReason", Value::Null);
+            entity.set("failureBlockHash", Value::Null);
+            entity.set("failureBlockNumber", Value::Null);
+        }
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+        )]
+    }
+
+    /// Marks the deployment as failed and records why and at which block
+    /// it failed.
+    pub fn update_failure_operations(
+        id: &SubgraphDeploymentId,
+        reason: String,
+        block_ptr: Option<EthereumBlockPointer>,
+    ) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("failed", true);
+        entity.set("failureReason", reason);
+        entity.set(
+            "failureBlockHash",
+            Value::from(block_ptr.map(|ptr| ptr.hash)),
+        );
+        entity.set(
+            "failureBlockNumber",
+            Value::from(block_ptr.map(|ptr| ptr.number)),
+        );
+
         vec![update_metadata_operation(
             Self::TYPENAME,
             id.as_str(),
@@ -392,6 +437,73 @@ impl SubgraphDeploymentAssignmentEntity {
         entity.set("cost", self.cost);
         vec![set_metadata_operation(Self::TYPENAME, id.as_str(), entity)]
     }
+
+    /// Pause the deployment `id` if `paused` is `true`, or resume it
+    /// otherwise. Nodes don't index the paused deployments assigned to them.
+    pub fn paused_operations(id: &SubgraphDeploymentId, paused: bool) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("paused", paused);
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+        )]
+    }
+
+    /// Whether the assignment `entity` has its deployment paused
+    pub fn is_paused(entity: &Entity) -> bool {
+        entity.get("paused") == Some(&Value::Bool(true))
+    }
+}
+
+/// A query that was registered for a subgraph ahead of time so that
+/// clients can run it by sending its SHA-256 hash instead of the query text.
+/// The query can be run against all versions of the subgraph
+#[derive(Debug)]
+pub struct SubgraphPersistedQueryEntity {
+    subgraph: String,
+    query: String,
+}
+
+impl TypedEntity for SubgraphPersistedQueryEntity {
+    const TYPENAME: &'static str = "SubgraphPersistedQuery";
+    type IdType = String;
+}
+
+impl SubgraphPersistedQueryEntity {
+    /// Persist `query` for the subgraph with entity id `subgraph`
+    pub fn new(subgraph: String, query: String) -> Self {
+        Self { subgraph, query }
+    }
+
+    /// The hex-encoded SHA-256 hash of `query` under which the query is
+    /// persisted
+    pub fn hash(query: &str) -> String {
+        hex::encode(Sha256::digest(query.as_bytes()))
+    }
+
+    pub fn id(subgraph: &str, hash: &str) -> String {
+        format!("{}-{}", subgraph, hash)
+    }
+
+    pub fn write_operations(self) -> Vec<MetadataOperation> {
+        let hash = Self::hash(&self.query);
+        let id = Self::id(&self.subgraph, &hash);
+
+        let mut entity = Entity::new();
+        entity.set("id", id.clone());
+        entity.set("subgraph", self.subgraph);
+        entity.set("hash", hash);
+        entity.set("query", self.query);
+        vec![set_metadata_operation(Self::TYPENAME, id, entity)]
+    }
+
+    pub fn remove_operations(subgraph: &str, hash: &str) -> Vec<MetadataOperation> {
+        vec![MetadataOperation::Remove {
+            entity: Self::TYPENAME.to_owned(),
+            id: Self::id(subgraph, hash),
+        }]
+    }
 }
 
 #[derive(Debug)]
@@ -475,6 +587,9 @@ pub struct EthereumContractDataSourceEntity {
     pub source: EthereumContractSourceEntity,
     pub mapping: EthereumContractMappingEntity,
     pub templates: Vec<EthereumContractDataSourceTemplateEntity>,
+    /// Only set for dynamic data sources; the context is not written for
+    /// the data sources in the manifest since they can not have one
+    pub context: Option<DataSourceContext>,
 }
 
 impl TypedEntity for EthereumContractDataSourceEntity {
@@ -531,6 +646,7 @@ impl<'a> From<&'a super::DataSource> for EthereumContractDataSourceEntity {
                 .iter()
                 .map(|template| EthereumContractDataSourceTemplateEntity::from(template))
                 .collect(),
+            context: data_source.context.clone(),
         }
     }
 }
@@ -552,6 +668,11 @@ impl TryFromValue for EthereumContractDataSourceEntity {
             source: map.get_required("source")?,
             mapping: map.get_required("mapping")?,
             templates: map.get_optional("templates")?.unwrap_or_default(),
+            context: map
+                .get_optional::<String>("context")?
+                .map(|context| serde_json::from_str(&context))
+                .transpose()
+                .map_err(|e| format_err!("Cannot parse data source context: {}", e))?,
         })
     }
 }
@@ -567,12 +688,89 @@ pub struct DynamicEthereumContractDataSourceEntity {
     source: EthereumContractSourceEntity,
     mapping: EthereumContractMappingEntity,
     templates: Vec<EthereumContractDataSourceTemplateEntity>,
+    context: Option<DataSourceContext>,
 }
 
 impl DynamicEthereumContractDataSourceEntity {
+    /// The types of the entities that make up the source, mapping and
+    /// templates of a dynamic data source. Their ids all start with the id
+    /// of the data source followed by a `-`
+    pub const NESTED_TYPES: &'static [&'static str] = &[
+        EthereumContractSourceEntity::TYPENAME,
+        EthereumContractMappingEntity::TYPENAME,
+        EthereumContractAbiEntity::TYPENAME,
+        EthereumBlockHandlerEntity::TYPENAME,
+        EthereumBlockHandlerFilterEntity::TYPENAME,
+        EthereumCallHandlerEntity::TYPENAME,
+        EthereumContractEventHandlerEntity::TYPENAME,
+        EthereumContractDataSourceTemplateEntity::TYPENAME,
+        EthereumContractDataSourceTemplateSourceEntity::TYPENAME,
+    ];
+
     pub fn write_entity_operations(self, id: &str) -> Vec<EntityOperation> {
         WriteOperations::write_entity_operations(self, id)
     }
+
+    /// Generate the operations that copy the dynamic data sources in
+    /// `data_sources`, together with the entities nested in them, which
+    /// must all be in `nested`, to `deployment`. Each copied data source
+    /// gets the id returned by `new_id`, and the ids of its nested entities
+    /// and all references to them are changed to match
+    pub fn copy_operations(
+        deployment: &SubgraphDeploymentId,
+        data_sources: Vec<Entity>,
+        nested: &[Entity],
+        mut new_id: impl FnMut() -> String,
+    ) -> Result<Vec<MetadataOperation>, Error> {
+        // Replace the prefix `old` of an id with `new`
+        fn rekey(value: Value, old: &str, new: &str) -> Value {
+            match value {
+                Value::String(s) if s.starts_with(old) => {
+                    Value::String(format!("{}{}", new, &s[old.len()..]))
+                }
+                Value::List(values) => Value::List(
+                    values
+                        .into_iter()
+                        .map(|value| rekey(value, old, new))
+                        .collect(),
+                ),
+                value => value,
+            }
+        }
+
+        let mut ops = vec![];
+        for data_source in data_sources {
+            let old_id = data_source.id()?;
+            let new_id = new_id();
+            let old_prefix = format!("{}-", old_id);
+            let children = nested
+                .iter()
+                .filter(|entity| {
+                    entity
+                        .id()
+                        .map(|id| id.starts_with(&old_prefix))
+                        .unwrap_or(false)
+                })
+                .cloned();
+
+            for mut entity in std::iter::once(data_source).chain(children) {
+                for value in entity.values_mut() {
+                    let old_value = std::mem::replace(value, Value::Null);
+                    *value = rekey(old_value, &old_id, &new_id);
+                }
+                let entity_type = match entity.remove("__typename") {
+                    Some(Value::String(entity_type)) => entity_type,
+                    _ => return Err(format_err!("metadata entity is missing its type")),
+                };
+                if entity_type == Self::TYPENAME {
+                    entity.set("deployment", deployment.to_string());
+                }
+                let id = entity.id()?;
+                ops.push(set_metadata_operation(entity_type, id, entity));
+            }
+        }
+        Ok(ops)
+    }
 }
 
 impl TypedEntity for DynamicEthereumContractDataSourceEntity {
@@ -610,6 +808,12 @@ impl WriteOperations for DynamicEthereumContractDataSourceEntity {
         entity.set("deployment", self.deployment);
         entity.set("ethereumBlockHash", self.ethereum_block_hash);
         entity.set("ethereumBlockNumber", self.ethereum_block_number);
+        entity.set(
+            "context",
+            self.context.map(|context| {
+                serde_json::to_string(&context).expect("failed to serialize data source context")
+            }),
+        );
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -644,6 +848,7 @@ impl<'a, 'b, 'c>
                 .iter()
                 .map(|template| EthereumContractDataSourceTemplateEntity::from(template))
                 .collect(),
+            context: data_source.context.clone(),
         }
     }
 }
@@ -1041,6 +1246,7 @@ pub struct EthereumContractEventHandlerEntity {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    pub receipt: bool,
 }
 
 impl TypedEntity for EthereumContractEventHandlerEntity {
@@ -1055,6 +1261,7 @@ impl WriteOperations for EthereumContractEventHandlerEntity {
         entity.set("event", self.event);
         entity.set("topic0", self.topic0.map_or(Value::Null, Value::from));
         entity.set("handler", self.handler);
+        entity.set("receipt", self.receipt);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -1065,6 +1272,7 @@ impl From<super::MappingEventHandler> for EthereumContractEventHandlerEntity {
             event: event_handler.event,
             topic0: event_handler.topic0,
             handler: event_handler.handler,
+            receipt: event_handler.receipt,
         }
     }
 }
@@ -1083,6 +1291,7 @@ impl TryFromValue for EthereumContractEventHandlerEntity {
             event: map.get_required("event")?,
             topic0: map.get_optional("topic0")?,
             handler: map.get_required("handler")?,
+            receipt: map.get_optional("receipt")?.unwrap_or_default(),
         })
     }
 }
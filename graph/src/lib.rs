@@ -44,14 +44,17 @@ pub mod prelude {
         EthereumBlock, EthereumBlockData, EthereumBlockFilter, EthereumBlockPointer,
         EthereumBlockTriggerType, EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall,
         EthereumCallData, EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
-        EthereumEventData, EthereumLogFilter, EthereumNetworkIdentifier, EthereumTransactionData,
-        EthereumTrigger, LightEthereumBlock, LightEthereumBlockExt, ProviderEthRpcMetrics,
+        EthereumEventData, EthereumLogData, EthereumLogFilter, EthereumNetworkIdentifier,
+        EthereumTransactionData, EthereumTransactionReceiptData, EthereumTrigger,
+        LightEthereumBlock, LightEthereumBlockExt, NodeCapabilities, ProviderEthRpcMetrics,
         SubgraphEthRpcMetrics,
     };
     pub use crate::components::graphql::{
         GraphQlRunner, QueryResultFuture, SubscriptionResultFuture,
     };
-    pub use crate::components::link_resolver::{JsonStreamValue, JsonValueStream, LinkResolver};
+    pub use crate::components::link_resolver::{
+        JsonStreamValue, JsonValueStream, LineStreamValue, LineValueStream, LinkResolver,
+    };
     pub use crate::components::metrics::{
         Collector, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec,
         MetricsRegistry, Opts, PrometheusError, Registry,
@@ -62,16 +65,17 @@ pub mod prelude {
     pub use crate::components::server::query::GraphQLServer;
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
-        AttributeIndexDefinition, ChainStore, EntityCache, EntityChange, EntityChangeOperation,
-        EntityFilter, EntityKey, EntityModification, EntityOperation, EntityOrder, EntityQuery,
-        EntityRange, EthereumCallCache, MetadataOperation, Store, StoreError, StoreEvent,
+        AttributeIndexDefinition, BlockNumber, ChainStore, EntityAggregate, EntityCache,
+        EntityChange, EntityChangeOperation, EntityCursor, EntityFilter, EntityKey,
+        EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange, EntityWindow,
+        EthereumCallCache, MetadataOperation, PersistedQueryLookup, Store, StoreError, StoreEvent,
         StoreEventStream, StoreEventStreamBox, SubgraphDeploymentStore, TransactionAbortError,
-        SUBSCRIPTION_THROTTLE_INTERVAL,
+        WindowAttribute, BLOCK_NUMBER_MAX, PERSISTED_QUERIES_ONLY, SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
-        BlockState, DataSourceLoader, DataSourceTemplateInfo, HostMetrics, RuntimeHost,
-        RuntimeHostBuilder, SubgraphAssignmentProvider, SubgraphInstance, SubgraphInstanceManager,
-        SubgraphRegistrar, SubgraphVersionSwitchingMode,
+        BlockState, DataSourceLoader, DataSourceTemplateInfo, DeterministicError, HostMetrics,
+        RuntimeHost, RuntimeHostBuilder, SubgraphAssignmentProvider, SubgraphInstance,
+        SubgraphInstanceManager, SubgraphRegistrar, SubgraphVersionSwitchingMode,
     };
     pub use crate::components::{EventConsumer, EventProducer};
 
@@ -87,11 +91,11 @@ pub mod prelude {
     };
     pub use crate::data::subgraph::schema::{SubgraphDeploymentEntity, TypedEntity};
     pub use crate::data::subgraph::{
-        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceTemplate, Link, MappingABI,
-        MappingBlockHandler, MappingCallHandler, MappingEventHandler,
-        SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent, SubgraphDeploymentId,
-        SubgraphManifest, SubgraphManifestResolveError, SubgraphManifestValidationError,
-        SubgraphName, SubgraphRegistrarError,
+        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceContext,
+        DataSourceTemplate, Graft, Link, MappingABI, MappingBlockHandler, MappingCallHandler,
+        MappingEventHandler, SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent,
+        SubgraphDeploymentId, SubgraphManifest, SubgraphManifestResolveError,
+        SubgraphManifestValidationError, SubgraphName, SubgraphRegistrarError,
     };
     pub use crate::data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
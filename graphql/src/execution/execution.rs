@@ -43,6 +43,10 @@ where
 
     /// Max value for `first`.
     pub max_first: u32,
+
+    /// The block at which entities are resolved; set from the `block`
+    /// argument of a field and inherited by all fields nested inside it.
+    pub block: BlockNumber,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -77,6 +81,7 @@ where
             variable_values: self.variable_values.clone(),
             deadline: self.deadline,
             max_first: std::u32::MAX,
+            block: self.block,
         }
     }
 
@@ -175,13 +180,26 @@ where
                         }
                         .ok_or(Invalid)?;
 
+                        let field_type =
+                            get_type_definition_from_type(schema, &s_field.field_type)?;
                         let field_complexity = self.query_complexity(
-                            &get_type_definition_from_type(schema, &s_field.field_type)?,
+                            &field_type,
                             &field.selection_set,
                             max_depth,
                             depth + 1,
                         )?;
 
+                        // Aggregates look at every matching entity, and
+                        // cost as much as the largest allowed page of them.
+                        if let s::TypeDefinition::Object(t) = &field_type {
+                            if sast::get_aggregate_node_type(schema, t).is_some() {
+                                return total_complexity
+                                    .checked_add(self.max_first as u64)
+                                    .and_then(|c| c.checked_add(field_complexity))
+                                    .ok_or(Overflow);
+                            }
+                        }
+
                         // Non-collection queries pass through.
                         if !sast::is_list_or_non_null_list_field(&s_field) {
                             return Ok(total_complexity + field_complexity);
@@ -510,19 +528,253 @@ fn execute_field<'a, R>(
 where
     R: Resolver,
 {
-    coerce_argument_values(ctx, object_type, field)
-        .and_then(|argument_values| {
-            resolve_field_value(
-                ctx,
-                object_type,
-                object_value,
-                field,
-                field_definition,
-                &field_definition.field_type,
-                &argument_values,
-            )
-        })
-        .and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
+    coerce_argument_values(ctx, object_type, field).and_then(|argument_values| {
+        let ctx = context_for_block(ctx, field_definition, &argument_values)?;
+        let value = match prefetched_value(object_value, field) {
+            // The value, and everything nested inside it, was already
+            // resolved together with the parent object
+            Some(value) => value.clone(),
+            // The fields of pagination and aggregate types were all
+            // resolved together with the connection or aggregate they
+            // belong to
+            None if sast::is_pagination_type(&ctx.schema.document, object_type)
+                || sast::is_aggregate_type(&ctx.schema.document, object_type) =>
+            {
+                match object_value {
+                    Some(q::Value::Object(object)) => {
+                        object.get(&field.name).cloned().unwrap_or(q::Value::Null)
+                    }
+                    _ => q::Value::Null,
+                }
+            }
+            None => {
+                let mut value = resolve_field_value(
+                    &ctx,
+                    object_type,
+                    object_value,
+                    field,
+                    field_definition,
+                    &field_definition.field_type,
+                    &argument_values,
+                )?;
+                prefetch(&ctx, &field_definition.field_type, &fields, &mut value);
+                value
+            }
+        };
+        complete_value(&ctx, field, &field_definition.field_type, fields, value)
+    })
+}
+
+/// The key under which the prefetched value of the field with the given
+/// response key is stored in its parent object. The key can never clash
+/// with the name of a field since it is not a valid GraphQL name.
+fn prefetch_key(response_key: &str) -> String {
+    format!("prefetch:{}", response_key)
+}
+
+/// Returns the value of `field` if it was prefetched for `object_value`.
+fn prefetched_value<'v>(
+    object_value: &'v Option<q::Value>,
+    field: &q::Field,
+) -> Option<&'v q::Value> {
+    match object_value {
+        Some(q::Value::Object(object)) => object.get(&prefetch_key(qast::get_response_key(field))),
+        _ => None,
+    }
+}
+
+/// Returns the objects contained in a resolved field value.
+fn objects_in_value(value: &mut q::Value) -> Vec<&mut q::Value> {
+    match value {
+        q::Value::List(values) => values
+            .iter_mut()
+            .filter(|value| match value {
+                q::Value::Object(_) => true,
+                _ => false,
+            })
+            .collect(),
+        q::Value::Object(_) => vec![value],
+        _ => vec![],
+    }
+}
+
+/// Resolves the entity fields nested in `fields` for all objects in `value`
+/// at once, one level of the query at a time, and stores the results in
+/// those objects. When the objects are completed later, their fields do not
+/// need to be resolved for each object separately.
+///
+/// Errors are ignored here; fields that could not be prefetched are resolved
+/// for each object separately, which reports errors in the usual way.
+fn prefetch<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    field_type: &s::Type,
+    fields: &[&'a q::Field],
+    value: &mut q::Value,
+) where
+    R: Resolver,
+{
+    let selection_set = merge_selection_sets(fields.to_vec());
+    prefetch_selection_set(ctx, field_type, &selection_set, objects_in_value(value));
+}
+
+fn prefetch_selection_set<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    field_type: &s::Type,
+    selection_set: &'a q::SelectionSet,
+    objects: Vec<&mut q::Value>,
+) where
+    R: Resolver,
+{
+    if objects.is_empty() {
+        return;
+    }
+
+    let type_def = match sast::get_type_definition_from_type(&ctx.schema.document, field_type) {
+        Some(type_def) => type_def,
+        None => return,
+    };
+
+    // The objects for an interface can be of different types, each of which
+    // needs to be prefetched separately
+    let mut objects_by_type: BTreeMap<&s::Name, (&s::ObjectType, Vec<&mut q::Value>)> =
+        BTreeMap::new();
+    for object in objects {
+        let object_type = match type_def {
+            s::TypeDefinition::Object(object_type) => object_type,
+            s::TypeDefinition::Interface(_) => {
+                match ctx
+                    .resolver
+                    .resolve_abstract_type(&ctx.schema.document, type_def, object)
+                {
+                    Some(object_type) => object_type,
+                    None => continue,
+                }
+            }
+            _ => return,
+        };
+        objects_by_type
+            .entry(&object_type.name)
+            .or_insert_with(|| (object_type, vec![]))
+            .1
+            .push(object);
+    }
+
+    for (_, (object_type, mut objects)) in objects_by_type {
+        prefetch_fields(ctx, object_type, selection_set, &mut objects);
+    }
+}
+
+fn prefetch_fields<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    object_type: &s::ObjectType,
+    selection_set: &'a q::SelectionSet,
+    objects: &mut [&mut q::Value],
+) where
+    R: Resolver,
+{
+    // The fields of pagination and aggregate types are already resolved,
+    // but the objects they contain might have fields that we can prefetch
+    let is_resolved_type = sast::is_pagination_type(&ctx.schema.document, object_type)
+        || sast::is_aggregate_type(&ctx.schema.document, object_type);
+
+    for (response_key, fields) in collect_fields(ctx.clone(), object_type, selection_set, None) {
+        let field = fields[0];
+        let field_definition = match sast::get_field(object_type, &field.name) {
+            Some(field_definition) => field_definition,
+            None => continue,
+        };
+
+        if is_resolved_type {
+            let children = objects
+                .iter_mut()
+                .filter_map(|object| match &mut **object {
+                    q::Value::Object(object) => object.get_mut(&field.name),
+                    _ => None,
+                })
+                .flat_map(objects_in_value)
+                .collect();
+            let selection_set = merge_selection_sets(fields);
+            prefetch_selection_set(ctx, &field_definition.field_type, &selection_set, children);
+            continue;
+        }
+
+        // Only fields that refer to other objects are resolved by the
+        // resolver; all other fields are taken from the objects themselves
+        let child_type =
+            match sast::get_type_definition_from_field(&ctx.schema.document, field_definition) {
+                Some(s::TypeDefinition::Object(t)) => ObjectOrInterface::from(t),
+                Some(s::TypeDefinition::Interface(i)) => ObjectOrInterface::from(i),
+                _ => continue,
+            };
+
+        let ctx = ctx.for_field(field);
+        let argument_values = match coerce_argument_values(&ctx, object_type, field) {
+            Ok(argument_values) => argument_values,
+            Err(_) => continue,
+        };
+        let ctx = match context_for_block(&ctx, field_definition, &argument_values) {
+            Ok(ctx) => ctx,
+            Err(_) => continue,
+        };
+
+        let parents: Vec<&q::Value> = objects.iter().map(|object| &**object).collect();
+        let mut values = match ctx.resolver.resolve_field_for_parents(
+            &parents,
+            field,
+            field_definition,
+            child_type,
+            &argument_values,
+            ctx.schema.types_for_interface(),
+            ctx.block,
+            ctx.max_first,
+        ) {
+            Ok(Some(values)) => values,
+            Ok(None) | Err(_) => continue,
+        };
+
+        // Prefetch the next level for all children of all objects at once
+        let children = values.iter_mut().flat_map(objects_in_value).collect();
+        let selection_set = merge_selection_sets(fields);
+        prefetch_selection_set(&ctx, &field_definition.field_type, &selection_set, children);
+
+        for (object, value) in objects.iter_mut().zip(values) {
+            if let q::Value::Object(object) = &mut **object {
+                object.insert(prefetch_key(response_key), value);
+            }
+        }
+    }
+}
+
+/// Returns the context in which a field is executed. If the field has a
+/// `block` argument, the resolver translates it into a block number and the
+/// field and everything nested inside it are resolved at that block;
+/// otherwise, the field is resolved at the same block as its parent.
+fn context_for_block<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    field_definition: &s::Field,
+    argument_values: &HashMap<&q::Name, q::Value>,
+) -> Result<ExecutionContext<'a, R>, Vec<QueryExecutionError>>
+where
+    R: Resolver,
+{
+    let mut ctx = ctx.clone();
+    let block = match argument_values.get(&q::Name::from("block")) {
+        None | Some(q::Value::Null) => return Ok(ctx),
+        Some(block) => block,
+    };
+
+    let object_type =
+        match sast::get_type_definition_from_field(&ctx.schema.document, field_definition) {
+            Some(s::TypeDefinition::Object(t)) => ObjectOrInterface::from(t),
+            Some(s::TypeDefinition::Interface(i)) => ObjectOrInterface::from(i),
+            _ => return Ok(ctx),
+        };
+
+    ctx.block = ctx
+        .resolver
+        .locate_block(object_type, block)
+        .map_err(|e| vec![e])?;
+    Ok(ctx)
 }
 
 /// Resolves the value of a field.
@@ -591,14 +843,38 @@ where
     match named_type {
         // Let the resolver decide how the field (with the given object type)
         // is resolved into an entity based on the (potential) parent object
-        s::TypeDefinition::Object(t) => ctx.resolver.resolve_object(
-            object_value,
-            field,
-            field_definition,
-            t.into(),
-            argument_values,
-            ctx.schema.types_for_interface(),
-        ),
+        s::TypeDefinition::Object(t) => {
+            match sast::get_connection_node_type(&ctx.schema.document, t) {
+                Some(node_type) => ctx.resolver.resolve_connection(
+                    field,
+                    field_definition,
+                    node_type,
+                    argument_values,
+                    ctx.schema.types_for_interface(),
+                    ctx.block,
+                    ctx.max_first,
+                ),
+                None => match sast::get_aggregate_node_type(&ctx.schema.document, t) {
+                    Some(node_type) => ctx.resolver.resolve_aggregate(
+                        field,
+                        field_definition,
+                        node_type,
+                        argument_values,
+                        ctx.schema.types_for_interface(),
+                        ctx.block,
+                    ),
+                    None => ctx.resolver.resolve_object(
+                        object_value,
+                        field,
+                        field_definition,
+                        t.into(),
+                        argument_values,
+                        ctx.schema.types_for_interface(),
+                        ctx.block,
+                    ),
+                },
+            }
+        }
 
         // Let the resolver decide how values in the resolved object value
         // map to values of GraphQL enums
@@ -627,6 +903,7 @@ where
             i.into(),
             argument_values,
             ctx.schema.types_for_interface(),
+            ctx.block,
         ),
 
         s::TypeDefinition::Union(_) => Err(QueryExecutionError::Unimplemented("unions".to_owned())),
@@ -676,6 +953,7 @@ where
                         t.into(),
                         argument_values,
                         ctx.schema.types_for_interface(),
+                        ctx.block,
                         ctx.max_first,
                     )
                     .map_err(|e| vec![e]),
@@ -709,6 +987,7 @@ where
                         t.into(),
                         argument_values,
                         ctx.schema.types_for_interface(),
+                        ctx.block,
                         ctx.max_first,
                     )
                     .map_err(|e| vec![e]),
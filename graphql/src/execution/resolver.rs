@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::prelude::*;
 use crate::schema::ast::get_named_type;
-use graph::prelude::{QueryExecutionError, StoreEventStreamBox};
+use graph::prelude::{BlockNumber, QueryExecutionError, StoreEventStreamBox, BLOCK_NUMBER_MAX};
 
 #[derive(Copy, Clone, Debug)]
 pub enum ObjectOrInterface<'a> {
@@ -57,6 +57,7 @@ pub trait Resolver: Clone + Send + Sync {
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
         max_first: u32,
     ) -> Result<q::Value, QueryExecutionError>;
 
@@ -69,8 +70,72 @@ pub trait Resolver: Clone + Send + Sync {
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError>;
 
+    /// Resolves the field `field` for all of `parents` at once. Returns one
+    /// value per parent, in the same order as `parents`, or `None` if the
+    /// resolver can not resolve the field for several parents at once; in
+    /// that case, the field is resolved for each parent separately.
+    fn resolve_field_for_parents(
+        &self,
+        _parents: &[&q::Value],
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _block: BlockNumber,
+        _max_first: u32,
+    ) -> Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        Ok(None)
+    }
+
+    /// Resolves a `*Connection` field into a page of entities of type
+    /// `node_type`. The result is an object with the `edges` of the page,
+    /// each holding a `cursor` and the entity as its `node`, and a `pageInfo`
+    /// object; all of these are resolved here and used as is by the executor.
+    fn resolve_connection(
+        &self,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _node_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _block: BlockNumber,
+        _max_first: u32,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Err(QueryExecutionError::Unimplemented("connections".to_owned()))
+    }
+
+    /// Resolves an `*Aggregate` field into the aggregates over all entities
+    /// of type `node_type` that match the field's filter. The result is an
+    /// object with the `count` of those entities and, for its numeric
+    /// fields, objects with their `sum`, `avg`, `min` and `max`; all of
+    /// these are resolved here and used as is by the executor.
+    fn resolve_aggregate(
+        &self,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _node_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _block: BlockNumber,
+    ) -> Result<q::Value, QueryExecutionError> {
+        Err(QueryExecutionError::Unimplemented("aggregates".to_owned()))
+    }
+
+    /// Translates the value of a `block` argument into the number of the
+    /// block at which entities of `object_type` should be resolved. Resolvers
+    /// that do not support historical queries always resolve the latest state.
+    fn locate_block(
+        &self,
+        _object_type: ObjectOrInterface<'_>,
+        _block: &q::Value,
+    ) -> Result<BlockNumber, QueryExecutionError> {
+        Ok(BLOCK_NUMBER_MAX)
+    }
+
     /// Resolves an enum value for a given enum type.
     fn resolve_enum_value(
         &self,
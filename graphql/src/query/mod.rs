@@ -68,6 +68,7 @@ where
         variable_values: Arc::new(coerced_variable_values),
         deadline: options.deadline,
         max_first: options.max_first,
+        block: BLOCK_NUMBER_MAX,
     };
 
     let result = match operation {
@@ -1,4 +1,5 @@
 use crate::schema::ast;
+use graph::data::schema::FulltextDefinition;
 use graph::prelude::*;
 use graphql_parser::schema::{Value, *};
 use graphql_parser::Pos;
@@ -10,6 +11,8 @@ pub enum APISchemaError {
     TypeExists(String),
     #[fail(display = "Type {} not found", _0)]
     TypeNotFound(String),
+    #[fail(display = "Type {} has invalid @fulltext: {}", _0, _1)]
+    FulltextInvalid(String, String),
 }
 
 /// Derives a full-fledged GraphQL API schema from an input schema.
@@ -27,6 +30,8 @@ pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     let mut schema = input_schema.clone();
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
+    add_block_height_type(&mut schema);
+    add_page_info_type(&mut schema)?;
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
     add_field_arguments(&mut schema, &input_schema)?;
@@ -86,6 +91,59 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `Block_height` input type to the schema. It is used to
+/// query entities as of a given block, identified either by its number or
+/// by its hash.
+fn add_block_height_type(schema: &mut Document) {
+    let typedef = TypeDefinition::InputObject(InputObjectType {
+        position: Pos::default(),
+        description: None,
+        name: "Block_height".to_string(),
+        directives: vec![],
+        fields: vec![
+            input_value(
+                &"hash".to_string(),
+                "",
+                Type::NamedType("Bytes".to_string()),
+            ),
+            input_value(
+                &"number".to_string(),
+                "",
+                Type::NamedType("Int".to_string()),
+            ),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
+/// Adds a global `PageInfo` type to the schema. It describes the page of
+/// entities returned by a `*Connection` query.
+fn add_page_info_type(schema: &mut Document) -> Result<(), APISchemaError> {
+    let type_name = "PageInfo".to_string();
+    if ast::get_named_type(schema, &type_name).is_some() {
+        return Err(APISchemaError::TypeExists(type_name));
+    }
+
+    let non_null_boolean = Type::NonNullType(Box::new(Type::NamedType("Boolean".to_string())));
+    let typedef = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: type_name,
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            output_field("startCursor", Type::NamedType("String".to_string())),
+            output_field("endCursor", Type::NamedType("String".to_string())),
+            output_field("hasNextPage", non_null_boolean.clone()),
+            output_field("hasPreviousPage", non_null_boolean),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+    Ok(())
+}
+
 fn add_types_for_object_types(
     schema: &mut Document,
     object_types: &Vec<&ObjectType>,
@@ -93,6 +151,8 @@ fn add_types_for_object_types(
     for object_type in object_types {
         add_order_by_type(schema, &object_type.name, &object_type.fields)?;
         add_filter_type(schema, &object_type.name, &object_type.fields)?;
+        add_connection_types(schema, &object_type.name)?;
+        add_aggregate_types(schema, &object_type.name, &object_type.fields)?;
     }
     Ok(())
 }
@@ -105,10 +165,154 @@ fn add_types_for_interface_types(
     for interface_type in interface_types {
         add_order_by_type(schema, &interface_type.name, &interface_type.fields)?;
         add_filter_type(schema, &interface_type.name, &interface_type.fields)?;
+        add_connection_types(schema, &interface_type.name)?;
+        add_aggregate_types(schema, &interface_type.name, &interface_type.fields)?;
+    }
+    Ok(())
+}
+
+/// Adds the `<type_name>_edge` and `<type_name>_connection` types that
+/// the `*Connection` query for the type returns to the schema.
+fn add_connection_types(schema: &mut Document, type_name: &Name) -> Result<(), APISchemaError> {
+    let edge_type_name = format!("{}_edge", type_name);
+    let connection_type_name = format!("{}_connection", type_name);
+    for name in &[&edge_type_name, &connection_type_name] {
+        if ast::get_named_type(schema, name).is_some() {
+            return Err(APISchemaError::TypeExists(name.to_string()));
+        }
+    }
+
+    let edge_type = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: edge_type_name.clone(),
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            output_field(
+                "cursor",
+                Type::NonNullType(Box::new(Type::NamedType("String".to_string()))),
+            ),
+            output_field(
+                "node",
+                Type::NonNullType(Box::new(Type::NamedType(type_name.to_owned()))),
+            ),
+        ],
+    });
+    let connection_type = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: connection_type_name,
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            output_field(
+                "edges",
+                Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+                    Box::new(Type::NamedType(edge_type_name)),
+                ))))),
+            ),
+            output_field(
+                "pageInfo",
+                Type::NonNullType(Box::new(Type::NamedType("PageInfo".to_string()))),
+            ),
+        ],
+    });
+    schema
+        .definitions
+        .push(Definition::TypeDefinition(edge_type));
+    schema
+        .definitions
+        .push(Definition::TypeDefinition(connection_type));
+    Ok(())
+}
+
+/// Adds the `<type_name>_aggregate` type that the `*Aggregate` query for
+/// the type returns to the schema, together with one
+/// `<type_name>_aggregate_<function>` type for each of `sum`, `avg`, `min`
+/// and `max` if the type has fields that these can be computed for.
+fn add_aggregate_types(
+    schema: &mut Document,
+    type_name: &Name,
+    fields: &[Field],
+) -> Result<(), APISchemaError> {
+    let aggregate_type_name = format!("{}_aggregate", type_name);
+    if ast::get_named_type(schema, &aggregate_type_name).is_some() {
+        return Err(APISchemaError::TypeExists(aggregate_type_name));
+    }
+
+    let mut aggregate_fields = vec![output_field(
+        "count",
+        Type::NonNullType(Box::new(Type::NamedType("BigInt".to_string()))),
+    )];
+
+    let numeric_fields = ast::get_aggregatable_fields(fields);
+    if !numeric_fields.is_empty() {
+        for function in ast::AGGREGATE_FUNCTIONS {
+            let function_type_name = format!("{}_{}", aggregate_type_name, function);
+            if ast::get_named_type(schema, &function_type_name).is_some() {
+                return Err(APISchemaError::TypeExists(function_type_name));
+            }
+
+            let function_type = TypeDefinition::Object(ObjectType {
+                position: Pos::default(),
+                description: None,
+                name: function_type_name.clone(),
+                implements_interfaces: vec![],
+                directives: vec![],
+                fields: numeric_fields
+                    .iter()
+                    .map(|field| {
+                        let field_type = ast::get_field_name(&field.field_type);
+                        let value_type = match (*function, field_type.as_str()) {
+                            ("sum", "BigDecimal") | ("avg", _) => "BigDecimal".to_string(),
+                            ("sum", _) => "BigInt".to_string(),
+                            _ => field_type,
+                        };
+                        // Everything but counts is null when there is
+                        // nothing to aggregate
+                        output_field(&field.name, Type::NamedType(value_type))
+                    })
+                    .collect(),
+            });
+            schema
+                .definitions
+                .push(Definition::TypeDefinition(function_type));
+
+            aggregate_fields.push(output_field(
+                function,
+                Type::NonNullType(Box::new(Type::NamedType(function_type_name))),
+            ));
+        }
     }
+
+    let aggregate_type = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: aggregate_type_name,
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: aggregate_fields,
+    });
+    schema
+        .definitions
+        .push(Definition::TypeDefinition(aggregate_type));
     Ok(())
 }
 
+/// Generates a field without arguments for one of the object types we add
+/// to the schema.
+fn output_field(name: &str, field_type: Type) -> Field {
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_string(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    }
+}
+
 /// Adds a `<type_name>_orderBy` enum type for the given fields to the schema.
 fn add_order_by_type(
     schema: &mut Document,
@@ -369,18 +573,28 @@ fn add_query_type(
         return Err(APISchemaError::TypeExists(type_name));
     }
 
+    let mut fields: Vec<_> = object_types
+        .iter()
+        .map(|t| &t.name)
+        .chain(interface_types.iter().map(|t| &t.name))
+        .flat_map(|name| {
+            let mut fields = query_fields_for_type(schema, name);
+            fields.push(connection_field_for_type(schema, name));
+            fields.push(aggregate_field_for_type(schema, name));
+            fields
+        })
+        .collect();
+    for object_type in object_types {
+        fields.extend(fulltext_fields_for_type(schema, object_type)?);
+    }
+
     let typedef = TypeDefinition::Object(ObjectType {
         position: Pos::default(),
         description: None,
         name: type_name,
         implements_interfaces: vec![],
         directives: vec![],
-        fields: object_types
-            .iter()
-            .map(|t| &t.name)
-            .chain(interface_types.iter().map(|t| &t.name))
-            .flat_map(|name| query_fields_for_type(schema, name))
-            .collect(),
+        fields,
     });
     let def = Definition::TypeDefinition(typedef);
     schema.definitions.push(def);
@@ -425,14 +639,17 @@ fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
             position: Pos::default(),
             description: None,
             name: type_name.as_str().to_camel_case(),
-            arguments: vec![InputValue {
-                position: Pos::default(),
-                description: None,
-                name: "id".to_string(),
-                value_type: Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
-                default_value: None,
-                directives: vec![],
-            }],
+            arguments: vec![
+                InputValue {
+                    position: Pos::default(),
+                    description: None,
+                    name: "id".to_string(),
+                    value_type: Type::NonNullType(Box::new(Type::NamedType("ID".to_string()))),
+                    default_value: None,
+                    directives: vec![],
+                },
+                block_argument(),
+            ],
             field_type: Type::NamedType(type_name.to_owned()),
             directives: vec![],
         },
@@ -449,6 +666,101 @@ fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
     ]
 }
 
+/// Generates the `Query` field that returns a page of entities of the given
+/// type together with cursors for them (e.g. `usersConnection`). Besides the
+/// usual collection arguments, the field accepts `last` to page backwards.
+fn connection_field_for_type(schema: &Document, type_name: &Name) -> Field {
+    let input_objects = ast::get_input_object_definitions(schema);
+    let mut arguments = collection_arguments_for_named_type(&input_objects, type_name);
+    arguments.push(input_value(
+        &"last".to_string(),
+        "",
+        Type::NamedType("Int".to_string()),
+    ));
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: format!("{}Connection", type_name.to_plural().to_camel_case()),
+        arguments,
+        field_type: Type::NonNullType(Box::new(Type::NamedType(format!(
+            "{}_connection",
+            type_name
+        )))),
+        directives: vec![],
+    }
+}
+
+/// Generates the `Query` field that aggregates all entities of the given
+/// type that match a filter (e.g. `usersAggregate`).
+fn aggregate_field_for_type(schema: &Document, type_name: &Name) -> Field {
+    let input_objects = ast::get_input_object_definitions(schema);
+    // Aggregates are computed over all matching entities; ordering and
+    // paging have no effect on them
+    let arguments = collection_arguments_for_named_type(&input_objects, type_name)
+        .into_iter()
+        .filter(|argument| match argument.name.as_str() {
+            "where" | "block" => true,
+            _ => false,
+        })
+        .collect();
+
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: format!("{}Aggregate", type_name.to_plural().to_camel_case()),
+        arguments,
+        field_type: Type::NonNullType(Box::new(Type::NamedType(format!(
+            "{}_aggregate",
+            type_name
+        )))),
+        directives: vec![],
+    }
+}
+
+/// Generates the `Query` fields for the fulltext searches declared with
+/// `@fulltext` on the given object type (e.g. `userSearch`).
+fn fulltext_fields_for_type(
+    schema: &Document,
+    object_type: &ObjectType,
+) -> Result<Vec<Field>, APISchemaError> {
+    let input_objects = ast::get_input_object_definitions(schema);
+    let definitions = FulltextDefinition::for_object_type(object_type)
+        .map_err(|e| APISchemaError::FulltextInvalid(object_type.name.to_owned(), e))?;
+
+    Ok(definitions
+        .into_iter()
+        .map(|definition| {
+            // Search results are always ordered by how well they match the
+            // search text, and can therefore not be ordered differently or
+            // paged through with cursors
+            let mut arguments = vec![input_value(
+                &"text".to_string(),
+                "",
+                Type::NonNullType(Box::new(Type::NamedType("String".to_string()))),
+            )];
+            arguments.extend(
+                collection_arguments_for_named_type(&input_objects, &object_type.name)
+                    .into_iter()
+                    .filter(|argument| match argument.name.as_str() {
+                        "skip" | "first" | "where" | "block" => true,
+                        _ => false,
+                    }),
+            );
+
+            Field {
+                position: Pos::default(),
+                description: None,
+                name: definition.name,
+                arguments,
+                field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(
+                    Type::NonNullType(Box::new(Type::NamedType(object_type.name.to_owned()))),
+                )))),
+                directives: vec![],
+            }
+        })
+        .collect())
+}
+
 /// Generates arguments for collection queries of a named type (e.g. User).
 fn collection_arguments_for_named_type(
     input_objects: &[InputObjectType],
@@ -466,6 +778,16 @@ fn collection_arguments_for_named_type(
     let mut args = vec![
         skip,
         first,
+        input_value(
+            &"after".to_string(),
+            "",
+            Type::NamedType("String".to_string()),
+        ),
+        input_value(
+            &"before".to_string(),
+            "",
+            Type::NamedType("String".to_string()),
+        ),
         input_value(
             &"orderBy".to_string(),
             "",
@@ -488,9 +810,21 @@ fn collection_arguments_for_named_type(
         ));
     }
 
+    args.push(block_argument());
+
     args
 }
 
+/// Generates the `block` argument that lets queries ask for entities as
+/// of a given block.
+fn block_argument() -> InputValue {
+    input_value(
+        &"block".to_string(),
+        "",
+        Type::NamedType("Block_height".to_string()),
+    )
+}
+
 fn add_field_arguments(
     schema: &mut Document,
     input_schema: &Document,
@@ -612,6 +946,137 @@ mod tests {
         assert_eq!(values, [&"asc".to_string(), &"desc".to_string()]);
     }
 
+    #[test]
+    fn api_schema_contains_block_height_type() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let block_height = ast::get_named_type(&schema, &"Block_height".to_string())
+            .expect("Block_height type is missing in derived API schema");
+        let input_type = match block_height {
+            TypeDefinition::InputObject(t) => Some(t),
+            _ => None,
+        }
+        .expect("Block_height type is not an input object");
+
+        let fields: Vec<&Name> = input_type.fields.iter().map(|field| &field.name).collect();
+        assert_eq!(fields, [&"hash".to_string(), &"number".to_string()]);
+    }
+
+    #[test]
+    fn api_schema_contains_connection_types() {
+        let input_schema =
+            parse_schema("type User { id: ID! }").expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let field_names = |type_name: &str| -> Vec<Name> {
+            match ast::get_named_type(&schema, &type_name.to_string()) {
+                Some(TypeDefinition::Object(t)) => {
+                    t.fields.iter().map(|field| field.name.to_owned()).collect()
+                }
+                _ => panic!("{} type is missing in derived API schema", type_name),
+            }
+        };
+        assert_eq!(
+            field_names("PageInfo"),
+            ["startCursor", "endCursor", "hasNextPage", "hasPreviousPage"]
+        );
+        assert_eq!(field_names("User_edge"), ["cursor", "node"]);
+        assert_eq!(field_names("User_connection"), ["edges", "pageInfo"]);
+
+        let connection_field = match ast::get_named_type(&schema, &"Query".to_string()) {
+            Some(TypeDefinition::Object(t)) => ast::get_field(t, &"usersConnection".to_string()),
+            _ => None,
+        }
+        .expect("\"usersConnection\" field is missing on Query type");
+        assert_eq!(
+            connection_field.field_type,
+            Type::NonNullType(Box::new(Type::NamedType("User_connection".to_string())))
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_aggregate_types() {
+        let input_schema = parse_schema(
+            "type User { id: ID!, name: String!, age: Int!, balance: BigInt, \
+                         weight: BigDecimal!, scores: [Int!]! }
+             type Tag { id: ID!, name: String! }",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        let fields = |type_name: &str| -> Vec<(Name, Type)> {
+            match ast::get_named_type(&schema, &type_name.to_string()) {
+                Some(TypeDefinition::Object(t)) => t
+                    .fields
+                    .iter()
+                    .map(|field| (field.name.to_owned(), field.field_type.to_owned()))
+                    .collect(),
+                _ => panic!("{} type is missing in derived API schema", type_name),
+            }
+        };
+        let named = |name: &str| Type::NamedType(name.to_string());
+        let non_null = |name: &str| Type::NonNullType(Box::new(named(name)));
+
+        assert_eq!(
+            fields("User_aggregate"),
+            vec![
+                ("count".to_string(), non_null("BigInt")),
+                ("sum".to_string(), non_null("User_aggregate_sum")),
+                ("avg".to_string(), non_null("User_aggregate_avg")),
+                ("min".to_string(), non_null("User_aggregate_min")),
+                ("max".to_string(), non_null("User_aggregate_max")),
+            ]
+        );
+        assert_eq!(
+            fields("User_aggregate_sum"),
+            vec![
+                ("age".to_string(), named("BigInt")),
+                ("balance".to_string(), named("BigInt")),
+                ("weight".to_string(), named("BigDecimal")),
+            ]
+        );
+        assert_eq!(
+            fields("User_aggregate_avg"),
+            vec![
+                ("age".to_string(), named("BigDecimal")),
+                ("balance".to_string(), named("BigDecimal")),
+                ("weight".to_string(), named("BigDecimal")),
+            ]
+        );
+        assert_eq!(
+            fields("User_aggregate_max"),
+            vec![
+                ("age".to_string(), named("Int")),
+                ("balance".to_string(), named("BigInt")),
+                ("weight".to_string(), named("BigDecimal")),
+            ]
+        );
+
+        // Types without numeric fields can only be counted
+        assert_eq!(
+            fields("Tag_aggregate"),
+            vec![("count".to_string(), non_null("BigInt"))]
+        );
+        assert!(ast::get_named_type(&schema, &"Tag_aggregate_sum".to_string()).is_none());
+
+        let aggregate_field = match ast::get_named_type(&schema, &"Query".to_string()) {
+            Some(TypeDefinition::Object(t)) => ast::get_field(t, &"usersAggregate".to_string()),
+            _ => None,
+        }
+        .expect("\"usersAggregate\" field is missing on Query type");
+        assert_eq!(aggregate_field.field_type, non_null("User_aggregate"));
+        assert_eq!(
+            aggregate_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            vec!["where".to_string(), "block".to_string()]
+        );
+    }
+
     #[test]
     fn api_schema_contains_query_type() {
         let input_schema =
@@ -760,7 +1225,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let user_plural_field = match query_type {
@@ -782,10 +1247,19 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
-                .into_iter()
-                .map(|name| name.to_string())
-                .collect::<Vec<String>>()
+            [
+                "skip",
+                "first",
+                "after",
+                "before",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "block"
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
         );
 
         let user_profile_singular_field = match query_type {
@@ -813,6 +1287,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn api_schema_contains_fulltext_fields_on_query_type() {
+        let input_schema = parse_schema(
+            "type User @fulltext(name: \"userSearch\", language: \"english\", fields: [\"name\", \"bio\"]) {
+               id: ID!, name: String!, bio: String
+             }",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+
+        let query_type = ast::get_named_type(&schema, &"Query".to_string())
+            .expect("Query type is missing in derived API schema");
+
+        let user_search_field = match query_type {
+            TypeDefinition::Object(t) => ast::get_field(t, &"userSearch".to_string()),
+            _ => None,
+        }
+        .expect("\"userSearch\" field is missing on Query type");
+
+        assert_eq!(
+            user_search_field.field_type,
+            Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+                Box::new(Type::NamedType("User".to_string()))
+            )))))
+        );
+
+        assert_eq!(
+            user_search_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            ["text", "skip", "first", "where", "block"]
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
     #[test]
     fn api_schema_contains_interface_fields_on_query_type() {
         let input_schema = parse_schema(
@@ -844,7 +1357,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let plural_field = match query_type {
@@ -866,10 +1379,19 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
-                .into_iter()
-                .map(|name| name.to_string())
-                .collect::<Vec<String>>()
+            [
+                "skip",
+                "first",
+                "after",
+                "before",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "block"
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
         );
     }
 }
@@ -383,6 +383,82 @@ pub fn is_list_or_non_null_list_field(field: &Field) -> bool {
     }
 }
 
+/// Returns the type of the entities in a generated type like
+/// `<Type>_connection`, `<Type>_edge` or `<Type>_aggregate`, i.e., `<Type>`
+/// if the name of `object_type` ends with `suffix` and the rest of the name
+/// is an object or interface type
+fn get_generated_type_base<'a>(
+    schema: &'a Document,
+    object_type: &ObjectType,
+    suffix: &str,
+) -> Option<ObjectOrInterface<'a>> {
+    if !object_type.name.ends_with(suffix) {
+        return None;
+    }
+    let name = object_type.name[..object_type.name.len() - suffix.len()].to_owned();
+    match get_named_type(schema, &name) {
+        Some(TypeDefinition::Object(t)) => Some(t.into()),
+        Some(TypeDefinition::Interface(i)) => Some(i.into()),
+        _ => None,
+    }
+}
+
+/// If `object_type` is a `<Type>_connection` type, returns `<Type>`
+pub fn get_connection_node_type<'a>(
+    schema: &'a Document,
+    object_type: &ObjectType,
+) -> Option<ObjectOrInterface<'a>> {
+    get_generated_type_base(schema, object_type, "_connection")
+}
+
+/// Returns true if `object_type` is one of the types that make up the
+/// result of a `*Connection` query: `PageInfo`, a `<Type>_connection` or a
+/// `<Type>_edge`. The values of these types are resolved together with the
+/// connection they belong to.
+pub fn is_pagination_type(schema: &Document, object_type: &ObjectType) -> bool {
+    object_type.name == "PageInfo"
+        || get_connection_node_type(schema, object_type).is_some()
+        || get_generated_type_base(schema, object_type, "_edge").is_some()
+}
+
+/// The aggregates that a `<Type>_aggregate` offers for the numeric fields
+/// of `<Type>`; the values for each of them are grouped in a
+/// `<Type>_aggregate_<function>` type
+pub const AGGREGATE_FUNCTIONS: &[&str] = &["sum", "avg", "min", "max"];
+
+/// Returns the fields that aggregates like sums and averages can be
+/// computed for, i.e., all fields that hold a single `Int`, `BigInt` or
+/// `BigDecimal`
+pub fn get_aggregatable_fields(fields: &[Field]) -> Vec<&Field> {
+    fields
+        .iter()
+        .filter(|field| !is_list_or_non_null_list_field(field))
+        .filter(|field| match get_field_name(&field.field_type).as_str() {
+            "Int" | "BigInt" | "BigDecimal" => true,
+            _ => false,
+        })
+        .collect()
+}
+
+/// If `object_type` is a `<Type>_aggregate` type, returns `<Type>`
+pub fn get_aggregate_node_type<'a>(
+    schema: &'a Document,
+    object_type: &ObjectType,
+) -> Option<ObjectOrInterface<'a>> {
+    get_generated_type_base(schema, object_type, "_aggregate")
+}
+
+/// Returns true if `object_type` is a `<Type>_aggregate` or one of the
+/// `<Type>_aggregate_<function>` types it contains. The values of these
+/// types are resolved together with the aggregate they belong to.
+pub fn is_aggregate_type(schema: &Document, object_type: &ObjectType) -> bool {
+    get_aggregate_node_type(schema, object_type).is_some()
+        || AGGREGATE_FUNCTIONS.iter().any(|function| {
+            get_generated_type_base(schema, object_type, &format!("_aggregate_{}", function))
+                .is_some()
+        })
+}
+
 fn unpack_type<'a>(schema: &'a Document, t: &Type) -> Option<&'a TypeDefinition> {
     use self::Type::*;
 
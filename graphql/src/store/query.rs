@@ -2,6 +2,7 @@ use graphql_parser::{query as q, query::Name, schema as s, schema::ObjectType};
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::mem::discriminant;
 
+use graph::data::schema::FulltextDefinition;
 use graph::prelude::*;
 
 use crate::execution::ObjectOrInterface;
@@ -14,31 +15,95 @@ pub fn build_query<'a>(
     entity: impl Into<ObjectOrInterface<'a>>,
     arguments: &HashMap<&q::Name, q::Value>,
     types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    block: BlockNumber,
     max_first: u32,
 ) -> Result<EntityQuery, QueryExecutionError> {
     let entity = entity.into();
-    let entity_types = match &entity {
+    let order_by = build_order_by(entity, arguments)?;
+    Ok(EntityQuery {
+        subgraph_id: parse_subgraph_id(entity)?,
+        entity_types: build_entity_types(entity, types_for_interface),
+        range: build_range(arguments, &order_by, max_first)?,
+        filter: build_filter(entity, arguments)?,
+        order_by,
+        order_direction: build_order_direction(arguments)?,
+        block,
+    })
+}
+
+/// Builds the EntityQuery for the entities that an aggregate is computed
+/// over from GraphQL arguments. Only the `where` argument affects which
+/// entities are aggregated; the query is neither ordered nor paged.
+///
+/// Panics if `entity` is not present in `schema`.
+pub fn build_aggregate_query<'a>(
+    entity: impl Into<ObjectOrInterface<'a>>,
+    arguments: &HashMap<&q::Name, q::Value>,
+    types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    block: BlockNumber,
+) -> Result<EntityQuery, QueryExecutionError> {
+    let entity = entity.into();
+    Ok(EntityQuery {
+        subgraph_id: parse_subgraph_id(entity)?,
+        entity_types: build_entity_types(entity, types_for_interface),
+        range: EntityRange::default(),
+        filter: build_filter(entity, arguments)?,
+        order_by: None,
+        order_direction: None,
+        block,
+    })
+}
+
+/// Returns the names of the entity types that a query for `entity` needs
+/// to look at; for an interface, those are all types implementing it.
+fn build_entity_types(
+    entity: ObjectOrInterface,
+    types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+) -> Vec<String> {
+    match entity {
         ObjectOrInterface::Object(object) => vec![object.name.clone()],
         ObjectOrInterface::Interface(interface) => types_for_interface[&interface.name]
             .iter()
             .map(|o| o.name.clone())
             .collect(),
+    }
+}
+
+/// Restricts `query` to the entities that match the search text in the
+/// `text` argument of the fulltext search field `fulltext`, and orders them
+/// so that the best matches come first.
+pub fn add_fulltext_filter(
+    query: &mut EntityQuery,
+    fulltext: &FulltextDefinition,
+    arguments: &HashMap<&q::Name, q::Value>,
+) {
+    let text = match arguments.get(&"text".to_string()) {
+        Some(q::Value::String(text)) => text.to_owned(),
+        _ => unreachable!("text is a non-null String"),
     };
-    Ok(EntityQuery {
-        subgraph_id: parse_subgraph_id(entity)?,
-        entity_types,
-        range: build_range(arguments, max_first)?,
-        filter: build_filter(entity, arguments)?,
-        order_by: build_order_by(entity, arguments)?,
-        order_direction: build_order_direction(arguments)?,
-    })
+    let filter = EntityFilter::Fulltext(fulltext.name.clone(), Value::String(text));
+
+    query.filter = Some(match query.filter.take() {
+        Some(EntityFilter::And(mut filters)) => {
+            filters.insert(0, filter);
+            EntityFilter::And(filters)
+        }
+        Some(other) => EntityFilter::And(vec![filter, other]),
+        None => filter,
+    });
+    query.order_by = Some((fulltext.name.clone(), ValueType::String));
+    query.order_direction = Some(EntityOrder::Descending);
 }
 
 /// Parses GraphQL arguments into a EntityRange, if present.
 fn build_range(
     arguments: &HashMap<&q::Name, q::Value>,
+    order_by: &Option<(String, ValueType)>,
     max_first: u32,
 ) -> Result<EntityRange, QueryExecutionError> {
+    let after = build_cursor(arguments, "after", order_by)?;
+    let before = build_cursor(arguments, "before", order_by)?;
+
     let first = match arguments.get(&"first".to_string()) {
         Some(q::Value::Int(n)) => {
             let n = n.as_i64().expect("first is Int");
@@ -69,6 +134,9 @@ fn build_range(
         (Ok(first), Ok(skip)) => Ok(EntityRange {
             first: Some(first),
             skip,
+            after,
+            before,
+            reverse: false,
         }),
         _ => {
             let errors: Vec<_> = vec![first, skip]
@@ -81,6 +149,60 @@ fn build_range(
     }
 }
 
+/// Parses the cursor in the GraphQL argument `name`, if present.
+fn build_cursor(
+    arguments: &HashMap<&q::Name, q::Value>,
+    name: &str,
+    order_by: &Option<(String, ValueType)>,
+) -> Result<Option<EntityCursor>, QueryExecutionError> {
+    match arguments.get(&name.to_string()) {
+        Some(q::Value::String(cursor)) => decode_cursor(cursor, order_by).map(Some),
+        None | Some(q::Value::Null) => Ok(None),
+        Some(value) => Err(QueryExecutionError::InvalidCursor(
+            value.to_string(),
+            "cursors must be strings".to_owned(),
+        )),
+    }
+}
+
+/// Encodes the position of `entity` in the result of a query that is
+/// ordered by `order_by` as an opaque cursor. The cursor contains the name
+/// of the attribute the query was ordered by, the value of that attribute
+/// and the id of the entity.
+pub fn encode_cursor(order_by: &Option<(String, ValueType)>, entity: &Entity) -> String {
+    let attribute = order_by.as_ref().map(|(attribute, _)| attribute.as_str());
+    let value = attribute
+        .and_then(|attribute| entity.get(attribute))
+        .cloned()
+        .unwrap_or(Value::Null);
+    let id = entity.id().unwrap_or_default();
+    hex::encode(
+        serde_json::to_vec(&(attribute, value, id)).expect("cursors can always be serialized"),
+    )
+}
+
+/// Decodes a cursor produced by `encode_cursor`. The cursor must have been
+/// produced for a query that orders by the same attribute as `order_by`.
+fn decode_cursor(
+    cursor: &str,
+    order_by: &Option<(String, ValueType)>,
+) -> Result<EntityCursor, QueryExecutionError> {
+    let invalid = |e: String| QueryExecutionError::InvalidCursor(cursor.to_owned(), e);
+
+    let bytes = hex::decode(cursor).map_err(|e| invalid(e.to_string()))?;
+    let (attribute, value, id): (Option<String>, Value, String) =
+        serde_json::from_slice(&bytes).map_err(|e| invalid(e.to_string()))?;
+
+    let expected = order_by.as_ref().map(|(attribute, _)| attribute);
+    if attribute.as_ref() != expected {
+        return Err(invalid(format!(
+            "the cursor was created for a query ordered by `{}`",
+            attribute.unwrap_or_else(|| "id".to_owned())
+        )));
+    }
+    Ok(EntityCursor { value, id })
+}
+
 /// Parses GraphQL arguments into a EntityFilter, if present.
 fn build_filter(
     entity: ObjectOrInterface,
@@ -287,7 +409,7 @@ mod tests {
 
     use graph::prelude::*;
 
-    use super::build_query;
+    use super::{build_query, encode_cursor};
 
     fn default_object() -> ObjectType {
         let subgraph_id_argument = (
@@ -368,6 +490,7 @@ mod tests {
                 &object("Entity1"),
                 &default_arguments(),
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX
             )
             .unwrap()
@@ -379,6 +502,7 @@ mod tests {
                 &object("Entity2"),
                 &default_arguments(),
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX
             )
             .unwrap()
@@ -394,6 +518,7 @@ mod tests {
                 &default_object(),
                 &default_arguments(),
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX
             )
             .unwrap()
@@ -405,6 +530,7 @@ mod tests {
                 &default_object(),
                 &default_arguments(),
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX
             )
             .unwrap()
@@ -419,18 +545,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::Enum("name".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_by,
             Some(("name".to_string(), ValueType::String))
         );
 
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::Enum("email".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_by,
             Some(("email".to_string(), ValueType::String))
         );
     }
@@ -441,18 +579,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::String("name".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_by,
             None,
         );
 
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::String("email".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_by,
             None,
         );
     }
@@ -463,27 +613,45 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("asc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_direction,
             Some(EntityOrder::Ascending)
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("desc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_direction,
             Some(EntityOrder::Descending)
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("ascending...".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
     }
@@ -494,18 +662,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::String("asc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::String("desc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
     }
@@ -517,6 +697,7 @@ mod tests {
                 &default_object(),
                 &default_arguments(),
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX
             )
             .unwrap()
@@ -531,12 +712,21 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&skip, q::Value::Int(q::Number::from(50)));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .range,
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .range,
             EntityRange {
                 first: Some(100),
                 skip: 50,
+                after: None,
+                before: None,
+                reverse: false,
             },
         );
     }
@@ -560,6 +750,7 @@ mod tests {
                 },
                 &args,
                 &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
                 std::u32::MAX,
             )
             .unwrap()
@@ -570,4 +761,50 @@ mod tests {
             )]))
         )
     }
+
+    #[test]
+    fn build_query_yields_cursors() {
+        let order_by = "orderBy".to_string();
+        let after = "after".to_string();
+        let entity = Entity::from(vec![
+            ("id", Value::from("1")),
+            ("name", Value::from("Alice")),
+        ]);
+        let cursor = encode_cursor(&Some(("name".to_owned(), ValueType::String)), &entity);
+
+        let mut args = default_arguments();
+        args.insert(&order_by, q::Value::Enum("name".to_string()));
+        args.insert(&after, q::Value::String(cursor.clone()));
+        assert_eq!(
+            build_query(
+                &default_object(),
+                &args,
+                &BTreeMap::new(),
+                BLOCK_NUMBER_MAX,
+                std::u32::MAX
+            )
+            .unwrap()
+            .range
+            .after,
+            Some(EntityCursor {
+                value: Value::from("Alice"),
+                id: "1".to_owned(),
+            })
+        );
+
+        // The cursor can not be used with a different order
+        let mut args = default_arguments();
+        args.insert(&order_by, q::Value::Enum("email".to_string()));
+        args.insert(&after, q::Value::String(cursor));
+        match build_query(
+            &default_object(),
+            &args,
+            &BTreeMap::new(),
+            BLOCK_NUMBER_MAX,
+            std::u32::MAX,
+        ) {
+            Err(QueryExecutionError::InvalidCursor(_, _)) => (),
+            result => panic!("expected an invalid cursor error, got {:?}", result),
+        }
+    }
 }
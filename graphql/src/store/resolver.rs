@@ -1,14 +1,20 @@
 use graphql_parser::{query as q, schema as s};
 use std::collections::{BTreeMap, HashMap};
+use std::mem;
 use std::result;
 use std::sync::Arc;
 
 use graph::components::store::*;
+use graph::data::schema::FulltextDefinition;
 use graph::prelude::*;
+use web3::types::H256;
 
 use crate::prelude::*;
 use crate::schema::ast as sast;
-use crate::store::query::{collect_entities_from_query_field, parse_subgraph_id};
+use crate::store::query::{
+    add_fulltext_filter, build_aggregate_query, collect_entities_from_query_field, encode_cursor,
+    parse_subgraph_id,
+};
 
 /// A resolver that fetches entities from a `Store`.
 pub struct StoreResolver<S> {
@@ -139,7 +145,234 @@ where
         }
     }
 
+    /// Returns the id of a parent object.
+    fn parent_id(parent: &q::Value) -> &str {
+        match parent {
+            q::Value::Object(object) => match object.get("id") {
+                Some(q::Value::String(id)) => id,
+                _ => panic!("Parent object is missing an \"id\""),
+            },
+            _ => unreachable!("parents are always objects"),
+        }
+    }
+
+    /// Returns the ids of the entities that `parent` references through the
+    /// field `field`.
+    fn referenced_ids<'p>(parent: &'p q::Value, field: &q::Name) -> Vec<&'p str> {
+        match parent {
+            q::Value::Object(object) => match object.get(field) {
+                Some(q::Value::String(id)) => vec![id.as_str()],
+                Some(q::Value::List(ids)) => ids
+                    .iter()
+                    .filter_map(|id| match id {
+                        q::Value::String(id) => Some(id.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Resolves a field derived with `@derivedFrom` for all `parents` with
+    /// one windowed query.
+    fn resolve_derived_field_for_parents(
+        &self,
+        parents: &[&q::Value],
+        field: &q::Field,
+        derived_from_field: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        is_list: bool,
+        block: BlockNumber,
+        max_first: u32,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        let query = if is_list {
+            build_query(
+                object_type,
+                arguments,
+                types_for_interface,
+                block,
+                max_first,
+            )?
+        } else {
+            // As in `resolve_object`, we use first: 2 to detect and fail if
+            // there is more than one entity that matches the `@derivedFrom`
+            let mut arguments = arguments.clone();
+            let first_arg_name = q::Name::from("first");
+            arguments.insert(&first_arg_name, q::Value::Int(q::Number::from(2)));
+            let skip_arg_name = q::Name::from("skip");
+            arguments.insert(&skip_arg_name, q::Value::Int(q::Number::from(0)));
+            build_query(object_type, &arguments, types_for_interface, block, 2)?
+        };
+
+        let link = if sast::is_list_or_non_null_list_field(derived_from_field) {
+            WindowAttribute::List(derived_from_field.name.clone())
+        } else {
+            WindowAttribute::Scalar(derived_from_field.name.clone())
+        };
+        let mut ids: Vec<String> = parents
+            .iter()
+            .map(|parent| Self::parent_id(parent).to_owned())
+            .collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut children: HashMap<String, Vec<Entity>> = HashMap::new();
+        for (parent_id, entity) in self
+            .store
+            .find_windowed(query, EntityWindow { link, ids })?
+        {
+            children.entry(parent_id).or_default().push(entity);
+        }
+
+        parents
+            .iter()
+            .map(|parent| {
+                let entities = children
+                    .get(Self::parent_id(parent))
+                    .cloned()
+                    .unwrap_or_default();
+                if is_list {
+                    Ok(q::Value::List(
+                        entities.into_iter().map(Into::into).collect(),
+                    ))
+                } else if entities.len() > 1 {
+                    Err(QueryExecutionError::AmbiguousDerivedFromResult(
+                        field.position.clone(),
+                        field.name.to_owned(),
+                        object_type.name().to_owned(),
+                        derived_from_field.name.to_owned(),
+                    ))
+                } else {
+                    Ok(entities
+                        .into_iter()
+                        .next()
+                        .map_or(q::Value::Null, Into::into))
+                }
+            })
+            .collect()
+    }
+
+    /// Resolves a field that holds the ids of other entities for all
+    /// `parents` with one query for all referenced entities.
+    fn resolve_reference_field_for_parents(
+        &self,
+        parents: &[&q::Value],
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        is_list: bool,
+        block: BlockNumber,
+        max_first: u32,
+    ) -> Result<Vec<q::Value>, QueryExecutionError> {
+        let references: Vec<_> = parents
+            .iter()
+            .map(|parent| Self::referenced_ids(parent, &field_definition.name))
+            .collect();
+        let mut ids: Vec<&str> = references.iter().flatten().cloned().collect();
+        ids.sort();
+        ids.dedup();
+
+        // Each parent only references a subset of all the entities we fetch.
+        // We therefore apply the range to each parent's entities ourselves
+        let mut query = if is_list {
+            build_query(
+                object_type,
+                arguments,
+                types_for_interface,
+                block,
+                max_first,
+            )?
+        } else {
+            let entity_types = match object_type {
+                ObjectOrInterface::Object(object) => vec![object.name.clone()],
+                ObjectOrInterface::Interface(interface) => types_for_interface[&interface.name]
+                    .iter()
+                    .map(|o| o.name.clone())
+                    .collect(),
+            };
+            EntityQuery::new(
+                parse_subgraph_id(object_type)?,
+                entity_types,
+                EntityRange::default(),
+            )
+            .block(block)
+        };
+        // Cursors restrict which entities qualify independently of the
+        // parent and can therefore stay in the query
+        let first = query.range.first.take();
+        let skip = mem::replace(&mut query.range.skip, 0);
+
+        let entities = if ids.is_empty() {
+            vec![]
+        } else {
+            let filter = EntityFilter::Or(
+                ids.iter()
+                    .map(|id| EntityFilter::Equal(String::from("id"), Value::from(*id)))
+                    .collect(),
+            );
+            let top_level_filter = query.filter.get_or_insert(EntityFilter::And(vec![]));
+            match top_level_filter {
+                EntityFilter::And(ref mut filters) => {
+                    filters.push(filter);
+                }
+                _ => unreachable!("top level filter is always `And`"),
+            };
+            self.store.find(query)?
+        };
+
+        Ok(references
+            .iter()
+            .map(|referenced_ids| {
+                let mut matching = entities.iter().filter(|entity| {
+                    entity
+                        .id()
+                        .map(|id| referenced_ids.contains(&id.as_str()))
+                        .unwrap_or(false)
+                });
+                if is_list {
+                    q::Value::List(
+                        matching
+                            .skip(skip as usize)
+                            .take(first.map_or(usize::max_value(), |first| first as usize))
+                            .cloned()
+                            .map(Into::into)
+                            .collect(),
+                    )
+                } else {
+                    matching.next().cloned().map_or(q::Value::Null, Into::into)
+                }
+            })
+            .collect())
+    }
+
     /// Returns true if the object has no references in the given field.
+    /// Returns the fulltext search that `field_definition` performs if it is
+    /// one of the search fields declared with `@fulltext` on `object_type`.
+    fn fulltext_definition(
+        object_type: ObjectOrInterface<'_>,
+        field_definition: &s::Field,
+    ) -> Option<FulltextDefinition> {
+        match object_type {
+            ObjectOrInterface::Object(object_type)
+                if field_definition
+                    .arguments
+                    .iter()
+                    .any(|argument| argument.name == "text") =>
+            {
+                FulltextDefinition::for_object_type(object_type)
+                    .ok()?
+                    .into_iter()
+                    .find(|definition| definition.name == field_definition.name)
+            }
+            _ => None,
+        }
+    }
+
     fn references_field_is_empty(parent: &Option<q::Value>, field: &q::Name) -> bool {
         parent
             .as_ref()
@@ -154,6 +387,26 @@ where
             })
             .unwrap_or(true)
     }
+
+    /// Return `true` if any entities that match `query` come after
+    /// `cursor` in the order of the query, or before it if `after` is
+    /// `false`, ignoring the range of the query otherwise.
+    fn has_entities_beyond(
+        &self,
+        query: &EntityQuery,
+        cursor: &EntityCursor,
+        after: bool,
+    ) -> Result<bool, QueryExecutionError> {
+        let mut query = query.clone();
+        query.range = EntityRange {
+            first: Some(1),
+            skip: 0,
+            after: if after { Some(cursor.clone()) } else { None },
+            before: if after { None } else { Some(cursor.clone()) },
+            reverse: false,
+        };
+        Ok(!self.store.find(query)?.is_empty())
+    }
 }
 
 impl<S> Resolver for StoreResolver<S>
@@ -168,10 +421,27 @@ where
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
         max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         let object_type = object_type.into();
-        let mut query = build_query(object_type, arguments, types_for_interface, max_first)?;
+        let mut query = build_query(
+            object_type,
+            arguments,
+            types_for_interface,
+            block,
+            max_first,
+        )?;
+
+        // Fulltext search fields return the entities matching the search
+        // text; they do not refer to other entities
+        if let Some(fulltext) = Self::fulltext_definition(object_type, field_definition) {
+            add_fulltext_filter(&mut query, &fulltext, arguments);
+            let entities = self.store.find(query)?;
+            return Ok(q::Value::List(
+                entities.into_iter().map(|entity| entity.into()).collect(),
+            ));
+        }
 
         // Add matching filter for derived fields
         let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
@@ -210,6 +480,7 @@ where
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
@@ -222,20 +493,30 @@ where
 
         let resolve_object_with_id = |id: &String| -> Result<Option<Entity>, QueryExecutionError> {
             match object_type {
-                ObjectOrInterface::Object(_) => self.store.get(EntityKey {
-                    subgraph_id: subgraph_id_for_resolve_object,
-                    entity_type: object_type.name().to_owned(),
-                    entity_id: id.to_owned(),
-                }),
-                ObjectOrInterface::Interface(interface) => {
-                    let entity_types = types_for_interface[&interface.name]
-                        .iter()
-                        .map(|o| o.name.clone())
-                        .collect();
+                ObjectOrInterface::Object(_) if block == BLOCK_NUMBER_MAX => {
+                    self.store.get(EntityKey {
+                        subgraph_id: subgraph_id_for_resolve_object,
+                        entity_type: object_type.name().to_owned(),
+                        entity_id: id.to_owned(),
+                    })
+                }
+                _ => {
+                    // Looking up an entity at an earlier block, or one that
+                    // could be of any of the types implementing an interface,
+                    // requires a query
+                    let entity_types = match object_type {
+                        ObjectOrInterface::Object(object) => vec![object.name.clone()],
+                        ObjectOrInterface::Interface(interface) => types_for_interface
+                            [&interface.name]
+                            .iter()
+                            .map(|o| o.name.clone())
+                            .collect(),
+                    };
                     let range = EntityRange::first(1);
-                    let mut query =
-                        EntityQuery::new(subgraph_id_for_resolve_object, entity_types, range);
-                    query.filter = Some(EntityFilter::Equal(String::from("id"), Value::from(id)));
+                    let query =
+                        EntityQuery::new(subgraph_id_for_resolve_object, entity_types, range)
+                            .filter(EntityFilter::Equal(String::from("id"), Value::from(id)))
+                            .block(block);
                     Ok(self.store.find(query)?.into_iter().next())
                 }
             }
@@ -259,7 +540,8 @@ where
 
                 let skip_arg_name = q::Name::from("skip");
                 arguments.insert(&skip_arg_name, q::Value::Int(q::Number::from(0)));
-                let mut query = build_query(object_type, &arguments, types_for_interface, 2)?;
+                let mut query =
+                    build_query(object_type, &arguments, types_for_interface, block, 2)?;
                 Self::add_filter_for_derived_field(&mut query, parent, derived_from_field);
 
                 // Find the entity or entities that reference the parent entity
@@ -289,6 +571,253 @@ where
         Ok(entity.map_or(q::Value::Null, Into::into))
     }
 
+    fn resolve_field_for_parents(
+        &self,
+        parents: &[&q::Value],
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
+        max_first: u32,
+    ) -> Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        let is_list = sast::is_list_or_non_null_list_field(field_definition);
+        match sast::get_derived_from_field(object_type, field_definition) {
+            Some(derived_from_field) => self.resolve_derived_field_for_parents(
+                parents,
+                field,
+                derived_from_field,
+                object_type,
+                arguments,
+                types_for_interface,
+                is_list,
+                block,
+                max_first,
+            ),
+            None => self.resolve_reference_field_for_parents(
+                parents,
+                field_definition,
+                object_type,
+                arguments,
+                types_for_interface,
+                is_list,
+                block,
+                max_first,
+            ),
+        }
+        .map(Some)
+    }
+
+    fn resolve_connection(
+        &self,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        node_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
+        max_first: u32,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let mut query = build_query(node_type, arguments, types_for_interface, block, max_first)?;
+        let order_by = query.order_by.clone();
+
+        // With `last`, we page backwards: we query in the reverse order,
+        // which makes `first` and `skip` count from the end, and then put
+        // the entities back into the order of the query. `first` is ignored
+        // in that case since it always has a value
+        let last = match arguments.get(&"last".to_string()) {
+            Some(q::Value::Int(n)) => {
+                let n = n.as_i64().expect("last is Int");
+                if n > 0 && n <= max_first as i64 {
+                    Some(n as u32)
+                } else {
+                    return Err(QueryExecutionError::RangeArgumentsError(
+                        vec!["last"],
+                        max_first,
+                    ));
+                }
+            }
+            _ => None,
+        };
+        let limit = match last {
+            Some(last) => {
+                query.range.reverse = true;
+                Some(last)
+            }
+            None => query.range.first,
+        };
+
+        // Ask for one more entity than we return to find out whether there
+        // are more entities in the direction we are paging in
+        query.range.first = limit.map(|limit| limit.saturating_add(1));
+        let mut entities = self.store.find(query.clone())?;
+        let has_more = limit.map_or(false, |limit| entities.len() > limit as usize);
+        if let Some(limit) = limit {
+            entities.truncate(limit as usize);
+        }
+        if last.is_some() {
+            entities.reverse();
+        }
+
+        // In the other direction, there are more entities if we skipped
+        // some, or if there are any on the other side of the cursor that
+        // bounds the page
+        let skipped = query.range.skip > 0;
+        let (has_previous_page, has_next_page) = if last.is_some() {
+            let has_next_page = match &query.range.before {
+                Some(before) => self.has_entities_beyond(&query, before, true)?,
+                None => skipped,
+            };
+            (has_more, has_next_page)
+        } else {
+            let has_previous_page = match &query.range.after {
+                Some(after) => skipped || self.has_entities_beyond(&query, after, false)?,
+                None => skipped,
+            };
+            (has_previous_page, has_more)
+        };
+
+        let cursors: Vec<_> = entities
+            .iter()
+            .map(|entity| encode_cursor(&order_by, entity))
+            .collect();
+        let cursor_value = |cursor: Option<&String>| {
+            cursor.map_or(q::Value::Null, |cursor| q::Value::String(cursor.clone()))
+        };
+
+        let mut page_info = BTreeMap::new();
+        page_info.insert("startCursor".to_owned(), cursor_value(cursors.first()));
+        page_info.insert("endCursor".to_owned(), cursor_value(cursors.last()));
+        page_info.insert("hasNextPage".to_owned(), q::Value::Boolean(has_next_page));
+        page_info.insert(
+            "hasPreviousPage".to_owned(),
+            q::Value::Boolean(has_previous_page),
+        );
+
+        let edges = cursors
+            .into_iter()
+            .zip(entities)
+            .map(|(cursor, entity)| {
+                let mut edge = BTreeMap::new();
+                edge.insert("cursor".to_owned(), q::Value::String(cursor));
+                edge.insert("node".to_owned(), entity.into());
+                q::Value::Object(edge)
+            })
+            .collect();
+
+        let mut connection = BTreeMap::new();
+        connection.insert("edges".to_owned(), q::Value::List(edges));
+        connection.insert("pageInfo".to_owned(), q::Value::Object(page_info));
+        Ok(q::Value::Object(connection))
+    }
+
+    fn resolve_aggregate(
+        &self,
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        node_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        block: BlockNumber,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let query = build_aggregate_query(node_type, arguments, types_for_interface, block)?;
+
+        // Compute all aggregates in one go, no matter which of them the
+        // query selects, since the store has to look at the same entities
+        // for each of them anyway
+        let fields = sast::get_aggregatable_fields(node_type.fields());
+        let mut aggregates = vec![EntityAggregate::Count];
+        for field in &fields {
+            aggregates.push(EntityAggregate::Sum(field.name.clone()));
+            aggregates.push(EntityAggregate::Avg(field.name.clone()));
+            aggregates.push(EntityAggregate::Min(field.name.clone()));
+            aggregates.push(EntityAggregate::Max(field.name.clone()));
+        }
+        let mut values = self.store.aggregate(query, &aggregates)?.into_iter();
+
+        let mut aggregate = BTreeMap::new();
+        aggregate.insert(
+            "count".to_owned(),
+            values.next().map_or(q::Value::Null, q::Value::from),
+        );
+        if !fields.is_empty() {
+            let mut by_function: Vec<_> = sast::AGGREGATE_FUNCTIONS
+                .iter()
+                .map(|function| (function, BTreeMap::new()))
+                .collect();
+            for field in &fields {
+                for (_, object) in by_function.iter_mut() {
+                    object.insert(
+                        field.name.clone(),
+                        values.next().map_or(q::Value::Null, q::Value::from),
+                    );
+                }
+            }
+            for (function, object) in by_function {
+                aggregate.insert(function.to_string(), q::Value::Object(object));
+            }
+        }
+        Ok(q::Value::Object(aggregate))
+    }
+
+    fn locate_block(
+        &self,
+        object_type: ObjectOrInterface<'_>,
+        block: &q::Value,
+    ) -> Result<BlockNumber, QueryExecutionError> {
+        let constraint = |name: &str| match block {
+            q::Value::Object(constraint) => constraint.get(name).filter(|v| **v != q::Value::Null),
+            _ => unreachable!("the `block` argument is a `Block_height` input object"),
+        };
+
+        let subgraph_id = parse_subgraph_id(object_type)?;
+
+        let number = match (constraint("number"), constraint("hash")) {
+            (None, None) => return Ok(BLOCK_NUMBER_MAX),
+            (Some(q::Value::Int(number)), None) => {
+                let number = number.as_i64().expect("block number is an Int");
+                if number < 0 || number > BLOCK_NUMBER_MAX as i64 {
+                    return Err(QueryExecutionError::ValueParseError(
+                        "block.number".to_owned(),
+                        format!(
+                            "block numbers must be between 0 and {}, but got {}",
+                            BLOCK_NUMBER_MAX, number
+                        ),
+                    ));
+                }
+                number as BlockNumber
+            }
+            (None, Some(q::Value::String(hash))) => {
+                let block_hash = hash.trim_start_matches("0x").parse::<H256>().map_err(|e| {
+                    QueryExecutionError::ValueParseError("block.hash".to_owned(), e.to_string())
+                })?;
+                self.store
+                    .block_number(&subgraph_id, block_hash)?
+                    .ok_or_else(|| QueryExecutionError::BlockNotFound(hash.to_owned()))?
+            }
+            _ => {
+                return Err(QueryExecutionError::ValueParseError(
+                    "block".to_owned(),
+                    "a block must be identified by either its number or its hash".to_owned(),
+                ))
+            }
+        };
+
+        // Only allow queries for blocks the subgraph has already processed
+        let latest = self
+            .store
+            .block_ptr(subgraph_id)
+            .map_err(QueryExecutionError::StoreError)?;
+        match latest {
+            Some(latest) if number as u64 <= latest.number => Ok(number),
+            latest => Err(QueryExecutionError::BlockNotIndexed(
+                number,
+                latest.map(|ptr| ptr.number),
+            )),
+        }
+    }
+
     fn resolve_field_stream<'a, 'b>(
         &self,
         schema: &'a s::Document,
@@ -3,7 +3,7 @@ extern crate pretty_assertions;
 
 use graphql_parser::{query as q, Pos};
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::{Duration, Instant};
 
 use graph::prelude::*;
@@ -75,6 +75,8 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        graft: None,
+        confirmations: None,
     };
 
     let ops = SubgraphDeploymentEntity::new(&manifest, false, false, None, None)
@@ -82,7 +84,9 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
         .into_iter()
         .map(|op| op.into())
         .collect();
-    store.create_subgraph_deployment(&schema, ops).unwrap();
+    store
+        .create_subgraph_deployment(&schema, ops, None)
+        .unwrap();
 
     let entities = vec![
         Entity::from(vec![
@@ -1240,3 +1244,379 @@ fn can_use_nested_filter() {
         )])
     )
 }
+
+#[test]
+fn can_paginate_nested_fields_per_parent() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(first: 100, orderBy: id) {
+                name
+                bands(first: 1, skip: 1, orderBy: name) { name }
+                writtenSongs(first: 1, orderBy: title, orderDirection: desc) { title }
+            }
+        }
+        ",
+        )
+        .expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let musician = |name: &str, bands: Vec<&str>, songs: Vec<&str>| {
+        object_value(vec![
+            ("name", q::Value::String(String::from(name))),
+            (
+                "bands",
+                q::Value::List(
+                    bands
+                        .into_iter()
+                        .map(|band| object_value(vec![("name", q::Value::String(band.into()))]))
+                        .collect(),
+                ),
+            ),
+            (
+                "writtenSongs",
+                q::Value::List(
+                    songs
+                        .into_iter()
+                        .map(|song| object_value(vec![("title", q::Value::String(song.into()))]))
+                        .collect(),
+                ),
+            ),
+        ])
+    };
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                musician("John", vec!["The Musicians"], vec!["Pop Tune"]),
+                musician("Lisa", vec![], vec!["Rock Tune"]),
+                musician("Tom", vec!["The Musicians"], vec!["Folk Tune"]),
+                musician("Valerie", vec![], vec![]),
+            ])
+        )]))
+    )
+}
+
+/// Query a page of `musiciansConnection` with the given arguments and
+/// return the names of the musicians in it and its `pageInfo`
+fn query_musicians_connection(arguments: &str) -> (Vec<String>, BTreeMap<String, q::Value>) {
+    let result = execute_query_document(
+        graphql_parser::parse_query(&format!(
+            "
+            query {{
+                musiciansConnection({}) {{
+                    edges {{ node {{ name }} }}
+                    pageInfo {{ startCursor endCursor hasNextPage hasPreviousPage }}
+                }}
+            }}
+            ",
+            arguments
+        ))
+        .expect("Invalid test query"),
+    );
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let connection = match result.data {
+        Some(q::Value::Object(mut data)) => data.remove("musiciansConnection").unwrap(),
+        data => panic!("unexpected query result {:?}", data),
+    };
+    let (edges, page_info) = match connection {
+        q::Value::Object(mut connection) => (
+            connection.remove("edges").unwrap(),
+            connection.remove("pageInfo").unwrap(),
+        ),
+        connection => panic!("unexpected connection {:?}", connection),
+    };
+    let names = match edges {
+        q::Value::List(edges) => edges
+            .into_iter()
+            .map(|edge| match edge {
+                q::Value::Object(mut edge) => match edge.remove("node") {
+                    Some(q::Value::Object(mut node)) => match node.remove("name") {
+                        Some(q::Value::String(name)) => name,
+                        name => panic!("unexpected name {:?}", name),
+                    },
+                    node => panic!("unexpected node {:?}", node),
+                },
+                edge => panic!("unexpected edge {:?}", edge),
+            })
+            .collect(),
+        edges => panic!("unexpected edges {:?}", edges),
+    };
+    let page_info = match page_info {
+        q::Value::Object(page_info) => page_info,
+        page_info => panic!("unexpected page info {:?}", page_info),
+    };
+    (names, page_info)
+}
+
+fn cursor(page_info: &BTreeMap<String, q::Value>, name: &str) -> String {
+    match &page_info[name] {
+        q::Value::String(cursor) => cursor.clone(),
+        cursor => panic!("unexpected cursor {:?}", cursor),
+    }
+}
+
+#[test]
+fn can_page_through_connection_with_cursors() {
+    let (names, page_info) = query_musicians_connection("first: 2, orderBy: name");
+    assert_eq!(names, vec!["John", "Lisa"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(true));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(false));
+
+    let (names, page_info) = query_musicians_connection(&format!(
+        "first: 2, orderBy: name, after: \"{}\"",
+        cursor(&page_info, "endCursor")
+    ));
+    assert_eq!(names, vec!["Tom", "Valerie"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(false));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(true));
+}
+
+#[test]
+fn can_page_backwards_through_connection_from_the_middle() {
+    // Get a cursor pointing at Tom, the third of four musicians
+    let (names, page_info) = query_musicians_connection("first: 3, orderBy: name");
+    assert_eq!(names, vec!["John", "Lisa", "Tom"]);
+    let tom = cursor(&page_info, "endCursor");
+
+    // The page right before Tom
+    let (names, page_info) =
+        query_musicians_connection(&format!("last: 1, orderBy: name, before: \"{}\"", tom));
+    assert_eq!(names, vec!["Lisa"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(true));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(true));
+
+    // Keep paging backwards from the start of that page
+    let (names, page_info) = query_musicians_connection(&format!(
+        "last: 2, orderBy: name, before: \"{}\"",
+        cursor(&page_info, "startCursor")
+    ));
+    assert_eq!(names, vec!["John"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(true));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(false));
+
+    // Backwards in descending order, and without a cursor
+    let (names, page_info) =
+        query_musicians_connection("last: 3, orderBy: name, orderDirection: desc");
+    assert_eq!(names, vec!["Tom", "Lisa", "John"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(false));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(true));
+
+    // `first` with `before` returns the first entities before the cursor,
+    // and knows that there are more entities after the cursor
+    let (names, page_info) =
+        query_musicians_connection(&format!("first: 1, orderBy: name, before: \"{}\"", tom));
+    assert_eq!(names, vec!["John"]);
+    assert_eq!(page_info["hasNextPage"], q::Value::Boolean(true));
+    assert_eq!(page_info["hasPreviousPage"], q::Value::Boolean(false));
+}
+
+#[test]
+fn cannot_use_cursor_with_different_order() {
+    let cursor = {
+        let result = execute_query_document(
+            graphql_parser::parse_query(
+                "query { musiciansConnection(first: 1, orderBy: name) { pageInfo { endCursor } } }",
+            )
+            .expect("Invalid test query"),
+        );
+        match result.data {
+            Some(q::Value::Object(data)) => match &data["musiciansConnection"] {
+                q::Value::Object(connection) => match &connection["pageInfo"] {
+                    q::Value::Object(page_info) => page_info["endCursor"].clone(),
+                    page_info => panic!("unexpected page info {:?}", page_info),
+                },
+                connection => panic!("unexpected connection {:?}", connection),
+            },
+            data => panic!("unexpected query result {:?}", data),
+        }
+    };
+    let cursor = match cursor {
+        q::Value::String(cursor) => cursor,
+        cursor => panic!("unexpected cursor {:?}", cursor),
+    };
+
+    let result = execute_query_document(
+        graphql_parser::parse_query(&format!(
+            "query {{ musicians(orderBy: id, after: \"{}\") {{ name }} }}",
+            cursor
+        ))
+        .expect("Invalid test query"),
+    );
+
+    match &result
+        .errors
+        .expect("cursor for a different order is rejected")[0]
+    {
+        QueryError::ExecutionError(QueryExecutionError::InvalidCursor(_, _)) => (),
+        e => panic!("expected InvalidCursor error, got {}", e),
+    }
+}
+
+#[test]
+fn can_query_at_block() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musician(id: \"m1\", block: { number: 0 }) {
+                name
+                mainBand { name }
+            }
+            musicians(orderBy: id, block: { number: 0 }) { name }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "musician",
+                object_value(vec![
+                    ("name", q::Value::String(String::from("John"))),
+                    (
+                        "mainBand",
+                        object_value(vec![(
+                            "name",
+                            q::Value::String(String::from("The Musicians")),
+                        )]),
+                    ),
+                ])
+            ),
+            (
+                "musicians",
+                q::Value::List(vec![
+                    object_value(vec![("name", q::Value::String(String::from("John")))]),
+                    object_value(vec![("name", q::Value::String(String::from("Lisa")))]),
+                    object_value(vec![("name", q::Value::String(String::from("Tom")))]),
+                    object_value(vec![("name", q::Value::String(String::from("Valerie")))]),
+                ])
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn cannot_query_at_unindexed_block() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(block: { number: 7000 }) { name }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    match &result.errors.expect("expected an error")[0] {
+        QueryError::ExecutionError(QueryExecutionError::BlockNotIndexed(block, latest)) => {
+            assert_eq!(*block, 7000);
+            assert_eq!(*latest, Some(0));
+        }
+        e => panic!(format!("expected BlockNotIndexed error, got {}", e)),
+    }
+}
+
+#[test]
+fn cannot_query_at_unknown_block_hash() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(block: { hash: \"0x0000000000000000000000000000000000000000000000000000000000000001\" }) {
+                name
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    match &result.errors.expect("expected an error")[0] {
+        QueryError::ExecutionError(QueryExecutionError::BlockNotFound(_)) => (),
+        e => panic!(format!("expected BlockNotFound error, got {}", e)),
+    }
+}
+
+#[test]
+fn can_query_aggregates() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+            query {
+                all: songStatsAggregate {
+                    count
+                    sum { played }
+                    min { played }
+                    max { played }
+                }
+                popular: songStatsAggregate(where: { played_gt: 12 }) {
+                    count
+                    max { played }
+                }
+                none: songStatsAggregate(where: { played_gt: 100 }) {
+                    count
+                    sum { played }
+                }
+            }
+            ",
+        )
+        .expect("Invalid test query"),
+    );
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+
+    let played = |value: q::Value| object_value(vec![("played", value)]);
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "all",
+                object_value(vec![
+                    ("count", q::Value::String(String::from("2"))),
+                    ("sum", played(q::Value::String(String::from("25")))),
+                    ("min", played(q::Value::Int(q::Number::from(10)))),
+                    ("max", played(q::Value::Int(q::Number::from(15)))),
+                ])
+            ),
+            (
+                "popular",
+                object_value(vec![
+                    ("count", q::Value::String(String::from("1"))),
+                    ("max", played(q::Value::Int(q::Number::from(15)))),
+                ])
+            ),
+            (
+                "none",
+                object_value(vec![
+                    ("count", q::Value::String(String::from("0"))),
+                    ("sum", played(q::Value::Null)),
+                ])
+            ),
+        ]))
+    )
+}
@@ -0,0 +1,240 @@
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use graph::prelude::ethabi::{self, ParamType, Token};
+use graph::prelude::web3::types::{Address, Bytes, H256, U256};
+use graph::prelude::*;
+
+/// A test case for the mappings of a subgraph: the Ethereum data to feed
+/// through the mappings and the entities expected in the store afterwards.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fixture {
+    /// Files that `ipfs.cat` and `ipfs.map` return, by IPFS hash. Paths
+    /// are relative to the directory of the subgraph manifest.
+    #[serde(default)]
+    pub ipfs: HashMap<String, PathBuf>,
+
+    /// Results for the `ethereum.call`s the mappings make.
+    #[serde(default)]
+    pub calls: Vec<MockCall>,
+
+    /// The blocks to process, in order.
+    pub blocks: Vec<FixtureBlock>,
+
+    /// The entities expected in the store after all blocks were processed.
+    #[serde(default)]
+    pub expect: Vec<ExpectedEntity>,
+}
+
+/// The result of a contract call. The arguments and return values are
+/// converted to tokens with the ABI `contract` from the subgraph manifest.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MockCall {
+    pub contract: String,
+    pub address: Address,
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<serde_json::Value>,
+    /// The values the call returns; if absent, the call reverts.
+    pub returns: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureBlock {
+    pub number: u64,
+    pub hash: Option<H256>,
+    #[serde(default)]
+    pub timestamp: u64,
+    /// The transactions in the block. Triggers refer to them by index, and
+    /// missing transactions are filled in with default ones.
+    #[serde(default)]
+    pub transactions: Vec<FixtureTransaction>,
+    /// The triggers in the block, processed in the order they are listed.
+    #[serde(default)]
+    pub triggers: Vec<FixtureTrigger>,
+}
+
+/// A transaction; all fields are optional, addresses default to zero.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureTransaction {
+    pub hash: Option<H256>,
+    #[serde(default)]
+    pub from: Address,
+    #[serde(default)]
+    pub to: Address,
+    #[serde(default)]
+    pub value: U256,
+    #[serde(default)]
+    pub input: Bytes,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FixtureTrigger {
+    /// An event emitted by `address`, which defaults to the address of the
+    /// data source. `event` is either the name or the signature of an
+    /// event in the ABI of the data source or template `dataSource`.
+    #[serde(rename_all = "camelCase")]
+    Log {
+        data_source: String,
+        address: Option<Address>,
+        event: String,
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+        #[serde(default)]
+        transaction: usize,
+    },
+
+    /// A call of `function` of the contract of `dataSource` at `address`
+    /// made by the transaction with index `transaction`.
+    #[serde(rename_all = "camelCase")]
+    Call {
+        data_source: String,
+        address: Option<Address>,
+        function: String,
+        #[serde(default)]
+        inputs: Vec<serde_json::Value>,
+        #[serde(default)]
+        outputs: Vec<serde_json::Value>,
+        #[serde(default)]
+        transaction: usize,
+    },
+
+    /// The block itself, optionally only for block handlers with a
+    /// `call` filter for calls to `callTo`.
+    #[serde(rename_all = "camelCase")]
+    Block { call_to: Option<Address> },
+}
+
+/// An entity the store must contain after processing the fixture. Only the
+/// listed attributes are compared; with `removed` set, the entity must not
+/// exist instead.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedEntity {
+    pub entity: String,
+    pub id: String,
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub removed: bool,
+}
+
+impl Fixture {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Convert the JSON representation of a value of type `kind` to a token.
+/// Numbers may be given as JSON numbers or as decimal strings, bytes and
+/// addresses as hex strings and arrays and tuples as JSON arrays.
+pub fn tokenize(kind: &ParamType, value: &serde_json::Value) -> Result<Token, Error> {
+    use ethabi::token::{LenientTokenizer, Tokenizer};
+    use serde_json::Value as Json;
+
+    let list = |kind: &ParamType| -> Result<Vec<Token>, Error> {
+        match value {
+            Json::Array(values) => values.iter().map(|value| tokenize(kind, value)).collect(),
+            _ => Err(format_err!("expected an array for type {}", kind)),
+        }
+    };
+
+    match kind {
+        ParamType::Array(inner) => list(inner).map(Token::Array),
+        ParamType::FixedArray(inner, size) => {
+            let tokens = list(inner)?;
+            if tokens.len() != *size {
+                return Err(format_err!("expected {} values for type {}", size, kind));
+            }
+            Ok(Token::FixedArray(tokens))
+        }
+        ParamType::Tuple(components) => match value {
+            Json::Array(values) if values.len() == components.len() => components
+                .iter()
+                .zip(values)
+                .map(|(kind, value)| tokenize(kind, value))
+                .collect::<Result<_, _>>()
+                .map(Token::Tuple),
+            _ => Err(format_err!(
+                "expected an array of {} values for type {}",
+                components.len(),
+                kind
+            )),
+        },
+        _ => {
+            let text = match value {
+                Json::String(s) => s.clone(),
+                Json::Number(_) | Json::Bool(_) => value.to_string(),
+                _ => return Err(format_err!("invalid value {} for type {}", value, kind)),
+            };
+            let text = match kind {
+                ParamType::Address | ParamType::Bytes | ParamType::FixedBytes(_) => {
+                    text.trim_start_matches("0x").to_owned()
+                }
+                _ => text,
+            };
+            LenientTokenizer::tokenize(kind, &text)
+                .map_err(|e| format_err!("invalid value `{}` for type {}: {}", text, kind, e))
+        }
+    }
+}
+
+/// Convert a list of JSON values to tokens for the parameters `kinds`.
+pub fn tokenize_all<'a>(
+    kinds: impl ExactSizeIterator<Item = &'a ParamType>,
+    values: &[serde_json::Value],
+) -> Result<Vec<Token>, Error> {
+    if kinds.len() != values.len() {
+        return Err(format_err!(
+            "expected {} values but got {}",
+            kinds.len(),
+            values.len()
+        ));
+    }
+    kinds
+        .zip(values)
+        .map(|(kind, value)| tokenize(kind, value))
+        .collect()
+}
+
+/// Whether the entity attribute `actual` matches the JSON value `expected`.
+/// Strings are compared with the way the value is displayed, which allows
+/// writing `BigInt`, `BigDecimal` and `Bytes` values as strings.
+pub fn value_matches(actual: &Value, expected: &serde_json::Value) -> bool {
+    match (actual, expected) {
+        (Value::Null, serde_json::Value::Null) => true,
+        (Value::Bool(actual), serde_json::Value::Bool(expected)) => actual == expected,
+        (Value::List(actual), serde_json::Value::Array(expected)) => {
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(actual, expected)| value_matches(actual, expected))
+        }
+        (Value::BigDecimal(actual), serde_json::Value::String(_))
+        | (Value::BigDecimal(actual), serde_json::Value::Number(_)) => {
+            let expected = match expected {
+                serde_json::Value::String(s) => s.clone(),
+                _ => expected.to_string(),
+            };
+            expected
+                .parse::<BigDecimal>()
+                .map_or(false, |expected| *actual == expected)
+        }
+        (Value::Bytes(actual), serde_json::Value::String(expected)) => {
+            actual.to_string().eq_ignore_ascii_case(expected)
+        }
+        (actual, serde_json::Value::String(expected)) => actual.to_string() == *expected,
+        (Value::Int(_), serde_json::Value::Number(expected))
+        | (Value::BigInt(_), serde_json::Value::Number(expected)) => {
+            actual.to_string() == expected.to_string()
+        }
+        _ => false,
+    }
+}
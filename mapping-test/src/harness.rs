@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use graph::data::subgraph::UnresolvedSubgraphManifest;
+use graph::prelude::ethabi::{self, Contract, Event, Token};
+use graph::prelude::tokio::runtime::Runtime;
+use graph::prelude::web3::types::{Address, Bytes, Log, Transaction, TransactionReceipt, H256};
+use graph::prelude::{RuntimeHostBuilder as RuntimeHostBuilderTrait, *};
+use graph::util::ethereum::{contract_event_with_signature, string_to_h256};
+use graph_mock::{MockEthereumAdapter, MockMetricsRegistry, MockStore};
+use graph_runtime_wasm::RuntimeHostBuilder;
+
+use crate::fixture::*;
+use crate::link_resolver::FileLinkResolver;
+
+type Host = <RuntimeHostBuilder<MockStore> as RuntimeHostBuilderTrait>::Host;
+
+/// An entity in the store that does not meet the expectations of a fixture.
+#[derive(Debug, Fail)]
+pub enum ExpectationFailure {
+    #[fail(display = "{} `{}` does not exist", _0, _1)]
+    Missing(String, String),
+    #[fail(display = "{} `{}` exists, but should have been removed", _0, _1)]
+    NotRemoved(String, String),
+    #[fail(
+        display = "{} `{}` has `{}` = {}, but {} was expected",
+        entity, id, attribute, actual, expected
+    )]
+    Mismatch {
+        entity: String,
+        id: String,
+        attribute: String,
+        actual: String,
+        expected: String,
+    },
+}
+
+/// Runs the mappings of a subgraph on the Ethereum data from fixtures,
+/// with a `MockStore` and a `MockEthereumAdapter` instead of Postgres and
+/// an Ethereum node.
+pub struct MappingTest {
+    logger: Logger,
+    base_dir: PathBuf,
+    manifest: SubgraphManifest,
+    network: String,
+}
+
+impl MappingTest {
+    /// Load the subgraph whose manifest is at `manifest_path`, usually
+    /// `subgraph.yaml` in the output directory of `graph build`. Files are
+    /// resolved relative to the directory of the manifest.
+    pub fn load(logger: &Logger, manifest_path: &Path) -> Result<Self, Error> {
+        let base_dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let file = fs::read_to_string(manifest_path)
+            .map_err(|e| format_err!("failed to read `{}`: {}", manifest_path.display(), e))?;
+
+        let mut raw: serde_yaml::Value = serde_yaml::from_str(&file)?;
+        link_files(&mut raw);
+        {
+            let raw_mapping = raw
+                .as_mapping_mut()
+                .ok_or_else(|| format_err!("subgraph manifest is not a YAML mapping"))?;
+            raw_mapping.insert(
+                serde_yaml::Value::from("id"),
+                serde_yaml::Value::from("MappingTest"),
+            );
+            raw_mapping.insert(
+                serde_yaml::Value::from("location"),
+                serde_yaml::Value::from(manifest_path.display().to_string()),
+            );
+        }
+        let unresolved: UnresolvedSubgraphManifest = serde_yaml::from_value(raw)?;
+
+        let resolver = FileLinkResolver::new(base_dir.clone(), HashMap::new());
+        let manifest = unresolved.resolve(&resolver, logger.clone()).wait()?;
+        let network = manifest.network_name()?;
+
+        Ok(MappingTest {
+            logger: logger.clone(),
+            base_dir,
+            manifest,
+            network,
+        })
+    }
+
+    /// Process the blocks of `fixture` with a fresh store and return how
+    /// the resulting entities differ from the expected ones. Errors in the
+    /// fixture or in the mappings are returned as an `Err`.
+    pub fn run(&self, fixture: &Fixture) -> Result<Vec<ExpectationFailure>, Error> {
+        let mut runtime = Runtime::new()?;
+
+        let store = Arc::new(MockStore::new(vec![(
+            self.manifest.id.clone(),
+            self.manifest.schema.clone(),
+        )]));
+
+        let ethereum_adapter = Arc::new(MockEthereumAdapter::default());
+        for call in &fixture.calls {
+            self.mock_call(&ethereum_adapter, call)?;
+        }
+
+        let ipfs_files = fixture
+            .ipfs
+            .iter()
+            .map(|(hash, path)| (hash.clone(), self.base_dir.join(path)))
+            .collect();
+        let link_resolver = Arc::new(FileLinkResolver::new(self.base_dir.clone(), ipfs_files));
+
+        let mut ethereum_adapters = HashMap::new();
+        ethereum_adapters.insert(
+            self.network.clone(),
+            ethereum_adapter as Arc<dyn EthereumAdapter>,
+        );
+        let mut stores = HashMap::new();
+        stores.insert(self.network.clone(), store.clone());
+        let host_builder = RuntimeHostBuilder::new(ethereum_adapters, link_resolver, stores);
+
+        let host_metrics = Arc::new(HostMetrics::new(
+            Arc::new(MockMetricsRegistry::new()),
+            self.manifest.id.to_string(),
+        ));
+
+        let mut hosts = self
+            .manifest
+            .data_sources
+            .iter()
+            .cloned()
+            .map(|data_source| {
+                self.new_host(&mut runtime, &host_builder, data_source, &host_metrics)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for block in &fixture.blocks {
+            self.process_block(
+                &mut runtime,
+                &host_builder,
+                &host_metrics,
+                &mut hosts,
+                &store,
+                block,
+            )?;
+        }
+
+        self.check(&store, &fixture.expect)
+    }
+
+    fn new_host(
+        &self,
+        runtime: &mut Runtime,
+        host_builder: &RuntimeHostBuilder<MockStore>,
+        data_source: DataSource,
+        host_metrics: &Arc<HostMetrics>,
+    ) -> Result<Arc<Host>, Error> {
+        let logger = self.logger.clone();
+        let subgraph_id = self.manifest.id.clone();
+        let network = self.network.clone();
+        let templates = self.manifest.templates.clone();
+        let host_builder = host_builder.clone();
+        let host_metrics = host_metrics.clone();
+
+        // The mapping thread spawns tasks on the runtime, so it has to be
+        // started from within the runtime.
+        runtime.block_on(future::lazy(move || {
+            let mapping_request_sender = RuntimeHostBuilder::<MockStore>::spawn_mapping(
+                data_source.mapping.runtime.as_ref().clone(),
+                logger,
+                subgraph_id.clone(),
+                host_metrics.clone(),
+            )?;
+            host_builder
+                .build(
+                    network,
+                    subgraph_id,
+                    data_source,
+                    templates,
+                    mapping_request_sender,
+                    host_metrics,
+                )
+                .map(Arc::new)
+        }))
+    }
+
+    fn process_block(
+        &self,
+        runtime: &mut Runtime,
+        host_builder: &RuntimeHostBuilder<MockStore>,
+        host_metrics: &Arc<HostMetrics>,
+        hosts: &mut Vec<Arc<Host>>,
+        store: &MockStore,
+        fixture_block: &FixtureBlock,
+    ) -> Result<(), Error> {
+        debug!(self.logger, "Process block"; "number" => fixture_block.number);
+
+        // The parent is the block that was processed last
+        let parent_hash = store
+            .block_ptr(self.manifest.id.clone())?
+            .map(|ptr| ptr.hash);
+        let (block, triggers) = self.block_with_triggers(fixture_block, parent_hash)?;
+        let block = Arc::new(block);
+        let block_ptr = EthereumBlockPointer::from((block.hash.unwrap(), fixture_block.number));
+
+        let mut state =
+            self.process_triggers(runtime, hosts, &block, &triggers, BlockState::default())?;
+
+        // Like the instance manager, create data sources from templates
+        // and process the triggers of the current block in them, until no
+        // more data sources are created.
+        while !state.created_data_sources.is_empty() {
+            let new_hosts = state
+                .created_data_sources
+                .drain(..)
+                .map(|info| {
+                    let data_source =
+                        DataSource::try_from_template(info.template, &info.params, info.context)?;
+                    self.new_host(runtime, host_builder, data_source, host_metrics)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            state = self.process_triggers(runtime, &new_hosts, &block, &triggers, state)?;
+            hosts.extend(new_hosts);
+        }
+
+        let mods = state.entity_cache.as_modifications(store)?;
+        store.transact_block_operations(self.manifest.id.clone(), block_ptr, mods)?;
+        Ok(())
+    }
+
+    /// Process each trigger in the hosts that match it, in the order of
+    /// the hosts.
+    fn process_triggers(
+        &self,
+        runtime: &mut Runtime,
+        hosts: &[Arc<Host>],
+        block: &Arc<LightEthereumBlock>,
+        triggers: &[EthereumTrigger],
+        mut state: BlockState,
+    ) -> Result<BlockState, Error> {
+        let logger = &self.logger;
+
+        for trigger in triggers {
+            for host in hosts {
+                let result = match trigger {
+                    EthereumTrigger::Log(log, receipt) => {
+                        if !host.matches_log(log) {
+                            continue;
+                        }
+                        let transaction = block
+                            .transaction_for_log(log)
+                            .ok_or_else(|| format_err!("Found no transaction for event"))?;
+                        host.process_log(
+                            logger.clone(),
+                            block.clone(),
+                            Arc::new(transaction),
+                            Arc::new(log.clone()),
+                            receipt.clone(),
+                            state,
+                        )
+                    }
+                    EthereumTrigger::Call(call) => {
+                        if !host.matches_call(call) {
+                            continue;
+                        }
+                        let transaction = block
+                            .transaction_for_call(call)
+                            .ok_or_else(|| format_err!("Found no transaction for call"))?;
+                        host.process_call(
+                            logger.clone(),
+                            block.clone(),
+                            Arc::new(transaction),
+                            Arc::new(call.clone()),
+                            state,
+                        )
+                    }
+                    EthereumTrigger::Block(ptr, trigger_type) => {
+                        if !host.matches_block(trigger_type.clone(), ptr.number) {
+                            continue;
+                        }
+                        host.process_block(
+                            logger.clone(),
+                            block.clone(),
+                            trigger_type.clone(),
+                            state,
+                        )
+                    }
+                };
+                state = runtime.block_on(result)?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Build the block for `fixture_block` and the triggers in it. The
+    /// parent of the block is the previous block of the fixture, whose hash
+    /// is `parent_hash`; the first block of the fixture has the default
+    /// hash for the block before it as its parent.
+    fn block_with_triggers(
+        &self,
+        fixture_block: &FixtureBlock,
+        parent_hash: Option<H256>,
+    ) -> Result<(LightEthereumBlock, Vec<EthereumTrigger>), Error> {
+        let number = fixture_block.number;
+        let hash = fixture_block
+            .hash
+            .unwrap_or_else(|| default_block_hash(number));
+
+        let mut block = LightEthereumBlock::default();
+        block.number = Some(number.into());
+        block.hash = Some(hash);
+        match parent_hash {
+            Some(parent_hash) => block.parent_hash = parent_hash,
+            None if number > 0 => block.parent_hash = default_block_hash(number - 1),
+            None => (),
+        }
+        block.timestamp = fixture_block.timestamp.into();
+
+        // Fill in the transactions that triggers refer to but that the
+        // fixture does not list
+        let transaction_count = fixture_block
+            .triggers
+            .iter()
+            .filter_map(|trigger| match trigger {
+                FixtureTrigger::Log { transaction, .. }
+                | FixtureTrigger::Call { transaction, .. } => Some(transaction + 1),
+                FixtureTrigger::Block { .. } => None,
+            })
+            .chain(Some(fixture_block.transactions.len()))
+            .max()
+            .unwrap_or(0);
+        block.transactions = (0..transaction_count)
+            .map(|index| {
+                transaction(
+                    number,
+                    hash,
+                    index,
+                    fixture_block
+                        .transactions
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_default(),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        let block_ptr = EthereumBlockPointer::from((hash, number));
+        let mut log_index = 0;
+        let triggers = fixture_block
+            .triggers
+            .iter()
+            .map(|trigger| match trigger {
+                FixtureTrigger::Log {
+                    data_source,
+                    address,
+                    event,
+                    params,
+                    transaction,
+                } => {
+                    let (contract, source_address) = self.contract(data_source)?;
+                    let event = find_event(contract, event)?;
+                    let params = tokenize_all(event.inputs.iter().map(|p| &p.kind), params)
+                        .map_err(|e| format_err!("event `{}`: {}", event.name, e))?;
+                    let address = address
+                        .or(source_address)
+                        .ok_or_else(|| format_err!("event `{}` needs an address", event.name))?;
+                    let log = log(
+                        &block.transactions[*transaction],
+                        log_index,
+                        address,
+                        event,
+                        params,
+                    )?;
+                    log_index += 1;
+                    Ok(EthereumTrigger::Log(log, None))
+                }
+                FixtureTrigger::Call {
+                    data_source,
+                    address,
+                    function,
+                    inputs,
+                    outputs,
+                    transaction,
+                } => {
+                    let (contract, source_address) = self.contract(data_source)?;
+                    let function = contract
+                        .function(function)
+                        .map_err(|e| format_err!("function `{}`: {}", function, e))?;
+                    let inputs = tokenize_all(function.inputs.iter().map(|p| &p.kind), inputs)
+                        .map_err(|e| format_err!("inputs of `{}`: {}", function.name, e))?;
+                    let outputs =
+                        tokenize_all(function.outputs.iter().map(|p| &p.kind), outputs)
+                            .map_err(|e| format_err!("outputs of `{}`: {}", function.name, e))?;
+
+                    let mut call = EthereumCall::from_transaction(
+                        &block.transactions[*transaction],
+                        Bytes(ethabi::encode(&outputs)),
+                    )
+                    .expect("fixture transactions are in a block and have a recipient");
+                    call.to = address.or(source_address).ok_or_else(|| {
+                        format_err!("call to `{}` needs an address", function.name)
+                    })?;
+                    call.input = Bytes(
+                        function
+                            .encode_input(&inputs)
+                            .map_err(|e| format_err!("inputs of `{}`: {}", function.name, e))?,
+                    );
+                    Ok(EthereumTrigger::Call(call))
+                }
+                FixtureTrigger::Block { call_to } => Ok(EthereumTrigger::Block(
+                    block_ptr,
+                    call_to.map_or(EthereumBlockTriggerType::Every, |address| {
+                        EthereumBlockTriggerType::WithCallTo(address)
+                    }),
+                )),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // Every log comes with the receipt of its transaction, which is
+        // only passed to handlers that ask for it
+        let receipts = block
+            .transactions
+            .iter()
+            .map(|transaction| {
+                let logs = triggers
+                    .iter()
+                    .filter_map(|trigger| match trigger {
+                        EthereumTrigger::Log(log, _)
+                            if log.transaction_hash == Some(transaction.hash) =>
+                        {
+                            Some(log.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                receipt(transaction, logs).map(|receipt| (transaction.hash, Arc::new(receipt)))
+            })
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+        let triggers = triggers
+            .into_iter()
+            .map(|trigger| match trigger {
+                EthereumTrigger::Log(log, _) => {
+                    let receipt = log
+                        .transaction_hash
+                        .and_then(|hash| receipts.get(&hash).cloned());
+                    EthereumTrigger::Log(log, receipt)
+                }
+                trigger => trigger,
+            })
+            .collect();
+
+        Ok((block, triggers))
+    }
+
+    /// The ABI of the contract of the data source or template `name` and the
+    /// address of the data source.
+    fn contract(&self, name: &str) -> Result<(&Contract, Option<Address>), Error> {
+        let source = self
+            .manifest
+            .data_sources
+            .iter()
+            .find(|data_source| data_source.name == name)
+            .map(|data_source| {
+                (
+                    &data_source.mapping,
+                    &data_source.source.abi,
+                    data_source.source.address,
+                )
+            })
+            .or_else(|| {
+                self.templates()
+                    .find(|template| template.name == name)
+                    .map(|template| (&template.mapping, &template.source.abi, None))
+            });
+
+        match source {
+            Some((mapping, abi, address)) => mapping
+                .abis
+                .iter()
+                .find(|mapping_abi| mapping_abi.name == *abi)
+                .map(|mapping_abi| (&mapping_abi.contract, address))
+                .ok_or_else(|| format_err!("no ABI `{}` in data source `{}`", abi, name)),
+            None => Err(format_err!("no data source or template `{}`", name)),
+        }
+    }
+
+    /// The ABI `name` from any data source or template.
+    fn abi(&self, name: &str) -> Result<&Contract, Error> {
+        self.manifest
+            .data_sources
+            .iter()
+            .map(|data_source| &data_source.mapping)
+            .chain(self.templates().map(|template| &template.mapping))
+            .flat_map(|mapping| mapping.abis.iter())
+            .find(|abi| abi.name == name)
+            .map(|abi| &abi.contract)
+            .ok_or_else(|| format_err!("no ABI `{}` in the subgraph manifest", name))
+    }
+
+    fn templates(&self) -> impl Iterator<Item = &DataSourceTemplate> {
+        self.manifest.templates.iter().chain(
+            self.manifest
+                .data_sources
+                .iter()
+                .flat_map(|data_source| data_source.templates.iter()),
+        )
+    }
+
+    fn mock_call(&self, adapter: &MockEthereumAdapter, call: &MockCall) -> Result<(), Error> {
+        let function = self
+            .abi(&call.contract)?
+            .function(&call.function)
+            .map_err(|e| format_err!("function `{}`: {}", call.function, e))?;
+        let args = tokenize_all(function.inputs.iter().map(|p| &p.kind), &call.args)
+            .map_err(|e| format_err!("arguments of `{}`: {}", call.function, e))?;
+        let result = call
+            .returns
+            .as_ref()
+            .map(|returns| tokenize_all(function.outputs.iter().map(|p| &p.kind), returns))
+            .transpose()
+            .map_err(|e| format_err!("return values of `{}`: {}", call.function, e))?;
+
+        adapter.mock_contract_call(call.address, &call.function, args, result);
+        Ok(())
+    }
+
+    fn check(
+        &self,
+        store: &MockStore,
+        expected_entities: &[ExpectedEntity],
+    ) -> Result<Vec<ExpectationFailure>, Error> {
+        let mut failures = vec![];
+
+        for expected in expected_entities {
+            let key = EntityKey {
+                subgraph_id: self.manifest.id.clone(),
+                entity_type: expected.entity.clone(),
+                entity_id: expected.id.clone(),
+            };
+
+            match (store.get(key)?, expected.removed) {
+                (None, true) => {}
+                (Some(_), true) => failures.push(ExpectationFailure::NotRemoved(
+                    expected.entity.clone(),
+                    expected.id.clone(),
+                )),
+                (None, false) => failures.push(ExpectationFailure::Missing(
+                    expected.entity.clone(),
+                    expected.id.clone(),
+                )),
+                (Some(entity), false) => {
+                    let mut attributes = expected.attributes.iter().collect::<Vec<_>>();
+                    attributes.sort_by_key(|(attribute, _)| *attribute);
+
+                    for (attribute, value) in attributes {
+                        let actual = entity.get(attribute).cloned().unwrap_or(Value::Null);
+                        if !value_matches(&actual, value) {
+                            failures.push(ExpectationFailure::Mismatch {
+                                entity: expected.entity.clone(),
+                                id: expected.id.clone(),
+                                attribute: attribute.clone(),
+                                actual: actual.to_string(),
+                                expected: value.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(failures)
+    }
+}
+
+/// Turn the `file: path` entries that `graph build` writes into the
+/// `file: { /: path }` links that the manifest is parsed with.
+fn link_files(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                if key.as_str() == Some("file") {
+                    if let Some(path) = value.as_str().map(str::to_owned) {
+                        let mut link = serde_yaml::Mapping::new();
+                        link.insert(serde_yaml::Value::from("/"), serde_yaml::Value::from(path));
+                        *value = serde_yaml::Value::Mapping(link);
+                        continue;
+                    }
+                }
+                link_files(value);
+            }
+        }
+        serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(link_files),
+        _ => {}
+    }
+}
+
+fn default_block_hash(number: u64) -> H256 {
+    string_to_h256(&format!("block{}", number))
+}
+
+/// Find an event by its name or by its signature.
+fn find_event<'a>(contract: &'a Contract, event: &str) -> Result<&'a Event, Error> {
+    if event.contains('(') {
+        contract_event_with_signature(contract, event)
+    } else {
+        contract.events().find(|candidate| candidate.name == event)
+    }
+    .ok_or_else(|| format_err!("no event `{}` in the ABI", event))
+}
+
+fn transaction(
+    block_number: u64,
+    block_hash: H256,
+    index: usize,
+    transaction: FixtureTransaction,
+) -> Result<Transaction, Error> {
+    let hash = transaction
+        .hash
+        .unwrap_or_else(|| string_to_h256(&format!("block{}transaction{}", block_number, index)));
+
+    // Going through JSON avoids depending on every field of `Transaction`
+    Ok(serde_json::from_value(serde_json::json!({
+        "hash": hash,
+        "nonce": "0x0",
+        "blockHash": block_hash,
+        "blockNumber": format!("{:#x}", block_number),
+        "transactionIndex": format!("{:#x}", index),
+        "from": transaction.from,
+        "to": transaction.to,
+        "value": transaction.value,
+        "gasPrice": "0x0",
+        "gas": "0x0",
+        "input": transaction.input,
+    }))?)
+}
+
+/// The log `event` with `params` emitted by `address` in `transaction`.
+fn log(
+    transaction: &Transaction,
+    log_index: u64,
+    address: Address,
+    event: &Event,
+    params: Vec<Token>,
+) -> Result<Log, Error> {
+    let mut topics = vec![];
+    if !event.anonymous {
+        topics.push(string_to_h256(&format!(
+            "{}({})",
+            event.name,
+            event
+                .inputs
+                .iter()
+                .map(|input| input.kind.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        )));
+    }
+
+    let mut data = vec![];
+    for (input, param) in event.inputs.iter().zip(params) {
+        if input.indexed {
+            topics.push(topic(param));
+        } else {
+            data.push(param);
+        }
+    }
+
+    Ok(serde_json::from_value(serde_json::json!({
+        "address": address,
+        "topics": topics,
+        "data": Bytes(ethabi::encode(&data)),
+        "blockHash": transaction.block_hash,
+        "blockNumber": transaction.block_number,
+        "transactionHash": transaction.hash,
+        "transactionIndex": transaction.transaction_index,
+        "logIndex": format!("{:#x}", log_index),
+        "transactionLogIndex": format!("{:#x}", log_index),
+        "logType": null,
+        "removed": false,
+    }))?)
+}
+
+/// A successful receipt for `transaction` with the given `logs`. Fixture
+/// transactions use no gas.
+fn receipt(transaction: &Transaction, logs: Vec<Log>) -> Result<TransactionReceipt, Error> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "transactionHash": transaction.hash,
+        "transactionIndex": transaction.transaction_index,
+        "blockHash": transaction.block_hash,
+        "blockNumber": transaction.block_number,
+        "cumulativeGasUsed": "0x0",
+        "gasUsed": "0x0",
+        "contractAddress": null,
+        "logs": logs,
+        "status": "0x1",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+    }))?)
+}
+
+/// The topic for an indexed event parameter. Values that do not fit into
+/// a topic are hashed, like Solidity does.
+fn topic(param: Token) -> H256 {
+    let bytes = match param {
+        Token::String(s) => tiny_keccak::keccak256(s.as_bytes()),
+        Token::Bytes(bytes) => tiny_keccak::keccak256(&bytes),
+        Token::Array(_) | Token::FixedArray(_) | Token::Tuple(_) => {
+            tiny_keccak::keccak256(&ethabi::encode(&[param]))
+        }
+        _ => {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&ethabi::encode(&[param]));
+            bytes
+        }
+    };
+    H256::from(bytes)
+}
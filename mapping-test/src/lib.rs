@@ -0,0 +1,19 @@
+//! Runs the mappings of a subgraph against fixtures: blocks with logs, calls
+//! and block triggers are fed through the runtime hosts of the subgraph with a
+//! `MockStore` and a `MockEthereumAdapter`, and the entities in the store are
+//! compared with the ones the fixture expects.
+
+/// The JSON format of fixtures.
+mod fixture;
+
+/// Runs the mappings against a fixture.
+mod harness;
+
+/// Resolves links to files on disk.
+mod link_resolver;
+
+pub use crate::fixture::{
+    ExpectedEntity, Fixture, FixtureBlock, FixtureTransaction, FixtureTrigger, MockCall,
+};
+pub use crate::harness::{ExpectationFailure, MappingTest};
+pub use crate::link_resolver::FileLinkResolver;
@@ -0,0 +1,77 @@
+use clap::{App, Arg};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use graph::log::logger;
+use graph::prelude::*;
+use graph_mapping_test::{Fixture, MappingTest};
+
+fn main() {
+    let matches = App::new("graph-test")
+        .version("0.1.0")
+        .author("Graph Protocol, Inc.")
+        .about("Runs subgraph mappings against fixtures with a mock store and chain")
+        .arg(
+            Arg::with_name("manifest")
+                .required(true)
+                .value_name("MANIFEST")
+                .help("Subgraph manifest, usually build/subgraph.yaml"),
+        )
+        .arg(
+            Arg::with_name("fixtures")
+                .required(true)
+                .multiple(true)
+                .value_name("FIXTURE")
+                .help("JSON fixtures with the blocks to process and the expected entities"),
+        )
+        .arg(
+            Arg::with_name("debug")
+                .long("debug")
+                .help("Enable debug logging"),
+        )
+        .get_matches();
+
+    let logger = logger(matches.is_present("debug"));
+
+    let manifest = matches.value_of("manifest").unwrap();
+    let test = MappingTest::load(&logger, Path::new(manifest)).unwrap_or_else(|e| {
+        eprintln!("failed to load subgraph `{}`: {}", manifest, e);
+        process::exit(1);
+    });
+
+    let mut failed = 0;
+    let fixtures = matches.values_of("fixtures").unwrap().collect::<Vec<_>>();
+    for path in &fixtures {
+        let result = fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|json| Fixture::from_json(&json))
+            .and_then(|fixture| test.run(&fixture));
+
+        match result {
+            Ok(ref failures) if failures.is_empty() => println!("{} ... ok", path),
+            Ok(failures) => {
+                failed += 1;
+                println!("{} ... FAILED", path);
+                for failure in failures {
+                    println!("    {}", failure);
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{} ... ERROR", path);
+                println!("    {}", e);
+            }
+        }
+    }
+
+    println!(
+        "\n{} fixture(s): {} passed, {} failed",
+        fixtures.len(),
+        fixtures.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use graph::prelude::*;
+use graph_mapping_test::{ExpectationFailure, Fixture, MappingTest};
+
+fn load_test_subgraph() -> MappingTest {
+    let logger = Logger::root(slog::Discard, o!());
+    MappingTest::load(&logger, Path::new("tests/subgraph/subgraph.yaml"))
+        .expect("failed to load test subgraph")
+}
+
+#[test]
+fn reports_missing_entities() {
+    let test = load_test_subgraph();
+    let fixture = Fixture::from_json(
+        r#"{
+            "blocks": [{ "number": 1 }, { "number": 2, "triggers": [] }],
+            "expect": [
+                { "entity": "ExampleEntity", "id": "gone", "removed": true },
+                { "entity": "ExampleEntity", "id": "missing" }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let failures = test.run(&fixture).expect("failed to run fixture");
+    match failures.as_slice() {
+        [ExpectationFailure::Missing(entity, id)] => {
+            assert_eq!("ExampleEntity", entity);
+            assert_eq!("missing", id);
+        }
+        _ => panic!("unexpected failures: {:?}", failures),
+    }
+}
+
+#[test]
+fn runs_handlers_for_matching_logs() {
+    let test = load_test_subgraph();
+
+    // A log that no data source handles is skipped
+    let fixture = Fixture::from_json(
+        r#"{
+            "blocks": [{
+                "number": 1,
+                "triggers": [{
+                    "kind": "log",
+                    "dataSource": "ExampleDataSource",
+                    "address": "0x0000000000000000000000000000000000000001",
+                    "event": "ExampleEvent",
+                    "params": ["hello"]
+                }]
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert!(test.run(&fixture).unwrap().is_empty());
+
+    // The matching log runs `handleExampleEvent`, which the empty module
+    // of the test subgraph does not export
+    let fixture = Fixture::from_json(
+        r#"{
+            "blocks": [{
+                "number": 1,
+                "triggers": [{
+                    "kind": "log",
+                    "dataSource": "ExampleDataSource",
+                    "event": "ExampleEvent(indexed string)",
+                    "params": ["hello"]
+                }]
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert!(test.run(&fixture).is_err());
+}
+
+#[test]
+fn rejects_invalid_fixtures() {
+    let test = load_test_subgraph();
+
+    for json in &[
+        // Unknown data source
+        r#"{ "blocks": [{ "number": 1, "triggers": [
+            { "kind": "log", "dataSource": "Unknown", "event": "ExampleEvent" }
+        ] }] }"#,
+        // Wrong number of event parameters
+        r#"{ "blocks": [{ "number": 1, "triggers": [
+            { "kind": "log", "dataSource": "ExampleDataSource", "event": "ExampleEvent" }
+        ] }] }"#,
+        // Unknown ABI for a mocked call
+        r#"{ "calls": [
+            { "contract": "Unknown", "address": "0x0000000000000000000000000000000000000001",
+              "function": "name", "returns": ["x"] }
+        ], "blocks": [] }"#,
+    ] {
+        let fixture = Fixture::from_json(json).unwrap();
+        assert!(test.run(&fixture).is_err(), "fixture should fail: {}", json);
+    }
+}
@@ -57,6 +57,7 @@ impl BlockStreamBuilder for MockBlockStreamBuilder {
         _: EthereumCallFilter,
         _: EthereumBlockFilter,
         _: bool,
+        _: Option<u64>,
         _: Arc<BlockStreamMetrics>,
     ) -> Self::Stream {
         MockBlockStream::new()
@@ -1,84 +1,324 @@
 use graph::components::ethereum::*;
 use graph::prelude::{
-    ethabi, future,
-    web3::types::{Log, H256},
+    ethabi, format_err, future, stream, web3,
+    web3::types::{Address, Log, TransactionReceipt, H256},
     Arc, ChainStore, Error, EthereumCallCache, Future, Logger, Stream,
 };
 use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// A mocked result of calling a contract function with specific arguments.
+struct MockContractCall {
+    address: Address,
+    function: String,
+    args: Vec<ethabi::Token>,
+    result: Option<Vec<ethabi::Token>>,
+}
+
+/// How a `MockEthereumAdapter` fails the requests it gets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MockFailure {
+    /// Requests fail with an error of the JSON-RPC transport
+    Error,
+    /// Requests fail with an error that every provider would return, like
+    /// one of the chain store; contract calls succeed
+    StoreError,
+    /// Requests never finish
+    Hang,
+    /// Streams of blocks fail after returning this many blocks; other
+    /// requests succeed
+    AfterBlocks(usize),
+}
+
+/// How the subscriptions to new chain heads of a `MockEthereumAdapter` behave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MockNewHeads {
+    /// Subscribing fails
+    Fail,
+    /// The subscription sends the chain head and then ends
+    End,
+    /// The subscription never sends a chain head
+    Silent,
+}
+
+impl Default for MockNewHeads {
+    fn default() -> Self {
+        MockNewHeads::Fail
+    }
+}
+
+/// The error of a mocked request to `method` that failed in the JSON-RPC
+/// transport.
+fn transport_error(method: &str) -> Error {
+    web3::error::Error::Transport(format!("mocked failure of {}", method)).into()
+}
 
 #[derive(Default)]
-pub struct MockEthereumAdapter {}
+pub struct MockEthereumAdapter {
+    contract_calls: Mutex<Vec<MockContractCall>>,
+    blocks: Mutex<Vec<LightEthereumBlock>>,
+    failure: Mutex<Option<MockFailure>>,
+    new_heads: Mutex<MockNewHeads>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockEthereumAdapter {
+    /// Add `block` to the chain of the adapter. The block with the highest
+    /// number is the chain head. If several blocks have the same number, only
+    /// the first one is found by its number; the others, like blocks that
+    /// were reorged out of the chain, are only found by their hash.
+    pub fn add_block(&self, block: LightEthereumBlock) {
+        self.blocks.lock().unwrap().push(block);
+    }
+
+    /// Make all following requests fail as described by `failure`, or
+    /// succeed again if it is `None`.
+    pub fn fail_with(&self, failure: Option<MockFailure>) {
+        *self.failure.lock().unwrap() = failure;
+    }
+
+    /// Make all following subscriptions to new chain heads behave as
+    /// described by `new_heads`.
+    pub fn new_heads(&self, new_heads: MockNewHeads) {
+        *self.new_heads.lock().unwrap() = new_heads;
+    }
+
+    /// The names of the methods that were called, in the order in which
+    /// they were called. Only methods that are mocked are recorded.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Record a request to `method` and check whether it should fail.
+    fn request(&self, method: &str) -> Option<MockFailure> {
+        self.requests.lock().unwrap().push(method.to_owned());
+        *self.failure.lock().unwrap()
+    }
+
+    /// Respond to a request to `method` with `result`, unless the adapter is
+    /// set up to fail.
+    fn respond<T, E>(
+        &self,
+        method: &str,
+        result: Result<T, E>,
+    ) -> Box<dyn Future<Item = T, Error = E> + Send>
+    where
+        T: Send + 'static,
+        E: From<Error> + Send + 'static,
+    {
+        match self.request(method) {
+            Some(MockFailure::Error) => Box::new(future::err(E::from(transport_error(method)))),
+            Some(MockFailure::StoreError) => Box::new(future::err(E::from(format_err!(
+                "mocked store failure of {}",
+                method
+            )))),
+            Some(MockFailure::Hang) => Box::new(future::empty()),
+            Some(MockFailure::AfterBlocks(_)) | None => Box::new(future::result(result)),
+        }
+    }
+
+    /// Stream `blocks`, unless the adapter is set up to fail.
+    fn stream_blocks(
+        &self,
+        method: &str,
+        blocks: Vec<LightEthereumBlock>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
+        let failure = transport_error(method);
+        match self.request(method) {
+            Some(MockFailure::Error) => Box::new(stream::once(Err(failure))),
+            Some(MockFailure::StoreError) => Box::new(stream::once(Err(format_err!(
+                "mocked store failure of {}",
+                method
+            )))),
+            Some(MockFailure::Hang) => Box::new(future::empty().into_stream()),
+            Some(MockFailure::AfterBlocks(count)) => Box::new(
+                stream::iter_ok(blocks.into_iter().take(count)).chain(stream::once(Err(failure))),
+            ),
+            None => Box::new(stream::iter_ok(blocks)),
+        }
+    }
+
+    fn block_with_hash(&self, hash: H256) -> Option<LightEthereumBlock> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|block| block.hash == Some(hash))
+            .cloned()
+    }
+
+    fn block_with_number(&self, number: u64) -> Option<LightEthereumBlock> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|block| block.number.map(|n| n.as_u64()) == Some(number))
+            .cloned()
+    }
+
+    /// Make `contract_call` return `result` when `function` of the contract
+    /// at `address` is called with `args`. If `result` is `None`, the call
+    /// reverts.
+    pub fn mock_contract_call(
+        &self,
+        address: Address,
+        function: &str,
+        args: Vec<ethabi::Token>,
+        result: Option<Vec<ethabi::Token>>,
+    ) {
+        self.contract_calls.lock().unwrap().push(MockContractCall {
+            address,
+            function: function.to_owned(),
+            args,
+            result,
+        });
+    }
+}
 
 impl EthereumAdapter for MockEthereumAdapter {
     fn net_identifiers(
         &self,
         _: &Logger,
     ) -> Box<dyn Future<Item = EthereumNetworkIdentifier, Error = Error> + Send> {
-        unimplemented!();
+        let genesis_block_hash = self
+            .block_with_number(0)
+            .and_then(|block| block.hash)
+            .unwrap_or_default();
+        self.respond(
+            "net_identifiers",
+            Ok(EthereumNetworkIdentifier {
+                net_version: "1".to_owned(),
+                genesis_block_hash,
+            }),
+        )
     }
 
     fn latest_block(
         &self,
         _: &Logger,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = EthereumAdapterError> + Send> {
-        unimplemented!();
+        let head = self
+            .blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .max_by_key(|block| block.number)
+            .cloned()
+            .ok_or_else(|| {
+                EthereumAdapterError::from(format_err!("the mocked chain has no blocks"))
+            });
+        self.respond("latest_block", head)
+    }
+
+    fn subscribe_new_heads(
+        &self,
+        _: &Logger,
+    ) -> Box<
+        dyn Future<
+                Item = Box<dyn Stream<Item = EthereumBlockPointer, Error = Error> + Send>,
+                Error = Error,
+            > + Send,
+    > {
+        let head = self
+            .blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .max_by_key(|block| block.number)
+            .map(EthereumBlockPointer::from);
+        let new_heads: Result<Box<dyn Stream<Item = _, Error = _> + Send>, _> =
+            match *self.new_heads.lock().unwrap() {
+                MockNewHeads::Fail => Err(transport_error("subscribe_new_heads")),
+                MockNewHeads::End => Ok(Box::new(stream::iter_ok(head))),
+                MockNewHeads::Silent => Ok(Box::new(future::empty().into_stream())),
+            };
+        self.respond("subscribe_new_heads", new_heads)
     }
 
     fn load_block(
         &self,
         _: &Logger,
-        _: H256,
+        block_hash: H256,
     ) -> Box<dyn Future<Item = LightEthereumBlock, Error = Error> + Send> {
-        unimplemented!()
+        let block = self
+            .block_with_hash(block_hash)
+            .ok_or_else(|| format_err!("no mocked block with hash {:x}", block_hash));
+        self.respond("load_block", block)
     }
 
     fn block_by_hash(
         &self,
         _: &Logger,
-        _: H256,
+        block_hash: H256,
     ) -> Box<dyn Future<Item = Option<LightEthereumBlock>, Error = Error> + Send> {
-        unimplemented!();
+        self.respond("block_by_hash", Ok(self.block_with_hash(block_hash)))
     }
 
     fn load_full_block(
         &self,
         _: &Logger,
-        _: LightEthereumBlock,
+        _: Arc<dyn ChainStore>,
+        block: LightEthereumBlock,
     ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
+        // The mocked blocks contain no transactions, so there are no receipts
+        self.respond(
+            "load_full_block",
+            Ok(EthereumBlock {
+                block,
+                transaction_receipts: vec![],
+            }),
+        )
+    }
+
+    fn load_transaction_receipts(
+        &self,
+        _: &Logger,
+        _: Arc<dyn ChainStore>,
+        _: H256,
+        _: Vec<H256>,
+    ) -> Box<dyn Future<Item = Vec<TransactionReceipt>, Error = EthereumAdapterError> + Send> {
         unimplemented!();
     }
 
     fn block_pointer_from_number(
         &self,
         _: &Logger,
-        _: u64,
+        block_number: u64,
     ) -> Box<dyn Future<Item = EthereumBlockPointer, Error = EthereumAdapterError> + Send> {
-        Box::new(future::ok(EthereumBlockPointer::from((
-            H256::zero(),
-            0 as i64,
-        ))))
+        let ptr = self
+            .block_with_number(block_number)
+            .map(|block| EthereumBlockPointer::from(&block))
+            .unwrap_or_else(|| EthereumBlockPointer::from((H256::zero(), 0 as i64)));
+        self.respond("block_pointer_from_number", Ok(ptr))
     }
 
     fn block_hash_by_block_number(
         &self,
         _: &Logger,
-        _: u64,
+        block_number: u64,
     ) -> Box<dyn Future<Item = Option<H256>, Error = Error> + Send> {
-        unimplemented!();
+        let hash = self
+            .block_with_number(block_number)
+            .and_then(|block| block.hash);
+        self.respond("block_hash_by_block_number", Ok(hash))
     }
 
     fn is_on_main_chain(
         &self,
         _: &Logger,
         _: Arc<SubgraphEthRpcMetrics>,
-        _: EthereumBlockPointer,
+        block_ptr: EthereumBlockPointer,
     ) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
-        unimplemented!();
+        let on_main_chain = self
+            .block_with_number(block_ptr.number)
+            .map_or(false, |block| block.hash == Some(block_ptr.hash));
+        self.respond("is_on_main_chain", Ok(on_main_chain))
     }
 
     fn calls_in_block(
         &self,
         _: &Logger,
+        _: Arc<dyn ChainStore>,
         _: Arc<SubgraphEthRpcMetrics>,
         _: u64,
         _: H256,
@@ -89,6 +329,7 @@ impl EthereumAdapter for MockEthereumAdapter {
     fn logs_in_block_range(
         &self,
         _: &Logger,
+        _: Arc<dyn ChainStore>,
         _: Arc<SubgraphEthRpcMetrics>,
         _: u64,
         _: u64,
@@ -100,21 +341,55 @@ impl EthereumAdapter for MockEthereumAdapter {
     fn calls_in_block_range(
         &self,
         _: &Logger,
+        _: Arc<dyn ChainStore>,
         _: Arc<SubgraphEthRpcMetrics>,
         _: u64,
         _: u64,
         _: EthereumCallFilter,
     ) -> Box<dyn Stream<Item = EthereumCall, Error = Error> + Send> {
-        unimplemented!();
+        Box::new(
+            self.respond("calls_in_block_range", Ok(vec![]))
+                .map(stream::iter_ok)
+                .flatten_stream(),
+        )
     }
 
     fn contract_call(
         &self,
         _: &Logger,
-        _: EthereumContractCall,
+        call: EthereumContractCall,
         _: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<ethabi::Token>, Error = EthereumContractCallError> + Send> {
-        unimplemented!();
+        match self.request("contract_call") {
+            Some(MockFailure::Error) => {
+                return Box::new(future::err(EthereumContractCallError::Timeout))
+            }
+            Some(MockFailure::Hang) => return Box::new(future::empty()),
+            Some(MockFailure::StoreError) | Some(MockFailure::AfterBlocks(_)) | None => (),
+        }
+
+        let contract_calls = self.contract_calls.lock().unwrap();
+        let mocked = contract_calls.iter().find(|mocked| {
+            mocked.address == call.address
+                && mocked.function == call.function.name
+                && mocked.args == call.args
+        });
+
+        Box::new(future::result(match mocked {
+            Some(MockContractCall {
+                result: Some(tokens),
+                ..
+            }) => Ok(tokens.clone()),
+            Some(MockContractCall { result: None, .. }) => Err(EthereumContractCallError::Revert(
+                "mocked revert".to_owned(),
+            )),
+            None => Err(EthereumContractCallError::from(ethabi::Error::from(
+                format!(
+                    "no mocked result for call to `{}` of contract {:x} with arguments {:?}",
+                    call.function.name, call.address, call.args
+                ),
+            ))),
+        }))
     }
 
     fn triggers_in_block(
@@ -125,9 +400,13 @@ impl EthereumAdapter for MockEthereumAdapter {
         _: EthereumLogFilter,
         _: EthereumCallFilter,
         _: EthereumBlockFilter,
-        _: BlockFinality,
+        ethereum_block: BlockFinality,
     ) -> Box<dyn Future<Item = EthereumBlockWithTriggers, Error = Error> + Send> {
-        unimplemented!();
+        // The mocked blocks contain no transactions and therefore no triggers
+        self.respond(
+            "triggers_in_block",
+            Ok(EthereumBlockWithTriggers::new(vec![], ethereum_block)),
+        )
     }
 
     /// Load Ethereum blocks in bulk, returning results as they come back as a Stream.
@@ -135,9 +414,33 @@ impl EthereumAdapter for MockEthereumAdapter {
         &self,
         _: Logger,
         _: Arc<dyn ChainStore>,
-        _: HashSet<H256>,
+        block_hashes: HashSet<H256>,
     ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
-        unimplemented!()
+        let blocks = self
+            .blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|block| {
+                block
+                    .hash
+                    .map_or(false, |hash| block_hashes.contains(&hash))
+            })
+            .cloned()
+            .collect();
+        self.stream_blocks("load_blocks", blocks)
+    }
+
+    fn blocks_by_numbers(
+        &self,
+        _: &Logger,
+        block_numbers: Vec<u64>,
+    ) -> Box<dyn Stream<Item = LightEthereumBlock, Error = Error> + Send> {
+        let blocks = block_numbers
+            .into_iter()
+            .filter_map(|number| self.block_with_number(number))
+            .collect();
+        self.stream_blocks("blocks_by_numbers", blocks)
     }
 
     fn block_range_to_ptrs(
@@ -12,6 +12,6 @@ mod metrics_registry;
 mod store;
 
 pub use self::block_stream::{MockBlockStream, MockBlockStreamBuilder};
-pub use self::ethereum_adapter::MockEthereumAdapter;
+pub use self::ethereum_adapter::{MockEthereumAdapter, MockFailure, MockNewHeads};
 pub use self::metrics_registry::MockMetricsRegistry;
 pub use self::store::{FakeStore, MockStore};
@@ -9,7 +9,7 @@ use graph::components::store::*;
 use graph::data::subgraph::schema::*;
 use graph::prelude::*;
 use graph_graphql::prelude::api_schema;
-use web3::types::H256;
+use web3::types::{Log, TransactionReceipt, H256};
 
 #[derive(Debug)]
 pub struct MockStore {
@@ -19,6 +19,14 @@ pub struct MockStore {
     entities: Mutex<HashMap<SubgraphDeploymentId, HashMap<String, HashMap<String, Entity>>>>,
 
     subscriptions: Mutex<Vec<(HashSet<SubgraphEntityPair>, mpsc::Sender<StoreEvent>)>>,
+
+    // Block pointers of subgraph deployments
+    block_ptrs: Mutex<HashMap<SubgraphDeploymentId, EthereumBlockPointer>>,
+
+    // Blocks in the chain store by hash, and the chain head
+    blocks: Arc<Mutex<HashMap<H256, LightEthereumBlock>>>,
+    chain_head: Mutex<Option<EthereumBlockPointer>>,
+    chain_head_subscriptions: Mutex<Vec<mpsc::UnboundedSender<()>>>,
 }
 
 fn entity_matches_filter(entity: &Entity, filter: &EntityFilter) -> bool {
@@ -39,6 +47,13 @@ fn entity_matches_filter(entity: &Entity, filter: &EntityFilter) -> bool {
                 .iter()
                 .any(|allowed_attr_value| attr_value == allowed_attr_value)
         }
+        EntityFilter::NotIn(attr_name, excluded_attr_values) => {
+            let attr_value = entity.get(attr_name).unwrap_or(&Value::Null);
+
+            excluded_attr_values
+                .iter()
+                .all(|excluded_attr_value| attr_value != excluded_attr_value)
+        }
         _ => unimplemented!(),
     }
 }
@@ -50,6 +65,10 @@ impl MockStore {
             schemas: schemas.into_iter().collect(),
             entities: Default::default(),
             subscriptions: Default::default(),
+            block_ptrs: Default::default(),
+            blocks: Default::default(),
+            chain_head: Default::default(),
+            chain_head_subscriptions: Default::default(),
         }
     }
 
@@ -105,6 +124,7 @@ impl MockStore {
             order_by,
             order_direction,
             range: _,
+            block: _,
         } = query;
 
         // List all entities with correct type
@@ -179,17 +199,57 @@ impl Store for MockStore {
         }
     }
 
-    fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<Option<EthereumBlockPointer>, Error> {
+    fn block_number(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
         unimplemented!();
     }
 
+    fn block_ptr(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+    ) -> Result<Option<EthereumBlockPointer>, Error> {
+        Ok(self.block_ptrs.lock().unwrap().get(&subgraph_id).cloned())
+    }
+
     fn transact_block_operations(
         &self,
-        _: SubgraphDeploymentId,
-        _: EthereumBlockPointer,
-        _: Vec<EntityModification>,
+        subgraph_id: SubgraphDeploymentId,
+        block_ptr_to: EthereumBlockPointer,
+        mods: Vec<EntityModification>,
     ) -> Result<bool, StoreError> {
-        unimplemented!();
+        self.block_ptrs
+            .lock()
+            .unwrap()
+            .insert(subgraph_id, block_ptr_to);
+
+        let mut entities = self.entities.lock().unwrap();
+
+        for modification in mods {
+            match modification {
+                EntityModification::Insert { key, data }
+                | EntityModification::Overwrite { key, data } => {
+                    entities
+                        .entry(key.subgraph_id)
+                        .or_default()
+                        .entry(key.entity_type)
+                        .or_default()
+                        .insert(key.entity_id, data);
+                }
+                EntityModification::Remove { key } => {
+                    if let Some(of_type) = entities
+                        .get_mut(&key.subgraph_id)
+                        .and_then(|in_subgraph| in_subgraph.get_mut(&key.entity_type))
+                    {
+                        of_type.remove(&key.entity_id);
+                    }
+                }
+            }
+        }
+
+        Ok(false)
     }
 
     fn apply_metadata_operations(&self, ops: Vec<MetadataOperation>) -> Result<(), StoreError> {
@@ -324,6 +384,20 @@ impl Store for MockStore {
     }
 
     fn revert_block_operations(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        _: EthereumBlockPointer,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError> {
+        // Entity changes are not reverted, only the block pointer
+        self.block_ptrs
+            .lock()
+            .unwrap()
+            .insert(subgraph_id, block_ptr_to);
+        Ok(())
+    }
+
+    fn rewind_block_operations(
         &self,
         _: SubgraphDeploymentId,
         _: EthereumBlockPointer,
@@ -347,6 +421,7 @@ impl Store for MockStore {
         &self,
         _schema: &Schema,
         ops: Vec<MetadataOperation>,
+        _graft: Option<&Graft>,
     ) -> Result<(), StoreError> {
         self.apply_metadata_operations(ops)
     }
@@ -397,40 +472,147 @@ impl ChainStore for MockStore {
         })
     }
 
-    fn upsert_blocks<'a, B, E>(&self, _: B) -> Box<dyn Future<Item = (), Error = E> + Send + 'a>
+    fn upsert_blocks<'a, B, E>(
+        &self,
+        blocks: B,
+    ) -> Box<dyn Future<Item = (), Error = E> + Send + 'a>
     where
         B: Stream<Item = EthereumBlock, Error = E> + Send + 'a,
         E: From<Error> + Send + 'a,
     {
+        // Only the blocks are kept, not their transaction receipts
+        let stored = self.blocks.clone();
+        Box::new(blocks.for_each(move |block| {
+            let hash = block
+                .block
+                .hash
+                .ok_or_else(|| E::from(format_err!("block to upsert has no hash")))?;
+            stored.lock().unwrap().insert(hash, block.block);
+            Ok(())
+        }))
+    }
+
+    fn upsert_light_blocks(&self, blocks: Vec<LightEthereumBlock>) -> Result<(), Error> {
+        let mut stored = self.blocks.lock().unwrap();
+        for block in blocks {
+            let hash = block
+                .hash
+                .ok_or_else(|| format_err!("block to upsert has no hash"))?;
+            stored.insert(hash, block);
+        }
+        Ok(())
+    }
+
+    fn attempt_chain_head_update(&self, ancestor_count: u64) -> Result<Vec<H256>, Error> {
+        let head = {
+            let blocks = self.blocks.lock().unwrap();
+            let head = match blocks.values().max_by_key(|block| block.number) {
+                Some(head) => head,
+                None => return Ok(vec![]),
+            };
+
+            // Like the real store, only move the head once all of its
+            // `ancestor_count` ancestors are present
+            let mut block = head;
+            for _ in 0..ancestor_count {
+                if block.number() == 0 {
+                    break;
+                }
+                match blocks.get(&block.parent_hash) {
+                    Some(parent) => block = parent,
+                    None => return Ok(vec![block.parent_hash]),
+                }
+            }
+            EthereumBlockPointer::from(head)
+        };
+
+        *self.chain_head.lock().unwrap() = Some(head);
+        self.chain_head_subscriptions
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.unbounded_send(()).is_ok());
+        Ok(vec![])
+    }
+
+    fn chain_head_updates(&self) -> ChainHeadUpdateStream {
+        let (sender, receiver) = mpsc::unbounded();
+        self.chain_head_subscriptions.lock().unwrap().push(sender);
+        Box::new(receiver)
+    }
+
+    fn chain_head_ptr(&self) -> Result<Option<EthereumBlockPointer>, Error> {
+        Ok(*self.chain_head.lock().unwrap())
+    }
+
+    fn upsert_full_blocks(&self, _: Vec<EthereumBlock>) -> Result<(), Error> {
         unimplemented!();
     }
 
-    fn upsert_light_blocks(&self, _: Vec<LightEthereumBlock>) -> Result<(), Error> {
+    fn blocks(&self, hashes: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error> {
+        let blocks = self.blocks.lock().unwrap();
+        Ok(hashes
+            .iter()
+            .filter_map(|hash| blocks.get(hash).cloned())
+            .collect())
+    }
+
+    fn full_blocks(&self, _: Vec<H256>) -> Result<Vec<EthereumBlock>, Error> {
         unimplemented!();
     }
 
-    fn attempt_chain_head_update(&self, _: u64) -> Result<Vec<H256>, Error> {
+    fn block_calls(&self, _: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
         unimplemented!();
     }
 
-    fn chain_head_updates(&self) -> ChainHeadUpdateStream {
+    fn set_block_calls(&self, _: EthereumBlockPointer, _: &[EthereumCall]) -> Result<(), Error> {
         unimplemented!();
     }
 
-    fn chain_head_ptr(&self) -> Result<Option<EthereumBlockPointer>, Error> {
-        Ok(None)
+    fn transaction_receipts(
+        &self,
+        _: H256,
+        _: Vec<H256>,
+    ) -> Result<Vec<TransactionReceipt>, Error> {
+        unimplemented!();
     }
 
-    fn blocks(&self, _: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error> {
+    fn upsert_transaction_receipts(&self, _: &[TransactionReceipt]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn range_logs(&self, _: &str, _: u64, _: u64) -> Result<Option<Vec<Log>>, Error> {
+        unimplemented!();
+    }
+
+    fn set_range_logs(&self, _: &str, _: u64, _: u64, _: &[Log]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn range_calls(&self, _: &str, _: u64, _: u64) -> Result<Option<Vec<EthereumCall>>, Error> {
+        unimplemented!();
+    }
+
+    fn set_range_calls(&self, _: &str, _: u64, _: u64, _: &[EthereumCall]) -> Result<(), Error> {
         unimplemented!();
     }
 
     fn ancestor_block(
         &self,
-        _: EthereumBlockPointer,
-        _: u64,
+        block_ptr: EthereumBlockPointer,
+        offset: u64,
     ) -> Result<Option<EthereumBlock>, Error> {
-        unimplemented!();
+        let blocks = self.blocks.lock().unwrap();
+        let mut hash = block_ptr.hash;
+        for _ in 0..offset {
+            match blocks.get(&hash) {
+                Some(block) => hash = block.parent_hash,
+                None => return Ok(None),
+            }
+        }
+        Ok(blocks.get(&hash).map(|block| EthereumBlock {
+            block: block.clone(),
+            transaction_receipts: vec![],
+        }))
     }
 }
 
@@ -478,6 +660,14 @@ impl Store for FakeStore {
         }
     }
 
+    fn block_number(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        unimplemented!();
+    }
+
     fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<Option<EthereumBlockPointer>, Error> {
         unimplemented!();
     }
@@ -512,6 +702,15 @@ impl Store for FakeStore {
         unimplemented!();
     }
 
+    fn rewind_block_operations(
+        &self,
+        _: SubgraphDeploymentId,
+        _: EthereumBlockPointer,
+        _: EthereumBlockPointer,
+    ) -> Result<(), StoreError> {
+        unimplemented!();
+    }
+
     fn subscribe(&self, _: Vec<SubgraphEntityPair>) -> StoreEventStreamBox {
         unimplemented!();
     }
@@ -520,6 +719,7 @@ impl Store for FakeStore {
         &self,
         _schema: &Schema,
         _ops: Vec<MetadataOperation>,
+        _graft: Option<&Graft>,
     ) -> Result<(), StoreError> {
         unimplemented!()
     }
@@ -571,10 +771,54 @@ impl ChainStore for FakeStore {
         unimplemented!();
     }
 
+    fn upsert_full_blocks(&self, _: Vec<EthereumBlock>) -> Result<(), Error> {
+        unimplemented!();
+    }
+
     fn blocks(&self, _: Vec<H256>) -> Result<Vec<LightEthereumBlock>, Error> {
         unimplemented!();
     }
 
+    fn full_blocks(&self, _: Vec<H256>) -> Result<Vec<EthereumBlock>, Error> {
+        unimplemented!();
+    }
+
+    fn block_calls(&self, _: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
+        unimplemented!();
+    }
+
+    fn set_block_calls(&self, _: EthereumBlockPointer, _: &[EthereumCall]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn transaction_receipts(
+        &self,
+        _: H256,
+        _: Vec<H256>,
+    ) -> Result<Vec<TransactionReceipt>, Error> {
+        unimplemented!();
+    }
+
+    fn upsert_transaction_receipts(&self, _: &[TransactionReceipt]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn range_logs(&self, _: &str, _: u64, _: u64) -> Result<Option<Vec<Log>>, Error> {
+        unimplemented!();
+    }
+
+    fn set_range_logs(&self, _: &str, _: u64, _: u64, _: &[Log]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    fn range_calls(&self, _: &str, _: u64, _: u64) -> Result<Option<Vec<EthereumCall>>, Error> {
+        unimplemented!();
+    }
+
+    fn set_range_calls(&self, _: &str, _: u64, _: u64, _: &[EthereumCall]) -> Result<(), Error> {
+        unimplemented!();
+    }
+
     fn ancestor_block(
         &self,
         _: EthereumBlockPointer,
@@ -4,7 +4,8 @@ use git_testament::{git_testament, render_testament};
 use ipfs_api::IpfsClient;
 use lazy_static::lazy_static;
 use prometheus::Registry;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::str::FromStr;
 use std::time::Duration;
@@ -19,7 +20,9 @@ use graph_core::{
     LinkResolver, MetricsRegistry, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
     SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
 };
-use graph_datasource_ethereum::{BlockStreamBuilder, Transport};
+use graph_datasource_ethereum::{
+    BlockStreamBuilder, EthereumAdapterPool, EthereumProvider, Transport,
+};
 use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
 use graph_server_http::GraphQLServer as GraphQLQueryServer;
 use graph_server_index_node::IndexNodeServer;
@@ -46,6 +49,32 @@ lazy_static! {
              .unwrap_or_else(|_| panic!("failed to parse env var ETHEREUM_ANCESTOR_COUNT")))
         .unwrap_or(50);
 
+    // How many confirmations blocks need per network before subgraphs process them,
+    // e.g. `mainnet:12,ropsten:3`; networks that are not listed need none
+    static ref CONFIRMATIONS: HashMap<String, u64> = env::var("ETHEREUM_CONFIRMATIONS")
+        .ok()
+        .map(|s| s
+            .split(',')
+            .map(|network| {
+                let mut parts = network.splitn(2, ':');
+                let name = parts.next().unwrap().trim().to_owned();
+                let confirmations = parts
+                    .next()
+                    .and_then(|n| u64::from_str(n.trim()).ok())
+                    .unwrap_or_else(|| panic!("failed to parse env var ETHEREUM_CONFIRMATIONS"));
+                (name, confirmations)
+            })
+            .collect())
+        .unwrap_or_default();
+
+    // How many times an Ethereum node is asked for something before the request is sent
+    // to another node of the same network; networks with just one node keep retrying it
+    static ref FAILOVER_RETRIES: usize = env::var("GRAPH_ETHEREUM_FAILOVER_RETRIES")
+        .ok()
+        .map(|s| usize::from_str(&s)
+             .unwrap_or_else(|_| panic!("failed to parse env var GRAPH_ETHEREUM_FAILOVER_RETRIES")))
+        .unwrap_or(3);
+
     static ref TOKIO_THREAD_COUNT: usize = env::var("GRAPH_TOKIO_THREAD_COUNT")
         .ok()
         .map(|s| usize::from_str(&s)
@@ -143,10 +172,11 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .required_unless_one(&["ethereum-ws", "ethereum-ipc"])
                 .conflicts_with_all(&["ethereum-ws", "ethereum-ipc"])
                 .long("ethereum-rpc")
-                .value_name("NETWORK_NAME:URL")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:URL")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum RPC URL, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional comma-separated \
+                     capabilities of the node (e.g. 'archive,traces') and Ethereum RPC URL, \
+                     separated by a ':'",
                 ),
         )
         .arg(
@@ -157,10 +187,11 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .required_unless_one(&["ethereum-rpc", "ethereum-ipc"])
                 .conflicts_with_all(&["ethereum-rpc", "ethereum-ipc"])
                 .long("ethereum-ws")
-                .value_name("NETWORK_NAME:URL")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:URL")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum WebSocket URL, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional comma-separated \
+                     capabilities of the node (e.g. 'archive,traces') and Ethereum WebSocket \
+                     URL, separated by a ':'",
                 ),
         )
         .arg(
@@ -171,10 +202,11 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .required_unless_one(&["ethereum-rpc", "ethereum-ws"])
                 .conflicts_with_all(&["ethereum-rpc", "ethereum-ws"])
                 .long("ethereum-ipc")
-                .value_name("NETWORK_NAME:FILE")
+                .value_name("NETWORK_NAME:[CAPABILITIES]:FILE")
                 .help(
-                    "Ethereum network name (e.g. 'mainnet') and \
-                     Ethereum IPC pipe, separated by a ':'",
+                    "Ethereum network name (e.g. 'mainnet'), optional comma-separated \
+                     capabilities of the node (e.g. 'archive,traces') and Ethereum IPC pipe, \
+                     separated by a ':'",
                 ),
         )
         .arg(
@@ -261,7 +293,10 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("MILLISECONDS")
                 .default_value("500")
                 .env("ETHEREUM_POLLING_INTERVAL")
-                .help("How often to poll the Ethereum node for new blocks"),
+                .help(
+                    "How often to poll the Ethereum node for new blocks, \
+                     unless subscribed to new blocks over WebSocket or IPC",
+                ),
         )
         .arg(
             Arg::with_name("disable-block-ingestor")
@@ -450,7 +485,9 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     let mut metrics_server =
         PrometheusMetricsServer::new(&logger_factory, prometheus_registry.clone());
 
-    // Ethereum clients
+    // Ethereum clients; JSON-RPC over HTTP can't subscribe to new chain heads,
+    // so blocks are polled for on networks that are connected to that way
+    let mut polling_networks = HashSet::new();
     let eth_adapters = [
         (ConnectionType::RPC, ethereum_rpc),
         (ConnectionType::IPC, ethereum_ipc),
@@ -460,13 +497,26 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     .cloned()
     .filter(|(_, values)| values.is_some())
     .fold(HashMap::new(), |adapters, (connection_type, values)| {
+        let can_subscribe = match connection_type {
+            ConnectionType::RPC => false,
+            ConnectionType::IPC | ConnectionType::WS => true,
+        };
         match parse_ethereum_networks_and_nodes(
             logger.clone(),
             values.unwrap(),
             connection_type,
             metrics_registry.clone(),
         ) {
-            Ok(adapter) => adapters.into_iter().chain(adapter).collect(),
+            Ok(adapter) => {
+                for network_name in adapter.keys() {
+                    if can_subscribe {
+                        polling_networks.remove(network_name);
+                    } else {
+                        polling_networks.insert(network_name.clone());
+                    }
+                }
+                adapters.into_iter().chain(adapter).collect()
+            }
             Err(e) => {
                 panic!(
                     "Failed to parse Ethereum networks and create Ethereum adapters: {}",
@@ -555,18 +605,25 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
 
         // Create Ethereum block ingestors and spawn a thread to run each
         eth_adapters.iter().for_each(|(network_name, eth_adapter)| {
+            // Keep enough ancestors to find the final block of subgraphs that wait
+            // for more confirmations than the reorg threshold
+            let ancestor_count = cmp::max(
+                *ANCESTOR_COUNT,
+                CONFIRMATIONS.get(network_name).cloned().unwrap_or(0),
+            );
             let block_ingestor = graph_datasource_ethereum::BlockIngestor::new(
                 stores.get(network_name).expect("network with name").clone(),
                 eth_adapter.clone(),
-                *ANCESTOR_COUNT,
+                ancestor_count,
                 network_name.to_string(),
                 &logger_factory,
                 block_polling_interval,
+                !polling_networks.contains(network_name),
             )
             .expect("failed to create Ethereum block ingestor");
 
             // Run the Ethereum block ingestor in the background
-            tokio::spawn(block_ingestor.into_polling_stream());
+            tokio::spawn(block_ingestor.into_stream());
         });
     }
 
@@ -576,6 +633,8 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         eth_adapters.clone(),
         node_id.clone(),
         *REORG_THRESHOLD,
+        *ANCESTOR_COUNT,
+        CONFIRMATIONS.clone(),
         metrics_registry.clone(),
     );
     let runtime_host_builder =
@@ -728,71 +787,126 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     future::empty()
 }
 
-/// Parses an Ethereum connection string and returns the network name and Ethereum adapter.
+/// Parses Ethereum connection strings and returns an Ethereum adapter for each network, which
+/// spreads requests across all nodes given for that network.
 fn parse_ethereum_networks_and_nodes(
     logger: Logger,
     networks: clap::Values,
     connection_type: ConnectionType,
     registry: Arc<MetricsRegistry>,
 ) -> Result<HashMap<String, Arc<dyn EthereumAdapterTrait>>, Error> {
-    let eth_rpc_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
-    networks
-        .map(|network| {
-            if network.starts_with("wss://")
-                || network.starts_with("http://")
-                || network.starts_with("https://")
-            {
-                return Err(format_err!(
-                    "Is your Ethereum node string missing a network name? \
-                     Try 'mainnet:' + the Ethereum node URL."
-                ));
-            } else {
-                // Parse string (format is "NETWORK_NAME:URL")
-                let split_at = network.find(':').ok_or_else(|| {
-                    return format_err!(
-                        "A network name must be provided alongside the \
-                         Ethereum node location. Try e.g. 'mainnet:URL'."
-                    );
+    let mut nodes: HashMap<String, Vec<(String, NodeCapabilities, Transport)>> = HashMap::new();
+    for network in networks {
+        if network.starts_with("wss://")
+            || network.starts_with("http://")
+            || network.starts_with("https://")
+        {
+            return Err(format_err!(
+                "Is your Ethereum node string missing a network name? \
+                 Try 'mainnet:' + the Ethereum node URL."
+            ));
+        }
+
+        // Parse string (format is "NETWORK_NAME:[CAPABILITIES:]URL")
+        let split_at = network.find(':').ok_or_else(|| {
+            return format_err!(
+                "A network name must be provided alongside the \
+                 Ethereum node location. Try e.g. 'mainnet:URL'."
+            );
+        })?;
+
+        let (name, loc_with_delim) = network.split_at(split_at);
+        let loc = &loc_with_delim[1..];
+
+        if name.is_empty() {
+            return Err(format_err!(
+                "Ethereum network name cannot be an empty string"
+            ));
+        }
+
+        // Unless the location starts with a URL scheme, anything before a ':' are the
+        // capabilities of the node. Nodes without explicit capabilities are assumed to
+        // support none of them, so that they are never sent requests they can't serve
+        let (capabilities, loc) = match loc.find(':') {
+            Some(i) if !["http", "https", "ws", "wss"].contains(&&loc[..i]) => {
+                let capabilities = loc[..i].parse::<NodeCapabilities>().map_err(|e| {
+                    format_err!(
+                        "Invalid capabilities for Ethereum node `{}`: {}",
+                        network,
+                        e
+                    )
                 })?;
+                (capabilities, &loc[i + 1..])
+            }
+            _ => (
+                NodeCapabilities {
+                    archive: false,
+                    traces: false,
+                },
+                loc,
+            ),
+        };
 
-                let (name, loc_with_delim) = network.split_at(split_at);
-                let loc = &loc_with_delim[1..];
+        if loc.is_empty() {
+            return Err(format_err!("Ethereum node URL cannot be an empty string"));
+        }
 
-                if name.is_empty() {
-                    return Err(format_err!(
-                        "Ethereum network name cannot be an empty string"
-                    ));
-                }
+        let network_nodes = nodes.entry(name.to_string()).or_default();
+        let provider_name = format!("{}-{}", name, network_nodes.len());
 
-                if loc.is_empty() {
-                    return Err(format_err!("Ethereum node URL cannot be an empty string"));
-                }
+        info!(
+            logger,
+            "Creating transport";
+            "network" => &name,
+            "provider" => &provider_name,
+            "capabilities" => capabilities.to_string(),
+            "url" => &loc,
+        );
 
-                info!(
-                    logger,
-                    "Creating transport";
-                    "network" => &name,
-                    "url" => &loc,
-                );
+        let (transport_event_loop, transport) = match connection_type {
+            ConnectionType::RPC => Transport::new_rpc(loc),
+            ConnectionType::IPC => Transport::new_ipc(loc),
+            ConnectionType::WS => Transport::new_ws(loc),
+        };
 
-                let (transport_event_loop, transport) = match connection_type {
-                    ConnectionType::RPC => Transport::new_rpc(loc),
-                    ConnectionType::IPC => Transport::new_ipc(loc),
-                    ConnectionType::WS => Transport::new_ws(loc),
-                };
-
-                // If we drop the event loop the transport will stop working.
-                // For now it's fine to just leak it.
-                std::mem::forget(transport_event_loop);
-
-                Ok((
-                    name.to_string(),
-                    Arc::new(graph_datasource_ethereum::EthereumAdapter::new(
-                        transport,
-                        eth_rpc_metrics.clone(),
-                    )) as Arc<dyn EthereumAdapter>,
-                ))
-            }
+        // If we drop the event loop the transport will stop working.
+        // For now it's fine to just leak it.
+        std::mem::forget(transport_event_loop);
+
+        network_nodes.push((provider_name, capabilities, transport));
+    }
+
+    nodes
+        .into_iter()
+        .map(|(name, nodes)| {
+            // With other nodes to fall back to, the pool rather than the adapter keeps
+            // retrying failed requests
+            let retry_limit = if nodes.len() > 1 {
+                Some(*FAILOVER_RETRIES)
+            } else {
+                None
+            };
+            let providers = nodes
+                .into_iter()
+                .map(|(provider_name, capabilities, transport)| {
+                    let metrics =
+                        Arc::new(ProviderEthRpcMetrics::new(registry.clone(), &provider_name));
+                    let adapter =
+                        graph_datasource_ethereum::EthereumAdapter::new(transport, metrics.clone());
+                    let adapter = match retry_limit {
+                        Some(limit) => adapter.with_retry_limit(limit),
+                        None => adapter,
+                    };
+                    EthereumProvider {
+                        name: provider_name,
+                        capabilities,
+                        adapter: Arc::new(adapter),
+                        metrics,
+                    }
+                })
+                .collect();
+            let pool = EthereumAdapterPool::new(name.clone(), providers)?;
+            Ok((name, Arc::new(pool) as Arc<dyn EthereumAdapterTrait>))
         })
         .collect()
 }
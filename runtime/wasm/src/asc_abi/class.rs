@@ -456,6 +456,27 @@ where
     pub block: AscPtr<AscEthereumBlock>,
     pub transaction: AscPtr<T>,
     pub params: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscEthereumTransactionReceipt>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumLog {
+    pub address: AscPtr<AscAddress>,
+    pub topics: AscPtr<Array<AscPtr<AscH256>>>,
+    pub data: AscPtr<Bytes>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumTransactionReceipt {
+    pub status: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub cumulative_gas_used: AscPtr<AscBigInt>,
+    pub logs: AscPtr<Array<AscPtr<AscEthereumLog>>>,
 }
 
 #[repr(C)]
@@ -17,9 +17,15 @@ use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 use graph::util;
-use web3::types::{Log, Transaction};
+use web3::types::{Log, Transaction, TransactionReceipt};
 
 pub(crate) const TIMEOUT_ENV_VAR: &str = "GRAPH_MAPPING_HANDLER_TIMEOUT";
+pub(crate) const GAS_LIMIT_ENV_VAR: &str = "GRAPH_MAPPING_HANDLER_GAS_LIMIT";
+
+/// The gas limit for mapping handlers if `GRAPH_MAPPING_HANDLER_GAS_LIMIT` is
+/// not set. Since running out of gas fails a subgraph for good, this is high
+/// enough that only handlers stuck in a loop reach it.
+pub(crate) const DEFAULT_GAS_LIMIT: u64 = 10_000_000_000;
 
 struct RuntimeHostConfig {
     subgraph_id: SubgraphDeploymentId,
@@ -28,6 +34,7 @@ struct RuntimeHostConfig {
     data_source_name: String,
     contract: Source,
     templates: Vec<DataSourceTemplate>,
+    context: Option<DataSourceContext>,
 }
 
 pub struct RuntimeHostBuilder<S> {
@@ -124,6 +131,7 @@ where
                 data_source_name: data_source.name,
                 contract: data_source.source,
                 templates,
+                context: data_source.context,
             },
             mapping_request_sender,
             metrics,
@@ -187,6 +195,7 @@ impl RuntimeHost {
             data_source_name.clone(),
             config.contract.address.clone(),
             Some(config.data_source_network),
+            config.context,
             config.templates,
             config.mapping.abis,
             ethereum_adapter,
@@ -197,6 +206,10 @@ impl RuntimeHost {
                 .ok()
                 .and_then(|s| u64::from_str(&s).ok())
                 .map(Duration::from_secs),
+            std::env::var(GAS_LIMIT_ENV_VAR)
+                .ok()
+                .and_then(|s| u64::from_str(&s).ok())
+                .or(Some(DEFAULT_GAS_LIMIT)),
         ));
 
         Ok(RuntimeHost {
@@ -267,7 +280,9 @@ impl RuntimeHost {
         // Get signature from the log
         let topic0 = match log.topics.iter().next() {
             Some(topic0) => topic0,
-            None => return Err(format_err!("Ethereum event has no topics")),
+            None => {
+                return Err(DeterministicError("Ethereum event has no topics".to_owned()).into())
+            }
         };
 
         let handlers = self
@@ -280,10 +295,10 @@ impl RuntimeHost {
         if !handlers.is_empty() {
             Ok(handlers)
         } else {
-            Err(format_err!(
+            Err(Error::from(DeterministicError(format!(
                 "No event handler found for event in data source \"{}\"",
                 self.data_source_name,
-            ))
+            ))))
         }
     }
 
@@ -291,9 +306,10 @@ impl RuntimeHost {
         // First four bytes of the input for the call are the first four
         // bytes of hash of the function signature
         if call.input.0.len() < 4 {
-            return Err(format_err!(
-                "Ethereum call has input with less than 4 bytes"
-            ));
+            return Err(DeterministicError(
+                "Ethereum call has input with less than 4 bytes".to_owned(),
+            )
+            .into());
         }
 
         let target_method_id = &call.input.0[..4];
@@ -307,10 +323,10 @@ impl RuntimeHost {
             })
             .cloned()
             .ok_or_else(|| {
-                format_err!(
+                Error::from(DeterministicError(format!(
                     "No call handler found for call in data source \"{}\"",
                     self.data_source_name,
-                )
+                )))
             })
     }
 
@@ -325,11 +341,11 @@ impl RuntimeHost {
                 .find(move |handler| handler.filter == None)
                 .cloned()
                 .ok_or_else(|| {
-                    format_err!(
+                    Error::from(DeterministicError(format!(
                         "No block handler for `Every` block trigger \
                          type found in data source \"{}\"",
                         self.data_source_name,
-                    )
+                    )))
                 }),
             EthereumBlockTriggerType::WithCallTo(_address) => self
                 .data_source_block_handlers
@@ -340,11 +356,11 @@ impl RuntimeHost {
                 })
                 .cloned()
                 .ok_or_else(|| {
-                    format_err!(
+                    Error::from(DeterministicError(format!(
                         "No block handler for `WithCallTo` block trigger \
                          type found in data source \"{}\"",
                         self.data_source_name,
-                    )
+                    )))
                 }),
         }
     }
@@ -393,13 +409,13 @@ impl RuntimeHostTrait for RuntimeHost {
         ) {
             Some(function_abi) => function_abi,
             None => {
-                return Box::new(future::err(format_err!(
+                return Box::new(future::err(Error::from(DeterministicError(format!(
                     "Function with the signature \"{}\" not found in \
                      contract \"{}\" of data source \"{}\"",
                     call_handler.function,
                     self.data_source_contract_abi.name,
                     self.data_source_name
-                )));
+                )))));
             }
         };
 
@@ -411,17 +427,19 @@ impl RuntimeHostTrait for RuntimeHost {
         let inputs = match function_abi
             .decode_input(&call.input.0[4..])
             .map_err(|err| {
-                format_err!(
+                Error::from(DeterministicError(format!(
                     "Generating function inputs for an Ethereum call failed = {}",
                     err,
-                )
+                )))
             })
             .and_then(|tokens| {
                 if tokens.len() != function_abi.inputs.len() {
-                    return Err(format_err!(
+                    return Err(DeterministicError(
                         "Number of arguments in call does not match \
                          number of inputs in function signature."
-                    ));
+                            .to_owned(),
+                    )
+                    .into());
                 }
                 let inputs = tokens
                     .into_iter()
@@ -445,17 +463,19 @@ impl RuntimeHostTrait for RuntimeHost {
         let outputs = match function_abi
             .decode_output(&call.output.0)
             .map_err(|err| {
-                format_err!(
+                Error::from(DeterministicError(format!(
                     "Generating function outputs for an Ethereum call failed = {}",
                     err,
-                )
+                )))
             })
             .and_then(|tokens| {
                 if tokens.len() != function_abi.outputs.len() {
-                    return Err(format_err!(
+                    return Err(DeterministicError(
                         "Number of paramters in the call output does not match \
                          number of outputs in the function signature."
-                    ));
+                            .to_owned(),
+                    )
+                    .into());
                 }
                 let outputs = tokens
                     .into_iter()
@@ -596,6 +616,7 @@ impl RuntimeHostTrait for RuntimeHost {
         block: Arc<LightEthereumBlock>,
         transaction: Arc<Transaction>,
         log: Arc<Log>,
+        receipt: Option<Arc<TransactionReceipt>>,
         state: BlockState,
     ) -> Box<dyn Future<Item = BlockState, Error = Error> + Send> {
         let logger = logger.clone();
@@ -629,13 +650,11 @@ impl RuntimeHostTrait for RuntimeHost {
                     ) {
                         Some(event_abi) => event_abi,
                         None => {
-                            return Err(format_err!(
+                            return Err(Error::from(DeterministicError(format!(
                                 "Event with the signature \"{}\" not found in \
                                  contract \"{}\" of data source \"{}\"",
-                                event_handler.event,
-                                abi_name,
-                                data_source_name,
-                            ))
+                                event_handler.event, abi_name, data_source_name,
+                            ))))
                         }
                     };
 
@@ -703,12 +722,27 @@ impl RuntimeHostTrait for RuntimeHost {
 
         // Fail if there is more than one matching handler
         if !matching_handlers.is_empty() {
-            return Box::new(future::err(format_err!(
+            return Box::new(future::err(Error::from(DeterministicError(format!(
                 "Multiple handlers defined for event `{}`, only one is suported",
                 &event_handler.event
-            )));
+            )))));
         }
 
+        // Only pass the receipt to handlers that asked for it
+        let receipt = if event_handler.receipt {
+            match receipt {
+                Some(receipt) => Some(receipt),
+                None => {
+                    return Box::new(future::err(Error::from(DeterministicError(format!(
+                        "No transaction receipt for event `{}` handled by `{}`",
+                        event_handler.event, event_handler.handler
+                    )))))
+                }
+            }
+        } else {
+            None
+        };
+
         debug!(
             logger, "Start processing Ethereum event";
             "signature" => &event_handler.event,
@@ -737,6 +771,7 @@ impl RuntimeHostTrait for RuntimeHost {
                     trigger: MappingTrigger::Log {
                         transaction: transaction.clone(),
                         log: log.clone(),
+                        receipt,
                         params,
                         handler: event_handler.clone(),
                     },
@@ -1,6 +1,6 @@
 use crate::UnresolvedContractCall;
 use ethabi::{Address, Token};
-use futures::sync::oneshot;
+use futures::sync::{mpsc, oneshot};
 use graph::components::ethereum::*;
 use graph::components::store::EntityKey;
 use graph::data::store;
@@ -14,9 +14,15 @@ use std::time::{Duration, Instant};
 use web3::types::H160;
 
 use graph_graphql::prelude::validate_entity;
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
 
+use crate::mapping::ModulePool;
 use crate::module::WasmiModule;
 
+/// The largest number of entities a single `store.find` may return.
+const STORE_FIND_MAX_LIMIT: u32 = 1000;
+
 pub(crate) trait ExportError: fmt::Debug + fmt::Display + Send + Sync + 'static {}
 
 impl<E> ExportError for E where E: fmt::Debug + fmt::Display + Send + Sync + 'static {}
@@ -37,12 +43,71 @@ impl From<graph::prelude::Error> for HostExportError<String> {
     }
 }
 
+/// The ways in which `ipfs.map` can turn the lines of a file into the values
+/// it passes to the callback. The format is selected by passing exactly one
+/// of `json`, `csv` or `raw` in the `flags` argument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum IpfsMapFormat {
+    /// Each line is a JSON value
+    Json,
+    /// Each line is a CSV record, which is passed to the callback as a JSON
+    /// array of strings. Quoted fields can not contain newlines
+    Csv,
+    /// Each line is passed to the callback as a JSON string
+    Raw,
+}
+
+impl IpfsMapFormat {
+    pub(crate) fn from_flags(flags: &[String]) -> Result<Self, String> {
+        let formats: Vec<_> = flags
+            .iter()
+            .filter_map(|flag| match flag.as_str() {
+                "json" => Some(IpfsMapFormat::Json),
+                "csv" => Some(IpfsMapFormat::Csv),
+                "raw" => Some(IpfsMapFormat::Raw),
+                _ => None,
+            })
+            .collect();
+        match formats.as_slice() {
+            [format] => Ok(*format),
+            _ => Err(format!(
+                "Flags must contain exactly one of 'json', 'csv' or 'raw', but are {:?}",
+                flags
+            )),
+        }
+    }
+
+    /// Convert a line of a `csv` or `raw` file into the JSON value that is
+    /// passed to the callback. Lines of `json` files are deserialized while
+    /// streaming the file and never converted here.
+    pub(crate) fn line_to_json(self, line: &str) -> Result<serde_json::Value, Error> {
+        match self {
+            IpfsMapFormat::Json => Ok(serde_json::from_str(line)?),
+            IpfsMapFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(line.as_bytes());
+                let fields = match reader.records().next() {
+                    Some(record) => record?
+                        .iter()
+                        .map(|field| serde_json::Value::String(field.to_owned()))
+                        .collect(),
+                    None => vec![],
+                };
+                Ok(serde_json::Value::Array(fields))
+            }
+            IpfsMapFormat::Raw => Ok(serde_json::Value::String(line.to_owned())),
+        }
+    }
+}
+
 pub(crate) struct HostExports {
     subgraph_id: SubgraphDeploymentId,
     pub(crate) api_version: Version,
     data_source_name: String,
     data_source_address: Option<Address>,
     data_source_network: Option<String>,
+    data_source_context: Option<DataSourceContext>,
     templates: Vec<DataSourceTemplate>,
     abis: Vec<MappingABI>,
     ethereum_adapter: Arc<dyn EthereumAdapter>,
@@ -50,6 +115,7 @@ pub(crate) struct HostExports {
     call_cache: Arc<dyn EthereumCallCache>,
     store: Arc<dyn crate::RuntimeStore>,
     handler_timeout: Option<Duration>,
+    pub(crate) handler_gas_limit: Option<u64>,
 }
 
 // Not meant to be useful, only to allow deriving.
@@ -66,6 +132,7 @@ impl HostExports {
         data_source_name: String,
         data_source_address: Option<Address>,
         data_source_network: Option<String>,
+        data_source_context: Option<DataSourceContext>,
         templates: Vec<DataSourceTemplate>,
         abis: Vec<MappingABI>,
         ethereum_adapter: Arc<dyn EthereumAdapter>,
@@ -73,6 +140,7 @@ impl HostExports {
         store: Arc<dyn crate::RuntimeStore>,
         call_cache: Arc<dyn EthereumCallCache>,
         handler_timeout: Option<Duration>,
+        handler_gas_limit: Option<u64>,
     ) -> Self {
         Self {
             subgraph_id,
@@ -80,6 +148,7 @@ impl HostExports {
             data_source_name,
             data_source_address,
             data_source_network,
+            data_source_context,
             templates,
             abis,
             ethereum_adapter,
@@ -87,6 +156,7 @@ impl HostExports {
             call_cache,
             store,
             handler_timeout,
+            handler_gas_limit,
         }
     }
 
@@ -96,7 +166,7 @@ impl HostExports {
         file_name: Option<String>,
         line_number: Option<u32>,
         column_number: Option<u32>,
-    ) -> Result<(), HostExportError<impl ExportError>> {
+    ) -> Result<(), HostExportError<DeterministicError>> {
         let message = message
             .map(|message| format!("message: {}", message))
             .unwrap_or_else(|| "no message".into());
@@ -112,10 +182,10 @@ impl HostExports {
             ),
             _ => unreachable!(),
         };
-        Err(HostExportError(format!(
+        Err(HostExportError(DeterministicError(format!(
             "Mapping aborted at {}, with {}",
             location, message
-        )))
+        ))))
     }
 
     pub(crate) fn store_set(
@@ -199,6 +269,38 @@ impl HostExports {
         result
     }
 
+    /// Entities of type `entity_type` whose `field` is `value`, ordered by
+    /// ID. Changes made earlier in the block are visible to the lookup.
+    pub(crate) fn store_find(
+        &self,
+        logger: &Logger,
+        state: &mut BlockState,
+        entity_type: String,
+        field: String,
+        value: Value,
+        limit: u32,
+    ) -> Result<Vec<Entity>, HostExportError<impl ExportError>> {
+        let start_time = Instant::now();
+        let result = state
+            .entity_cache
+            .find(
+                self.store.as_ref(),
+                &self.subgraph_id,
+                &entity_type,
+                &field,
+                &value,
+                limit,
+            )
+            .map_err(HostExportError);
+
+        debug!(logger, "Store find finished";
+               "type" => &entity_type,
+               "field" => &field,
+               "limit" => limit,
+               "time" => format!("{}ms", start_time.elapsed().as_millis()));
+        result
+    }
+
     /// Returns `Ok(None)` if the call was reverted.
     pub(crate) fn ethereum_call(
         &self,
@@ -360,21 +462,32 @@ impl HostExports {
         )
     }
 
-    // Read the IPFS file `link`, split it into JSON objects, and invoke the
-    // exported function `callback` on each JSON object. The successful return
-    // value contains the block state produced by each callback invocation. Each
-    // invocation of `callback` happens in its own instance of a WASM module,
-    // which is identical to `module` when it was first started. The signature
-    // of the callback must be `callback(JSONValue, Value)`, and the `userData`
-    // parameter is passed to the callback without any changes
+    // Read the IPFS file `link`, split it into lines, and invoke the exported
+    // function `callback` on the value of each line. How lines are turned
+    // into the value passed to the callback is determined by the `flags`, see
+    // `IpfsMapFormat`. The entity changes and data sources produced by each
+    // callback invocation are merged into `state` as soon as the invocation
+    // finishes, so that the file is never held in memory in its entirety. The
+    // successful return value contains the resulting state and the number of
+    // callback invocations. Each invocation of `callback` happens in an
+    // instance of a WASM module that behaves like `module` when it was first
+    // started; instances are reused between lines through a `ModulePool`. The
+    // file is downloaded on the runtime while the callbacks run on the
+    // current thread. The signature of the callback must be
+    // `callback(JSONValue, Value)`, and the `userData` parameter is passed to
+    // the callback without any changes. The callbacks are charged against the
+    // gas budget of the calling handler, which has already used `gas_used`;
+    // the gas they use is added to `gas_used`
     pub(crate) fn ipfs_map<U>(
         &self,
         module: &WasmiModule<U>,
+        mut state: BlockState,
         link: String,
         callback: &str,
         user_data: store::Value,
         flags: Vec<String>,
-    ) -> Result<Vec<BlockState>, HostExportError<impl ExportError>>
+        gas_used: &mut u64,
+    ) -> Result<(BlockState, usize), HostExportError<impl ExportError>>
     where
         U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
             + Clone
@@ -382,10 +495,7 @@ impl HostExports {
             + Sync
             + 'static,
     {
-        const JSON_FLAG: &str = "json";
-        if !flags.contains(&JSON_FLAG.to_string()) {
-            return Err(HostExportError(format!("Flags must contain 'json'")));
-        }
+        let format = IpfsMapFormat::from_flags(&flags).map_err(HostExportError)?;
 
         let host_metrics = module.host_metrics.clone();
         let task_sink = module.task_sink.clone();
@@ -401,37 +511,78 @@ impl HostExports {
         let start = Instant::now();
         let mut last_log = Instant::now();
         let logger = ctx.logger.new(o!("ipfs_map" => link.clone()));
-        block_on(
-            &mut task_sink.clone(),
-            self.link_resolver
-                .json_stream(&Link { link })
-                .and_then(move |stream| {
-                    stream
-                        .and_then(move |sv| {
-                            let module = WasmiModule::from_valid_module_with_ctx(
-                                valid_module.clone(),
-                                ctx.clone(),
-                                task_sink.clone(),
-                                host_metrics.clone(),
-                            )?;
-                            let result =
-                                module.handle_json_callback(&*callback, &sv.value, &user_data);
-                            // Log progress every 15s
-                            if last_log.elapsed() > Duration::from_secs(15) {
-                                debug!(
-                                    logger,
-                                    "Processed {} lines in {}s so far",
-                                    sv.line,
-                                    start.elapsed().as_secs()
-                                );
-                                last_log = Instant::now();
-                            }
-                            result
+        let link = Link { link };
+        let stream: Box<dyn Future<Item = JsonValueStream, Error = Error> + Send> = match format {
+            IpfsMapFormat::Json => self.link_resolver.json_stream(&link),
+            IpfsMapFormat::Csv | IpfsMapFormat::Raw => {
+                Box::new(self.link_resolver.line_stream(&link).map(move |stream| {
+                    Box::new(stream.and_then(move |line| {
+                        Ok(JsonStreamValue {
+                            value: format.line_to_json(&line.value).map_err(|e| {
+                                format_err!("{} at line {}: '{}'", e, line.line, line.value)
+                            })?,
+                            line: line.line,
                         })
-                        .collect()
-                })
-                .map_err(move |e| HostExportError(format!("{}: {}", errmsg, e.to_string()))),
-        )
+                    })) as JsonValueStream
+                }))
+            }
+        };
+
+        // Forward the values from the file, and any error reading it, to this
+        // thread. The channel is bounded so that we never read much further
+        // ahead than the callbacks have gotten
+        let (value_sender, value_receiver) = mpsc::channel(100);
+        let forward = stream.then(
+            move |result| -> Box<dyn Future<Item = (), Error = ()> + Send> {
+                match result {
+                    Ok(stream) => Box::new(
+                        stream
+                            .then(Ok::<_, mpsc::SendError<Result<JsonStreamValue, Error>>>)
+                            .forward(value_sender)
+                            .map(|_| ())
+                            .map_err(|_| ()),
+                    ),
+                    Err(e) => Box::new(value_sender.send(Err(e)).map(|_| ()).map_err(|_| ())),
+                }
+            },
+        );
+        task_sink
+            .clone()
+            .send(Box::new(forward))
+            .wait()
+            .map_err(|_| HostExportError(format!("{}: task receiver dropped", errmsg)))?;
+
+        let mut pool = ModulePool::new(valid_module, task_sink, host_metrics);
+        let mut calls = 0;
+        for value in value_receiver.wait() {
+            let result = value.expect("`mpsc::Receiver` never fails").and_then(|sv| {
+                let mut module = pool.get(ctx.clone())?;
+                let output =
+                    module.handle_json_callback(&*callback, &sv.value, &user_data, gas_used);
+                pool.put(module);
+                Ok((sv, output?))
+            });
+            let (sv, output) =
+                result.map_err(|e| HostExportError(format!("{}: {}", errmsg, e.to_string())))?;
+
+            state.entity_cache.extend(output.entity_cache);
+            state
+                .created_data_sources
+                .extend(output.created_data_sources);
+            calls += 1;
+
+            // Log progress every 15s
+            if last_log.elapsed() > Duration::from_secs(15) {
+                debug!(
+                    logger,
+                    "Processed {} lines in {}s so far",
+                    sv.line,
+                    start.elapsed().as_secs()
+                );
+                last_log = Instant::now();
+            }
+        }
+        Ok((state, calls))
     }
 
     /// Expects a decimal string.
@@ -475,6 +626,87 @@ impl HostExports {
         tiny_keccak::keccak256(&input)
     }
 
+    pub(crate) fn crypto_sha256(&self, input: Vec<u8>) -> Vec<u8> {
+        Sha256::digest(&input).to_vec()
+    }
+
+    pub(crate) fn crypto_sha3(&self, input: Vec<u8>) -> [u8; 32] {
+        tiny_keccak::sha3_256(&input)
+    }
+
+    pub(crate) fn crypto_ripemd160(&self, input: Vec<u8>) -> Vec<u8> {
+        Ripemd160::digest(&input).to_vec()
+    }
+
+    /// Recover the public key that produced `signature` for the 32 byte
+    /// message `hash`. The signature is expected in the 65 byte `(r, s, v)`
+    /// form, where `v` is either `0`/`1` or `27`/`28`. The public key is
+    /// returned as the 64 bytes of its uncompressed serialization, without
+    /// the leading `0x04`, or `None` if the signature is not valid.
+    pub(crate) fn crypto_secp256k1_recover(
+        &self,
+        hash: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, HostExportError<DeterministicError>> {
+        if hash.len() != 32 {
+            return Err(HostExportError(DeterministicError(format!(
+                "Message hash must be 32 bytes long, but is {} bytes long",
+                hash.len()
+            ))));
+        }
+        if signature.len() != 65 {
+            return Err(HostExportError(DeterministicError(format!(
+                "Signature must be 65 bytes long, but is {} bytes long",
+                signature.len()
+            ))));
+        }
+
+        let mut message = [0u8; 32];
+        message.copy_from_slice(&hash);
+        let mut rs = [0u8; 64];
+        rs.copy_from_slice(&signature[0..64]);
+        let v = match signature[64] {
+            v @ 27..=28 => v - 27,
+            v => v,
+        };
+
+        let recovery_id = match secp256k1::RecoveryId::parse(v) {
+            Ok(recovery_id) => recovery_id,
+            Err(_) => return Ok(None),
+        };
+        let public_key = secp256k1::recover(
+            &secp256k1::Message::parse(&message),
+            &secp256k1::Signature::parse(&rs),
+            &recovery_id,
+        );
+
+        Ok(public_key.ok().map(|key| key.serialize()[1..].to_vec()))
+    }
+
+    pub(crate) fn ethereum_encode(&self, token: Token) -> Vec<u8> {
+        ethabi::encode(&[token])
+    }
+
+    /// Decode `data` as a single value of the Solidity type `types`, e.g.
+    /// `(uint256,address[])`. Returns `None` if `data` can not be decoded
+    /// as that type.
+    pub(crate) fn ethereum_decode(
+        &self,
+        types: String,
+        data: Vec<u8>,
+    ) -> Result<Option<Token>, HostExportError<DeterministicError>> {
+        let param_type = ethabi::param_type::Reader::read(&types).map_err(|e| {
+            HostExportError(DeterministicError(format!(
+                "Failed to parse ABI type `{}`: {}",
+                types, e
+            )))
+        })?;
+
+        Ok(ethabi::decode(&[param_type], &data)
+            .ok()
+            .and_then(|mut tokens| tokens.pop()))
+    }
+
     pub(crate) fn big_int_plus(&self, x: BigInt, y: BigInt) -> BigInt {
         x + y
     }
@@ -522,6 +754,36 @@ impl HostExports {
         Ok(())
     }
 
+    pub(crate) fn check_gas(
+        &self,
+        gas_used: u64,
+    ) -> Result<(), HostExportError<DeterministicError>> {
+        if let Some(gas_limit) = self.handler_gas_limit {
+            if gas_used > gas_limit {
+                return Err(HostExportError(DeterministicError(format!(
+                    "Mapping handler exceeded the gas limit of {}",
+                    gas_limit
+                ))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails deterministically if a `store.find` asks for more entities than
+    /// a single lookup may return.
+    pub(crate) fn check_store_find_limit(
+        &self,
+        limit: u32,
+    ) -> Result<(), HostExportError<DeterministicError>> {
+        if limit > STORE_FIND_MAX_LIMIT {
+            return Err(HostExportError(DeterministicError(format!(
+                "store.find limit {} exceeds the maximum of {}",
+                limit, STORE_FIND_MAX_LIMIT
+            ))));
+        }
+        Ok(())
+    }
+
     /// Useful for IPFS hashes stored as bytes
     pub(crate) fn bytes_to_base58(&self, bytes: Vec<u8>) -> String {
         ::bs58::encode(&bytes).into_string()
@@ -577,12 +839,14 @@ impl HostExports {
         state: &mut BlockState,
         name: String,
         params: Vec<String>,
+        context: Option<DataSourceContext>,
     ) -> Result<(), HostExportError<impl ExportError>> {
         info!(
             logger,
             "Create data source";
             "name" => &name,
-            "params" => format!("{}", params.join(","))
+            "params" => format!("{}", params.join(",")),
+            "context" => format!("{:?}", context),
         );
 
         // Resolve the name into the right template
@@ -611,6 +875,7 @@ impl HostExports {
             data_source: self.data_source_name.clone(),
             template,
             params,
+            context,
         });
 
         Ok(())
@@ -644,6 +909,10 @@ impl HostExports {
     pub(crate) fn data_source_network(&self) -> String {
         self.data_source_network.clone().unwrap_or_default()
     }
+
+    pub(crate) fn data_source_context(&self) -> Entity {
+        self.data_source_context.clone().unwrap_or_default()
+    }
 }
 
 pub(crate) fn string_to_h160(string: &str) -> Result<H160, HostExportError<impl ExportError>> {
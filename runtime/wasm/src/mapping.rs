@@ -6,7 +6,7 @@ use graph::components::ethereum::*;
 use graph::prelude::*;
 use std::thread;
 use std::time::Instant;
-use web3::types::{Log, Transaction};
+use web3::types::{Log, Transaction, TransactionReceipt};
 
 /// Spawn a wasm module in its own thread.
 pub fn spawn_module(
@@ -36,6 +36,8 @@ pub fn spawn_module(
     let conf =
         thread::Builder::new().name(format!("mapping-{}-{}", &subgraph_id, uuid::Uuid::new_v4()));
     conf.spawn(move || {
+        let mut pool = ModulePool::new(valid_module, task_sender, host_metrics);
+
         // Pass incoming triggers to the WASM module and return entity changes;
         // Stop when canceled because all RuntimeHosts and their senders were dropped.
         match mapping_request_receiver
@@ -47,24 +49,21 @@ pub fn spawn_module(
                     result_sender,
                 } = request;
 
-                // Start the WASMI module runtime.
-                let module = WasmiModule::from_valid_module_with_ctx(
-                    valid_module.clone(),
-                    ctx,
-                    task_sender.clone(),
-                    host_metrics.clone(),
-                )?;
+                // Get a started WASMI module runtime.
+                let mut module = pool.get(ctx)?;
 
                 let result = match trigger {
                     MappingTrigger::Log {
                         transaction,
                         log,
+                        receipt,
                         params,
                         handler,
                     } => module.handle_ethereum_log(
                         handler.handler.as_str(),
                         transaction,
                         log,
+                        receipt,
                         params,
                     ),
                     MappingTrigger::Call {
@@ -84,6 +83,7 @@ pub fn spawn_module(
                         module.handle_ethereum_block(handler.handler.as_str())
                     }
                 };
+                pool.put(module);
 
                 result_sender
                     .send((result, future::ok(Instant::now())))
@@ -107,6 +107,7 @@ pub(crate) enum MappingTrigger {
     Log {
         transaction: Arc<Transaction>,
         log: Arc<Log>,
+        receipt: Option<Arc<TransactionReceipt>>,
         params: Vec<LogParam>,
         handler: MappingEventHandler,
     },
@@ -153,6 +154,64 @@ impl Clone for MappingContext {
     }
 }
 
+/// Started instances of a WASM module that are reused for handling triggers,
+/// since instantiating and starting a module for every trigger is expensive.
+/// Instances are reset when they are returned to the pool, so that every
+/// trigger is handled by an instance that behaves like a freshly started one.
+pub(crate) struct ModulePool<U> {
+    valid_module: Arc<ValidModule>,
+    task_sink: U,
+    host_metrics: Arc<HostMetrics>,
+    instances: Vec<WasmiModule<U>>,
+}
+
+impl<U> ModulePool<U>
+where
+    U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    pub(crate) fn new(
+        valid_module: Arc<ValidModule>,
+        task_sink: U,
+        host_metrics: Arc<HostMetrics>,
+    ) -> Self {
+        ModulePool {
+            valid_module,
+            task_sink,
+            host_metrics,
+            instances: vec![],
+        }
+    }
+
+    /// Take an instance from the pool, or start a new one if there is none,
+    /// and prepare it for handling a trigger with `ctx`.
+    pub(crate) fn get(&mut self, ctx: MappingContext) -> Result<WasmiModule<U>, Error> {
+        match self.instances.pop() {
+            Some(mut module) => {
+                module.ctx = ctx;
+                Ok(module)
+            }
+            None => WasmiModule::from_valid_module_with_ctx(
+                self.valid_module.clone(),
+                ctx,
+                self.task_sink.clone(),
+                self.host_metrics.clone(),
+            ),
+        }
+    }
+
+    /// Return `module` to the pool. Instances that can not be reset, like
+    /// those whose memory grew, are dropped.
+    pub(crate) fn put(&mut self, mut module: WasmiModule<U>) {
+        if module.reset().is_ok() {
+            self.instances.push(module);
+        }
+    }
+}
+
 /// A pre-processed and valid WASM module, ready to be started as a WasmiModule.
 pub(crate) struct ValidModule {
     pub(super) module: wasmi::Module,
@@ -162,7 +221,8 @@ pub(crate) struct ValidModule {
 impl ValidModule {
     /// Pre-process and validate the module.
     pub fn new(parsed_module: parity_wasm::elements::Module) -> Result<Self, Error> {
-        // Inject metering calls, which are used for checking timeouts.
+        // Inject metering calls, which charge each block of instructions to
+        // the handler's gas budget and are also used for checking timeouts.
         let parsed_module = pwasm_utils::inject_gas_counter(parsed_module, &Default::default())
             .map_err(|_| err_msg("failed to inject gas counter"))?;
 
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
@@ -5,9 +6,9 @@ use std::time::Instant;
 
 use semver::Version;
 use wasmi::{
-    nan_preserving_float::F64, Error, Externals, FuncInstance, FuncRef, HostError, ImportsBuilder,
-    MemoryRef, ModuleImportResolver, ModuleInstance, ModuleRef, RuntimeArgs, RuntimeValue,
-    Signature, Trap,
+    memory_units::Bytes, nan_preserving_float::F64, Error, Externals, FuncInstance, FuncRef,
+    HostError, ImportsBuilder, MemoryRef, ModuleImportResolver, ModuleInstance, ModuleRef,
+    RuntimeArgs, RuntimeValue, Signature, Trap,
 };
 
 use crate::host_exports::{self, HostExportError};
@@ -16,7 +17,7 @@ use ethabi::LogParam;
 use graph::components::ethereum::*;
 use graph::data::store;
 use graph::prelude::{Error as FailureError, *};
-use web3::types::{Log, Transaction, U256};
+use web3::types::{Log, Transaction, TransactionReceipt, U256};
 
 use crate::asc_abi::asc_ptr::*;
 use crate::asc_abi::class::*;
@@ -68,11 +69,21 @@ const LOG_LOG: usize = 37;
 const BIG_INT_POW: usize = 38;
 const DATA_SOURCE_ADDRESS: usize = 39;
 const DATA_SOURCE_NETWORK: usize = 40;
+const DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX: usize = 41;
+const DATA_SOURCE_CONTEXT: usize = 42;
+const ETHEREUM_ENCODE_FUNC_INDEX: usize = 43;
+const ETHEREUM_DECODE_FUNC_INDEX: usize = 44;
+const CRYPTO_SHA256_INDEX: usize = 45;
+const CRYPTO_SHA3_INDEX: usize = 46;
+const CRYPTO_RIPEMD160_INDEX: usize = 47;
+const CRYPTO_SECP256K1_RECOVER_INDEX: usize = 48;
+const STORE_FIND_FUNC_INDEX: usize = 49;
 
 /// Transform function index into the function name string
 fn fn_index_to_metrics_string(index: usize) -> Option<String> {
     match index {
         STORE_GET_FUNC_INDEX => Some(String::from("store_get")),
+        STORE_FIND_FUNC_INDEX => Some(String::from("store_find")),
         ETHEREUM_CALL_FUNC_INDEX => Some(String::from("ethereum_call")),
         IPFS_MAP_FUNC_INDEX => Some(String::from("ipfs_map")),
         IPFS_CAT_FUNC_INDEX => Some(String::from("ipfs_cat")),
@@ -80,6 +91,23 @@ fn fn_index_to_metrics_string(index: usize) -> Option<String> {
     }
 }
 
+/// The fixed amount of gas charged for calling a host export, in addition to
+/// the gas for the WASM instructions of the handler. Host exports that need
+/// to reach the store, Ethereum or IPFS are much more expensive than those
+/// that only compute something.
+fn host_fn_gas_cost(index: usize) -> u64 {
+    match index {
+        GAS_FUNC_INDEX => 0,
+        STORE_GET_FUNC_INDEX
+        | STORE_SET_FUNC_INDEX
+        | STORE_REMOVE_FUNC_INDEX
+        | STORE_FIND_FUNC_INDEX => 100_000,
+        ETHEREUM_CALL_FUNC_INDEX => 5_000_000,
+        IPFS_CAT_FUNC_INDEX | IPFS_MAP_FUNC_INDEX => 10_000_000,
+        _ => 1_000,
+    }
+}
+
 /// A common error is a trap in the host, so simplify the message in that case.
 fn format_wasmi_error(e: Error) -> String {
     match e {
@@ -91,6 +119,37 @@ fn format_wasmi_error(e: Error) -> String {
     }
 }
 
+/// Traps raised by the WASM code itself and explicit aborts happen again every
+/// time a handler runs on the same input; all other host errors may not.
+fn is_deterministic_wasmi_error(e: &Error) -> bool {
+    match e {
+        Error::Trap(trap) => match trap.kind() {
+            wasmi::TrapKind::Host(host_error) => host_error
+                .downcast_ref::<HostExportError<DeterministicError>>()
+                .is_some(),
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+/// Builds the error for a failed handler invocation, marking it as a
+/// `DeterministicError` if the failure would happen again.
+fn handler_error(trigger: &str, handler_name: &str, e: Error) -> FailureError {
+    let deterministic = is_deterministic_wasmi_error(&e);
+    let message = format!(
+        "Failed to handle {} with handler \"{}\": {}",
+        trigger,
+        handler_name,
+        format_wasmi_error(e)
+    );
+    if deterministic {
+        DeterministicError(message).into()
+    } else {
+        err_msg(message)
+    }
+}
+
 /// A WASM module based on wasmi that powers a subgraph runtime.
 pub(crate) struct WasmiModule<U> {
     pub module: ModuleRef,
@@ -104,6 +163,10 @@ pub(crate) struct WasmiModule<U> {
     // Time when the current handler began processing.
     start_time: Instant,
 
+    // Gas consumed by the current handler, both by executing WASM
+    // instructions and by calling host exports.
+    gas_used: u64,
+
     // True if `run_start` has not yet been called on the module.
     // This is used to prevent mutating store state in start.
     running_start: bool,
@@ -113,6 +176,18 @@ pub(crate) struct WasmiModule<U> {
 
     // Number of free bytes starting from `arena_start_ptr`.
     arena_free_size: u32,
+
+    // The memory and globals right after the module was started.
+    snapshot: InstanceSnapshot,
+}
+
+/// The memory and globals of a module instance right after it was started.
+/// Restoring them makes an instance behave like a freshly started one, which
+/// is much cheaper than instantiating and starting the module again.
+#[derive(Default)]
+struct InstanceSnapshot {
+    memory: Vec<u8>,
+    globals: Vec<RuntimeValue>,
 }
 
 impl<U> WasmiModule<U>
@@ -158,11 +233,15 @@ where
             task_sink,
             host_metrics: host_metrics.clone(),
             start_time: Instant::now(),
+            gas_used: 0,
             running_start: true,
 
             // `arena_start_ptr` will be set on the first call to `raw_new`.
             arena_free_size: 0,
             arena_start_ptr: 0,
+
+            // Taken once the module has been started.
+            snapshot: InstanceSnapshot::default(),
         };
 
         this.module = module
@@ -170,94 +249,149 @@ where
             .map_err(|e| format_err!("Failed to start WASM module instance: {}", e))?;
         this.running_start = false;
 
+        let memory_size = Bytes::from(this.memory.current_size()).0;
+        this.snapshot = InstanceSnapshot {
+            memory: this
+                .memory
+                .get(0, memory_size)
+                .map_err(|e| format_err!("Failed to read WASM module memory: {}", e))?,
+            globals: this
+                .module
+                .globals()
+                .iter()
+                .map(|global| global.get())
+                .collect(),
+        };
+
         Ok(this)
     }
 
+    /// Restore the memory and globals of this instance to their state right
+    /// after the module was started, so that the instance can be used for
+    /// handling another trigger. The gas and time used so far are forgotten,
+    /// too.
+    ///
+    /// Memory can not be shrunk again once it has grown, and a mapping that
+    /// finds more memory than a fresh instance has would allocate, and use
+    /// gas, differently. Instances whose memory grew can therefore not be
+    /// reset.
+    pub(crate) fn reset(&mut self) -> Result<(), FailureError> {
+        let memory_size = Bytes::from(self.memory.current_size()).0;
+        let snapshot_size = self.snapshot.memory.len();
+        if memory_size != snapshot_size {
+            return Err(format_err!(
+                "WASM module memory grew from {} to {} bytes",
+                snapshot_size,
+                memory_size
+            ));
+        }
+        self.memory
+            .set(0, &self.snapshot.memory)
+            .map_err(|e| format_err!("Failed to reset WASM module memory: {}", e))?;
+
+        for (global, value) in self.module.globals().iter().zip(&self.snapshot.globals) {
+            if global.is_mutable() {
+                global
+                    .set(*value)
+                    .map_err(|e| format_err!("Failed to reset WASM module global: {}", e))?;
+            }
+        }
+
+        self.ctx.state = BlockState::default();
+        self.arena_start_ptr = 0;
+        self.arena_free_size = 0;
+        self.start_time = Instant::now();
+        self.gas_used = 0;
+        Ok(())
+    }
+
     pub(crate) fn handle_ethereum_log(
-        mut self,
+        &mut self,
         handler_name: &str,
         transaction: Arc<Transaction>,
         log: Arc<Log>,
+        receipt: Option<Arc<TransactionReceipt>>,
         params: Vec<LogParam>,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
 
         let block = self.ctx.block.clone();
 
+        let event_data = EthereumEventData {
+            block: EthereumBlockData::from(block.as_ref()),
+            transaction: EthereumTransactionData::from(transaction.deref()),
+            address: log.address,
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+            params,
+            receipt: receipt
+                .as_ref()
+                .map(|receipt| EthereumTransactionReceiptData::from(receipt.deref())),
+        };
+
         // Prepare an EthereumEvent for the WASM runtime
         // Decide on the destination type using the mapping
         // api version provided in the subgraph manifest
         let event = if self.ctx.host_exports.api_version >= Version::new(0, 0, 2) {
             RuntimeValue::from(
-                self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(
-                    &EthereumEventData {
-                        block: EthereumBlockData::from(block.as_ref()),
-                        transaction: EthereumTransactionData::from(transaction.deref()),
-                        address: log.address,
-                        log_index: log.log_index.unwrap_or(U256::zero()),
-                        transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                        log_type: log.log_type.clone(),
-                        params,
-                    },
-                ),
+                self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(&event_data),
             )
         } else {
-            RuntimeValue::from(self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(
-                &EthereumEventData {
-                    block: EthereumBlockData::from(block.as_ref()),
-                    transaction: EthereumTransactionData::from(transaction.deref()),
-                    address: log.address,
-                    log_index: log.log_index.unwrap_or(U256::zero()),
-                    transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                    log_type: log.log_type.clone(),
-                    params,
-                },
-            ))
+            RuntimeValue::from(
+                self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(&event_data),
+            )
         };
 
         // Invoke the event handler
         let result = self
             .module
             .clone()
-            .invoke_export(handler_name, &[event], &mut self);
+            .invoke_export(handler_name, &[event], self);
+        self.host_metrics
+            .observe_handler_gas_used(self.gas_used as f64, handler_name.to_owned());
 
         // Return either the output state (collected entity operations etc.) or an error
-        result.map(|_| self.ctx.state).map_err(|e| {
-            format_err!(
-                "Failed to handle Ethereum event with handler \"{}\": {}",
-                handler_name,
-                format_wasmi_error(e)
-            )
-        })
+        result
+            .map(|_| std::mem::replace(&mut self.ctx.state, BlockState::default()))
+            .map_err(|e| handler_error("Ethereum event", handler_name, e))
     }
 
+    /// Run an `ipfs.map` callback. The callback is charged against the budget
+    /// of the handler that called `ipfs.map`, which has already used
+    /// `gas_used`; the gas used by the handler and the callback together is
+    /// added to `gas_used`.
     pub(crate) fn handle_json_callback(
-        mut self,
+        &mut self,
         handler_name: &str,
         value: &serde_json::Value,
         user_data: &store::Value,
+        gas_used: &mut u64,
     ) -> Result<BlockState, FailureError> {
+        self.start_time = Instant::now();
+        self.gas_used = *gas_used;
+
         let value = RuntimeValue::from(self.asc_new(value));
         let user_data = RuntimeValue::from(self.asc_new(user_data));
 
         // Invoke the callback
-        let result =
-            self.module
-                .clone()
-                .invoke_export(handler_name, &[value, user_data], &mut self);
+        let result = self
+            .module
+            .clone()
+            .invoke_export(handler_name, &[value, user_data], self);
+        self.host_metrics
+            .observe_handler_gas_used((self.gas_used - *gas_used) as f64, handler_name.to_owned());
+        *gas_used = self.gas_used;
 
         // Return either the collected entity operations or an error
-        result.map(|_| self.ctx.state).map_err(|e| {
-            format_err!(
-                "Failed to handle callback with handler \"{}\": {}",
-                handler_name,
-                format_wasmi_error(e),
-            )
-        })
+        result
+            .map(|_| std::mem::replace(&mut self.ctx.state, BlockState::default()))
+            .map_err(|e| handler_error("callback", handler_name, e))
     }
 
     pub(crate) fn handle_ethereum_call(
-        mut self,
+        &mut self,
         handler_name: &str,
         transaction: Arc<Transaction>,
         call: Arc<EthereumCall>,
@@ -265,6 +399,7 @@ where
         outputs: Vec<LogParam>,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
 
         let call = EthereumCallData {
             to: call.to,
@@ -283,22 +418,21 @@ where
         let result = self
             .module
             .clone()
-            .invoke_export(handler_name, &[arg], &mut self);
+            .invoke_export(handler_name, &[arg], self);
+        self.host_metrics
+            .observe_handler_gas_used(self.gas_used as f64, handler_name.to_owned());
 
-        result.map(|_| self.ctx.state).map_err(|err| {
-            format_err!(
-                "Failed to handle Ethereum call with handler \"{}\": {}",
-                handler_name,
-                format_wasmi_error(err),
-            )
-        })
+        result
+            .map(|_| std::mem::replace(&mut self.ctx.state, BlockState::default()))
+            .map_err(|e| handler_error("Ethereum call", handler_name, e))
     }
 
     pub(crate) fn handle_ethereum_block(
-        mut self,
+        &mut self,
         handler_name: &str,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
 
         // Prepare an EthereumBlock for the WASM runtime
         let arg = EthereumBlockData::from(self.ctx.block.as_ref());
@@ -306,16 +440,14 @@ where
         let result = self.module.clone().invoke_export(
             handler_name,
             &[RuntimeValue::from(self.asc_new(&arg))],
-            &mut self,
+            self,
         );
+        self.host_metrics
+            .observe_handler_gas_used(self.gas_used as f64, handler_name.to_owned());
 
-        result.map(|_| self.ctx.state).map_err(|err| {
-            format_err!(
-                "Failed to handle Ethereum block with handler \"{}\": {}",
-                handler_name,
-                format_wasmi_error(err)
-            )
-        })
+        result
+            .map(|_| std::mem::replace(&mut self.ctx.state, BlockState::default()))
+            .map_err(|e| handler_error("Ethereum block", handler_name, e))
     }
 }
 
@@ -374,11 +506,19 @@ where
         + Sync
         + 'static,
 {
-    fn gas(&mut self, _gas_spent: u32) -> Result<Option<RuntimeValue>, Trap> {
+    fn gas(&mut self, gas_spent: u32) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas_spent as u64)?;
         self.ctx.host_exports.check_timeout(self.start_time)?;
         Ok(None)
     }
 
+    /// Charge `gas` to the current handler, failing deterministically once
+    /// the handler has used more than its budget.
+    fn consume_gas(&mut self, gas: u64) -> Result<(), HostExportError<DeterministicError>> {
+        self.gas_used = self.gas_used.saturating_add(gas);
+        self.ctx.host_exports.check_gas(self.gas_used)
+    }
+
     /// function abort(message?: string | null, fileName?: string | null, lineNumber?: u32, columnNumber?: u32): void
     /// Always returns a trap.
     fn abort(
@@ -469,6 +609,31 @@ where
         }))
     }
 
+    /// function store.find(entity: string, field: string, value: Value, limit: u32): Array<Entity>
+    fn store_find(
+        &mut self,
+        entity_ptr: AscPtr<AscString>,
+        field_ptr: AscPtr<AscString>,
+        value_ptr: AscPtr<AscEnum<StoreValueKind>>,
+        limit: u32,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        self.ctx.host_exports.check_store_find_limit(limit)?;
+        let entity = self.asc_get(entity_ptr);
+        let field = self.asc_get(field_ptr);
+        let value = self.asc_get(value_ptr);
+        let entities = self.ctx.host_exports.store_find(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            entity,
+            field,
+            value,
+            limit,
+        )?;
+
+        let entities: AscPtr<Array<AscPtr<AscEntity>>> = self.asc_new(entities.as_slice());
+        Ok(Some(RuntimeValue::from(entities)))
+    }
+
     /// function ethereum.call(call: SmartContractCall): Array<Token> | null
     fn ethereum_call(
         &mut self,
@@ -600,35 +765,38 @@ where
 
         let flags = self.asc_get(flags);
         let start_time = Instant::now();
-        let result =
-            match self
-                .ctx
-                .host_exports
-                .ipfs_map(&self, link.clone(), &*callback, user_data, flags)
-            {
-                Ok(output_states) => {
-                    debug!(
-                        &self.ctx.logger,
-                        "Successfully processed file with ipfs.map";
-                        "link" => &link,
-                        "callback" => &*callback,
-                        "n_calls" => output_states.len(),
-                        "time" => format!("{}ms", start_time.elapsed().as_millis())
-                    );
-                    for output_state in output_states {
-                        self.ctx
-                            .state
-                            .entity_cache
-                            .extend(output_state.entity_cache);
-                        self.ctx
-                            .state
-                            .created_data_sources
-                            .extend(output_state.created_data_sources);
-                    }
-                    Ok(None)
-                }
-                Err(e) => Err(e.into()),
-            };
+        // Take the state out of the context so that the state produced by the
+        // callbacks can be merged into it while the file is being processed
+        let state = std::mem::replace(&mut self.ctx.state, BlockState::default());
+        let mut gas_used = self.gas_used;
+        let result = match self.ctx.host_exports.ipfs_map(
+            &self,
+            state,
+            link.clone(),
+            &*callback,
+            user_data,
+            flags,
+            &mut gas_used,
+        ) {
+            Ok((state, n_calls)) => {
+                debug!(
+                    &self.ctx.logger,
+                    "Successfully processed file with ipfs.map";
+                    "link" => &link,
+                    "callback" => &*callback,
+                    "n_calls" => n_calls,
+                    "time" => format!("{}ms", start_time.elapsed().as_millis())
+                );
+                self.ctx.state = state;
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        // The callbacks count against the gas budget of this handler. If they
+        // used it up, that is what made `ipfs_map` fail
+        self.gas_used = gas_used;
+        self.ctx.host_exports.check_gas(self.gas_used)?;
 
         // Advance this module's start time by the time it took to run the entire
         // ipfs_map. This has the effect of not charging this module for the time
@@ -672,6 +840,35 @@ where
         Ok(Some(RuntimeValue::from(big_int_ptr)))
     }
 
+    /// function ethereum.encode(token: ethereum.Value): Bytes
+    fn ethereum_encode(
+        &mut self,
+        token_ptr: AscPtr<AscEnum<EthereumValueKind>>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let data = self
+            .ctx
+            .host_exports
+            .ethereum_encode(self.asc_get(token_ptr));
+        let data_ptr: AscPtr<Uint8Array> = self.asc_new(data.as_slice());
+        Ok(Some(RuntimeValue::from(data_ptr)))
+    }
+
+    /// function ethereum.decode(types: String, data: Bytes): ethereum.Value | null
+    fn ethereum_decode(
+        &mut self,
+        types_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = self
+            .ctx
+            .host_exports
+            .ethereum_decode(self.asc_get(types_ptr), self.asc_get(data_ptr))?;
+        Ok(Some(match result {
+            Some(token) => RuntimeValue::from(self.asc_new(&token)),
+            None => RuntimeValue::from(0),
+        }))
+    }
+
     /// function crypto.keccak256(input: Bytes): Bytes
     fn crypto_keccak_256(
         &mut self,
@@ -685,6 +882,55 @@ where
         Ok(Some(RuntimeValue::from(hash_ptr)))
     }
 
+    /// function crypto.sha256(input: Bytes): Bytes
+    fn crypto_sha256(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let hash = self.ctx.host_exports.crypto_sha256(self.asc_get(input_ptr));
+        let hash_ptr: AscPtr<Uint8Array> = self.asc_new(hash.as_slice());
+        Ok(Some(RuntimeValue::from(hash_ptr)))
+    }
+
+    /// function crypto.sha3(input: Bytes): Bytes
+    fn crypto_sha3(&mut self, input_ptr: AscPtr<Uint8Array>) -> Result<Option<RuntimeValue>, Trap> {
+        let hash = self.ctx.host_exports.crypto_sha3(self.asc_get(input_ptr));
+        let hash_ptr: AscPtr<Uint8Array> = self.asc_new(hash.as_ref());
+        Ok(Some(RuntimeValue::from(hash_ptr)))
+    }
+
+    /// function crypto.ripemd160(input: Bytes): Bytes
+    fn crypto_ripemd160(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let hash = self
+            .ctx
+            .host_exports
+            .crypto_ripemd160(self.asc_get(input_ptr));
+        let hash_ptr: AscPtr<Uint8Array> = self.asc_new(hash.as_slice());
+        Ok(Some(RuntimeValue::from(hash_ptr)))
+    }
+
+    /// function crypto.secp256k1Recover(hash: Bytes, signature: Bytes): Bytes | null
+    fn crypto_secp256k1_recover(
+        &mut self,
+        hash_ptr: AscPtr<Uint8Array>,
+        signature_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let public_key = self
+            .ctx
+            .host_exports
+            .crypto_secp256k1_recover(self.asc_get(hash_ptr), self.asc_get(signature_ptr))?;
+        Ok(Some(match public_key {
+            Some(public_key) => {
+                let public_key_ptr: AscPtr<Uint8Array> = self.asc_new(public_key.as_slice());
+                RuntimeValue::from(public_key_ptr)
+            }
+            None => RuntimeValue::from(0),
+        }))
+    }
+
     /// function bigInt.plus(x: BigInt, y: BigInt): BigInt
     fn big_int_plus(
         &mut self,
@@ -895,6 +1141,29 @@ where
             &mut self.ctx.state,
             name,
             params,
+            None,
+        )?;
+        Ok(None)
+    }
+
+    /// function dataSource.createWithContext(
+    ///   name: string, params: Array<string>, context: DataSourceContext
+    /// ): void
+    fn data_source_create_with_context(
+        &mut self,
+        name_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        context_ptr: AscPtr<AscEntity>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let name: String = self.asc_get(name_ptr);
+        let params: Vec<String> = self.asc_get(params_ptr);
+        let context: HashMap<String, store::Value> = self.asc_get(context_ptr);
+        self.ctx.host_exports.data_source_create(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            name,
+            params,
+            Some(context.into()),
         )?;
         Ok(None)
     }
@@ -913,6 +1182,13 @@ where
         )))
     }
 
+    /// function dataSource.context(): DataSourceContext
+    fn data_source_context(&mut self) -> Result<Option<RuntimeValue>, Trap> {
+        Ok(Some(RuntimeValue::from(
+            self.asc_new(&self.ctx.host_exports.data_source_context()),
+        )))
+    }
+
     fn ens_name_by_hash(
         &mut self,
         hash_ptr: AscPtr<AscString>,
@@ -950,6 +1226,8 @@ where
         index: usize,
         args: RuntimeArgs,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(host_fn_gas_cost(index))?;
+
         let start = Instant::now();
         let res = match index {
             ABORT_FUNC_INDEX => self.abort(
@@ -967,7 +1245,17 @@ where
             STORE_REMOVE_FUNC_INDEX => {
                 self.store_remove(args.nth_checked(0)?, args.nth_checked(1)?)
             }
+            STORE_FIND_FUNC_INDEX => self.store_find(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+                args.nth_checked(3)?,
+            ),
             ETHEREUM_CALL_FUNC_INDEX => self.ethereum_call(args.nth_checked(0)?),
+            ETHEREUM_ENCODE_FUNC_INDEX => self.ethereum_encode(args.nth_checked(0)?),
+            ETHEREUM_DECODE_FUNC_INDEX => {
+                self.ethereum_decode(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
             TYPE_CONVERSION_BYTES_TO_STRING_FUNC_INDEX => {
                 self.bytes_to_string(args.nth_checked(0)?)
             }
@@ -986,6 +1274,12 @@ where
             JSON_TO_BIG_INT_FUNC_INDEX => self.json_to_big_int(args.nth_checked(0)?),
             IPFS_CAT_FUNC_INDEX => self.ipfs_cat(args.nth_checked(0)?),
             CRYPTO_KECCAK_256_INDEX => self.crypto_keccak_256(args.nth_checked(0)?),
+            CRYPTO_SHA256_INDEX => self.crypto_sha256(args.nth_checked(0)?),
+            CRYPTO_SHA3_INDEX => self.crypto_sha3(args.nth_checked(0)?),
+            CRYPTO_RIPEMD160_INDEX => self.crypto_ripemd160(args.nth_checked(0)?),
+            CRYPTO_SECP256K1_RECOVER_INDEX => {
+                self.crypto_secp256k1_recover(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
             BIG_INT_PLUS => self.big_int_plus(args.nth_checked(0)?, args.nth_checked(1)?),
             BIG_INT_MINUS => self.big_int_minus(args.nth_checked(0)?, args.nth_checked(1)?),
             BIG_INT_TIMES => self.big_int_times(args.nth_checked(0)?, args.nth_checked(1)?),
@@ -1023,6 +1317,12 @@ where
             LOG_LOG => self.log_log(args.nth_checked(0)?, args.nth_checked(1)?),
             DATA_SOURCE_ADDRESS => self.data_source_address(),
             DATA_SOURCE_NETWORK => self.data_source_network(),
+            DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX => self.data_source_create_with_context(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+            ),
+            DATA_SOURCE_CONTEXT => self.data_source_context(),
             _ => panic!("Unimplemented function at {}", index),
         };
         // Record execution time
@@ -1062,9 +1362,12 @@ impl ModuleImportResolver for ModuleResolver {
             "store.set" => FuncInstance::alloc_host(signature, STORE_SET_FUNC_INDEX),
             "store.remove" => FuncInstance::alloc_host(signature, STORE_REMOVE_FUNC_INDEX),
             "store.get" => FuncInstance::alloc_host(signature, STORE_GET_FUNC_INDEX),
+            "store.find" => FuncInstance::alloc_host(signature, STORE_FIND_FUNC_INDEX),
 
             // ethereum
             "ethereum.call" => FuncInstance::alloc_host(signature, ETHEREUM_CALL_FUNC_INDEX),
+            "ethereum.encode" => FuncInstance::alloc_host(signature, ETHEREUM_ENCODE_FUNC_INDEX),
+            "ethereum.decode" => FuncInstance::alloc_host(signature, ETHEREUM_DECODE_FUNC_INDEX),
 
             // typeConversion
             "typeConversion.bytesToString" => {
@@ -1105,6 +1408,12 @@ impl ModuleImportResolver for ModuleResolver {
 
             // crypto
             "crypto.keccak256" => FuncInstance::alloc_host(signature, CRYPTO_KECCAK_256_INDEX),
+            "crypto.sha256" => FuncInstance::alloc_host(signature, CRYPTO_SHA256_INDEX),
+            "crypto.sha3" => FuncInstance::alloc_host(signature, CRYPTO_SHA3_INDEX),
+            "crypto.ripemd160" => FuncInstance::alloc_host(signature, CRYPTO_RIPEMD160_INDEX),
+            "crypto.secp256k1Recover" => {
+                FuncInstance::alloc_host(signature, CRYPTO_SECP256K1_RECOVER_INDEX)
+            }
 
             // bigInt
             "bigInt.plus" => FuncInstance::alloc_host(signature, BIG_INT_PLUS),
@@ -1130,6 +1439,10 @@ impl ModuleImportResolver for ModuleResolver {
             "dataSource.create" => FuncInstance::alloc_host(signature, DATA_SOURCE_CREATE_INDEX),
             "dataSource.address" => FuncInstance::alloc_host(signature, DATA_SOURCE_ADDRESS),
             "dataSource.network" => FuncInstance::alloc_host(signature, DATA_SOURCE_NETWORK),
+            "dataSource.createWithContext" => {
+                FuncInstance::alloc_host(signature, DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX)
+            }
+            "dataSource.context" => FuncInstance::alloc_host(signature, DATA_SOURCE_CONTEXT),
 
             // ens.nameByHash
             "ens.nameByHash" => FuncInstance::alloc_host(signature, ENS_NAME_BY_HASH),
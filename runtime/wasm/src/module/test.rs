@@ -8,15 +8,17 @@ use std::collections::HashMap;
 use std::env;
 use std::io::Cursor;
 use std::str::FromStr;
+use wasmi::memory_units::Pages;
 use wasmi::nan_preserving_float::F64;
 
-use crate::host_exports::HostExports;
+use crate::host_exports::{HostExports, IpfsMapFormat};
+use crate::mapping::ModulePool;
 use graph::components::store::*;
 use graph::data::store::scalar;
 use graph::data::subgraph::*;
 use graph::prelude::Error;
 use graph_core;
-use web3::types::{Address, H160};
+use web3::types::{Address, H160, U256};
 
 use super::*;
 
@@ -119,6 +121,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        context: None,
     }
 }
 
@@ -130,6 +133,7 @@ fn mock_host_exports(data_source: DataSource, store: Arc<MockStore>) -> HostExpo
         data_source.name,
         data_source.source.address,
         data_source.network,
+        data_source.context,
         data_source.templates,
         data_source.mapping.abis,
         mock_ethereum_adapter,
@@ -142,6 +146,10 @@ fn mock_host_exports(data_source: DataSource, store: Arc<MockStore>) -> HostExpo
             .ok()
             .and_then(|s| u64::from_str(&s).ok())
             .map(std::time::Duration::from_secs),
+        std::env::var(crate::host::GAS_LIMIT_ENV_VAR)
+            .ok()
+            .and_then(|s| u64::from_str(&s).ok())
+            .or(Some(crate::host::DEFAULT_GAS_LIMIT)),
     )
 }
 
@@ -387,6 +395,92 @@ fn ipfs_map() {
     assert!(errmsg.contains("api returned error"))
 }
 
+#[test]
+fn ipfs_map_charges_callbacks_to_the_calling_handler() {
+    let ipfs = ipfs_api::IpfsClient::default();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let user_data = store::Value::from(USER_DATA);
+
+    // Measure how much gas the callback needs for one line
+    let (mut module, store) =
+        test_valid_module_and_store(mock_data_source("wasm_test/ipfs_map.wasm"));
+    let (line, _) = make_thing("one", "eins");
+    let mut gas_per_line = 0;
+    module
+        .handle_json_callback(
+            "echoToStore",
+            &serde_json::from_str(&line).unwrap(),
+            &user_data,
+            &mut gas_per_line,
+        )
+        .expect("callback failed");
+
+    let lines = (0..10)
+        .map(|i| make_thing(&format!("id{}", i), "eins").0)
+        .collect::<Vec<_>>();
+    let hash = runtime
+        .block_on(ipfs.add(Cursor::new(lines.join("\n"))))
+        .unwrap()
+        .hash;
+    let run_ipfs_map = |module: &mut WasmiModule<_>, gas_limit: u64| {
+        let mut host_exports =
+            mock_host_exports(mock_data_source("wasm_test/ipfs_map.wasm"), store.clone());
+        host_exports.handler_gas_limit = Some(gas_limit);
+        module.ctx.host_exports = Arc::new(host_exports);
+        let host_exports = module.ctx.host_exports.clone();
+
+        // The calling handler has already used as much gas as one line needs
+        let mut gas_used = gas_per_line;
+        host_exports
+            .ipfs_map(
+                module,
+                BlockState::default(),
+                hash.clone(),
+                "echoToStore",
+                user_data.clone(),
+                vec!["json".to_owned()],
+                &mut gas_used,
+            )
+            .map(|(_, calls)| (calls, gas_used))
+    };
+
+    // The budget covers the calling handler and all ten lines
+    let (calls, gas_used) =
+        run_ipfs_map(&mut module, gas_per_line * 11).expect("ipfs_map ran out of gas");
+    assert_eq!(10, calls);
+    assert_eq!(gas_per_line * 11, gas_used);
+
+    // The budget would cover every line by itself, but not all of them on
+    // top of what the calling handler used
+    let err = run_ipfs_map(&mut module, gas_per_line * 10)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("exceeded the gas limit"));
+}
+
+#[test]
+fn ipfs_map_formats() {
+    let flags = |flags: &[&str]| {
+        IpfsMapFormat::from_flags(&flags.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    };
+    assert_eq!(Ok(IpfsMapFormat::Json), flags(&["json"]));
+    assert_eq!(Ok(IpfsMapFormat::Csv), flags(&["csv"]));
+    assert_eq!(Ok(IpfsMapFormat::Raw), flags(&["raw", "unknown"]));
+    assert!(flags(&[]).is_err());
+    assert!(flags(&["json", "csv"]).is_err());
+
+    assert_eq!(
+        serde_json::json!(["one", "two, three", ""]),
+        IpfsMapFormat::Csv
+            .line_to_json("one,\"two, three\",")
+            .unwrap()
+    );
+    assert_eq!(
+        serde_json::json!("one,\"two"),
+        IpfsMapFormat::Raw.line_to_json("one,\"two").unwrap()
+    );
+}
+
 #[test]
 fn ipfs_fail() {
     let mut module = test_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
@@ -418,6 +512,153 @@ fn crypto_keccak256() {
     );
 }
 
+#[test]
+fn ethereum_encode_decode() {
+    let host_exports = mock_host_exports(
+        mock_data_source("wasm_test/abort.wasm"),
+        Arc::new(MockStore::user_store()),
+    );
+
+    let token = Token::Tuple(vec![
+        Token::Uint(U256::from(42)),
+        Token::Address(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
+        Token::Array(vec![Token::Bool(true), Token::Bool(false)]),
+    ]);
+    let data = host_exports.ethereum_encode(token.clone());
+    let decoded = host_exports
+        .ethereum_decode("(uint256,address,bool[])".to_owned(), data)
+        .expect("failed to parse ABI type");
+    assert_eq!(decoded, Some(token));
+
+    // Data that is too short for the type can not be decoded
+    let decoded = host_exports
+        .ethereum_decode("uint256".to_owned(), vec![1, 2, 3])
+        .expect("failed to parse ABI type");
+    assert_eq!(decoded, None);
+
+    host_exports
+        .ethereum_decode("notAType".to_owned(), vec![])
+        .expect_err("expected an error for an invalid ABI type");
+}
+
+#[test]
+fn crypto_hashes() {
+    let host_exports = mock_host_exports(
+        mock_data_source("wasm_test/abort.wasm"),
+        Arc::new(MockStore::user_store()),
+    );
+    let input = b"eth".to_vec();
+
+    assert_eq!(
+        hex::encode(host_exports.crypto_sha256(input.clone())),
+        "c69ea13227bace6e1f8a06364d93f4a6f04632432b64ca9b1fc4036baea4d34c"
+    );
+    assert_eq!(
+        hex::encode(host_exports.crypto_sha3(input.clone())),
+        "4b3cdfda85c576e43c848d43fdf8e901d8d02553fec8ee56289d10b8dc47d997"
+    );
+    assert_eq!(
+        hex::encode(host_exports.crypto_ripemd160(input)),
+        "71ccfeb69d983a6af967c8afe14eeb420fa68f52"
+    );
+}
+
+#[test]
+fn crypto_secp256k1_recover() {
+    let host_exports = mock_host_exports(
+        mock_data_source("wasm_test/abort.wasm"),
+        Arc::new(MockStore::user_store()),
+    );
+    let hash =
+        hex::decode("c69ea13227bace6e1f8a06364d93f4a6f04632432b64ca9b1fc4036baea4d34c").unwrap();
+    let signature = hex::decode(
+        "63c01a2d7a85bcec3c5f10515bcbb1fc772e0353bd32f96925bd4e6e957fd15d\
+         2959e553df9dab275eedc19f857311ae8fbfa1888ece4cf635cbb8cd586f0c981b",
+    )
+    .unwrap();
+    let public_key = hex::decode(
+        "4e3b81af9c2234cad09d679ce6035ed1392347ce64ce405f5dcd36228a25de6e\
+         47fd35c4215d1edf53e6f83de344615ce719bdb0fd878f6ed76f06dd277956de",
+    )
+    .unwrap();
+
+    let recovered = host_exports
+        .crypto_secp256k1_recover(hash.clone(), signature.clone())
+        .expect("failed to recover public key");
+    assert_eq!(recovered, Some(public_key.clone()));
+
+    // `v` may also be given as 0 or 1
+    let mut raw_signature = signature.clone();
+    raw_signature[64] -= 27;
+    let recovered = host_exports
+        .crypto_secp256k1_recover(hash.clone(), raw_signature)
+        .expect("failed to recover public key");
+    assert_eq!(recovered, Some(public_key));
+
+    // An invalid signature does not recover a public key
+    let recovered = host_exports
+        .crypto_secp256k1_recover(hash.clone(), vec![0; 65])
+        .expect("failed to recover public key");
+    assert_eq!(recovered, None);
+
+    host_exports
+        .crypto_secp256k1_recover(hash.clone(), signature[0..64].to_vec())
+        .expect_err("expected an error for a short signature");
+    host_exports
+        .crypto_secp256k1_recover(hash[0..31].to_vec(), signature)
+        .expect_err("expected an error for a short hash");
+}
+
+#[test]
+fn module_pool_resets_instances() {
+    let (module, _) =
+        test_valid_module_and_store(mock_data_source("wasm_test/token_to_numeric.wasm"));
+    let ctx = module.ctx.clone();
+    let mut pool = ModulePool::new(
+        module.valid_module.clone(),
+        module.task_sink.clone(),
+        module.host_metrics.clone(),
+    );
+    pool.put(module);
+
+    let mut module = pool.get(ctx.clone()).unwrap();
+    let memory = module.memory.get(0, module.snapshot.memory.len()).unwrap();
+    let token_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.takes_val_returns_ptr("token_from_i32", RuntimeValue::from(42));
+    assert_ne!(memory, module.memory.get(0, memory.len()).unwrap());
+    pool.put(module);
+
+    // The instance we get back behaves as if it had just been started
+    let mut module = pool.get(ctx).unwrap();
+    assert_eq!(memory, module.memory.get(0, memory.len()).unwrap());
+    let token_ptr_again: AscPtr<AscEnum<EthereumValueKind>> =
+        module.takes_val_returns_ptr("token_from_i32", RuntimeValue::from(42));
+    assert_eq!(
+        RuntimeValue::from(token_ptr),
+        RuntimeValue::from(token_ptr_again)
+    );
+}
+
+#[test]
+fn module_pool_drops_instances_with_grown_memory() {
+    let (module, _) =
+        test_valid_module_and_store(mock_data_source("wasm_test/token_to_numeric.wasm"));
+    let ctx = module.ctx.clone();
+    let initial_size = module.memory.current_size();
+    let mut pool = ModulePool::new(
+        module.valid_module.clone(),
+        module.task_sink.clone(),
+        module.host_metrics.clone(),
+    );
+
+    module.memory.grow(Pages(1)).unwrap();
+    pool.put(module);
+
+    // We get a fresh instance instead of the one whose memory grew
+    let module = pool.get(ctx).unwrap();
+    assert_eq!(initial_size, module.memory.current_size());
+}
+
 #[test]
 fn token_numeric_conversion() {
     let mut module = test_module(mock_data_source("wasm_test/token_to_numeric.wasm"));
@@ -663,7 +904,12 @@ fn abort() {
         .clone()
         .invoke_export("abort", &[], &mut module)
         .unwrap_err();
-    assert_eq!(err.to_string(), "Trap: Trap { kind: Host(HostExportError(\"Mapping aborted at abort.ts, line 6, column 2, with message: not true\")) }");
+    assert_eq!(err.to_string(), "Trap: Trap { kind: Host(HostExportError(DeterministicError(\"Mapping aborted at abort.ts, line 6, column 2, with message: not true\"))) }");
+    assert!(is_deterministic_wasmi_error(&err));
+
+    let err = handler_error("Ethereum event", "abort", err);
+    assert!(DeterministicError::is_deterministic(&err));
+    assert_eq!(err.to_string(), "Failed to handle Ethereum event with handler \"abort\": Mapping aborted at abort.ts, line 6, column 2, with message: not true");
 }
 
 #[test]
@@ -702,6 +948,7 @@ fn data_source_create() {
     assert_eq!(result[0].data_source, data_source);
     assert_eq!(result[0].params, params.clone());
     assert_eq!(result[0].template.name, template);
+    assert!(result[0].context.is_none());
 
     // Test with a template that doesn't exist
     let template = String::from("nonexistent template");
@@ -719,6 +966,54 @@ fn data_source_create() {
     };
 }
 
+#[test]
+fn data_source_create_with_context() {
+    // Run the module for a data source that has a context; the host exports
+    // are called the same way a mapping calls them
+    let mut context = Entity::new();
+    context.set("decimals", 18);
+    let mut data_source = mock_data_source("wasm_test/data_source_create.wasm");
+    data_source.context = Some(context.clone());
+    let mut module = test_module(data_source);
+
+    // `dataSource.context` returns the context of the data source
+    let context_ptr: AscPtr<AscEntity> = module
+        .invoke_index(
+            DATA_SOURCE_CONTEXT,
+            RuntimeArgs::from(&[] as &[RuntimeValue]),
+        )
+        .expect("call failed")
+        .expect("call returned nothing")
+        .try_into()
+        .expect("call did not return pointer");
+    let returned: HashMap<String, store::Value> = module.asc_get(context_ptr);
+    assert_eq!(context, Entity::from(returned));
+
+    // `dataSource.createWithContext` passes the new context on to the
+    // created data source
+    let mut new_context = Entity::new();
+    new_context.set("token", "0xc0a47dFe034B400B47bDaD5FecDa2621de6c4d95");
+    new_context.set("decimals", 6);
+    let params = vec![String::from("0xc0a47dFe034B400B47bDaD5FecDa2621de6c4d95")];
+    let args = [
+        RuntimeValue::from(module.asc_new("example template")),
+        RuntimeValue::from(module.asc_new(&*params)),
+        RuntimeValue::from(module.asc_new::<AscEntity, _>(&new_context)),
+    ];
+    module
+        .invoke_index(
+            DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX,
+            RuntimeArgs::from(&args[..]),
+        )
+        .expect("call failed");
+
+    let created = &module.ctx.state.created_data_sources;
+    assert_eq!(1, created.len());
+    assert_eq!("example template", created[0].template.name);
+    assert_eq!(params, created[0].params);
+    assert_eq!(Some(new_context), created[0].context);
+}
+
 #[test]
 fn ens_name_by_hash() {
     let mut module = test_module(mock_data_source("wasm_test/ens_name_by_hash.wasm"));
@@ -818,3 +1113,62 @@ fn entity_store() {
         _ => assert!(false, "expected Insert modification"),
     }
 }
+
+#[test]
+fn store_find() {
+    let host_exports = mock_host_exports(
+        mock_data_source("wasm_test/abort.wasm"),
+        Arc::new(MockStore::user_store()),
+    );
+    let logger = Logger::root(slog::Discard, o!());
+    let mut state = BlockState::default();
+    let user_key = |id: &str| EntityKey {
+        subgraph_id: MockStore::user_subgraph_id(),
+        entity_type: "User".to_owned(),
+        entity_id: id.to_owned(),
+    };
+    let find_users = |state: &mut BlockState, name: &str, limit: u32| -> Vec<String> {
+        host_exports
+            .store_find(
+                &logger,
+                state,
+                "User".to_owned(),
+                "name".to_owned(),
+                Value::from(name),
+                limit,
+            )
+            .expect("store.find failed")
+            .iter()
+            .map(|user| user.id().unwrap())
+            .collect()
+    };
+
+    assert_eq!(
+        vec!["steve".to_owned()],
+        find_users(&mut state, "Steve", 10)
+    );
+    assert!(find_users(&mut state, "Herobrine", 10).is_empty());
+
+    // Changes made earlier in the block are visible to the lookup
+    let mut herobrine = Entity::new();
+    herobrine.set("id", "herobrine");
+    herobrine.set("name", "Steve");
+    state.entity_cache.set(user_key("herobrine"), herobrine);
+    let mut alex = Entity::new();
+    alex.set("name", "Steve");
+    state.entity_cache.set(user_key("alex"), alex);
+    state.entity_cache.remove(user_key("steve"));
+    assert_eq!(
+        vec!["alex".to_owned(), "herobrine".to_owned()],
+        find_users(&mut state, "Steve", 10)
+    );
+    assert_eq!(vec!["alex".to_owned()], find_users(&mut state, "Steve", 1));
+    assert!(find_users(&mut state, "Alex", 10).is_empty());
+
+    host_exports
+        .check_store_find_limit(1000)
+        .expect("limit of 1000 should be allowed");
+    host_exports
+        .check_store_find_limit(1001)
+        .expect_err("expected an error for a limit above 1000");
+}
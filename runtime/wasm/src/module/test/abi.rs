@@ -17,6 +17,26 @@ fn unbounded_loop() {
     );
 }
 
+#[test]
+fn gas_limit() {
+    let data_source = mock_data_source("wasm_test/non_terminating.wasm");
+    let mut module = test_module(data_source.clone());
+    let mut host_exports = mock_host_exports(data_source, Arc::new(MockStore::user_store()));
+    host_exports.handler_gas_limit = Some(1_000_000);
+    module.ctx.host_exports = Arc::new(host_exports);
+    let err = module
+        .module
+        .clone()
+        .invoke_export("loop", &[], &mut module)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Trap: Trap { kind: Host(HostExportError(DeterministicError(\"Mapping handler \
+         exceeded the gas limit of 1000000\"))) }"
+    );
+    assert!(module.gas_used > 1_000_000);
+}
+
 #[test]
 fn unbounded_recursion() {
     let mut module = test_module(mock_data_source("wasm_test/non_terminating.wasm"));
@@ -384,3 +404,97 @@ fn invalid_discriminant() {
         .expect("call did not return ptr");
     let _value: Value = module.asc_get(value_ptr);
 }
+
+/// Read the 32-bit word `offset` bytes into the object `ptr` points to.
+/// All fields of the classes we pass to handlers are pointers of that size.
+fn read_word<H: AscHeap, C>(heap: &H, ptr: AscPtr<C>, offset: u32) -> u32 {
+    let start: u32 = RuntimeValue::from(ptr).try_into().unwrap();
+    let bytes = heap.get(start + offset, 4).unwrap();
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_ptr_field<H: AscHeap, C, F>(heap: &H, ptr: AscPtr<C>, offset: u32) -> AscPtr<F> {
+    RuntimeValue::from(read_word(heap, ptr, offset))
+        .try_into()
+        .unwrap()
+}
+
+#[test]
+fn abi_ethereum_transaction_receipt() {
+    use web3::types::{Bytes, H256, U128};
+
+    let mut module = test_module(mock_data_source("wasm_test/abi_classes.wasm"));
+
+    let address = H160([7; 20]);
+    let receipt = EthereumTransactionReceiptData {
+        status: Some(U256::from(1)),
+        gas_used: U256::from(21000),
+        cumulative_gas_used: U256::from(42000),
+        logs: vec![EthereumLogData {
+            address,
+            topics: vec![H256([1; 32])],
+            data: Bytes(vec![1, 2, 3]),
+            log_index: U256::from(5),
+            transaction_log_index: U256::from(0),
+            log_type: None,
+        }],
+    };
+    let event = EthereumEventData {
+        address,
+        log_index: U256::from(5),
+        transaction_log_index: U256::from(0),
+        log_type: None,
+        block: EthereumBlockData::from(module.ctx.block.as_ref()),
+        transaction: EthereumTransactionData {
+            hash: H256([2; 32]),
+            index: U128::from(0),
+            from: H160([8; 20]),
+            to: Some(address),
+            value: U256::from(0),
+            gas_used: U256::from(21000),
+            gas_price: U256::from(1),
+            input: Bytes(vec![]),
+        },
+        params: vec![],
+        receipt: Some(receipt),
+    };
+
+    // The receipt is the last field of the event, after the address, the
+    // two log indexes, the log type, the block, the transaction and the
+    // params
+    let event_ptr: AscPtr<AscEthereumEvent<AscEthereumTransaction_0_0_2>> = module.asc_new(&event);
+    let receipt_ptr: AscPtr<AscEthereumTransactionReceipt> =
+        read_ptr_field(&module, event_ptr, 7 * 4);
+    assert!(!receipt_ptr.is_null());
+
+    // status, gasUsed, cumulativeGasUsed and logs, in that order
+    let status: BigInt = module.asc_get(read_ptr_field::<_, _, AscBigInt>(&module, receipt_ptr, 0));
+    assert_eq!(status, BigInt::from(1 as i32));
+    let gas_used: BigInt =
+        module.asc_get(read_ptr_field::<_, _, AscBigInt>(&module, receipt_ptr, 4));
+    assert_eq!(gas_used, BigInt::from(21000 as i32));
+    let cumulative_gas_used: BigInt =
+        module.asc_get(read_ptr_field::<_, _, AscBigInt>(&module, receipt_ptr, 8));
+    assert_eq!(cumulative_gas_used, BigInt::from(42000 as i32));
+
+    // `logs` is an array with a buffer and a length; the pointers to the
+    // logs follow the 8 byte header of the buffer
+    let logs: AscPtr<Array<AscPtr<AscEthereumLog>>> = read_ptr_field(&module, receipt_ptr, 12);
+    assert_eq!(read_word(&module, logs, 4), 1);
+    let buffer: AscPtr<ArrayBuffer<AscPtr<AscEthereumLog>>> = read_ptr_field(&module, logs, 0);
+    let log: AscPtr<AscEthereumLog> = read_ptr_field(&module, buffer, 8);
+    let log_address: H160 = module.asc_get(read_ptr_field::<_, _, Uint8Array>(&module, log, 0));
+    assert_eq!(log_address, address);
+    let log_index: BigInt = module.asc_get(read_ptr_field::<_, _, AscBigInt>(&module, log, 12));
+    assert_eq!(log_index, BigInt::from(5 as i32));
+
+    // Receipts from before Byzantium have no status
+    let receipt = EthereumTransactionReceiptData {
+        status: None,
+        gas_used: U256::from(21000),
+        cumulative_gas_used: U256::from(21000),
+        logs: vec![],
+    };
+    let receipt_ptr: AscPtr<AscEthereumTransactionReceipt> = module.asc_new(&receipt);
+    assert_eq!(read_word(&module, receipt_ptr, 0), 0);
+}
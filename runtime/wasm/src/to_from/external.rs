@@ -2,7 +2,8 @@ use ethabi;
 use std::collections::HashMap;
 
 use graph::components::ethereum::{
-    EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+    EthereumBlockData, EthereumCallData, EthereumEventData, EthereumLogData,
+    EthereumTransactionData, EthereumTransactionReceiptData,
 };
 use graph::data::store;
 use graph::prelude::serde_json;
@@ -355,6 +356,44 @@ where
             block: heap.asc_new(&self.block),
             transaction: heap.asc_new::<T, EthereumTransactionData>(&self.transaction),
             params: heap.asc_new(self.params.as_slice()),
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| heap.asc_new(receipt))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
+impl ToAscObj<AscEthereumLog> for EthereumLogData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumLog {
+        AscEthereumLog {
+            address: heap.asc_new(&self.address),
+            topics: heap.asc_new(self.topics.as_slice()),
+            data: heap.asc_new(&*self.data.0),
+            log_index: heap.asc_new(&BigInt::from_unsigned_u256(&self.log_index)),
+            transaction_log_index: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.transaction_log_index)),
+            log_type: self
+                .log_type
+                .clone()
+                .map(|log_type| heap.asc_new(&log_type))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
+impl ToAscObj<AscEthereumTransactionReceipt> for EthereumTransactionReceiptData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumTransactionReceipt {
+        AscEthereumTransactionReceipt {
+            status: self
+                .status
+                .map(|status| heap.asc_new(&BigInt::from_unsigned_u256(&status)))
+                .unwrap_or_else(|| AscPtr::null()),
+            gas_used: heap.asc_new(&BigInt::from_unsigned_u256(&self.gas_used)),
+            cumulative_gas_used: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.cumulative_gas_used)),
+            logs: heap.asc_new(self.logs.as_slice()),
         }
     }
 }
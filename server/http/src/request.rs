@@ -3,18 +3,113 @@ use graphql_parser;
 use hyper::Chunk;
 
 use graph::components::server::query::GraphQLServerError;
+use graph::data::subgraph::schema::SubgraphPersistedQueryEntity;
 use graph::prelude::*;
 
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
     body: Chunk,
     schema: Arc<Schema>,
+    persisted_queries: Option<PersistedQueryLookup>,
+    persisted_only: bool,
 }
 
 impl GraphQLRequest {
     /// Creates a new GraphQLRequest future based on an HTTP request and a result sender.
     pub fn new(body: Chunk, schema: Arc<Schema>) -> Self {
-        GraphQLRequest { body, schema }
+        GraphQLRequest {
+            body,
+            schema,
+            persisted_queries: None,
+            persisted_only: false,
+        }
+    }
+
+    /// Resolve requests that refer to a persisted query by its hash with
+    /// `lookup`. With `persisted_only`, requests that send query text that
+    /// is not also persisted are rejected.
+    pub fn with_persisted_queries(
+        mut self,
+        lookup: PersistedQueryLookup,
+        persisted_only: bool,
+    ) -> Self {
+        self.persisted_queries = Some(lookup);
+        self.persisted_only = persisted_only;
+        self
+    }
+
+    /// Determine the query text for a request, either from its `query`
+    /// field or, for persisted queries, from the hash in its
+    /// `extensions.persistedQuery.sha256Hash` field
+    fn query_text(
+        &self,
+        obj: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<String, GraphQLServerError> {
+        // Ensure the "query" field is a string, if present
+        let query_string = match obj.get("query") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(query_value) => Some(query_value.as_str().ok_or_else(|| {
+                GraphQLServerError::ClientError(String::from("The\"query\" field is not a string"))
+            })?),
+        };
+
+        let hash = match obj
+            .get("extensions")
+            .and_then(|extensions| extensions.get("persistedQuery"))
+        {
+            None | Some(serde_json::Value::Null) => None,
+            Some(persisted_query) => Some(
+                persisted_query
+                    .get("sha256Hash")
+                    .and_then(|hash| hash.as_str())
+                    .ok_or_else(|| {
+                        GraphQLServerError::ClientError(String::from(
+                            "The \"persistedQuery\" extension must have a \"sha256Hash\" string",
+                        ))
+                    })?,
+            ),
+        };
+
+        match (query_string, hash) {
+            (Some(query_string), None) => {
+                if self.persisted_only {
+                    let hash = SubgraphPersistedQueryEntity::hash(query_string);
+                    self.persisted_query(&hash)
+                } else {
+                    Ok(query_string.to_owned())
+                }
+            }
+            (query_string, Some(hash)) => {
+                if let Some(query_string) = query_string {
+                    if SubgraphPersistedQueryEntity::hash(query_string) != hash.to_lowercase() {
+                        return Err(GraphQLServerError::ClientError(String::from(
+                            "The \"query\" field does not match the \"sha256Hash\" of the persisted query",
+                        )));
+                    }
+                }
+                self.persisted_query(&hash.to_lowercase())
+            }
+            (None, None) => Err(GraphQLServerError::ClientError(String::from(
+                "The \"query\" field missing in request data",
+            ))),
+        }
+    }
+
+    fn persisted_query(&self, hash: &str) -> Result<String, GraphQLServerError> {
+        let lookup = self.persisted_queries.as_ref().ok_or_else(|| {
+            GraphQLServerError::ClientError(String::from("Persisted queries are not supported"))
+        })?;
+        lookup(hash)
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?
+            .ok_or_else(|| {
+                if self.persisted_only {
+                    GraphQLServerError::ClientError(String::from(
+                        "Only persisted queries are allowed and the query is not persisted",
+                    ))
+                } else {
+                    GraphQLServerError::ClientError(format!("Persisted query not found: {}", hash))
+                }
+            })
     }
 }
 
@@ -35,20 +130,10 @@ impl Future for GraphQLRequest {
             GraphQLServerError::ClientError(String::from("Request data is not an object"))
         })?;
 
-        // Ensure the JSON data has a "query" field
-        let query_value = obj.get("query").ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from(
-                "The \"query\" field missing in request data",
-            ))
-        })?;
+        let query_string = self.query_text(obj)?;
 
-        // Ensure the "query" field is a string
-        let query_string = query_value.as_str().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("The\"query\" field is not a string"))
-        })?;
-
-        // Parse the "query" field of the JSON body
-        let document = graphql_parser::parse_query(query_string)
+        // Parse the query text
+        let document = graphql_parser::parse_query(&query_string)
             .map_err(|e| GraphQLServerError::from(QueryError::from(e)))?;
 
         // Parse the "variables" field of the JSON body, if present
@@ -79,6 +164,7 @@ mod tests {
     use hyper;
     use std::collections::{BTreeMap, HashMap};
 
+    use graph::data::subgraph::schema::SubgraphPersistedQueryEntity;
     use graph::prelude::*;
 
     use super::GraphQLRequest;
@@ -211,4 +297,93 @@ mod tests {
         assert_eq!(query.document, expected_query);
         assert_eq!(query.variables, Some(expected_variables));
     }
+
+    fn persisted_queries(persisted: &'static [&'static str]) -> PersistedQueryLookup {
+        Box::new(move |hash| {
+            Ok(persisted
+                .iter()
+                .find(|query| SubgraphPersistedQueryEntity::hash(query) == hash)
+                .map(|query| query.to_string()))
+        })
+    }
+
+    #[test]
+    fn resolves_persisted_queries() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let hash = SubgraphPersistedQueryEntity::hash("{ user { name } }");
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(format!(
+                "{{\"extensions\": {{\"persistedQuery\": {{\"sha256Hash\": \"{}\"}}}}}}",
+                hash
+            )),
+            Arc::new(schema),
+        )
+        .with_persisted_queries(persisted_queries(&["{ user { name } }"]), false);
+        let query = request.wait().expect("Should accept persisted queries");
+        assert_eq!(
+            query.document,
+            graphql_parser::parse_query("{ user { name } }").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_persisted_queries() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let hash = SubgraphPersistedQueryEntity::hash("{ user { id } }");
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(format!(
+                "{{\"extensions\": {{\"persistedQuery\": {{\"sha256Hash\": \"{}\"}}}}}}",
+                hash
+            )),
+            Arc::new(schema),
+        )
+        .with_persisted_queries(persisted_queries(&["{ user { name } }"]), false);
+        request
+            .wait()
+            .expect_err("Should reject queries that are not persisted");
+    }
+
+    #[test]
+    fn rejects_query_text_that_does_not_match_hash() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let hash = SubgraphPersistedQueryEntity::hash("{ user { name } }");
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from(format!(
+                "{{\"query\": \"{{ user {{ id }} }}\", \
+                 \"extensions\": {{\"persistedQuery\": {{\"sha256Hash\": \"{}\"}}}}}}",
+                hash
+            )),
+            Arc::new(schema),
+        )
+        .with_persisted_queries(persisted_queries(&["{ user { name } }"]), false);
+        request
+            .wait()
+            .expect_err("Should reject query text that does not match the hash");
+    }
+
+    #[test]
+    fn persisted_only_rejects_ad_hoc_queries() {
+        let schema =
+            Schema::parse(EXAMPLE_SCHEMA, SubgraphDeploymentId::new("test").unwrap()).unwrap();
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from("{\"query\": \"{ user { id } }\"}"),
+            Arc::new(schema.clone()),
+        )
+        .with_persisted_queries(persisted_queries(&["{ user { name } }"]), true);
+        request
+            .wait()
+            .expect_err("Should reject queries that are not persisted");
+
+        let request = GraphQLRequest::new(
+            hyper::Chunk::from("{\"query\": \"{ user { name } }\"}"),
+            Arc::new(schema),
+        )
+        .with_persisted_queries(persisted_queries(&["{ user { name } }"]), true);
+        request
+            .wait()
+            .expect("Should accept the text of persisted queries");
+    }
 }
@@ -179,6 +179,15 @@ where
         }
     }
 
+    /// Look up the queries persisted for the subgraphs that the deployment
+    /// `id` belongs to in the store
+    fn persisted_query_lookup(&self, id: &SubgraphDeploymentId) -> PersistedQueryLookup {
+        let store = self.store.clone();
+        let id = id.clone();
+
+        Box::new(move |hash| store.persisted_query(&id, hash))
+    }
+
     fn handle_graphql_query(
         &self,
         id: &SubgraphDeploymentId,
@@ -212,12 +221,17 @@ where
             }
         };
 
+        let persisted_queries = self.persisted_query_lookup(id);
+
         let start = Instant::now();
         Box::new(
             request_body
                 .concat2()
                 .map_err(|_| GraphQLServerError::from("Failed to read request body"))
-                .and_then(move |body| GraphQLRequest::new(body, schema))
+                .and_then(move |body| {
+                    GraphQLRequest::new(body, schema)
+                        .with_persisted_queries(persisted_queries, *PERSISTED_QUERIES_ONLY)
+                })
                 .and_then(move |query| {
                     // Run the query using the query runner
                     service
@@ -470,6 +484,8 @@ mod tests {
             schema,
             data_sources: vec![],
             templates: vec![],
+            graft: None,
+            confirmations: None,
         };
 
         let graphql_runner = Arc::new(TestGraphQlRunner);
@@ -537,6 +553,8 @@ mod tests {
             schema,
             data_sources: vec![],
             templates: vec![],
+            graft: None,
+            confirmations: None,
         };
         let graphql_runner = Arc::new(TestGraphQlRunner);
 
@@ -88,6 +88,8 @@ mod test {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            graft: None,
+            confirmations: None,
         };
 
         let store = Arc::new(MockStore::new(vec![(id, schema)]));
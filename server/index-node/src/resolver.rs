@@ -59,6 +59,9 @@ struct EthereumIndexingStatus {
     earliest_block: Option<EthereumBlock>,
     /// The latest block that the subgraph has synced to.
     latest_block: Option<EthereumBlock>,
+    /// The most recent block that is no longer expected to be reverted, given the reorg
+    /// threshold and the confirmations the subgraph waits for.
+    final_block: Option<EthereumBlock>,
 }
 
 /// Indexing status information for different chains (only Ethereum right now).
@@ -91,6 +94,10 @@ impl From<ChainIndexingStatus> for q::Value {
                     "latestBlock",
                     inner.latest_block.map_or(q::Value::Null, q::Value::from),
                 ),
+                (
+                    "finalBlock",
+                    inner.final_block.map_or(q::Value::Null, q::Value::from),
+                ),
             ]),
         }
     }
@@ -169,7 +176,7 @@ impl TryFromValue for IndexingStatusWithoutNode {
             subgraph: value.get_required("id")?,
             synced: value.get_required("synced")?,
             failed: value.get_required("failed")?,
-            error: None,
+            error: value.get_optional("failureReason")?,
             chains: vec![ChainIndexingStatus::Ethereum(EthereumIndexingStatus {
                 network: value
                     .get_required::<q::Value>("manifest")?
@@ -179,6 +186,7 @@ impl TryFromValue for IndexingStatusWithoutNode {
                 chain_head_block: Self::block_from_value(value, "ethereumHeadBlock")?,
                 earliest_block: Self::block_from_value(value, "earliestEthereumBlock")?,
                 latest_block: Self::block_from_value(value, "latestEthereumBlock")?,
+                final_block: Self::block_from_value(value, "finalEthereumBlock")?,
             })],
         })
     }
@@ -295,12 +303,15 @@ where
                     id
                     synced
                     failed
+                    failureReason
                     ethereumHeadBlockNumber
                     ethereumHeadBlockHash
                     earliestEthereumBlockHash
                     earliestEthereumBlockNumber
                     latestEthereumBlockHash
                     latestEthereumBlockNumber
+                    finalEthereumBlockHash
+                    finalEthereumBlockNumber
                     manifest {
                       dataSources(first: 1) {
                         network
@@ -388,12 +399,15 @@ where
                         id
                         synced
                         failed
+                        failureReason
                         ethereumHeadBlockNumber
                         ethereumHeadBlockHash
                         earliestEthereumBlockHash
                         earliestEthereumBlockNumber
                         latestEthereumBlockHash
                         latestEthereumBlockNumber
+                        finalEthereumBlockHash
+                        finalEthereumBlockNumber
                         manifest {
                           dataSources(first: 1) {
                             network
@@ -509,6 +523,7 @@ where
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
         _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _block: BlockNumber,
         _max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         match (parent, object_type.name(), field.as_str()) {
@@ -555,6 +570,7 @@ where
         object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
         _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
         match (parent, object_type.name(), field.name.as_str()) {
             (Some(status), "EthereumBlock", "chainHeadBlock") => Ok(status
@@ -569,6 +585,10 @@ where
                 .get_optional("latestBlock")
                 .map_err(|e| QueryExecutionError::StoreError(e))?
                 .unwrap_or(q::Value::Null)),
+            (Some(status), "EthereumBlock", "finalBlock") => Ok(status
+                .get_optional("finalBlock")
+                .map_err(|e| QueryExecutionError::StoreError(e))?
+                .unwrap_or(q::Value::Null)),
 
             // Unknown fields on other types
             (_, type_name, name) => Err(QueryExecutionError::UnknownField(
@@ -4,6 +4,7 @@ extern crate lazy_static;
 extern crate serde;
 
 use graph::prelude::serde_json;
+use graph::prelude::web3::types::H256;
 use graph::prelude::{JsonRpcServer as JsonRpcServerTrait, *};
 use jsonrpc_http_server::{
     jsonrpc_core::{self, Compatibility, IoHandler, Params, Value},
@@ -27,6 +28,11 @@ const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_CREATE_ERROR: i64 = 2;
 const JSON_RPC_REASSIGN_ERROR: i64 = 3;
+const JSON_RPC_PAUSE_ERROR: i64 = 4;
+const JSON_RPC_RESUME_ERROR: i64 = 5;
+const JSON_RPC_REWIND_ERROR: i64 = 6;
+const JSON_RPC_PERSIST_QUERY_ERROR: i64 = 7;
+const JSON_RPC_REMOVE_PERSISTED_QUERY_ERROR: i64 = 8;
 
 #[derive(Debug, Deserialize)]
 struct SubgraphCreateParams {
@@ -51,6 +57,35 @@ struct SubgraphReassignParams {
     node_id: NodeId,
 }
 
+#[derive(Debug, Deserialize)]
+struct SubgraphPauseParams {
+    deployment: SubgraphDeploymentId,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphResumeParams {
+    deployment: SubgraphDeploymentId,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphRewindParams {
+    deployment: SubgraphDeploymentId,
+    block_hash: H256,
+    block_number: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphPersistQueryParams {
+    name: SubgraphName,
+    query: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphRemovePersistedQueryParams {
+    name: SubgraphName,
+    sha256_hash: String,
+}
+
 pub struct JsonRpcServer<R> {
     registrar: Arc<R>,
     http_port: u16,
@@ -173,6 +208,145 @@ where
                 .flatten(),
         )
     }
+
+    /// Handler for the `subgraph_pause` endpoint.
+    fn pause_handler(
+        &self,
+        params: SubgraphPauseParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_pause request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .pause_subgraph(params.deployment.clone())
+                .map_err(move |e| {
+                    error!(logger, "subgraph_pause failed";
+                           "error" => format!("{:?}", e),
+                           "params" => format!("{:?}", params));
+                    if let SubgraphRegistrarError::Unknown(_) = e {
+                        json_rpc_error(JSON_RPC_PAUSE_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_PAUSE_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Value::Null),
+        )
+    }
+
+    /// Handler for the `subgraph_resume` endpoint.
+    fn resume_handler(
+        &self,
+        params: SubgraphResumeParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_resume request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .resume_subgraph(params.deployment.clone())
+                .map_err(move |e| {
+                    error!(logger, "subgraph_resume failed";
+                           "error" => format!("{:?}", e),
+                           "params" => format!("{:?}", params));
+                    if let SubgraphRegistrarError::Unknown(_) = e {
+                        json_rpc_error(JSON_RPC_RESUME_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_RESUME_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Value::Null),
+        )
+    }
+
+    /// Handler for the `subgraph_rewind` endpoint.
+    fn rewind_handler(
+        &self,
+        params: SubgraphRewindParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_rewind request"; "params" => format!("{:?}", params));
+
+        let block_ptr = EthereumBlockPointer::from((params.block_hash, params.block_number));
+
+        Box::new(
+            self.registrar
+                .rewind_subgraph(params.deployment.clone(), block_ptr)
+                .map_err(move |e| {
+                    error!(logger, "subgraph_rewind failed";
+                           "error" => format!("{:?}", e),
+                           "params" => format!("{:?}", params));
+                    if let SubgraphRegistrarError::Unknown(_) = e {
+                        json_rpc_error(JSON_RPC_REWIND_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_REWIND_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Value::Null),
+        )
+    }
+
+    /// Handler for the `subgraph_persist_query` endpoint.
+    fn persist_query_handler(
+        &self,
+        params: SubgraphPersistQueryParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_persist_query request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .persist_query(params.name.clone(), params.query.clone())
+                .map_err(move |e| {
+                    error!(logger, "subgraph_persist_query failed";
+                           "error" => format!("{:?}", e),
+                           "params" => format!("{:?}", params));
+                    if let SubgraphRegistrarError::Unknown(_) = e {
+                        json_rpc_error(JSON_RPC_PERSIST_QUERY_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_PERSIST_QUERY_ERROR, e.to_string())
+                    }
+                })
+                .map(|query_hash| {
+                    let mut map = BTreeMap::new();
+                    map.insert("sha256Hash", query_hash);
+                    serde_json::to_value(map).expect("invalid persisted query hash")
+                }),
+        )
+    }
+
+    /// Handler for the `subgraph_remove_persisted_query` endpoint.
+    fn remove_persisted_query_handler(
+        &self,
+        params: SubgraphRemovePersistedQueryParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_remove_persisted_query request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .remove_persisted_query(params.name.clone(), params.sha256_hash.clone())
+                .map_err(move |e| {
+                    error!(logger, "subgraph_remove_persisted_query failed";
+                           "error" => format!("{:?}", e),
+                           "params" => format!("{:?}", params));
+                    if let SubgraphRegistrarError::Unknown(_) = e {
+                        json_rpc_error(
+                            JSON_RPC_REMOVE_PERSISTED_QUERY_ERROR,
+                            "internal error".to_owned(),
+                        )
+                    } else {
+                        json_rpc_error(JSON_RPC_REMOVE_PERSISTED_QUERY_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Value::Null),
+        )
+    }
 }
 
 impl<R> JsonRpcServerTrait<R> for JsonRpcServer<R>
@@ -244,6 +418,51 @@ where
                 .and_then(move |params| me.reassign_handler(params))
         });
 
+        let me = arc_self.clone();
+        handler.add_method("subgraph_pause", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.pause_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_resume", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.resume_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_rewind", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.rewind_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_persist_query", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.persist_query_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_remove_persisted_query", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.remove_persisted_query_handler(params))
+        });
+
         ServerBuilder::new(handler)
             // Enable REST API:
             // POST /<method>/<param1>/<param2>
@@ -10,6 +10,7 @@ use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
 use tokio_tungstenite::WebSocketStream;
 use uuid::Uuid;
 
+use graph::data::subgraph::schema::SubgraphPersistedQueryEntity;
 use graph::prelude::serde_json;
 use graph::prelude::*;
 
@@ -163,6 +164,7 @@ pub struct GraphQlConnection<Q, S> {
     graphql_runner: Arc<Q>,
     stream: WebSocketStream<S>,
     schema: Arc<Schema>,
+    persisted_queries: PersistedQueryLookup,
 }
 
 impl<Q, S> GraphQlConnection<Q, S>
@@ -176,6 +178,7 @@ where
         schema: Arc<Schema>,
         stream: WebSocketStream<S>,
         graphql_runner: Arc<Q>,
+        persisted_queries: PersistedQueryLookup,
     ) -> Self {
         GraphQlConnection {
             id: Uuid::new_v4().to_string(),
@@ -183,6 +186,7 @@ where
             graphql_runner,
             stream,
             schema,
+            persisted_queries,
         }
     }
 
@@ -193,6 +197,7 @@ where
         connection_id: String,
         schema: Arc<Schema>,
         graphql_runner: Arc<Q>,
+        persisted_queries: PersistedQueryLookup,
     ) -> impl Future<Item = (), Error = WsError> {
         let mut operations = Operations::new(msg_sink.clone());
 
@@ -251,6 +256,32 @@ where
                         }
                     }
 
+                    // Respond with a GQL_ERROR if only persisted queries are
+                    // allowed and the query is not one of them
+                    if *PERSISTED_QUERIES_ONLY {
+                        let hash = SubgraphPersistedQueryEntity::hash(&payload.query);
+                        match persisted_queries(&hash) {
+                            Ok(Some(_)) => (),
+                            Ok(None) => {
+                                return send_error_string(
+                                    &msg_sink,
+                                    id.clone(),
+                                    String::from(
+                                        "Only persisted queries are allowed and the query \
+                                         is not persisted",
+                                    ),
+                                );
+                            }
+                            Err(e) => {
+                                return send_error_string(
+                                    &msg_sink,
+                                    id.clone(),
+                                    format!("Failed to look up persisted query: {}", e),
+                                );
+                            }
+                        }
+                    }
+
                     // Parse the GraphQL query document; respond with a GQL_ERROR if
                     // the query is invalid
                     let query = match parse_query(&payload.query) {
@@ -384,6 +415,7 @@ where
             self.id.clone(),
             self.schema.clone(),
             self.graphql_runner.clone(),
+            self.persisted_queries,
         );
 
         // Send outgoing messages asynchronously
@@ -175,12 +175,20 @@ where
                                 }
                             };
 
+                            // Look up persisted queries for the subgraph
+                            let store = store2.clone();
+                            let id = subgraph_id.clone();
+                            let persisted_queries = Box::new(move |hash: &str| {
+                                store.persisted_query(&id, hash)
+                            });
+
                             // Spawn a GraphQL over WebSocket connection
                             let service = GraphQlConnection::new(
                                 &logger2,
                                 schema,
                                 ws_stream,
                                 graphql_runner.clone(),
+                                persisted_queries,
                             );
                             tokio::spawn(service.into_future());
                         }
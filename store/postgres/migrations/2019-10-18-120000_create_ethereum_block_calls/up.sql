@@ -0,0 +1,10 @@
+/**************************************************************
+* CREATE TABLES
+**************************************************************/
+-- Stores all calls made in a block, as found by tracing the block
+CREATE TABLE IF NOT EXISTS ethereum_block_calls (
+    block_hash VARCHAR PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    network_name VARCHAR NOT NULL REFERENCES ethereum_networks (name),
+    calls JSONB NOT NULL
+);
@@ -0,0 +1,5 @@
+/**************************************************************
+* DROP TABLES
+**************************************************************/
+DROP TABLE ethereum_range_scans;
+DROP TABLE ethereum_transaction_receipts;
@@ -0,0 +1,23 @@
+/**************************************************************
+* CREATE TABLES
+**************************************************************/
+-- Stores transaction receipts that were loaded without the receipts of the
+-- other transactions in their block
+CREATE TABLE IF NOT EXISTS ethereum_transaction_receipts (
+    block_hash VARCHAR NOT NULL,
+    transaction_hash VARCHAR NOT NULL,
+    network_name VARCHAR NOT NULL REFERENCES ethereum_networks (name),
+    receipt JSONB NOT NULL,
+    PRIMARY KEY (block_hash, transaction_hash)
+);
+
+-- Stores what eth_getLogs and trace_filter requests found in a range of
+-- blocks, keyed by the filter of the request
+CREATE TABLE IF NOT EXISTS ethereum_range_scans (
+    network_name VARCHAR NOT NULL REFERENCES ethereum_networks (name),
+    scan_filter VARCHAR NOT NULL,
+    start_block BIGINT NOT NULL,
+    end_block BIGINT NOT NULL,
+    results JSONB NOT NULL,
+    PRIMARY KEY (network_name, scan_filter, start_block, end_block)
+);
@@ -9,12 +9,7 @@ use std::ops::{Bound, RangeBounds, RangeFrom};
 
 use crate::history_event::HistoryEvent;
 
-/// The type we use for block numbers. This has to be a signed integer type
-/// since Postgres does not support unsigned integer types. But 2G ought to
-/// be enough for everybody
-pub type BlockNumber = i32;
-
-pub const BLOCK_NUMBER_MAX: BlockNumber = std::i32::MAX;
+pub use graph::prelude::{BlockNumber, BLOCK_NUMBER_MAX};
 
 /// The name of the column in which we store the block range
 pub(crate) const BLOCK_RANGE_COLUMN: &str = "block_range";
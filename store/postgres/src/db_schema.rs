@@ -18,6 +18,34 @@ table! {
     }
 }
 
+table! {
+    ethereum_block_calls (block_hash) {
+        block_hash -> Varchar,
+        block_number -> BigInt,
+        network_name -> Varchar, // REFERENCES ethereum_networks (name),
+        calls -> Jsonb,
+    }
+}
+
+table! {
+    ethereum_transaction_receipts (block_hash, transaction_hash) {
+        block_hash -> Varchar,
+        transaction_hash -> Varchar,
+        network_name -> Varchar, // REFERENCES ethereum_networks (name),
+        receipt -> Jsonb,
+    }
+}
+
+table! {
+    ethereum_range_scans (network_name, scan_filter, start_block, end_block) {
+        network_name -> Varchar, // REFERENCES ethereum_networks (name),
+        scan_filter -> Varchar,
+        start_block -> BigInt,
+        end_block -> BigInt,
+        results -> Jsonb,
+    }
+}
+
 table! {
     large_notifications(id) {
         id -> Integer,
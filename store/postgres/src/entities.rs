@@ -42,13 +42,13 @@ use std::time::Instant;
 use graph::data::schema::Schema as SubgraphSchema;
 use graph::data::subgraph::schema::SUBGRAPHS_ID;
 use graph::prelude::{
-    debug, format_err, info, serde_json, warn, AttributeIndexDefinition, Entity, EntityChange,
-    EntityChangeOperation, EntityFilter, EntityKey, EntityModification, Error,
-    EthereumBlockPointer, Logger, QueryExecutionError, StoreError, StoreEvent,
-    SubgraphDeploymentId, SubgraphDeploymentStore, ValueType,
+    debug, format_err, info, serde_json, warn, AttributeIndexDefinition, Entity, EntityAggregate,
+    EntityChange, EntityChangeOperation, EntityFilter, EntityKey, EntityModification, EntityRange,
+    EntityWindow, Error, EthereumBlockPointer, Logger, QueryExecutionError, StoreError, StoreEvent,
+    SubgraphDeploymentId, SubgraphDeploymentStore, Value, ValueType,
 };
 
-use crate::block_range::{block_number, BlockNumber};
+use crate::block_range::{block_number, BlockNumber, BLOCK_NUMBER_MAX};
 use crate::filter::build_filter;
 use crate::history_event::HistoryEvent;
 use crate::jsonb::PgJsonbExpressionMethods as _;
@@ -417,23 +417,111 @@ impl Connection {
         }
     }
 
+    /// Query for entities; if `ids_by_bytes` is `true`, string ids are
+    /// compared byte by byte rather than with the collation of the database
     pub(crate) fn query(
         &self,
         entity_types: Vec<String>,
         filter: Option<EntityFilter>,
         order: Option<(String, ValueType, &str)>,
-        first: Option<u32>,
-        skip: u32,
+        range: EntityRange,
         block: BlockNumber,
+        ids_by_bytes: bool,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         match &*self.storage {
-            Storage::Json(json) => json.query(&self.conn, entity_types, filter, order, first, skip),
+            Storage::Json(_) if block != BLOCK_NUMBER_MAX => {
+                Err(QueryExecutionError::NotSupported(
+                    "querying entities at a block requires relational storage; \
+                     this subgraph stores its entities as JSONB"
+                        .to_owned(),
+                ))
+            }
+            Storage::Json(_) if range.has_cursor() || range.reverse => {
+                Err(QueryExecutionError::NotSupported(
+                    "paginating with cursors requires relational storage; \
+                     this subgraph stores its entities as JSONB"
+                        .to_owned(),
+                ))
+            }
+            Storage::Json(_) if filter.as_ref().map_or(false, has_fulltext) => {
+                Err(QueryExecutionError::NotSupported(
+                    "fulltext search requires relational storage; \
+                     this subgraph stores its entities as JSONB"
+                        .to_owned(),
+                ))
+            }
+            Storage::Json(json) => json.query(
+                &self.conn,
+                entity_types,
+                filter,
+                order,
+                range.first,
+                range.skip,
+                ids_by_bytes,
+            ),
+            Storage::Relational(layout) if ids_by_bytes => {
+                layout.query_in_byte_order(&self.conn, entity_types, filter, order, range, block)
+            }
             Storage::Relational(layout) => {
-                layout.query(&self.conn, entity_types, filter, order, first, skip, block)
+                layout.query(&self.conn, entity_types, filter, order, range, block)
             }
         }
     }
 
+    pub(crate) fn aggregate(
+        &self,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        aggregates: &[EntityAggregate],
+        block: BlockNumber,
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        match &*self.storage {
+            Storage::Json(_) => Err(QueryExecutionError::NotSupported(
+                "aggregations require relational storage; \
+                 this subgraph stores its entities as JSONB"
+                    .to_owned(),
+            )),
+            Storage::Relational(layout) => {
+                layout.aggregate(&self.conn, entity_types, filter, aggregates, block)
+            }
+        }
+    }
+
+    /// Return `true` if `query_window` can answer a windowed query for
+    /// `entity_types` in one go; otherwise, callers need to query each parent
+    /// in the window separately
+    pub(crate) fn supports_window(&self, entity_types: &[String], window: &EntityWindow) -> bool {
+        match &*self.storage {
+            Storage::Json(_) => false,
+            Storage::Relational(layout) => layout.supports_window(entity_types, window),
+        }
+    }
+
+    pub(crate) fn query_window(
+        &self,
+        entity_type: &str,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str)>,
+        first: Option<u32>,
+        skip: u32,
+        block: BlockNumber,
+        window: &EntityWindow,
+    ) -> Result<Vec<(String, Entity)>, QueryExecutionError> {
+        match &*self.storage {
+            Storage::Json(_) => unreachable!("JSONB storage does not support windowed queries"),
+            Storage::Relational(layout) => layout.query_window(
+                &self.conn,
+                entity_type,
+                filter,
+                order,
+                first,
+                skip,
+                block,
+                window,
+            ),
+        }
+    }
+
     pub(crate) fn conflicting_entity(
         &self,
         entity_id: &String,
@@ -562,6 +650,39 @@ impl Connection {
         }
     }
 
+    /// Revert the changes from all blocks after `block_ptr` at once. Only
+    /// relational storage can tell which changes those are from the block
+    /// numbers alone; JSONB storage keys its history by block hash
+    pub(crate) fn rewind(
+        &self,
+        block_ptr: &EthereumBlockPointer,
+    ) -> Result<(StoreEvent, i32), StoreError> {
+        let subgraph = self.storage.subgraph();
+
+        let (event, count) = match &*self.storage {
+            Storage::Json(_) => {
+                return Err(StoreError::Unknown(format_err!(
+                    "rewinding requires relational storage; \
+                     subgraph {} stores its entities as JSONB",
+                    subgraph
+                )))
+            }
+            Storage::Relational(layout) => {
+                let block: BlockNumber = block_ptr.number.try_into().unwrap();
+                layout.revert_block(&self.conn, block + 1)?
+            }
+        };
+        match &*self.metadata {
+            Storage::Json(json) => {
+                let (meta_event, _) = json.rewind_meta(&self.conn, subgraph, block_ptr.number)?;
+                Ok((event.extend(meta_event), count))
+            }
+            Storage::Relational(_) => unreachable!(
+                "Storing the subgraph of subgraphs in a relational schema is not supported"
+            ),
+        }
+    }
+
     pub(crate) fn update_entity_count(&self, count: i32) -> Result<(), StoreError> {
         if count == 0 {
             return Ok(());
@@ -770,7 +891,14 @@ impl Connection {
     /// It is an error if `deployment_schemas` already has an entry for this
     /// `subgraph_id`. Note that `self` must be a connection for the subgraph
     /// of subgraphs
-    pub(crate) fn create_schema(&self, schema: &SubgraphSchema) -> Result<(), StoreError> {
+    /// Create the database schema for the deployment `schema.id`. If `graft`
+    /// is given, fill it with the entities of the graft base as of the graft
+    /// block
+    pub(crate) fn create_schema(
+        &self,
+        schema: &SubgraphSchema,
+        graft: Option<(&Storage, BlockNumber)>,
+    ) -> Result<(), StoreError> {
         use self::public::DeploymentSchemaVersion as v;
 
         assert_eq!(
@@ -804,15 +932,29 @@ impl Connection {
         let query = format!("create schema {}", schema_name);
         self.conn.batch_execute(&*query)?;
 
-        match *GRAPH_STORAGE_SCHEME {
-            v::Relational => Layout::create_relational_schema(
+        match (*GRAPH_STORAGE_SCHEME, graft) {
+            (v::Relational, None) => Layout::create_relational_schema(
                 &self.conn,
                 &schema_name,
                 schema.id.clone(),
                 &schema.document,
             )
             .map(|_| ()),
-            v::Split => create_split_schema(&self.conn, &schema_name),
+            (v::Relational, Some((Storage::Relational(base), block))) => {
+                let layout = Layout::create_relational_schema(
+                    &self.conn,
+                    &schema_name,
+                    schema.id.clone(),
+                    &schema.document,
+                )?;
+                layout.copy_from(&self.conn, base, block)?;
+                Storage::Relational(layout).recount_entities(&self.conn)
+            }
+            (_, Some(_)) => Err(StoreError::Unknown(format_err!(
+                "grafting requires relational storage for both the new deployment \
+                 and the graft base"
+            ))),
+            (v::Split, None) => create_split_schema(&self.conn, &schema_name),
         }
     }
 
@@ -836,6 +978,16 @@ fn find_schema(
         .optional()?)
 }
 
+/// Return `true` if `filter` searches the text of entities with a
+/// fulltext search declared with `@fulltext`
+fn has_fulltext(filter: &EntityFilter) -> bool {
+    match filter {
+        EntityFilter::And(filters) | EntityFilter::Or(filters) => filters.iter().any(has_fulltext),
+        EntityFilter::Fulltext(..) => true,
+        _ => false,
+    }
+}
+
 fn entity_to_json(key: &EntityKey, entity: &Entity) -> Result<serde_json::Value, Error> {
     serde_json::to_value(entity).map_err(|e| {
         format_err!(
@@ -881,6 +1033,7 @@ impl JsonStorage {
         order: Option<(String, ValueType, &str)>,
         first: Option<u32>,
         skip: u32,
+        ids_by_bytes: bool,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         let entities = self.clone();
         let mut query = if entity_types.len() == 1 {
@@ -913,6 +1066,7 @@ impl JsonStorage {
                 ValueType::BigInt | ValueType::BigDecimal => "::numeric",
                 ValueType::Boolean => "::boolean",
                 ValueType::Bytes => "",
+                ValueType::ID if ids_by_bytes => " collate \"C\"",
                 ValueType::ID => "",
                 ValueType::Int => "::bigint",
                 ValueType::String => "",
@@ -944,7 +1098,11 @@ impl JsonStorage {
                 ),
             };
         }
-        query = query.then_order_by(entities.id.asc());
+        query = if ids_by_bytes {
+            query.then_order_by(sql::<Text>("id collate \"C\""))
+        } else {
+            query.then_order_by(entities.id.asc())
+        };
 
         if let Some(first) = first {
             query = query.limit(first as i64);
@@ -1198,6 +1356,37 @@ impl JsonStorage {
             .map(|(changes, count)| (StoreEvent::new(changes), count))
     }
 
+    /// Revert the metadata changes for `subgraph_id` from all blocks after
+    /// `block_number`. The only metadata changes we keep history for create
+    /// dynamic data sources, and those remember the block that created them,
+    /// which lets us find the history entries to revert
+    fn rewind_meta(
+        &self,
+        conn: &PgConnection,
+        subgraph_id: &SubgraphDeploymentId,
+        block_number: u64,
+    ) -> Result<(StoreEvent, i32), StoreError> {
+        let query = "select h.id, h.entity, h.entity_id, h.data_before, h.op_id
+                       from subgraphs.entity_history h, event_meta_data m
+                      where m.id = h.event_id
+                        and h.subgraph = $1
+                        and m.source in (
+                            select substring(e.data->'ethereumBlockHash'->>'data' from 3)
+                              from subgraphs.entities e
+                             where e.entity = 'DynamicEthereumContractDataSource'
+                               and e.data->'deployment'->>'data' = $1
+                               and (e.data->'ethereumBlockNumber'->>'data')::numeric > $2::numeric)
+                      order by h.event_id desc";
+
+        let entries: Vec<RawHistory> = diesel::sql_query(query)
+            .bind::<Text, _>(subgraph_id.to_string())
+            .bind::<Text, _>(block_number.to_string())
+            .get_results(conn)?;
+
+        self.revert_entity_history_records(conn, entries)
+            .map(|(changes, count)| (StoreEvent::new(changes), count))
+    }
+
     /// Revert the block with the given `block_ptr` which must be the hash
     /// of the block to revert. The returned `StoreEvent` reflects the changes
     /// that were made during reversion
@@ -1489,6 +1678,32 @@ impl Storage {
             .map(|_| ())?)
     }
 
+    /// Set the `entityCount` property of the `SubgraphDeployment` for
+    /// `subgraph` to the number of entities that are currently in the
+    /// store, regardless of what it was before
+    pub(crate) fn recount_entities(&self, conn: &PgConnection) -> Result<(), StoreError> {
+        let count_query = match self {
+            Storage::Json(json) => json.count_query.as_str(),
+            Storage::Relational(layout) => layout.count_query.as_str(),
+        };
+        let query = format!(
+            "
+            update subgraphs.entities
+            set data = data || (format('{{\"entityCount\":
+                                  {{ \"data\": \"%s\",
+                                    \"type\": \"BigInt\"}}}}',
+                                  coalesce(({count_query}), 0)))::jsonb
+            where entity='SubgraphDeployment'
+              and id = $1
+            ",
+            count_query = count_query
+        );
+        Ok(diesel::sql_query(query)
+            .bind::<Text, _>(self.subgraph().to_string())
+            .execute(conn)
+            .map(|_| ())?)
+    }
+
     fn needs_migrating(&self) -> bool {
         false
     }
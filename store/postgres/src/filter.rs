@@ -426,5 +426,10 @@ where
                 }
             }
         }
+
+        Fulltext(_, value) => Err(UnsupportedFilter {
+            filter: "fulltext".to_owned(),
+            value,
+        }),
     }
 }
@@ -38,8 +38,11 @@ mod store_events;
 
 #[cfg(debug_assertions)]
 pub mod db_schema_for_tests {
+    pub use crate::db_schema::ethereum_block_calls;
     pub use crate::db_schema::ethereum_blocks;
     pub use crate::db_schema::ethereum_networks;
+    pub use crate::db_schema::ethereum_range_scans;
+    pub use crate::db_schema::ethereum_transaction_receipts;
 }
 
 #[cfg(debug_assertions)]
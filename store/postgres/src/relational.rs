@@ -17,12 +17,16 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::relational_queries::{
-    ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterQuery, FindQuery, InsertQuery,
-    QueryFilter, RevertClampQuery, RevertRemoveQuery,
+    id_collation, AggregateData, AggregateQuery, ClampRangeQuery, ConflictingEntityQuery,
+    CopyEntityDataQuery, CursorClause, EntityData, FilterQuery, FindQuery, FulltextRank,
+    InsertQuery, QueryFilter, RevertClampQuery, RevertRemoveQuery, WindowQuery, WindowedEntityData,
 };
+use graph::data::schema::{FulltextDefinition, IndexDefinition};
+use graph::data::store::scalar;
 use graph::prelude::{
-    format_err, Entity, EntityChange, EntityChangeOperation, EntityFilter, EntityKey,
-    QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId, ValueType,
+    format_err, Entity, EntityAggregate, EntityChange, EntityChangeOperation, EntityFilter,
+    EntityKey, EntityRange, EntityWindow, QueryExecutionError, StoreError, StoreEvent,
+    SubgraphDeploymentId, Value, ValueType,
 };
 
 use crate::block_range::{BlockNumber, BLOCK_RANGE_COLUMN};
@@ -266,6 +270,57 @@ impl Layout {
         Ok(layout)
     }
 
+    /// Fill the tables of this layout with the entities in `base` as of
+    /// `block`, including their history up to `block`. This is how a
+    /// deployment that is grafted onto `base` starts out. Entity types that
+    /// `base` does not have start out empty, and attributes that `base`
+    /// does not have are left unset; it is an error if such an attribute
+    /// is required, or if an attribute has a different type in `base`
+    pub fn copy_from(
+        &self,
+        conn: &PgConnection,
+        base: &Layout,
+        block: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let mut tables = self.tables.values().collect::<Vec<_>>();
+        tables.sort_by_key(|table| table.position);
+        for dst in tables {
+            let src = match base.table_for_entity(&dst.object) {
+                Ok(src) => src,
+                Err(_) => continue,
+            };
+
+            let mut columns = vec![];
+            for column in &dst.columns {
+                match src.columns.iter().find(|src| src.name == column.name) {
+                    Some(src) if column.is_copyable_from(src) => columns.push(column),
+                    Some(_) => {
+                        return Err(StoreError::Unknown(format_err!(
+                            "can not graft onto {}: the type of `{}.{}` differs",
+                            base.subgraph,
+                            dst.object,
+                            column.field
+                        )))
+                    }
+                    None if !column.is_nullable() => {
+                        return Err(StoreError::Unknown(format_err!(
+                            "can not graft onto {}: it has no values for the \
+                             required attribute `{}.{}`",
+                            base.subgraph,
+                            dst.object,
+                            column.field
+                        )))
+                    }
+                    None => (),
+                }
+            }
+
+            CopyEntityDataQuery::new(&base.schema, src, &self.schema, dst, columns, block)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
     /// Generate the DDL for the entire layout, i.e., all `create table`
     /// and `create index` etc. statements needed in the database schema
     ///
@@ -365,12 +420,142 @@ impl Layout {
         entity_types: Vec<String>,
         filter: Option<EntityFilter>,
         order: Option<(String, ValueType, &str)>,
-        first: Option<u32>,
-        skip: u32,
+        range: EntityRange,
         block: BlockNumber,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.filter_query(conn, entity_types, filter, order, range, block, false)
+    }
+
+    /// Like `query`, but compare string ids byte by byte instead of with
+    /// the collation of the database
+    pub fn query_in_byte_order(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str)>,
+        range: EntityRange,
+        block: BlockNumber,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        self.filter_query(conn, entity_types, filter, order, range, block, true)
+    }
+
+    fn filter_query(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str)>,
+        range: EntityRange,
+        block: BlockNumber,
+        ids_by_bytes: bool,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         let filter = filter.as_ref();
-        let table_filter_pairs = entity_types
+        let table_filter_pairs = self.table_filter_pairs(entity_types, filter)?;
+        let first = range.first.map(|first| first.to_string());
+        let skip = if range.skip == 0 {
+            None
+        } else {
+            Some(range.skip.to_string())
+        };
+
+        // Fulltext search results are ordered by how well they match the
+        // search text; for those, the order names the search field rather
+        // than an attribute, and the filter contains the search text
+        let rank = match (&order, table_filter_pairs.as_slice()) {
+            (Some((attribute, _, _)), [(table, _)]) => match table.fulltext_for_field(attribute) {
+                Ok(fulltext) => {
+                    let text = filter
+                        .and_then(|filter| fulltext_text(filter, attribute))
+                        .ok_or_else(|| {
+                            QueryExecutionError::NotSupported(format!(
+                                "ordering by the fulltext search `{}` requires searching for a text",
+                                attribute
+                            ))
+                        })?;
+                    Some(FulltextRank::new(fulltext, text))
+                }
+                Err(_) => None,
+            },
+            _ => None,
+        };
+        if rank.is_some() && range.has_cursor() {
+            return Err(QueryExecutionError::NotSupported(
+                "paginating fulltext search results with cursors is not supported".to_owned(),
+            ));
+        }
+        let order = if rank.is_some() { None } else { order };
+
+        // Get the column we order by; if there is more than one table, we are
+        // querying an interface, and the order is on an attribute in that
+        // interface so that all tables have a column for that. It is
+        // therefore enough to just look at the first table to get the column
+        let order_column = match (order, table_filter_pairs.first()) {
+            (_, None) => {
+                unreachable!("an entity query always contains at least one entity type/table");
+            }
+            (Some((ref attribute, _, direction)), Some((table, _))) => {
+                let column = table.column_for_field(&attribute)?;
+                Some((column, direction))
+            }
+            (None, _) => None,
+        };
+        let order = order_column.map(|(column, direction)| (&column.name, direction));
+
+        let descending = order_column
+            .map(|(_, direction)| direction.eq_ignore_ascii_case("desc"))
+            .unwrap_or(false);
+        let order_column = order_column.map(|(column, _)| column);
+        let collation = match table_filter_pairs.first() {
+            Some((table, _)) if ids_by_bytes => id_collation(table),
+            _ => "",
+        };
+        let after = range
+            .after
+            .as_ref()
+            .map(|cursor| CursorClause::new(order_column, descending, cursor, true, collation));
+        let before = range
+            .before
+            .as_ref()
+            .map(|cursor| CursorClause::new(order_column, descending, cursor, false, collation));
+
+        let query = FilterQuery::new(
+            &self.schema,
+            table_filter_pairs,
+            order,
+            rank,
+            first,
+            skip,
+            after,
+            before,
+            range.reverse,
+            block,
+            ids_by_bytes,
+        );
+        let query_debug_info = query.clone();
+
+        let values = query.load::<EntityData>(conn).map_err(|e| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "{}, query = {:?}",
+                e,
+                debug_query(&query_debug_info).to_string()
+            ))
+        })?;
+
+        values
+            .into_iter()
+            .map(|entity_data| entity_data.to_entity(self).map_err(|e| e.into()))
+            .collect()
+    }
+
+    /// Pair the table for each of `entity_types` with the `filter` for
+    /// that table
+    fn table_filter_pairs<'a>(
+        &'a self,
+        entity_types: Vec<String>,
+        filter: Option<&'a EntityFilter>,
+    ) -> Result<Vec<(&'a Table, Option<QueryFilter<'a>>)>, StoreError> {
+        entity_types
             .into_iter()
             .map(|entity| {
                 self.table_for_entity(&entity)
@@ -382,33 +567,167 @@ impl Layout {
                             .map(|filter| (table, filter))
                     })
             })
-            .collect::<Result<Vec<_>, StoreError>>()?;
-        let first = first.map(|first| first.to_string());
-        let skip = if skip == 0 {
-            None
-        } else {
-            Some(skip.to_string())
+            .collect()
+    }
+
+    /// Compute `aggregates` over all entities of `entity_types` that match
+    /// `filter` at `block`. Only attributes holding a single `Int`, `BigInt`
+    /// or `BigDecimal` can be aggregated.
+    pub fn aggregate(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        aggregates: &[EntityAggregate],
+        block: BlockNumber,
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        let filter = filter.as_ref();
+        let table_filter_pairs = self.table_filter_pairs(entity_types, filter)?;
+
+        // As for ordering, all tables have the same columns for the
+        // attributes of the interface we are aggregating over, and looking
+        // at the first table is enough
+        let table = match table_filter_pairs.first() {
+            Some((table, _)) => table,
+            None => unreachable!("an entity query always contains at least one entity type/table"),
         };
+        let columns = aggregates
+            .iter()
+            .map(|aggregate| {
+                let (function, attribute) = match aggregate {
+                    EntityAggregate::Count => return Ok(("count", None)),
+                    EntityAggregate::Sum(attribute) => ("sum", attribute),
+                    EntityAggregate::Avg(attribute) => ("avg", attribute),
+                    EntityAggregate::Min(attribute) => ("min", attribute),
+                    EntityAggregate::Max(attribute) => ("max", attribute),
+                };
+                let column = table.column_for_field(attribute)?;
+                match column.column_type {
+                    ColumnType::Int | ColumnType::BigInt | ColumnType::BigDecimal
+                        if !column.is_list() =>
+                    {
+                        Ok((function, Some(column)))
+                    }
+                    _ => Err(QueryExecutionError::NotSupported(format!(
+                        "the {} of `{}` can not be computed since it is not a number",
+                        function, attribute
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, QueryExecutionError>>()?;
+
+        let query = AggregateQuery::new(
+            &self.schema,
+            table_filter_pairs,
+            columns
+                .iter()
+                .map(|(function, column)| (*function, column.map(|column| &column.name)))
+                .collect(),
+            block,
+        );
+        let query_debug_info = query.clone();
 
-        // Get the name of the column we order by; if there is more than one
-        // table, we are querying an interface, and the order is on an attribute
-        // in that interface so that all tables have a column for that. It is
-        // therefore enough to just look at the first table to get the name
-        let order = match (order, table_filter_pairs.first()) {
-            (_, None) => {
-                unreachable!("an entity query always contains at least one entity type/table");
-            }
-            (Some((ref attribute, _, direction)), Some((table, _))) => {
+        let data = query
+            .load::<AggregateData>(conn)
+            .map_err(|e| {
+                QueryExecutionError::ResolveEntitiesError(format!(
+                    "{}, query = {:?}",
+                    e,
+                    debug_query(&query_debug_info).to_string()
+                ))
+            })?
+            .pop()
+            .map(|data| data.data)
+            .unwrap_or_default();
+
+        columns
+            .into_iter()
+            .zip(data.into_iter().chain(std::iter::repeat(None)))
+            .map(|((function, column), text)| {
+                let text = match text {
+                    Some(text) => text,
+                    None => return Ok(Value::Null),
+                };
+                let parse_error = |e: String| {
+                    QueryExecutionError::ResolveEntitiesError(format!(
+                        "invalid {} `{}`: {}",
+                        function, text, e
+                    ))
+                };
+                let column_type = column.map(|column| &column.column_type);
+                match (function, column_type) {
+                    ("min", Some(ColumnType::Int)) | ("max", Some(ColumnType::Int)) => text
+                        .parse::<i32>()
+                        .map(Value::Int)
+                        .map_err(|e| parse_error(e.to_string())),
+                    ("count", _)
+                    | ("sum", Some(ColumnType::Int))
+                    | ("sum", Some(ColumnType::BigInt))
+                    | ("min", Some(ColumnType::BigInt))
+                    | ("max", Some(ColumnType::BigInt)) => scalar::BigInt::from_str(&text)
+                        .map(Value::BigInt)
+                        .map_err(|e| parse_error(e.to_string())),
+                    _ => scalar::BigDecimal::from_str(&text)
+                        .map(Value::BigDecimal)
+                        .map_err(|e| parse_error(e.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Return `true` if we can answer a windowed query for `entity_types`
+    /// with a single SQL query; that is only possible if we query just one
+    /// table and the parent ids are stored as strings
+    pub fn supports_window(&self, entity_types: &[String], window: &EntityWindow) -> bool {
+        match entity_types {
+            [entity_type] => self
+                .table_for_entity(entity_type)
+                .and_then(|table| table.column_for_field(window.link.name()))
+                .map(|column| column.column_type == ColumnType::String)
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    pub fn query_window(
+        &self,
+        conn: &PgConnection,
+        entity_type: &str,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str)>,
+        first: Option<u32>,
+        skip: u32,
+        block: BlockNumber,
+        window: &EntityWindow,
+    ) -> Result<Vec<(String, Entity)>, QueryExecutionError> {
+        let table = self.table_for_entity(entity_type)?.as_ref();
+        let filter = filter
+            .as_ref()
+            .map(|filter| QueryFilter::new(filter, table))
+            .transpose()?;
+        let link = table.column_for_field(window.link.name())?;
+        let order = match order {
+            Some((ref attribute, _, direction)) => {
                 let column = table.column_for_field(&attribute)?;
                 Some((&column.name, direction))
             }
-            (None, _) => None,
+            None => None,
         };
 
-        let query = FilterQuery::new(&self.schema, table_filter_pairs, order, first, skip, block);
+        let query = WindowQuery::new(
+            &self.schema,
+            table,
+            filter,
+            link,
+            &window.ids,
+            order,
+            first,
+            skip,
+            block,
+        );
         let query_debug_info = query.clone();
 
-        let values = query.load::<EntityData>(conn).map_err(|e| {
+        let values = query.load::<WindowedEntityData>(conn).map_err(|e| {
             QueryExecutionError::ResolveEntitiesError(format!(
                 "{}, query = {:?}",
                 e,
@@ -566,7 +885,7 @@ impl ColumnType {
         }
     }
 
-    fn sql_type(&self) -> &str {
+    pub(crate) fn sql_type(&self) -> &str {
         match self {
             ColumnType::Boolean => "boolean",
             ColumnType::BigDecimal => "numeric",
@@ -633,6 +952,14 @@ impl Column {
         is_list(&self.field_type)
     }
 
+    /// Return `true` if the values in the column `src` can be copied into
+    /// this column. Enums are copied by name; it is an error at copy time if
+    /// this column's enum does not have all the values that `src` uses
+    fn is_copyable_from(&self, src: &Column) -> bool {
+        self.is_list() == src.is_list()
+            && (self.column_type == src.column_type || (self.is_enum() && src.is_enum()))
+    }
+
     pub fn is_enum(&self) -> bool {
         if let ColumnType::Enum(_) = self.column_type {
             true
@@ -666,6 +993,34 @@ impl Column {
     }
 }
 
+/// A `tsvector` column that holds the search tokens for a fulltext search
+/// declared with `@fulltext`. The column does not correspond to an
+/// attribute of the entity; we compute its value from the entity's text
+/// attributes whenever we write a new version of the entity
+#[derive(Clone, Debug)]
+pub struct FulltextColumn {
+    pub name: SqlName,
+    /// The name of the search field in the GraphQL API
+    pub field: String,
+    /// The Postgres text search configuration, e.g., `english`
+    pub language: String,
+    /// The GraphQL names of the attributes whose text gets searched
+    pub fields: Vec<String>,
+}
+
+impl FulltextColumn {
+    fn new(defn: &FulltextDefinition) -> Result<FulltextColumn, StoreError> {
+        SqlName::check_valid_identifier(&defn.name, "fulltext search")?;
+
+        Ok(FulltextColumn {
+            name: SqlName::from(defn.name.as_str()),
+            field: defn.name.clone(),
+            language: defn.language.clone(),
+            fields: defn.fields.clone(),
+        })
+    }
+}
+
 /// The name for the primary key column of a table; hardcoded for now
 pub(crate) const PRIMARY_KEY_COLUMN: &str = "id";
 
@@ -682,6 +1037,11 @@ pub struct Table {
     pub name: SqlName,
 
     pub columns: Vec<Column>,
+    /// The columns for the fulltext searches declared on the type
+    pub fulltext: Vec<FulltextColumn>,
+    /// The composite indexes declared with `@index` on the type; each
+    /// entry lists the names of the indexed columns in index order
+    pub indexes: Vec<Vec<SqlName>>,
     /// The position of this table in all the tables for this layout; this
     /// is really only needed for the tests to make the names of indexes
     /// predictable
@@ -706,10 +1066,48 @@ impl Table {
             .filter(|field| !derived_column(field))
             .map(|field| Column::new(field, schema, enums, id_type))
             .collect::<Result<Vec<_>, _>>()?;
+        let fulltext = FulltextDefinition::for_object_type(defn)
+            .map_err(|e| {
+                StoreError::Unknown(format_err!("invalid @fulltext on {}: {}", defn.name, e))
+            })?
+            .iter()
+            .map(FulltextColumn::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(fulltext) = fulltext
+            .iter()
+            .find(|fulltext| columns.iter().any(|column| column.name == fulltext.name))
+        {
+            return Err(StoreError::Unknown(format_err!(
+                "the fulltext search {} on {} uses the same column name as an attribute",
+                fulltext.field,
+                defn.name
+            )));
+        }
+        let indexes = IndexDefinition::for_object_type(defn)
+            .map_err(|e| {
+                StoreError::Unknown(format_err!("invalid @index on {}: {}", defn.name, e))
+            })?
+            .iter()
+            .map(|index| {
+                index
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        columns
+                            .iter()
+                            .find(|column| &column.field == field)
+                            .map(|column| column.name.clone())
+                            .ok_or_else(|| StoreError::UnknownField(field.to_string()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
         let table = Table {
             object: defn.name.clone(),
             name: table_name.clone(),
             columns,
+            fulltext,
+            indexes,
             position,
         };
         for interface_name in &defn.implements_interfaces {
@@ -743,6 +1141,15 @@ impl Table {
             .ok_or_else(|| StoreError::UnknownField(field.to_string()))
     }
 
+    /// Find the column for the fulltext search `field` in this table. The
+    /// name must be the GraphQL name of the search field
+    pub fn fulltext_for_field(&self, field: &str) -> Result<&FulltextColumn, StoreError> {
+        self.fulltext
+            .iter()
+            .find(|fulltext| &fulltext.field == field)
+            .ok_or_else(|| StoreError::UnknownField(field.to_string()))
+    }
+
     /// Generate the DDL for one table, i.e. one `create table` statement
     /// and all `create index` statements for the table's columns
     ///
@@ -760,6 +1167,9 @@ impl Table {
             column.as_ddl(out)?;
             write!(out, ",\n")?;
         }
+        for fulltext in self.fulltext.iter() {
+            write!(out, "        {:20} tsvector,\n", fulltext.name.quoted())?;
+        }
         // Add block_range column and constraint
         write!(
             out,
@@ -803,6 +1213,47 @@ impl Table {
                 index_expr = index_expr,
             )?;
         }
+
+        // Fulltext searches use a GIN index on the search tokens
+        for (i, fulltext) in self.fulltext.iter().enumerate() {
+            write!(
+                out,
+                "create index fulltext_{table_index}_{fulltext_index}_{table_name}_{column_name}\n    on {schema_name}.\"{table_name}\" using gin({column});\n",
+                table_index = self.position,
+                table_name = self.name,
+                fulltext_index = i,
+                column_name = fulltext.name,
+                schema_name = layout.schema,
+                column = fulltext.name.quoted(),
+            )?;
+        }
+
+        // Composite indexes from `@index` directives. Like the indexes on
+        // single attributes, they do not include the block range, since a
+        // BTree index can not help with finding the ranges that contain a
+        // block
+        for (i, index) in self.indexes.iter().enumerate() {
+            let index_exprs = index
+                .iter()
+                .map(|name| {
+                    let column = self.column(name).expect("indexed columns exist");
+                    if column.is_text() {
+                        format!("left({}, {})", column.name.quoted(), STRING_PREFIX_SIZE)
+                    } else {
+                        column.name.quoted()
+                    }
+                })
+                .collect::<Vec<_>>();
+            write!(
+                out,
+                "create index index_{table_index}_{index_index}_{table_name}\n    on {schema_name}.\"{table_name}\" using btree({index_exprs});\n",
+                table_index = self.position,
+                table_name = self.name,
+                index_index = i,
+                schema_name = layout.schema,
+                index_exprs = index_exprs.join(", "),
+            )?;
+        }
         write!(out, "\n")
     }
 }
@@ -817,6 +1268,21 @@ fn named_type(field_type: &q::Type) -> &str {
     }
 }
 
+/// Find the search text for the fulltext search `field` in `filter`. Only
+/// a search text that all results must match can be used to rank them;
+/// we therefore only look at filters that are `and`-ed together
+fn fulltext_text<'a>(filter: &'a EntityFilter, field: &str) -> Option<&'a str> {
+    match filter {
+        EntityFilter::Fulltext(attribute, Value::String(text)) if attribute == field => {
+            Some(text.as_str())
+        }
+        EntityFilter::And(filters) => filters
+            .iter()
+            .find_map(|filter| fulltext_text(filter, field)),
+        _ => None,
+    }
+}
+
 fn derived_column(field: &s::Field) -> bool {
     field
         .directives
@@ -876,6 +1342,14 @@ mod tests {
         let layout = test_layout(FOREST_GQL);
         let sql = layout.as_ddl().expect("Failed to generate DDL");
         assert_eq!(FOREST_DDL, sql);
+
+        let layout = test_layout(FULLTEXT_GQL);
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert_eq!(FULLTEXT_DDL, sql);
+
+        let layout = test_layout(INDEX_GQL);
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert_eq!(INDEX_DDL, sql);
     }
 
     const THING_GQL: &str = "
@@ -1102,5 +1576,73 @@ create index attr_2_1_habitat_most_common
 create index attr_2_2_habitat_dwellers
     on rel.\"habitat\" using gin(\"dwellers\");
 
+";
+
+    const FULLTEXT_GQL: &str = "
+type Token @entity
+    @fulltext(name: \"tokenSearch\", language: \"english\", fields: [\"name\", \"description\"]) {
+    id: ID!,
+    name: String!,
+    description: String,
+    decimals: Int
+}";
+
+    const FULLTEXT_DDL: &str = "create table rel.\"token\" (
+        \"id\"                 text not null,
+        \"name\"               text not null,
+        \"description\"        text,
+        \"decimals\"           integer,
+        \"token_search\"       tsvector,
+
+        vid                  bigserial primary key,
+        block_range          int4range not null,
+        exclude using gist   (id with =, block_range with &&)
+);
+create index attr_0_0_token_id
+    on rel.\"token\" using btree(\"id\");
+create index attr_0_1_token_name
+    on rel.\"token\" using btree(left(\"name\", 256));
+create index attr_0_2_token_description
+    on rel.\"token\" using btree(left(\"description\", 256));
+create index attr_0_3_token_decimals
+    on rel.\"token\" using btree(\"decimals\");
+create index fulltext_0_0_token_token_search
+    on rel.\"token\" using gin(\"token_search\");
+
+";
+
+    const INDEX_GQL: &str = "
+type Transfer @entity
+    @index(fields: [\"from\", \"amount\"])
+    @index(fields: [\"to\"]) {
+    id: ID!,
+    from: String!,
+    to: String!,
+    amount: BigInt!
+}";
+
+    const INDEX_DDL: &str = "create table rel.\"transfer\" (
+        \"id\"                 text not null,
+        \"from\"               text not null,
+        \"to\"                 text not null,
+        \"amount\"             numeric not null,
+
+        vid                  bigserial primary key,
+        block_range          int4range not null,
+        exclude using gist   (id with =, block_range with &&)
+);
+create index attr_0_0_transfer_id
+    on rel.\"transfer\" using btree(\"id\");
+create index attr_0_1_transfer_from
+    on rel.\"transfer\" using btree(left(\"from\", 256));
+create index attr_0_2_transfer_to
+    on rel.\"transfer\" using btree(left(\"to\", 256));
+create index attr_0_3_transfer_amount
+    on rel.\"transfer\" using btree(\"amount\");
+create index index_0_0_transfer
+    on rel.\"transfer\" using btree(left(\"from\", 256), \"amount\");
+create index index_0_1_transfer
+    on rel.\"transfer\" using btree(left(\"to\", 256));
+
 ";
 }
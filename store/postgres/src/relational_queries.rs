@@ -9,14 +9,15 @@ use diesel::pg::{Pg, PgConnection};
 use diesel::query_builder::{AstPass, QueryFragment, QueryId};
 use diesel::query_dsl::{LoadQuery, RunQueryDsl};
 use diesel::result::QueryResult;
-use diesel::sql_types::{Array, Binary, Bool, Integer, Jsonb, Numeric, Range, Text};
+use diesel::sql_types::{Array, Binary, Bool, Integer, Jsonb, Nullable, Numeric, Range, Text};
 use diesel::Connection;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
 use graph::data::store::scalar;
 use graph::prelude::{
-    format_err, serde_json, Attribute, Entity, EntityFilter, EntityKey, StoreError, Value,
+    format_err, serde_json, Attribute, Entity, EntityCursor, EntityFilter, EntityKey, StoreError,
+    Value,
 };
 
 use crate::block_range::{
@@ -24,7 +25,9 @@ use crate::block_range::{
 };
 use crate::entities::STRING_PREFIX_SIZE;
 use crate::filter::UnsupportedFilter;
-use crate::relational::{Column, ColumnType, Layout, SqlName, Table, PRIMARY_KEY_COLUMN};
+use crate::relational::{
+    Column, ColumnType, FulltextColumn, Layout, SqlName, Table, PRIMARY_KEY_COLUMN,
+};
 use crate::sql_value::SqlValue;
 
 /// Helper struct for retrieving entities from the database. With diesel, we
@@ -217,6 +220,42 @@ impl<'a> QueryFragment<Pg> for QueryValue<'a> {
     }
 }
 
+/// Generate the `tsquery` that searches the tokens in a fulltext column
+/// for the words in a search text. The language of the column is one of a
+/// fixed list of text search configurations, and therefore safe to put
+/// into the query verbatim
+struct TsQuery<'a>(&'a FulltextColumn, &'a str);
+
+impl<'a> QueryFragment<Pg> for TsQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("plainto_tsquery('");
+        out.push_sql(&self.0.language);
+        out.push_sql("', ");
+        out.push_bind_param::<Text, _>(&self.1)?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// Generate an expression for how well the tokens in a fulltext column
+/// match a search text; bigger values mean better matches
+#[derive(Debug, Clone, Constructor)]
+pub struct FulltextRank<'a> {
+    column: &'a FulltextColumn,
+    text: &'a str,
+}
+
+impl<'a> QueryFragment<Pg> for FulltextRank<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("ts_rank(");
+        out.push_identifier(self.column.name.as_str())?;
+        out.push_sql(", ");
+        TsQuery(self.column, self.text).walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
 enum Comparison {
     Less,
@@ -415,6 +454,19 @@ impl<'a> QueryFilter<'a> {
             | NotEndsWith(attr, _) => {
                 table.column_for_field(attr)?;
             }
+            Fulltext(attr, value) => {
+                table.fulltext_for_field(attr)?;
+                match value {
+                    Value::String(_) => (),
+                    _ => {
+                        return Err(StoreError::Unknown(format_err!(
+                            "the search text for the fulltext search {} must be a string, not {}",
+                            attr,
+                            value
+                        )))
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -432,6 +484,26 @@ impl<'a> QueryFilter<'a> {
             .expect("the constructor already checked that all attribute names are valid")
     }
 
+    fn fulltext(
+        &self,
+        attribute: &Attribute,
+        value: &Value,
+        mut out: AstPass<Pg>,
+    ) -> QueryResult<()> {
+        let column = self
+            .table
+            .fulltext_for_field(attribute)
+            .expect("the constructor already checked that all attribute names are valid");
+        let text = match value {
+            Value::String(text) => text,
+            _ => unreachable!("the constructor already checked that the search text is a string"),
+        };
+
+        out.push_identifier(column.name.as_str())?;
+        out.push_sql(" @@ ");
+        TsQuery(column, text).walk_ast(out)
+    }
+
     fn binary_op(
         &self,
         filters: &Vec<EntityFilter>,
@@ -735,6 +807,8 @@ impl<'a> QueryFragment<Pg> for QueryFilter<'a> {
             NotEndsWith(attr, value) => {
                 self.starts_or_ends_with(attr, value, " not like ", false, out)?
             }
+
+            Fulltext(attr, value) => self.fulltext(attr, value, out)?,
         }
         Ok(())
     }
@@ -843,6 +917,10 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
                 out.push_sql(", ");
             }
         }
+        for fulltext in self.table.fulltext.iter() {
+            out.push_identifier(fulltext.name.as_str())?;
+            out.push_sql(", ");
+        }
         out.push_identifier(BLOCK_RANGE_COLUMN)?;
 
         out.push_sql(")\nvalues(");
@@ -852,6 +930,23 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
                 out.push_sql(", ");
             }
         }
+        // The search tokens for fulltext searches are computed from the
+        // text of all the attributes that the search covers; the language
+        // of the search comes from a fixed list and can go into the query
+        // verbatim
+        for fulltext in self.table.fulltext.iter() {
+            out.push_sql("to_tsvector('");
+            out.push_sql(&fulltext.language);
+            out.push_sql("', concat_ws(' '");
+            for field in fulltext.fields.iter() {
+                out.push_sql(", ");
+                match self.entity.get(field) {
+                    Some(Value::String(text)) => out.push_bind_param::<Text, _>(text)?,
+                    _ => out.push_sql("null::text"),
+                }
+            }
+            out.push_sql(")), ");
+        }
         let block_range: BlockRange = (self.block..).into();
         out.push_bind_param::<Range<Integer>, _>(&block_range)?;
         out.push_sql(")");
@@ -938,31 +1033,221 @@ impl<'a> LoadQuery<PgConnection, ConflictingEntityData> for ConflictingEntityQue
 
 impl<'a, Conn> RunQueryDsl<Conn> for ConflictingEntityQuery<'a> {}
 
+/// A condition that restricts the rows of a query that is ordered by
+/// `column` and then by `id` to the rows that come strictly after `cursor`
+/// in that order, or strictly before it if `after` is `false`. This lets us
+/// page through large results with a keyset query instead of skipping
+/// over ever more rows. The `id` is compared using `collation`, which must
+/// be the one the query orders by.
+#[derive(Debug, Clone, Constructor)]
+pub struct CursorClause<'a> {
+    column: Option<&'a Column>,
+    descending: bool,
+    cursor: &'a EntityCursor,
+    after: bool,
+    collation: &'static str,
+}
+
+impl<'a> CursorClause<'a> {
+    fn push_id_cmp(&self, op: &str, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        out.push_sql(self.collation);
+        out.push_sql(op);
+        out.push_bind_param::<Text, _>(&self.cursor.id)
+    }
+}
+
+impl<'a> QueryFragment<Pg> for CursorClause<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // Going forward means going towards bigger values for ascending
+        // order, and towards smaller values for descending order. Postgres
+        // sorts nulls as if they were bigger than any other value, and
+        // ties are always broken by ordering by `id` ascending
+        let forward = self.after != self.descending;
+        let value_op = if forward { " > " } else { " < " };
+        let id_op = if self.after { " > " } else { " < " };
+
+        let column = match self.column {
+            Some(column) if column.name.as_str() != PRIMARY_KEY_COLUMN => column,
+            _ => {
+                // We only order by `id`
+                return self.push_id_cmp(value_op, &mut out);
+            }
+        };
+
+        // Generate
+        //   (column > $value or column is null or (column = $value and id > $id))
+        // for a cursor with a value, and
+        //   (column is not null or (column is null and id > $id))
+        // for a cursor without one, flipping the comparisons and leaving
+        // out the tests for null as needed for the direction we are going in
+        out.push_sql("(");
+        match &self.cursor.value {
+            Value::Null => {
+                if !forward {
+                    out.push_identifier(column.name.as_str())?;
+                    out.push_sql(" is not null or ");
+                }
+                out.push_sql("(");
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(" is null and ");
+                self.push_id_cmp(id_op, &mut out)?;
+                out.push_sql(")");
+            }
+            value => {
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(value_op);
+                QueryValue(value, &column.column_type).walk_ast(out.reborrow())?;
+                if forward {
+                    out.push_sql(" or ");
+                    out.push_identifier(column.name.as_str())?;
+                    out.push_sql(" is null");
+                }
+                out.push_sql(" or (");
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(" = ");
+                QueryValue(value, &column.column_type).walk_ast(out.reborrow())?;
+                out.push_sql(" and ");
+                self.push_id_cmp(id_op, &mut out)?;
+                out.push_sql(")");
+            }
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Constructor)]
 pub struct FilterQuery<'a> {
     schema: &'a str,
     table_filter_pairs: Vec<(&'a Table, Option<QueryFilter<'a>>)>,
     order: Option<(&'a SqlName, &'a str)>,
+    rank: Option<FulltextRank<'a>>,
     first: Option<String>,
     skip: Option<String>,
+    after: Option<CursorClause<'a>>,
+    before: Option<CursorClause<'a>>,
+    reverse: bool,
     block: BlockNumber,
+    ids_by_bytes: bool,
+}
+
+/// The collation that makes Postgres compare the `id` of entities in
+/// `table` byte by byte, the same way Rust compares `String`s, rather than
+/// by the rules of the locale the database was created with
+pub(crate) fn id_collation(table: &Table) -> &'static str {
+    match table.column(&PRIMARY_KEY_COLUMN.into()) {
+        Ok(column) if column.column_type == ColumnType::String => " collate \"C\"",
+        _ => "",
+    }
+}
+
+/// Generate an `order by` clause that orders by the column in `order`
+/// and then by `id`, or only by `id` if no order is given. The `id` is
+/// compared using `collation`
+fn order_by(
+    order: &Option<(&SqlName, &str)>,
+    collation: &str,
+    out: &mut AstPass<Pg>,
+) -> QueryResult<()> {
+    out.push_sql("\n order by ");
+    if let Some((name, direction)) = order {
+        out.push_identifier(name.as_str())?;
+        if name.as_str() == PRIMARY_KEY_COLUMN {
+            out.push_sql(collation);
+        }
+        out.push_sql(" ");
+        out.push_sql(direction);
+        if name.as_str() != PRIMARY_KEY_COLUMN {
+            out.push_sql(", ");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            out.push_sql(collation);
+        }
+        Ok(())
+    } else {
+        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        out.push_sql(collation);
+        Ok(())
+    }
 }
 
 impl<'a> FilterQuery<'a> {
+    /// The collation for `id` in the `order by` clause. When we query
+    /// several tables, the `id` we order by is a column of their union,
+    /// which already carries the collation from the select list
+    fn collation(&self) -> &'static str {
+        match self.table_filter_pairs.as_slice() {
+            [(table, _)] => self.id_collation(table),
+            _ => "",
+        }
+    }
+
+    /// The collation for the `id` of `table`; we only compare ids byte by
+    /// byte if we were asked to since it keeps Postgres from using the
+    /// indexes on `id`
+    fn id_collation(&self, table: &Table) -> &'static str {
+        if self.ids_by_bytes {
+            id_collation(table)
+        } else {
+            ""
+        }
+    }
+
     fn order_by(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        if self.reverse {
+            return self.reverse_order_by(out);
+        }
+        match &self.rank {
+            // Order fulltext search results so that the best matches
+            // come first
+            Some(rank) => {
+                out.push_sql("\n order by ");
+                rank.walk_ast(out.reborrow())?;
+                out.push_sql(" desc, ");
+                out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                out.push_sql(self.collation());
+                Ok(())
+            }
+            None => order_by(&self.order, self.collation(), out),
+        }
+    }
+
+    /// Generate an `order by` clause that is the exact reverse of the one
+    /// `order_by` generates. Every part of the order is flipped, including
+    /// the tie break on `id`, which is always ascending otherwise
+    fn reverse_order_by(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        fn flip(direction: &str) -> &'static str {
+            if direction.eq_ignore_ascii_case("desc") {
+                " asc"
+            } else {
+                " desc"
+            }
+        }
+
         out.push_sql("\n order by ");
-        if let Some((name, direction)) = &self.order {
-            out.push_identifier(name.as_str())?;
-            out.push_sql(" ");
-            out.push_sql(direction);
-            if name.as_str() != PRIMARY_KEY_COLUMN {
+        match (&self.rank, &self.order) {
+            (Some(rank), _) => {
+                rank.walk_ast(out.reborrow())?;
+                out.push_sql(" asc, ");
+            }
+            (None, Some((name, direction))) if name.as_str() != PRIMARY_KEY_COLUMN => {
+                out.push_identifier(name.as_str())?;
+                out.push_sql(flip(direction));
                 out.push_sql(", ");
+            }
+            (None, Some((_, direction))) => {
+                // We only order by `id`
                 out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                out.push_sql(self.collation());
+                out.push_sql(flip(direction));
+                return Ok(());
             }
-            Ok(())
-        } else {
-            out.push_identifier(PRIMARY_KEY_COLUMN)
+            (None, None) => (),
         }
+        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        out.push_sql(self.collation());
+        out.push_sql(" desc");
+        Ok(())
     }
 
     fn add_sort_key(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
@@ -1005,7 +1290,11 @@ impl<'a> FilterQuery<'a> {
         BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
         if let Some(filter) = filter {
             out.push_sql(" and ");
-            filter.walk_ast(out)?;
+            filter.walk_ast(out.reborrow())?;
+        }
+        for cursor in self.after.iter().chain(self.before.iter()) {
+            out.push_sql(" and ");
+            cursor.walk_ast(out.reborrow())?;
         }
         Ok(())
     }
@@ -1082,7 +1371,9 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
                 }
                 out.push_sql("select ");
                 out.push_bind_param::<Text, _>(&table.object)?;
-                out.push_sql(" as entity, e.id, e.vid");
+                out.push_sql(" as entity, e.id");
+                out.push_sql(self.id_collation(table));
+                out.push_sql(" as id, e.vid");
                 self.add_sort_key(&mut out)?;
                 self.filtered_rows(table, filter, out.reborrow())?;
             }
@@ -1096,6 +1387,8 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
                     out.push_sql("\nunion all\n");
                 }
                 out.push_sql("select matches.entity, to_jsonb(e.*) as data, e.id");
+                out.push_sql(self.id_collation(table));
+                out.push_sql(" as id");
                 self.add_sort_key(&mut out)?;
                 out.push_sql("\n  from ");
                 out.push_identifier(&self.schema)?;
@@ -1125,6 +1418,246 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FilterQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FilterQuery<'a> {}
 
+/// Helper struct for retrieving the results of an `AggregateQuery`. Since
+/// the aggregates have different types, they are all returned as text and
+/// converted to the right type by the caller; an aggregate is `None` if
+/// there was nothing to aggregate.
+#[derive(QueryableByName)]
+pub struct AggregateData {
+    #[sql_type = "Array<Nullable<Text>>"]
+    pub data: Vec<Option<String>>,
+}
+
+/// Computes aggregates over all rows in the tables of `table_filter_pairs`
+/// that match their filter. Each aggregate is an aggregate function like
+/// `sum` together with the column it applies to, or `count` without a
+/// column to count the matching rows.
+#[derive(Debug, Clone, Constructor)]
+pub struct AggregateQuery<'a> {
+    schema: &'a str,
+    table_filter_pairs: Vec<(&'a Table, Option<QueryFilter<'a>>)>,
+    aggregates: Vec<(&'static str, Option<&'a SqlName>)>,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for AggregateQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //   select array[count(*)::text, sum(e.column)::text, ...] as data
+        //     from (
+        //       select e.vid, e.column, ...
+        //         from schema.table1 e
+        //        where block_range @> $block
+        //          and query_filter
+        //        union all
+        //        ...
+        //     ) e
+        let mut columns: Vec<&SqlName> = vec![];
+        for (_, column) in &self.aggregates {
+            if let Some(column) = column {
+                if !columns.contains(column) {
+                    columns.push(column);
+                }
+            }
+        }
+
+        out.push_sql("select array[");
+        for (i, (function, column)) in self.aggregates.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_sql(function);
+            out.push_sql("(");
+            match column {
+                Some(column) => {
+                    out.push_sql("e.");
+                    out.push_identifier(column.as_str())?;
+                }
+                None => out.push_sql("*"),
+            }
+            out.push_sql(")::text");
+        }
+        out.push_sql("] as data\n  from (");
+        for (i, (table, filter)) in self.table_filter_pairs.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            out.push_sql("select e.vid");
+            for column in &columns {
+                out.push_sql(", e.");
+                out.push_identifier(column.as_str())?;
+            }
+            out.push_sql("\n  from ");
+            out.push_identifier(&self.schema)?;
+            out.push_sql(".");
+            out.push_identifier(table.name.as_str())?;
+            out.push_sql(" e");
+            out.push_sql("\n where ");
+            BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+            if let Some(filter) = filter {
+                out.push_sql(" and ");
+                filter.walk_ast(out.reborrow())?;
+            }
+        }
+        out.push_sql(") e");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for AggregateQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, AggregateData> for AggregateQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<AggregateData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for AggregateQuery<'a> {}
+
+/// Helper struct for retrieving the entities of a `WindowQuery` together
+/// with the id of the parent they were found for
+#[derive(QueryableByName)]
+pub struct WindowedEntityData {
+    #[sql_type = "Text"]
+    parent_id: String,
+    #[sql_type = "Text"]
+    entity: String,
+    #[sql_type = "Jsonb"]
+    data: serde_json::Value,
+}
+
+impl WindowedEntityData {
+    pub fn to_entity(self, layout: &Layout) -> Result<(String, Entity), StoreError> {
+        let WindowedEntityData {
+            parent_id,
+            entity,
+            data,
+        } = self;
+        EntityData { entity, data }
+            .to_entity(layout)
+            .map(|entity| (parent_id, entity))
+    }
+}
+
+/// A query that finds, for each of the parents in `ids`, the entities in
+/// `table` that refer to that parent through the column `link`. The
+/// `first` and `skip` limits apply to the entities of each parent
+/// separately; an entity that refers to several parents is returned
+/// once for each of them.
+#[derive(Debug, Clone, Constructor)]
+pub struct WindowQuery<'a> {
+    schema: &'a str,
+    table: &'a Table,
+    filter: Option<QueryFilter<'a>>,
+    link: &'a Column,
+    ids: &'a Vec<String>,
+    order: Option<(&'a SqlName, &'a str)>,
+    first: Option<u32>,
+    skip: u32,
+    block: BlockNumber,
+}
+
+impl<'a> WindowQuery<'a> {
+    /// The expression for the parent id of an entity in the inner query
+    fn push_parent_id(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        if self.link.is_list() {
+            out.push_sql("p.\"g$parent_id\"");
+            Ok(())
+        } else {
+            out.push_sql("e.");
+            out.push_identifier(self.link.name.as_str())
+        }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for WindowQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //   select '..' as entity, c."g$parent_id" as parent_id,
+        //          to_jsonb(e.*) as data
+        //     from (
+        //       select e.vid, <parent> as "g$parent_id",
+        //              row_number() over (partition by <parent>
+        //                                 order by ...) as "g$pos"
+        //         from schema.table e
+        //        where block_range @> $block
+        //          and query_filter
+        //          and <parent> = any($ids)) c,
+        //          schema.table e
+        //    where e.vid = c.vid
+        //      and c."g$pos" > skip and c."g$pos" <= skip + first
+        //    order by c."g$parent_id", c."g$pos"
+        //
+        // where <parent> is the `link` column if it holds a single parent
+        // id. If it holds a list, we cross join with the distinct elements
+        // of that list and use those as <parent>
+        out.push_sql("select ");
+        out.push_bind_param::<Text, _>(&self.table.object)?;
+        out.push_sql(
+            " as entity, c.\"g$parent_id\" as parent_id, to_jsonb(e.*) as data\n  from (\n",
+        );
+        out.push_sql("  select e.vid, ");
+        self.push_parent_id(&mut out)?;
+        out.push_sql(" as \"g$parent_id\",\n         row_number() over (partition by ");
+        self.push_parent_id(&mut out)?;
+        order_by(&self.order, "", &mut out)?;
+        out.push_sql(") as \"g$pos\"\n    from ");
+        out.push_identifier(self.schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.table.name.as_str())?;
+        out.push_sql(" e");
+        if self.link.is_list() {
+            out.push_sql("\n   cross join lateral (select distinct unnest(e.");
+            out.push_identifier(self.link.name.as_str())?;
+            out.push_sql(") as \"g$parent_id\") p");
+        }
+        out.push_sql("\n   where ");
+        BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+        if let Some(filter) = &self.filter {
+            out.push_sql(" and ");
+            filter.walk_ast(out.reborrow())?;
+        }
+        out.push_sql(" and ");
+        self.push_parent_id(&mut out)?;
+        out.push_sql(" = any(");
+        out.push_bind_param::<Array<Text>, _>(self.ids)?;
+        out.push_sql(")) c,\n       ");
+        out.push_identifier(self.schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.table.name.as_str())?;
+        out.push_sql(" e\n where e.vid = c.vid\n   and c.\"g$pos\" > ");
+        out.push_sql(&self.skip.to_string());
+        if let Some(first) = self.first {
+            out.push_sql(" and c.\"g$pos\" <= ");
+            out.push_sql(&(self.skip as u64 + first as u64).to_string());
+        }
+        out.push_sql("\n order by c.\"g$parent_id\", c.\"g$pos\"");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for WindowQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, WindowedEntityData> for WindowQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<WindowedEntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for WindowQuery<'a> {}
+
 /// Reduce the upper bound of the current entry's block range to `block` as
 /// long as that does not result in an empty block range
 #[derive(Debug, Clone, Constructor)]
@@ -1171,6 +1704,112 @@ impl<'a> QueryId for ClampRangeQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for ClampRangeQuery<'a> {}
 
+/// Copy all versions of entities in the table `src` that existed at or
+/// before `block` into the table `dst`. Versions that were still current at
+/// `block` become current in `dst`, since `dst` does not know about any
+/// changes that were made to `src` after `block`. Only the `columns` of
+/// `dst` are copied, from the columns with the same names in `src`; the
+/// search tokens for fulltext searches are computed from the copied text.
+#[derive(Debug, Clone, Constructor)]
+pub struct CopyEntityDataQuery<'a> {
+    src_schema: &'a str,
+    src: &'a Table,
+    dst_schema: &'a str,
+    dst: &'a Table,
+    columns: Vec<&'a Column>,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for CopyEntityDataQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // Generate
+        //   insert into dst_schema.dst(column, .., fulltext, .., block_range)
+        //   select e.column, .., to_tsvector(..), ..,
+        //          case when upper(block_range) > $block
+        //               then int4range(lower(block_range), null)
+        //               else block_range end
+        //     from src_schema.src e
+        //    where lower(block_range) <= $block
+        out.unsafe_to_cache_prepared();
+        out.push_sql("insert into ");
+        out.push_identifier(self.dst_schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.dst.name.as_str())?;
+        out.push_sql("(");
+        for column in &self.columns {
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+        }
+        for fulltext in &self.dst.fulltext {
+            out.push_identifier(fulltext.name.as_str())?;
+            out.push_sql(", ");
+        }
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+
+        out.push_sql(")\nselect ");
+        for column in &self.columns {
+            out.push_sql("e.");
+            out.push_identifier(column.name.as_str())?;
+            // Enums are separate types in each schema, and values have to
+            // be converted by name
+            if column.is_enum() {
+                out.push_sql("::text");
+                if column.is_list() {
+                    out.push_sql("[]");
+                }
+                out.push_sql("::");
+                out.push_sql(column.column_type.sql_type());
+                if column.is_list() {
+                    out.push_sql("[]");
+                }
+            }
+            out.push_sql(", ");
+        }
+        for fulltext in &self.dst.fulltext {
+            out.push_sql("to_tsvector('");
+            out.push_sql(&fulltext.language);
+            out.push_sql("', concat_ws(' '");
+            for field in &fulltext.fields {
+                out.push_sql(", ");
+                match self.columns.iter().find(|column| &column.field == field) {
+                    Some(column) => {
+                        out.push_sql("e.");
+                        out.push_identifier(column.name.as_str())?;
+                    }
+                    None => out.push_sql("null::text"),
+                }
+            }
+            out.push_sql(")), ");
+        }
+        out.push_sql("\n       case when upper(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(") > ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        out.push_sql("\n            then int4range(lower(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql("), null)\n            else ");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(" end");
+        out.push_sql("\n  from ");
+        out.push_identifier(self.src_schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.src.name.as_str())?;
+        out.push_sql(" e\n where lower(");
+        out.push_identifier(BLOCK_RANGE_COLUMN)?;
+        out.push_sql(") <= ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for CopyEntityDataQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for CopyEntityDataQuery<'a> {}
+
 /// Helper struct for returning the id's touched by the RevertRemove and
 /// RevertExtend queries
 #[derive(QueryableByName, PartialEq, Eq, Hash)]
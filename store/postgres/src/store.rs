@@ -5,19 +5,21 @@ use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::{insert_into, select, update};
 use futures::sync::mpsc::{channel, Sender};
 use lru_time_cache::LruCache;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-use graph::components::store::Store as StoreTrait;
+use graph::components::store::{find_persisted_query, Store as StoreTrait};
 use graph::data::subgraph::schema::*;
 use graph::prelude::serde_json;
 use graph::prelude::{ChainHeadUpdateListener as _, *};
 use graph_graphql::prelude::api_schema;
 use tokio::timer::Interval;
-use web3::types::H256;
+use web3::types::{Log, TransactionReceipt, H256};
 
 use crate::block_range::BLOCK_NUMBER_MAX;
 use crate::chain_head_listener::ChainHeadUpdateListener;
@@ -112,6 +114,22 @@ struct SchemaPair {
     api: Arc<Schema>,
 }
 
+/// Clear `cache` if `event` removes a persisted query or a subgraph version,
+/// since the cached queries might then no longer be available for the
+/// deployments they were found for
+fn forget_persisted_queries(
+    cache: &Mutex<LruCache<(SubgraphDeploymentId, String), String>>,
+    event: &StoreEvent,
+) {
+    if event.changes.iter().any(|change| {
+        change.operation == EntityChangeOperation::Removed
+            && (change.entity_type == SubgraphPersistedQueryEntity::TYPENAME
+                || change.entity_type == SubgraphVersionEntity::TYPENAME)
+    }) {
+        cache.lock().unwrap().clear();
+    }
+}
+
 /// A Store based on Diesel and Postgres.
 pub struct Store {
     logger: Logger,
@@ -123,6 +141,10 @@ pub struct Store {
     genesis_block_ptr: EthereumBlockPointer,
     conn: Pool<ConnectionManager<PgConnection>>,
     schema_cache: Mutex<LruCache<SubgraphDeploymentId, SchemaPair>>,
+    /// Persisted queries we found, by deployment and hash. Entries are
+    /// dropped when a store event, from this node or any other, removes a
+    /// persisted query or a subgraph version
+    persisted_query_cache: Arc<Mutex<LruCache<(SubgraphDeploymentId, String), String>>>,
     /// A cache for the storage metadata for subgraphs. The Store just
     /// hosts this because it lives long enough, but it is managed from
     /// the entities module
@@ -162,6 +184,7 @@ impl Store {
             genesis_block_ptr: (net_identifiers.genesis_block_hash, 0 as u64).into(),
             conn: pool,
             schema_cache: Mutex::new(LruCache::with_capacity(100)),
+            persisted_query_cache: Arc::new(Mutex::new(LruCache::with_capacity(10_000))),
             storage_cache: e::make_storage_cache(),
         };
 
@@ -257,8 +280,11 @@ impl Store {
     ) {
         let logger = self.logger.clone();
         let subscriptions = self.subscriptions.clone();
+        let persisted_query_cache = self.persisted_query_cache.clone();
 
         tokio::spawn(store_events.for_each(move |event| {
+            forget_persisted_queries(&persisted_query_cache, &event);
+
             let senders = subscriptions.read().unwrap().clone();
             let logger = logger.clone();
             let subscriptions = subscriptions.clone();
@@ -337,34 +363,71 @@ impl Store {
         })
     }
 
+    /// Turn the order of `query` into the attribute, type, and SQL direction
+    /// that the entity connection expects
+    fn query_order(
+        order_by: Option<(String, ValueType)>,
+        order_direction: Option<EntityOrder>,
+    ) -> Option<(String, ValueType, &'static str)> {
+        order_by.map(|(attribute, value_type)| {
+            let direction = order_direction
+                .map(|direction| match direction {
+                    EntityOrder::Ascending => "ASC",
+                    EntityOrder::Descending => "DESC",
+                })
+                .unwrap_or("ASC");
+            (attribute, value_type, direction)
+        })
+    }
+
     fn execute_query(
         &self,
         conn: &e::Connection,
         query: EntityQuery,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         // Add order by filters to query
-        let order = match query.order_by {
-            Some((attribute, value_type)) => {
-                let direction = query
-                    .order_direction
-                    .map(|direction| match direction {
-                        EntityOrder::Ascending => "ASC",
-                        EntityOrder::Descending => "DESC",
-                    })
-                    .unwrap_or("ASC");
-                Some((attribute, value_type, direction))
-            }
-            None => None,
-        };
+        let order = Self::query_order(query.order_by, query.order_direction);
 
         // Process results; deserialize JSON data
         conn.query(
             query.entity_types,
             query.filter,
             order,
+            query.range,
+            query.block,
+            false,
+        )
+    }
+
+    fn execute_window_query(
+        &self,
+        conn: &e::Connection,
+        query: EntityQuery,
+        window: EntityWindow,
+    ) -> Result<Vec<(String, Entity)>, QueryExecutionError> {
+        if query.range.has_cursor()
+            || query.range.reverse
+            || !conn.supports_window(&query.entity_types, &window)
+        {
+            // Fall back to querying each parent separately
+            let mut entities = vec![];
+            for parent_id in &window.ids {
+                for entity in self.execute_query(conn, query.for_parent(&window, parent_id))? {
+                    entities.push((parent_id.clone(), entity));
+                }
+            }
+            return Ok(entities);
+        }
+
+        let order = Self::query_order(query.order_by, query.order_direction);
+        conn.query_window(
+            &query.entity_types[0],
+            query.filter,
+            order,
             query.range.first,
             query.range.skip,
-            BLOCK_NUMBER_MAX,
+            query.block,
+            &window,
         )
     }
 
@@ -760,6 +823,55 @@ impl Store {
             )),
         }
     }
+
+    /// Generate the operations that copy the dynamic data sources that the
+    /// graft base created up to and including the graft block to
+    /// `deployment`. Without them, a grafted deployment would miss all
+    /// events from contracts that were created before the graft
+    fn graft_dynamic_data_sources(
+        &self,
+        graft: &Graft,
+        deployment: &SubgraphDeploymentId,
+    ) -> Result<Vec<MetadataOperation>, StoreError> {
+        let data_sources = self.find(DynamicEthereumContractDataSourceEntity::query().filter(
+            EntityFilter::And(vec![
+                EntityFilter::Equal("deployment".to_owned(), graft.base.to_string().into()),
+                EntityFilter::LessOrEqual("ethereumBlockNumber".to_owned(), graft.block.into()),
+            ]),
+        ))?;
+
+        // Look up the entities nested in the data sources by the prefix of
+        // their ids, a limited number of data sources at a time to keep the
+        // queries reasonably small
+        let mut nested = vec![];
+        for chunk in data_sources.chunks(100) {
+            let prefixes = chunk
+                .iter()
+                .map(|data_source| {
+                    data_source.id().map(|id| {
+                        EntityFilter::StartsWith("id".to_owned(), format!("{}-", id).into())
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let query = EntityQuery::new(
+                SUBGRAPHS_ID.clone(),
+                DynamicEthereumContractDataSourceEntity::NESTED_TYPES
+                    .iter()
+                    .map(|entity_type| entity_type.to_string())
+                    .collect(),
+                EntityRange::default(),
+            )
+            .filter(EntityFilter::Or(prefixes));
+            nested.extend(self.find(query)?);
+        }
+
+        Ok(DynamicEthereumContractDataSourceEntity::copy_operations(
+            deployment,
+            data_sources,
+            &nested,
+            || format!("{}-dynamic", Uuid::new_v4().to_simple()),
+        )?)
+    }
 }
 
 impl StoreTrait for Store {
@@ -789,6 +901,43 @@ impl StoreTrait for Store {
         self.execute_query(&conn, query)
     }
 
+    fn find_in_byte_order(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
+        let conn = self
+            .get_entity_conn(&query.subgraph_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        let order = Self::query_order(query.order_by, query.order_direction);
+        conn.query(
+            query.entity_types,
+            query.filter,
+            order,
+            query.range,
+            query.block,
+            true,
+        )
+    }
+
+    fn find_windowed(
+        &self,
+        query: EntityQuery,
+        window: EntityWindow,
+    ) -> Result<Vec<(String, Entity)>, QueryExecutionError> {
+        let conn = self
+            .get_entity_conn(&query.subgraph_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        self.execute_window_query(&conn, query, window)
+    }
+
+    fn aggregate(
+        &self,
+        query: EntityQuery,
+        aggregates: &[EntityAggregate],
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        let conn = self
+            .get_entity_conn(&query.subgraph_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        conn.aggregate(query.entity_types, query.filter, aggregates, query.block)
+    }
+
     fn find_one(&self, mut query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
         query.range = EntityRange::first(1);
 
@@ -824,6 +973,28 @@ impl StoreTrait for Store {
             })
     }
 
+    fn block_number(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        block_hash: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        use crate::db_schema::ethereum_blocks::dsl;
+
+        // Each store only tracks the blocks of one network, and subgraphs
+        // are only deployed to the store for the network they index
+        dsl::ethereum_blocks
+            .select(dsl::number)
+            .filter(dsl::network_name.eq(&self.network_name))
+            .filter(dsl::hash.eq(format!("{:x}", block_hash)))
+            .first::<i64>(&*self.get_conn()?)
+            .optional()?
+            .map(|number| {
+                BlockNumber::try_from(number)
+                    .map_err(|e| StoreError::QueryExecutionError(e.to_string()))
+            })
+            .transpose()
+    }
+
     fn transact_block_operations(
         &self,
         subgraph_id: SubgraphDeploymentId,
@@ -889,6 +1060,26 @@ impl StoreTrait for Store {
 
     /// Apply a series of entity operations. Return `true` if the subgraph
     /// mentioned in `history_event` should have its schema migrated
+    fn persisted_query(
+        &self,
+        deployment: &SubgraphDeploymentId,
+        hash: &str,
+    ) -> Result<Option<String>, Error> {
+        let key = (deployment.clone(), hash.to_owned());
+        if let Some(query) = self.persisted_query_cache.lock().unwrap().get(&key) {
+            return Ok(Some(query.clone()));
+        }
+
+        let query = find_persisted_query(self, deployment, hash)?;
+        if let Some(query) = &query {
+            self.persisted_query_cache
+                .lock()
+                .unwrap()
+                .insert(key, query.clone());
+        }
+        Ok(query)
+    }
+
     fn apply_metadata_operations(
         &self,
         operations: Vec<MetadataOperation>,
@@ -897,6 +1088,10 @@ impl StoreTrait for Store {
         let event =
             econn.transaction(|| self.apply_metadata_operations_with_conn(&econn, operations))?;
 
+        // Don't wait for the event to come back from Postgres before
+        // forgetting removed persisted queries
+        forget_persisted_queries(&self.persisted_query_cache, &event);
+
         // Send the event separately, because NOTIFY uses a global DB lock.
         econn.transaction(|| econn.send_store_event(&event))
     }
@@ -945,6 +1140,44 @@ impl StoreTrait for Store {
         })
     }
 
+    fn rewind_block_operations(
+        &self,
+        subgraph_id: SubgraphDeploymentId,
+        block_ptr_from: EthereumBlockPointer,
+        block_ptr_to: EthereumBlockPointer,
+    ) -> Result<(), StoreError> {
+        let econn = self.get_entity_conn(&subgraph_id)?;
+        let (event, metadata_event) = econn.transaction(|| -> Result<_, StoreError> {
+            // The deployment might have moved on since the caller looked at
+            // its block pointer; in that case, don't revert anything
+            let current_ptr = self.block_ptr_with_conn(subgraph_id.clone(), &econn)?;
+            if current_ptr != Some(block_ptr_from) {
+                return Err(StoreError::Unknown(format_err!(
+                    "subgraph {} is at block {:?} and not at block {:?}",
+                    subgraph_id,
+                    current_ptr,
+                    block_ptr_from
+                )));
+            }
+
+            let ops = SubgraphDeploymentEntity::update_ethereum_block_pointer_operations(
+                &subgraph_id,
+                block_ptr_to,
+            );
+            let metadata_event = self.apply_metadata_operations_with_conn(&econn, ops)?;
+
+            let (event, count) = econn.rewind(&block_ptr_to)?;
+            econn.update_entity_count(count)?;
+            Ok((event, metadata_event))
+        })?;
+
+        // Send the events separately, because NOTIFY uses a global DB lock.
+        econn.transaction(|| {
+            econn.send_store_event(&metadata_event)?;
+            econn.send_store_event(&event)
+        })
+    }
+
     fn subscribe(&self, entities: Vec<SubgraphEntityPair>) -> StoreEventStreamBox {
         let subscriptions = self.subscriptions.clone();
 
@@ -973,11 +1206,32 @@ impl StoreTrait for Store {
         &self,
         schema: &Schema,
         ops: Vec<MetadataOperation>,
+        graft: Option<&Graft>,
     ) -> Result<(), StoreError> {
+        let base = match graft {
+            Some(graft) => {
+                let conn = self.get_conn()?;
+                let block = BlockNumber::try_from(graft.block).map_err(|e| {
+                    StoreError::Unknown(format_err!("invalid graft block {}: {}", graft.block, e))
+                })?;
+                Some((self.storage(&conn, &graft.base)?, block))
+            }
+            None => None,
+        };
+
+        let mut ops = ops;
+        if let Some(graft) = graft {
+            ops.extend(self.graft_dynamic_data_sources(graft, &schema.id)?);
+        }
+
         let econn = self.get_entity_conn(&*SUBGRAPHS_ID)?;
         econn.transaction(|| -> Result<(), StoreError> {
             let event = self.apply_metadata_operations_with_conn(&econn, ops.clone())?;
-            econn.create_schema(schema)?;
+            econn.create_schema(
+                schema,
+                base.as_ref()
+                    .map(|(storage, block)| (storage.as_ref(), *block)),
+            )?;
             econn.send_store_event(&event)
         })
     }
@@ -1052,36 +1306,22 @@ impl ChainStore for Store {
         B: Stream<Item = EthereumBlock, Error = E> + Send + 'a,
         E: From<Error> + Send + 'a,
     {
-        use crate::db_schema::ethereum_blocks::dsl::*;
-
         let conn = self.conn.clone();
         let net_name = self.network_name.clone();
         Box::new(blocks.for_each(move |block| {
-            let json_blob = serde_json::to_value(&block).expect("Failed to serialize block");
-            let values = (
-                hash.eq(format!("{:x}", block.block.hash.unwrap())),
-                number.eq(block.block.number.unwrap().as_u64() as i64),
-                parent_hash.eq(format!("{:x}", block.block.parent_hash)),
-                network_name.eq(&net_name),
-                data.eq(json_blob),
-            );
-
-            // Insert blocks.
-            //
-            // If the table already contains a block with the same hash, then overwrite that block
-            // if it may be adding transaction receipts.
-            insert_into(ethereum_blocks)
-                .values(values.clone())
-                .on_conflict(hash)
-                .do_update()
-                .set(values)
-                .execute(&*conn.get().map_err(Error::from)?)
-                .map_err(Error::from)
+            upsert_full_block(&*conn.get().map_err(Error::from)?, &net_name, &block)
                 .map_err(E::from)
-                .map(|_| ())
         }))
     }
 
+    fn upsert_full_blocks(&self, blocks: Vec<EthereumBlock>) -> Result<(), Error> {
+        let conn = self.get_conn()?;
+        for block in blocks {
+            upsert_full_block(&*conn, &self.network_name, &block)?;
+        }
+        Ok(())
+    }
+
     fn upsert_light_blocks(&self, blocks: Vec<LightEthereumBlock>) -> Result<(), Error> {
         use crate::db_schema::ethereum_blocks::dsl::*;
 
@@ -1177,6 +1417,175 @@ impl ChainStore for Store {
             .collect()
     }
 
+    fn full_blocks(&self, hashes: Vec<H256>) -> Result<Vec<EthereumBlock>, Error> {
+        use crate::db_schema::ethereum_blocks::dsl::*;
+        use diesel::dsl::{any, sql};
+        use diesel::sql_types::Bool;
+
+        // Blocks that were stored without receipts have an empty list of
+        // receipts, while full blocks have one receipt per transaction
+        ethereum_blocks
+            .select(data)
+            .filter(network_name.eq(&self.network_name))
+            .filter(hash.eq(any(Vec::from_iter(
+                hashes.into_iter().map(|h| format!("{:x}", h)),
+            ))))
+            .filter(sql::<Bool>(
+                "jsonb_array_length(data -> 'transaction_receipts') \
+                 = jsonb_array_length(data -> 'block' -> 'transactions')",
+            ))
+            .load::<serde_json::Value>(&*self.get_conn()?)?
+            .into_iter()
+            .map(|block| serde_json::from_value(block).map_err(Into::into))
+            .collect()
+    }
+
+    fn block_calls(&self, hash: H256) -> Result<Option<Vec<EthereumCall>>, Error> {
+        use crate::db_schema::ethereum_block_calls::dsl::*;
+
+        ethereum_block_calls
+            .select(calls)
+            .filter(network_name.eq(&self.network_name))
+            .filter(block_hash.eq(format!("{:x}", hash)))
+            .first::<serde_json::Value>(&*self.get_conn()?)
+            .optional()?
+            .map(|value| serde_json::from_value(value).map_err(Into::into))
+            .transpose()
+    }
+
+    fn set_block_calls(
+        &self,
+        block_ptr: EthereumBlockPointer,
+        block_calls: &[EthereumCall],
+    ) -> Result<(), Error> {
+        use crate::db_schema::ethereum_block_calls::dsl::*;
+
+        let values = (
+            block_hash.eq(block_ptr.hash_hex()),
+            block_number.eq(block_ptr.number as i64),
+            network_name.eq(&self.network_name),
+            calls.eq(serde_json::to_value(block_calls)?),
+        );
+
+        // The calls in a block never change, so there is nothing to do if
+        // they are already stored
+        insert_into(ethereum_block_calls)
+            .values(values)
+            .on_conflict(block_hash)
+            .do_nothing()
+            .execute(&*self.get_conn()?)?;
+        Ok(())
+    }
+
+    fn transaction_receipts(
+        &self,
+        hash: H256,
+        transaction_hashes: Vec<H256>,
+    ) -> Result<Vec<TransactionReceipt>, Error> {
+        use crate::db_schema::ethereum_transaction_receipts::dsl::*;
+        use diesel::dsl::any;
+
+        ethereum_transaction_receipts
+            .select(receipt)
+            .filter(network_name.eq(&self.network_name))
+            .filter(block_hash.eq(format!("{:x}", hash)))
+            .filter(transaction_hash.eq(any(Vec::from_iter(
+                transaction_hashes.into_iter().map(|h| format!("{:x}", h)),
+            ))))
+            .load::<serde_json::Value>(&*self.get_conn()?)?
+            .into_iter()
+            .map(|value| serde_json::from_value(value).map_err(Into::into))
+            .collect()
+    }
+
+    fn upsert_transaction_receipts(&self, receipts: &[TransactionReceipt]) -> Result<(), Error> {
+        use crate::db_schema::ethereum_transaction_receipts::dsl::*;
+
+        let conn = self.get_conn()?;
+        for tx_receipt in receipts {
+            // Receipts without a block hash are for transactions that are
+            // not in a block anymore
+            let receipt_block_hash = match tx_receipt.block_hash {
+                Some(hash) => hash,
+                None => continue,
+            };
+            let values = (
+                block_hash.eq(format!("{:x}", receipt_block_hash)),
+                transaction_hash.eq(format!("{:x}", tx_receipt.transaction_hash)),
+                network_name.eq(&self.network_name),
+                receipt.eq(serde_json::to_value(tx_receipt)?),
+            );
+            insert_into(ethereum_transaction_receipts)
+                .values(values.clone())
+                .on_conflict((block_hash, transaction_hash))
+                .do_update()
+                .set(values)
+                .execute(&*conn)?;
+        }
+        Ok(())
+    }
+
+    fn range_logs(&self, filter: &str, from: u64, to: u64) -> Result<Option<Vec<Log>>, Error> {
+        let logs: Option<Vec<Log>> =
+            range_scan(&*self.get_conn()?, &self.network_name, filter, from, to)?;
+
+        // The stored scan may have covered more blocks than were asked for
+        Ok(logs.map(|logs| {
+            logs.into_iter()
+                .filter(|log| {
+                    log.block_number
+                        .map_or(false, |number| (from..=to).contains(&number.as_u64()))
+                })
+                .collect()
+        }))
+    }
+
+    fn set_range_logs(&self, filter: &str, from: u64, to: u64, logs: &[Log]) -> Result<(), Error> {
+        set_range_scan(
+            &*self.get_conn()?,
+            &self.network_name,
+            filter,
+            from,
+            to,
+            logs,
+        )
+    }
+
+    fn range_calls(
+        &self,
+        filter: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Option<Vec<EthereumCall>>, Error> {
+        let calls: Option<Vec<EthereumCall>> =
+            range_scan(&*self.get_conn()?, &self.network_name, filter, from, to)?;
+
+        // The stored scan may have covered more blocks than were asked for
+        Ok(calls.map(|calls| {
+            calls
+                .into_iter()
+                .filter(|call| (from..=to).contains(&call.block_number))
+                .collect()
+        }))
+    }
+
+    fn set_range_calls(
+        &self,
+        filter: &str,
+        from: u64,
+        to: u64,
+        calls: &[EthereumCall],
+    ) -> Result<(), Error> {
+        set_range_scan(
+            &*self.get_conn()?,
+            &self.network_name,
+            filter,
+            from,
+            to,
+            calls,
+        )
+    }
+
     fn ancestor_block(
         &self,
         block_ptr: EthereumBlockPointer,
@@ -1198,6 +1607,82 @@ impl ChainStore for Store {
     }
 }
 
+/// Insert `block` together with its transaction receipts, overwriting the
+/// block if it is already stored since it may be stored without receipts.
+fn upsert_full_block(
+    conn: &PgConnection,
+    net_name: &str,
+    block: &EthereumBlock,
+) -> Result<(), Error> {
+    use crate::db_schema::ethereum_blocks::dsl::*;
+
+    let json_blob = serde_json::to_value(block).expect("Failed to serialize block");
+    let values = (
+        hash.eq(format!("{:x}", block.block.hash.unwrap())),
+        number.eq(block.block.number.unwrap().as_u64() as i64),
+        parent_hash.eq(format!("{:x}", block.block.parent_hash)),
+        network_name.eq(net_name),
+        data.eq(json_blob),
+    );
+
+    insert_into(ethereum_blocks)
+        .values(values.clone())
+        .on_conflict(hash)
+        .do_update()
+        .set(values)
+        .execute(conn)
+        .map(|_| ())
+        .map_err(Error::from)
+}
+
+/// The results of a scan with `filter` of a range of blocks that includes
+/// all blocks from `from` to `to`.
+fn range_scan<T: DeserializeOwned>(
+    conn: &PgConnection,
+    net_name: &str,
+    filter: &str,
+    from: u64,
+    to: u64,
+) -> Result<Option<T>, Error> {
+    use crate::db_schema::ethereum_range_scans::dsl::*;
+
+    ethereum_range_scans
+        .select(results)
+        .filter(network_name.eq(net_name))
+        .filter(scan_filter.eq(filter))
+        .filter(start_block.le(from as i64))
+        .filter(end_block.ge(to as i64))
+        .first::<serde_json::Value>(conn)
+        .optional()?
+        .map(|value| serde_json::from_value(value).map_err(Into::into))
+        .transpose()
+}
+
+fn set_range_scan<T: Serialize + ?Sized>(
+    conn: &PgConnection,
+    net_name: &str,
+    filter: &str,
+    from: u64,
+    to: u64,
+    scan_results: &T,
+) -> Result<(), Error> {
+    use crate::db_schema::ethereum_range_scans::dsl::*;
+
+    // Scans of blocks that are final always find the same results
+    insert_into(ethereum_range_scans)
+        .values((
+            network_name.eq(net_name),
+            scan_filter.eq(filter),
+            start_block.eq(from as i64),
+            end_block.eq(to as i64),
+            results.eq(serde_json::to_value(scan_results)?),
+        ))
+        .on_conflict((network_name, scan_filter, start_block, end_block))
+        .do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
 impl EthereumCallCache for Store {
     fn get_call(
         &self,
@@ -9,7 +9,10 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 use graph::components::store::ChainStore;
-use graph::prelude::serde_json;
+use graph::prelude::web3::types::{Log, Transaction, TransactionReceipt, H256};
+use graph::prelude::{
+    serde_json, EthereumBlock, EthereumBlockPointer, EthereumCall, LightEthereumBlock,
+};
 use graph_store_postgres::db_schema_for_tests as db_schema;
 use graph_store_postgres::Store as DieselStore;
 
@@ -106,6 +109,15 @@ fn remove_test_data() {
     diesel::delete(db_schema::ethereum_blocks::table)
         .execute(&conn)
         .expect("Failed to delete ethereum_blocks");
+    diesel::delete(db_schema::ethereum_block_calls::table)
+        .execute(&conn)
+        .expect("Failed to delete ethereum_block_calls");
+    diesel::delete(db_schema::ethereum_transaction_receipts::table)
+        .execute(&conn)
+        .expect("Failed to delete ethereum_transaction_receipts");
+    diesel::delete(db_schema::ethereum_range_scans::table)
+        .execute(&conn)
+        .expect("Failed to delete ethereum_range_scans");
     diesel::delete(db_schema::ethereum_networks::table)
         .execute(&conn)
         .expect("Failed to delete ethereum_networks");
@@ -263,3 +275,291 @@ fn long_chain_with_uncles() {
     ];
     check_chain_head_update(chain, Some(&*BLOCK_FOUR), None);
 }
+
+/// A block with `transactions` transactions. Going through JSON avoids
+/// depending on every field of the block and its transactions
+fn ethereum_block(number: u64, hash: &str, transactions: usize) -> LightEthereumBlock {
+    let hash: H256 = hash.parse().unwrap();
+    let mut block = LightEthereumBlock::default();
+    block.number = Some(number.into());
+    block.hash = Some(hash);
+    block.transactions = (0..transactions)
+        .map(|index| {
+            serde_json::from_value(serde_json::json!({
+                "hash": format!("{:#x}", transaction_hash(number, index)),
+                "nonce": "0x0",
+                "blockHash": format!("{:#x}", hash),
+                "blockNumber": format!("{:#x}", number),
+                "transactionIndex": format!("{:#x}", index),
+                "from": format!("0x{}", "00".repeat(20)),
+                "to": null,
+                "value": "0x0",
+                "gasPrice": "0x0",
+                "gas": "0x0",
+                "input": "0x",
+            }))
+            .unwrap()
+        })
+        .collect();
+    block
+}
+
+fn transaction_hash(block_number: u64, index: usize) -> H256 {
+    H256::from_low_u64_be(block_number * 1000 + index as u64)
+}
+
+fn receipt(transaction: &Transaction) -> TransactionReceipt {
+    serde_json::from_value(serde_json::json!({
+        "transactionHash": transaction.hash,
+        "transactionIndex": transaction.transaction_index,
+        "blockHash": transaction.block_hash,
+        "blockNumber": transaction.block_number,
+        "cumulativeGasUsed": "0x0",
+        "gasUsed": "0x0",
+        "contractAddress": null,
+        "logs": [],
+        "status": "0x1",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+    }))
+    .unwrap()
+}
+
+fn full_block(block: LightEthereumBlock) -> EthereumBlock {
+    let transaction_receipts = block.transactions.iter().map(receipt).collect();
+    EthereumBlock {
+        block,
+        transaction_receipts,
+    }
+}
+
+fn call(block_number: u64, to: u8) -> EthereumCall {
+    serde_json::from_value(serde_json::json!({
+        "from": format!("0x{}", "00".repeat(20)),
+        "to": format!("0x{}", format!("{:02x}", to).repeat(20)),
+        "value": "0x0",
+        "gas_used": "0x5208",
+        "input": "0xa9059cbb",
+        "output": "0x",
+        "block_number": block_number,
+        "block_hash": format!("{:#x}", H256::from_low_u64_be(block_number)),
+        "transaction_hash": format!("{:#x}", transaction_hash(block_number, 0)),
+        "transaction_index": 0,
+    }))
+    .unwrap()
+}
+
+fn log(block_number: u64) -> Log {
+    serde_json::from_value(serde_json::json!({
+        "address": format!("0x{}", "01".repeat(20)),
+        "topics": [format!("{:#x}", H256::from_low_u64_be(42))],
+        "data": "0x",
+        "blockHash": format!("{:#x}", H256::from_low_u64_be(block_number)),
+        "blockNumber": format!("{:#x}", block_number),
+        "transactionHash": format!("{:#x}", transaction_hash(block_number, 0)),
+        "transactionIndex": "0x0",
+        "logIndex": "0x0",
+        "transactionLogIndex": "0x0",
+        "logType": null,
+        "removed": false,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn block_calls() {
+    run_test(
+        vec![&*GENESIS_BLOCK, &*BLOCK_ONE],
+        |store| -> Result<(), ()> {
+            let block_ptr =
+                EthereumBlockPointer::from((BLOCK_ONE.hash.parse::<H256>().unwrap(), 1u64));
+
+            assert_eq!(
+                None,
+                store
+                    .block_calls(block_ptr.hash)
+                    .expect("block_calls failed")
+            );
+
+            store
+                .set_block_calls(block_ptr, &[])
+                .expect("set_block_calls failed");
+            assert_eq!(
+                Some(vec![]),
+                store
+                    .block_calls(block_ptr.hash)
+                    .expect("block_calls failed")
+            );
+
+            // The calls of a block never change, and storing them again
+            // keeps the calls that are already stored
+            let block_two_ptr =
+                EthereumBlockPointer::from((BLOCK_TWO.hash.parse::<H256>().unwrap(), 2u64));
+            let calls = vec![call(2, 1), call(2, 2)];
+            store
+                .set_block_calls(block_two_ptr, &calls)
+                .expect("set_block_calls failed");
+            store
+                .set_block_calls(block_two_ptr, &calls[..1])
+                .expect("set_block_calls failed");
+            assert_eq!(
+                Some(calls),
+                store
+                    .block_calls(block_two_ptr.hash)
+                    .expect("block_calls failed")
+            );
+            Ok(())
+        },
+    )
+}
+
+#[test]
+fn full_blocks() {
+    run_test(vec![&*GENESIS_BLOCK], |store| -> Result<(), ()> {
+        let light = ethereum_block(1, &BLOCK_ONE.hash, 2);
+        let empty = ethereum_block(2, &BLOCK_TWO.hash, 0);
+        let full = ethereum_block(3, &BLOCK_THREE.hash, 3);
+        let hashes = vec![light.hash.unwrap(), empty.hash.unwrap(), full.hash.unwrap()];
+
+        store
+            .upsert_light_blocks(vec![light.clone(), empty.clone()])
+            .expect("upsert_light_blocks failed");
+        store
+            .upsert_full_blocks(vec![full_block(full.clone())])
+            .expect("upsert_full_blocks failed");
+
+        // A block stored without receipts is only full if it has no
+        // transactions
+        let mut found: Vec<_> = store
+            .full_blocks(hashes.clone())
+            .expect("full_blocks failed")
+            .into_iter()
+            .map(|block| (block.block.hash.unwrap(), block.transaction_receipts.len()))
+            .collect();
+        found.sort_by_key(|(_, receipts)| *receipts);
+        assert_eq!(
+            vec![(empty.hash.unwrap(), 0), (full.hash.unwrap(), 3)],
+            found
+        );
+
+        // Storing the light block again with its receipts makes it full,
+        // and storing it without receipts afterwards keeps the receipts
+        store
+            .upsert_full_blocks(vec![full_block(light.clone())])
+            .expect("upsert_full_blocks failed");
+        store
+            .upsert_light_blocks(vec![light.clone()])
+            .expect("upsert_light_blocks failed");
+        let blocks = store
+            .full_blocks(vec![light.hash.unwrap()])
+            .expect("full_blocks failed");
+        assert_eq!(1, blocks.len());
+        assert_eq!(
+            light
+                .transactions
+                .iter()
+                .map(|tx| tx.hash)
+                .collect::<Vec<_>>(),
+            blocks[0]
+                .transaction_receipts
+                .iter()
+                .map(|receipt| receipt.transaction_hash)
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    })
+}
+
+#[test]
+fn transaction_receipts() {
+    run_test(vec![&*GENESIS_BLOCK], |store| -> Result<(), ()> {
+        let block = ethereum_block(1, &BLOCK_ONE.hash, 3);
+        let block_hash = block.hash.unwrap();
+        let receipts: Vec<_> = block.transactions.iter().map(receipt).collect();
+        let tx_hashes: Vec<_> = block.transactions.iter().map(|tx| tx.hash).collect();
+
+        store
+            .upsert_transaction_receipts(&receipts[..2])
+            .expect("upsert_transaction_receipts failed");
+
+        let mut found: Vec<_> = store
+            .transaction_receipts(block_hash, tx_hashes.clone())
+            .expect("transaction_receipts failed")
+            .into_iter()
+            .map(|receipt| receipt.transaction_hash)
+            .collect();
+        found.sort();
+        let mut expected = tx_hashes[..2].to_vec();
+        expected.sort();
+        assert_eq!(expected, found);
+
+        // Receipts are only found for the block they were stored for
+        let other_block = BLOCK_ONE_SIBLING.hash.parse::<H256>().unwrap();
+        assert!(store
+            .transaction_receipts(other_block, tx_hashes)
+            .expect("transaction_receipts failed")
+            .is_empty());
+        Ok(())
+    })
+}
+
+#[test]
+fn range_scans() {
+    run_test(vec![&*GENESIS_BLOCK], |store| -> Result<(), ()> {
+        let filter = "eth_getLogs address=[0101] topic0=[2a]";
+        let logs: Vec<_> = [2, 5, 7, 10].iter().map(|number| log(*number)).collect();
+        let block_numbers = |logs: Vec<Log>| -> Vec<u64> {
+            logs.iter()
+                .map(|log| log.block_number.unwrap().as_u64())
+                .collect()
+        };
+
+        assert_eq!(
+            None,
+            store.range_logs(filter, 1, 10).expect("range_logs failed")
+        );
+        store
+            .set_range_logs(filter, 1, 10, &logs)
+            .expect("set_range_logs failed");
+
+        // Scans of a part of the stored range only return what they would
+        // have found
+        let found = store
+            .range_logs(filter, 3, 7)
+            .expect("range_logs failed")
+            .expect("range is cached");
+        assert_eq!(vec![5, 7], block_numbers(found));
+        let found = store
+            .range_logs(filter, 1, 10)
+            .expect("range_logs failed")
+            .expect("range is cached");
+        assert_eq!(vec![2, 5, 7, 10], block_numbers(found));
+
+        // Ranges that go beyond the stored scan, and other filters, are not
+        // in the store
+        assert_eq!(
+            None,
+            store.range_logs(filter, 5, 11).expect("range_logs failed")
+        );
+        assert_eq!(
+            None,
+            store
+                .range_logs("eth_getLogs address=[] topic0=[2a]", 3, 7)
+                .expect("range_logs failed")
+        );
+
+        let filter = "trace_filter toAddress=[0101]";
+        let calls = vec![call(4, 1), call(4, 2), call(8, 1)];
+        store
+            .set_range_calls(filter, 4, 8, &calls)
+            .expect("set_range_calls failed");
+        assert_eq!(
+            Some(calls[..2].to_vec()),
+            store.range_calls(filter, 4, 6).expect("range_calls failed")
+        );
+        assert_eq!(
+            Some(vec![]),
+            store.range_calls(filter, 5, 7).expect("range_calls failed")
+        );
+        Ok(())
+    })
+}
@@ -1,7 +1,9 @@
 //! Test mapping of GraphQL schema to a relational schema
 use diesel::connection::SimpleConnection as _;
+use diesel::dsl::sql;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use diesel::sql_types::Text;
 use futures::future::{self, IntoFuture};
 use hex_literal::hex;
 use lazy_static::lazy_static;
@@ -10,8 +12,9 @@ use std::str::FromStr;
 
 use graph::data::store::scalar::{BigDecimal, BigInt, Bytes};
 use graph::prelude::{
-    bigdecimal::One, web3::types::H256, Entity, EntityFilter, EntityKey, EntityOrder, EntityQuery,
-    EntityRange, Schema, SubgraphDeploymentId, Value, ValueType,
+    bigdecimal::One, web3::types::H256, Entity, EntityAggregate, EntityCursor, EntityFilter,
+    EntityKey, EntityOrder, EntityQuery, EntityRange, Schema, SubgraphDeploymentId, Value,
+    ValueType,
 };
 use graph_store_postgres::layout_for_tests::{Layout, BLOCK_NUMBER_MAX, STRING_PREFIX_SIZE};
 
@@ -58,7 +61,7 @@ const THINGS_GQL: &str = "
         name: String!
     }
 
-    type User @entity {
+    type User @entity @index(fields: [\"name\", \"age\"]) {
         id: ID!,
         name: String!,
         bin_name: Bytes!,
@@ -70,13 +73,24 @@ const THINGS_GQL: &str = "
         favorite_color: Color,
         drinks: [String!]
     }
+
+    type Book @entity
+        @fulltext(name: \"bookSearch\", language: \"english\", fields: [\"title\", \"summary\"]) {
+        id: ID!,
+        title: String!,
+        summary: String,
+        pages: Int!
+    }
 ";
 
 const SCHEMA_NAME: &str = "layout";
+const GRAFT_SCHEMA_NAME: &str = "layout_graft";
 
 lazy_static! {
     static ref THINGS_SUBGRAPH_ID: SubgraphDeploymentId =
         SubgraphDeploymentId::new("things").unwrap();
+    static ref GRAFT_SUBGRAPH_ID: SubgraphDeploymentId =
+        SubgraphDeploymentId::new("graftedThings").unwrap();
     static ref LARGE_INT: BigInt = BigInt::from(std::i64::MAX).pow(17);
     static ref LARGE_DECIMAL: BigDecimal =
         BigDecimal::one() / LARGE_INT.clone().to_big_decimal(BigInt::from(1));
@@ -120,9 +134,11 @@ lazy_static! {
 
 /// Removes test data from the database behind the store.
 fn remove_test_data(conn: &PgConnection) {
-    let query = format!("drop schema if exists {} cascade", SCHEMA_NAME);
-    conn.batch_execute(&query)
-        .expect("Failed to drop test schema");
+    for schema in &[SCHEMA_NAME, GRAFT_SCHEMA_NAME] {
+        let query = format!("drop schema if exists {} cascade", schema);
+        conn.batch_execute(&query)
+            .expect("Failed to drop test schema");
+    }
 }
 
 fn insert_entity(conn: &PgConnection, layout: &Layout, entity_type: &str, entity: Entity) {
@@ -236,6 +252,45 @@ fn insert_pets(conn: &PgConnection, layout: &Layout) {
     insert_pet(conn, layout, "Cat", "garfield", "Garfield");
 }
 
+fn insert_book(
+    conn: &PgConnection,
+    layout: &Layout,
+    id: &str,
+    title: &str,
+    summary: Option<&str>,
+    pages: i32,
+) {
+    let mut book = Entity::new();
+    book.set("id", id);
+    book.set("title", title);
+    book.set(
+        "summary",
+        summary.map(|s| Value::from(s)).unwrap_or(Value::Null),
+    );
+    book.set("pages", pages);
+    insert_entity(conn, layout, "Book", book);
+}
+
+fn insert_books(conn: &PgConnection, layout: &Layout) {
+    insert_book(
+        conn,
+        layout,
+        "1",
+        "Rust in Action",
+        Some("Systems programming with Rust"),
+        456,
+    );
+    insert_book(
+        conn,
+        layout,
+        "2",
+        "Programming Go",
+        Some("Practical programming in Go"),
+        312,
+    );
+    insert_book(conn, layout, "3", "Cooking at Home", None, 128);
+}
+
 fn insert_test_data(conn: &PgConnection) -> Layout {
     let schema = Schema::parse(THINGS_GQL, THINGS_SUBGRAPH_ID.clone()).unwrap();
 
@@ -429,8 +484,7 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
             vec!["Scalar".to_owned()],
             Some(filter),
             None,
-            None,
-            0,
+            EntityRange::default(),
             BLOCK_NUMBER_MAX,
         )
         .expect("Count query failed")
@@ -526,8 +580,7 @@ fn test_find(expected_entity_ids: Vec<&str>, query: EntityQuery) {
                 query.entity_types,
                 query.filter,
                 order,
-                query.range.first,
-                query.range.skip,
+                query.range,
                 BLOCK_NUMBER_MAX,
             )
             .expect("layout.query failed to execute query");
@@ -558,6 +611,7 @@ fn find_interface() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -570,6 +624,7 @@ fn find_interface() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -582,6 +637,7 @@ fn find_interface() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -595,6 +651,7 @@ fn find_interface() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -607,6 +664,7 @@ fn find_interface() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -625,6 +683,7 @@ fn find_string_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -646,6 +705,7 @@ fn find_list_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         }
     }
 
@@ -671,6 +731,7 @@ fn find_string_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -687,6 +748,7 @@ fn find_string_equal() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -705,6 +767,7 @@ fn find_string_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -723,6 +786,7 @@ fn find_string_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -741,6 +805,7 @@ fn find_string_less_than_order_by_asc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -759,6 +824,7 @@ fn find_string_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -779,11 +845,476 @@ fn find_string_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
 
+fn user_page(
+    order_by: &str,
+    value_type: ValueType,
+    order_direction: EntityOrder,
+    after: Option<(Value, &str)>,
+    before: Option<(Value, &str)>,
+) -> EntityQuery {
+    let cursor = |(value, id): (Value, &str)| EntityCursor {
+        value,
+        id: id.to_owned(),
+    };
+    EntityQuery {
+        subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+        entity_types: vec!["User".to_owned()],
+        filter: None,
+        order_by: Some((order_by.to_owned(), value_type)),
+        order_direction: Some(order_direction),
+        range: EntityRange {
+            first: Some(100),
+            skip: 0,
+            after: after.map(cursor),
+            before: before.map(cursor),
+            reverse: false,
+        },
+        block: BLOCK_NUMBER_MAX,
+    }
+}
+
+#[test]
+fn find_after_cursor_order_by_asc() {
+    test_find(
+        vec!["1", "3"],
+        user_page(
+            "name",
+            ValueType::String,
+            EntityOrder::Ascending,
+            Some(("Cindini".into(), "2")),
+            None,
+        ),
+    )
+}
+
+#[test]
+fn find_before_cursor_order_by_desc() {
+    test_find(
+        vec!["3", "1"],
+        user_page(
+            "name",
+            ValueType::String,
+            EntityOrder::Descending,
+            None,
+            Some(("Cindini".into(), "2")),
+        ),
+    )
+}
+
+#[test]
+fn find_between_cursors_order_by_id() {
+    test_find(
+        vec!["2"],
+        user_page(
+            "id",
+            ValueType::String,
+            EntityOrder::Ascending,
+            Some(("1".into(), "1")),
+            Some(("3".into(), "3")),
+        ),
+    )
+}
+
+#[test]
+fn find_cursor_with_nulls() {
+    // Enums are ordered by the order of their values in the schema; users
+    // ordered by favorite_color are 1 (yellow), 2 (red), 3 (null)
+    test_find(
+        vec!["2", "3"],
+        user_page(
+            "favorite_color",
+            ValueType::String,
+            EntityOrder::Ascending,
+            Some(("yellow".into(), "1")),
+            None,
+        ),
+    );
+    test_find(
+        vec!["1", "2"],
+        user_page(
+            "favorite_color",
+            ValueType::String,
+            EntityOrder::Ascending,
+            None,
+            Some((Value::Null, "3")),
+        ),
+    );
+    test_find(
+        vec!["2", "1"],
+        user_page(
+            "favorite_color",
+            ValueType::String,
+            EntityOrder::Descending,
+            Some((Value::Null, "3")),
+            None,
+        ),
+    )
+}
+
+#[test]
+fn find_reverse() {
+    // Reversing returns the end of the result first
+    let mut query = user_page(
+        "name",
+        ValueType::String,
+        EntityOrder::Ascending,
+        None,
+        None,
+    );
+    query.range.reverse = true;
+    query.range.first = Some(2);
+    test_find(vec!["3", "1"], query);
+
+    // Cursors still refer to the order of the query
+    let mut query = user_page(
+        "name",
+        ValueType::String,
+        EntityOrder::Ascending,
+        Some(("Cindini".into(), "2")),
+        None,
+    );
+    query.range.reverse = true;
+    query.range.first = Some(1);
+    test_find(vec!["3"], query);
+
+    // Nulls come first in descending order, and therefore last when that
+    // order is reversed
+    let mut query = user_page(
+        "favorite_color",
+        ValueType::String,
+        EntityOrder::Descending,
+        None,
+        None,
+    );
+    query.range.reverse = true;
+    test_find(vec!["1", "2", "3"], query);
+}
+
+/// Search books for `text`, best matches first
+fn search_books(
+    conn: &PgConnection,
+    layout: &Layout,
+    text: &str,
+    filter: Option<EntityFilter>,
+) -> Vec<String> {
+    let search = EntityFilter::Fulltext("bookSearch".to_owned(), text.into());
+    let filter = match filter {
+        Some(filter) => EntityFilter::And(vec![search, filter]),
+        None => search,
+    };
+    layout
+        .query(
+            conn,
+            vec!["Book".to_owned()],
+            Some(filter),
+            Some(("bookSearch".to_owned(), ValueType::String, "DESC")),
+            EntityRange::first(100),
+            BLOCK_NUMBER_MAX,
+        )
+        .expect("layout.query failed to execute fulltext search")
+        .into_iter()
+        .map(|entity| entity.id().unwrap())
+        .collect()
+}
+
+#[test]
+fn fulltext_search_ranks_matches() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_books(conn, layout);
+
+        // Book 2 mentions programming twice and therefore ranks higher
+        assert_eq!(
+            vec!["2", "1"],
+            search_books(conn, layout, "programming", None)
+        );
+        // Words are stemmed and all of them must match
+        assert_eq!(vec!["1"], search_books(conn, layout, "rust programs", None));
+        assert_eq!(vec!["3"], search_books(conn, layout, "cook", None));
+        assert!(search_books(conn, layout, "gardening", None).is_empty());
+
+        // The search combines with other filters
+        let filter = EntityFilter::GreaterThan("pages".to_owned(), Value::Int(400));
+        assert_eq!(
+            vec!["1"],
+            search_books(conn, layout, "programming", Some(filter))
+        );
+
+        // Searched entities are still ordinary entities
+        let book = layout
+            .find(conn, "Book", "3", BLOCK_NUMBER_MAX)
+            .expect("Failed to read Book[3]")
+            .unwrap();
+        assert_eq!(Some(&Value::from("Cooking at Home")), book.get("title"));
+        assert_eq!(None, book.get("bookSearch"));
+        Ok(())
+    });
+}
+
+#[test]
+fn fulltext_search_follows_updates() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_books(conn, layout);
+
+        let mut book = layout
+            .find(conn, "Book", "3", BLOCK_NUMBER_MAX)
+            .expect("Failed to read Book[3]")
+            .unwrap();
+        book.set("summary", "Programming your kitchen");
+        let key = EntityKey {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_type: "Book".to_owned(),
+            entity_id: "3".to_owned(),
+        };
+        layout
+            .update(conn, &key, &book, 1)
+            .expect("Failed to update Book[3]");
+
+        assert_eq!(
+            vec!["2", "1", "3"],
+            search_books(conn, layout, "programming", None)
+        );
+        assert_eq!(vec!["3"], search_books(conn, layout, "kitchen", None));
+        Ok(())
+    });
+}
+
+#[test]
+fn aggregate_users() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+
+        let aggregates = vec![
+            EntityAggregate::Count,
+            EntityAggregate::Sum("age".to_owned()),
+            EntityAggregate::Avg("age".to_owned()),
+            EntityAggregate::Min("age".to_owned()),
+            EntityAggregate::Max("age".to_owned()),
+            EntityAggregate::Sum("seconds_age".to_owned()),
+            EntityAggregate::Max("weight".to_owned()),
+        ];
+        let aggregate = |filter: Option<EntityFilter>| {
+            layout
+                .aggregate(
+                    conn,
+                    vec!["User".to_owned()],
+                    filter,
+                    &aggregates,
+                    BLOCK_NUMBER_MAX,
+                )
+                .expect("layout.aggregate failed to execute query")
+        };
+        let weight = |id: &str| {
+            layout
+                .find(conn, "User", id, BLOCK_NUMBER_MAX)
+                .expect("Failed to read User")
+                .unwrap()
+                .get("weight")
+                .cloned()
+                .unwrap()
+        };
+
+        // Users 1 and 3 do not drink coffee and are 67 and 28 years old
+        let filter = EntityFilter::Equal("coffee".to_owned(), Value::Bool(false));
+        assert_eq!(
+            vec![
+                Value::BigInt(BigInt::from(2)),
+                Value::BigInt(BigInt::from(95)),
+                Value::BigDecimal(BigDecimal::from_str("47.5").unwrap()),
+                Value::Int(28),
+                Value::Int(67),
+                Value::BigInt(BigInt::from(95) * 31557600.into()),
+                weight("1"),
+            ],
+            aggregate(Some(filter))
+        );
+
+        // Without matching users, everything but the count is null
+        let filter = EntityFilter::GreaterThan("age".to_owned(), Value::Int(100));
+        assert_eq!(
+            vec![
+                Value::BigInt(BigInt::from(0)),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            ],
+            aggregate(Some(filter))
+        );
+
+        assert_eq!(Value::BigInt(BigInt::from(3)), aggregate(None)[0]);
+
+        // Only numbers can be aggregated
+        let result = layout.aggregate(
+            conn,
+            vec!["User".to_owned()],
+            None,
+            &[EntityAggregate::Sum("name".to_owned())],
+            BLOCK_NUMBER_MAX,
+        );
+        assert!(result.is_err());
+        Ok(())
+    });
+}
+
+#[test]
+fn composite_index() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+
+        // The `@index` on `User` creates one index on both columns, and
+        // nothing else
+        let indexes = diesel::select(sql::<Text>(&format!(
+            "indexdef from pg_indexes where schemaname = '{}' and indexname like 'index\\_%'",
+            SCHEMA_NAME
+        )))
+        .load::<String>(conn)
+        .expect("Failed to list indexes");
+        assert_eq!(1, indexes.len());
+        let index = &indexes[0];
+        assert!(index.contains("\"user\" USING btree"), "{}", index);
+        assert!(index.contains("name, 256), age)"), "{}", index);
+
+        // Queries on the indexed columns work as before
+        let entities = layout
+            .query(
+                conn,
+                vec!["User".to_owned()],
+                Some(EntityFilter::And(vec![
+                    EntityFilter::Equal("name".to_owned(), "Cindini".into()),
+                    EntityFilter::Equal("age".to_owned(), Value::Int(43)),
+                ])),
+                None,
+                EntityRange::default(),
+                BLOCK_NUMBER_MAX,
+            )
+            .expect("Failed to query users");
+        assert_eq!(1, entities.len());
+        assert_eq!(Some(&Value::from("2")), entities[0].get("id"));
+
+        Ok(())
+    });
+}
+
+#[test]
+fn find_orders_ids_by_bytes() {
+    fn pet_ids(conn: &PgConnection, layout: &Layout, entity_types: Vec<&str>) -> Vec<String> {
+        layout
+            .query_in_byte_order(
+                conn,
+                entity_types.into_iter().map(str::to_owned).collect(),
+                None,
+                Some(("id".to_owned(), ValueType::ID, "ASC")),
+                EntityRange::first(100),
+                BLOCK_NUMBER_MAX,
+            )
+            .expect("Failed to query pets")
+            .into_iter()
+            .map(|entity| entity.id().unwrap())
+            .collect()
+    }
+
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_pet(conn, layout, "Cat", "garfield", "Garfield");
+        insert_pet(conn, layout, "Cat", "Tom", "Tom");
+        insert_pet(conn, layout, "Cat", "felix", "Felix");
+        insert_pet(conn, layout, "Dog", "Odie", "Odie");
+
+        // Uppercase letters sort before lowercase ones, whatever the
+        // locale of the database is
+        assert_eq!(
+            vec!["Tom", "felix", "garfield"],
+            pet_ids(conn, layout, vec!["Cat"])
+        );
+        assert_eq!(
+            vec!["Odie", "Tom", "felix", "garfield"],
+            pet_ids(conn, layout, vec!["Cat", "Dog"])
+        );
+
+        Ok(())
+    });
+}
+
+#[test]
+fn copy_from_graft_base() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+        insert_books(conn, layout);
+
+        let user_key = |id: &str| EntityKey {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_type: "User".to_owned(),
+            entity_id: id.to_owned(),
+        };
+        let find_user = |layout: &Layout, id: &str, block| {
+            layout
+                .find(conn, "User", id, block)
+                .expect("Failed to read User")
+                .map(|user| user.get("name").cloned().unwrap())
+        };
+
+        // Change the base before and after the graft block 5
+        layout
+            .delete(conn, &user_key("2"), 3)
+            .expect("Failed to delete User[2]");
+        let mut user = layout.find(conn, "User", "1", 0).unwrap().unwrap();
+        user.set("name", "Johnny");
+        layout
+            .update(conn, &user_key("1"), &user, 5)
+            .expect("Failed to update User[1]");
+        let mut user = layout.find(conn, "User", "3", 0).unwrap().unwrap();
+        user.set("name", "Shaq");
+        layout
+            .update(conn, &user_key("3"), &user, 7)
+            .expect("Failed to update User[3]");
+
+        let schema = Schema::parse(THINGS_GQL, GRAFT_SUBGRAPH_ID.clone()).unwrap();
+        conn.batch_execute(&format!("create schema {}", GRAFT_SCHEMA_NAME))
+            .unwrap();
+        let graft = Layout::create_relational_schema(
+            conn,
+            GRAFT_SCHEMA_NAME,
+            GRAFT_SUBGRAPH_ID.clone(),
+            &schema.document,
+        )
+        .expect("Failed to create relational schema for graft");
+        graft
+            .copy_from(conn, layout, 5)
+            .expect("Failed to copy entities from graft base");
+
+        // The graft has the state of the base at block 5 as its current
+        // state, and keeps the history before that
+        assert_eq!(
+            Some(Value::from("Johnny")),
+            find_user(&graft, "1", BLOCK_NUMBER_MAX)
+        );
+        assert_eq!(Some(Value::from("Johnton")), find_user(&graft, "1", 4));
+        assert_eq!(None, find_user(&graft, "2", BLOCK_NUMBER_MAX));
+        assert_eq!(Some(Value::from("Cindini")), find_user(&graft, "2", 2));
+        assert_eq!(
+            Some(Value::from("Shaqueeena")),
+            find_user(&graft, "3", BLOCK_NUMBER_MAX)
+        );
+
+        // Search tokens for fulltext searches are computed for the graft
+        assert_eq!(
+            vec!["2", "1"],
+            search_books(conn, &graft, "programming", None)
+        );
+        Ok(())
+    });
+}
+
 #[test]
 fn find_string_multiple_and() {
     test_find(
@@ -798,6 +1329,7 @@ fn find_string_multiple_and() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -816,6 +1348,7 @@ fn find_string_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -834,6 +1367,7 @@ fn find_string_not_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -852,6 +1386,7 @@ fn find_string_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -870,6 +1405,7 @@ fn find_string_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -888,6 +1424,7 @@ fn find_float_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -906,6 +1443,7 @@ fn find_float_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -924,6 +1462,7 @@ fn find_float_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -942,6 +1481,7 @@ fn find_float_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -960,6 +1500,7 @@ fn find_float_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -980,7 +1521,11 @@ fn find_float_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1002,6 +1547,7 @@ fn find_float_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1023,6 +1569,7 @@ fn find_float_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1041,6 +1588,7 @@ fn find_int_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1059,6 +1607,7 @@ fn find_int_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1077,6 +1626,7 @@ fn find_int_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1095,6 +1645,7 @@ fn find_int_greater_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1113,6 +1664,7 @@ fn find_int_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1131,6 +1683,7 @@ fn find_int_less_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1149,6 +1702,7 @@ fn find_int_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1169,7 +1723,11 @@ fn find_int_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1188,6 +1746,7 @@ fn find_int_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1206,6 +1765,7 @@ fn find_int_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1224,6 +1784,7 @@ fn find_bool_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1242,6 +1803,7 @@ fn find_bool_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1260,6 +1822,7 @@ fn find_bool_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1278,6 +1841,7 @@ fn find_bool_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1296,6 +1860,7 @@ fn find_bytes_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1314,6 +1879,7 @@ fn find_null_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1329,6 +1895,7 @@ fn find_null_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1347,6 +1914,7 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -1362,6 +1930,7 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1377,6 +1946,7 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1388,6 +1958,7 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1403,6 +1974,7 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1414,6 +1986,7 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1429,6 +2002,7 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1440,6 +2014,7 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1455,6 +2030,7 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1466,6 +2042,7 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1484,6 +2061,7 @@ fn find_where_nested_and_or() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1502,6 +2080,7 @@ fn find_enum_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1520,6 +2099,7 @@ fn find_enum_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1538,6 +2118,7 @@ fn find_enum_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1556,6 +2137,7 @@ fn find_enum_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1594,6 +2176,7 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         };
 
         let order = match query.order_by {
@@ -1616,8 +2199,7 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
                 query.entity_types,
                 query.filter,
                 order,
-                query.range.first,
-                query.range.skip,
+                query.range,
                 BLOCK_NUMBER_MAX,
             )
             .expect("layout.query failed to execute query");
@@ -1767,6 +2349,7 @@ fn find_empty_and_or() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 
@@ -1780,6 +2363,7 @@ fn find_empty_and_or() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -146,13 +146,15 @@ fn insert_test_data(store: Arc<DieselStore>) {
         schema: TEST_SUBGRAPH_SCHEMA.clone(),
         data_sources: vec![],
         templates: vec![],
+        graft: None,
+        confirmations: None,
     };
 
     // Create SubgraphDeploymentEntity
     let ops = SubgraphDeploymentEntity::new(&manifest, false, false, None, Some(*TEST_BLOCK_0_PTR))
         .create_operations(&*TEST_SUBGRAPH_ID);
     store
-        .create_subgraph_deployment(&TEST_SUBGRAPH_SCHEMA, ops)
+        .create_subgraph_deployment(&TEST_SUBGRAPH_SCHEMA, ops, None)
         .unwrap();
 
     let test_entity_1 = create_test_entity(
@@ -571,6 +573,7 @@ fn find_string_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -589,6 +592,7 @@ fn find_string_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -607,6 +611,7 @@ fn find_string_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -625,6 +630,7 @@ fn find_string_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -643,6 +649,7 @@ fn find_string_less_than_order_by_asc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -661,6 +668,7 @@ fn find_string_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -681,7 +689,11 @@ fn find_string_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -700,6 +712,7 @@ fn find_string_multiple_and() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -718,6 +731,7 @@ fn find_string_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -736,6 +750,7 @@ fn find_string_not_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -754,6 +769,7 @@ fn find_string_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -772,6 +788,7 @@ fn find_string_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -790,6 +807,7 @@ fn find_float_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -808,6 +826,7 @@ fn find_float_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -826,6 +845,7 @@ fn find_float_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -844,6 +864,7 @@ fn find_float_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -862,6 +883,7 @@ fn find_float_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -882,7 +904,11 @@ fn find_float_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -904,6 +930,7 @@ fn find_float_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -925,6 +952,7 @@ fn find_float_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -943,6 +971,7 @@ fn find_int_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -961,6 +990,7 @@ fn find_int_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -979,6 +1009,7 @@ fn find_int_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -997,6 +1028,7 @@ fn find_int_greater_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1015,6 +1047,7 @@ fn find_int_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1033,6 +1066,7 @@ fn find_int_less_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1051,6 +1085,7 @@ fn find_int_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1071,7 +1106,11 @@ fn find_int_less_than_range() {
             range: EntityRange {
                 first: Some(1),
                 skip: 1,
+                after: None,
+                before: None,
+                reverse: false,
             },
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1090,6 +1129,7 @@ fn find_int_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1108,6 +1148,7 @@ fn find_int_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1126,6 +1167,7 @@ fn find_bool_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1144,6 +1186,7 @@ fn find_bool_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1162,6 +1205,7 @@ fn find_bool_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1180,6 +1224,7 @@ fn find_bool_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1198,6 +1243,7 @@ fn find_bytes_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1216,6 +1262,7 @@ fn find_null_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1231,6 +1278,7 @@ fn find_null_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1249,6 +1297,7 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1264,6 +1313,7 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1275,6 +1325,7 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1290,6 +1341,7 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1301,6 +1353,7 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1316,6 +1369,7 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1327,6 +1381,7 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1342,6 +1397,7 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
     test_find(
@@ -1353,6 +1409,7 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     );
 }
@@ -1371,6 +1428,7 @@ fn find_where_nested_and_or() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         },
     )
 }
@@ -1496,6 +1554,7 @@ fn check_basic_revert(
         order_by: Some(("name".to_owned(), ValueType::String)),
         order_direction: Some(EntityOrder::Descending),
         range: EntityRange::first(100),
+        block: BLOCK_NUMBER_MAX,
     };
 
     let subscription = subscribe_and_consume(store.clone(), subgraph_id, entity_type);
@@ -1568,6 +1627,7 @@ fn revert_block_with_delete() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
         };
 
         // Delete entity with id=2
@@ -1741,6 +1801,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        context: None,
     }
 }
 
@@ -1881,6 +1942,185 @@ fn revert_block_with_dynamic_data_source_operations() {
     })
 }
 
+#[test]
+fn rewind_block_operations() {
+    run_test(|store| -> Result<(), ()> {
+        let user_key = |id: &str| EntityKey {
+            subgraph_id: TEST_SUBGRAPH_ID.clone(),
+            entity_type: USER.to_owned(),
+            entity_id: id.to_owned(),
+        };
+
+        // Rewinding from a block the subgraph is not at fails and changes
+        // nothing
+        store
+            .rewind_block_operations(TEST_SUBGRAPH_ID.clone(), *TEST_BLOCK_1_PTR, *GENESIS_PTR)
+            .expect_err("rewinding from the wrong block succeeded");
+        assert_eq!(
+            Some(*TEST_BLOCK_2_PTR),
+            store.block_ptr(TEST_SUBGRAPH_ID.clone()).unwrap()
+        );
+
+        // Revert blocks 2 and 1 at once
+        store
+            .rewind_block_operations(TEST_SUBGRAPH_ID.clone(), *TEST_BLOCK_2_PTR, *GENESIS_PTR)
+            .unwrap();
+        assert_eq!(
+            Some(*GENESIS_PTR),
+            store.block_ptr(TEST_SUBGRAPH_ID.clone()).unwrap()
+        );
+        assert!(store.get(user_key("1")).unwrap().is_some());
+        assert!(store.get(user_key("2")).unwrap().is_none());
+        assert!(store.get(user_key("3")).unwrap().is_none());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn graft_copies_dynamic_data_sources_up_to_graft_block() {
+    run_test(|store| -> Result<(), ()> {
+        // Add one dynamic data source before and one after the graft block
+        let data_source = mock_data_source("../../runtime/wasm/wasm_test/abort.wasm");
+        let mut ops = DynamicEthereumContractDataSourceEntity::from((
+            &TEST_SUBGRAPH_ID.clone(),
+            &data_source,
+            &TEST_BLOCK_3_PTR.clone(),
+        ))
+        .write_entity_operations("early-dynamic");
+        ops.extend(
+            DynamicEthereumContractDataSourceEntity::from((
+                &TEST_SUBGRAPH_ID.clone(),
+                &data_source,
+                &TEST_BLOCK_5_PTR.clone(),
+            ))
+            .write_entity_operations("late-dynamic"),
+        );
+        transact_entity_operations(&store, TEST_SUBGRAPH_ID.clone(), *TEST_BLOCK_4_PTR, ops)
+            .unwrap();
+
+        // Create a deployment grafted onto the test subgraph at block 4
+        let subgraph_id = SubgraphDeploymentId::new("GraftedTestSubgraph").unwrap();
+        let schema =
+            Schema::parse(USER_GQL, subgraph_id.clone()).expect("Failed to parse user schema");
+        let graft = Graft {
+            base: TEST_SUBGRAPH_ID.clone(),
+            block: 4,
+        };
+        let manifest = SubgraphManifest {
+            id: subgraph_id.clone(),
+            location: "/ipfs/test".to_owned(),
+            spec_version: "1".to_owned(),
+            description: None,
+            repository: None,
+            schema: schema.clone(),
+            data_sources: vec![],
+            templates: vec![],
+            graft: Some(graft.clone()),
+            confirmations: None,
+        };
+        let ops = SubgraphDeploymentEntity::new(&manifest, false, false, None, None)
+            .create_operations(&subgraph_id);
+        store
+            .create_subgraph_deployment(&schema, ops, Some(&graft))
+            .unwrap();
+
+        // Only the data source created before the graft block is copied,
+        // under a new id
+        let copied = store
+            .find(
+                DynamicEthereumContractDataSourceEntity::query().filter(EntityFilter::Equal(
+                    "deployment".to_owned(),
+                    subgraph_id.to_string().into(),
+                )),
+            )
+            .unwrap();
+        assert_eq!(1, copied.len());
+        let copied_id = copied[0].id().unwrap();
+        assert_ne!("early-dynamic", copied_id);
+        assert_eq!(
+            Some(&Value::from(3u64)),
+            copied[0].get("ethereumBlockNumber")
+        );
+        assert_eq!(
+            Some(&Value::from(format!("{}-source", copied_id))),
+            copied[0].get("source")
+        );
+
+        // The nested entities are copied along with it
+        for (entity_type, suffix) in &[
+            (EthereumContractSourceEntity::TYPENAME, "source"),
+            (EthereumContractMappingEntity::TYPENAME, "mapping"),
+        ] {
+            let key = EntityKey {
+                subgraph_id: SUBGRAPHS_ID.clone(),
+                entity_type: entity_type.to_string(),
+                entity_id: format!("{}-{}", copied_id, suffix),
+            };
+            store
+                .get(key)
+                .unwrap()
+                .expect("nested entity of dynamic data source wasn't copied");
+        }
+
+        // The base subgraph keeps its own data sources
+        let base = store
+            .find(
+                DynamicEthereumContractDataSourceEntity::query().filter(EntityFilter::Equal(
+                    "deployment".to_owned(),
+                    TEST_SUBGRAPH_ID.to_string().into(),
+                )),
+            )
+            .unwrap();
+        assert_eq!(2, base.len());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn dynamic_data_source_context_is_persisted() {
+    run_test(|store| -> Result<(), ()> {
+        let context = Entity::from(vec![
+            (
+                "pool",
+                Value::from("0xc0a47dfe034b400b47bdad5fecda2621de6c4d95"),
+            ),
+            ("fee", Value::from(BigInt::from(3000))),
+        ]);
+
+        let mut data_source = mock_data_source("../../runtime/wasm/wasm_test/abort.wasm");
+        data_source.context = Some(context.clone());
+        let dynamic_ds = DynamicEthereumContractDataSourceEntity::from((
+            &TEST_SUBGRAPH_ID.clone(),
+            &data_source,
+            &TEST_BLOCK_4_PTR.clone(),
+        ));
+        let ops = dynamic_ds.write_entity_operations("dynamic-data-source");
+        transact_entity_operations(&store, TEST_SUBGRAPH_ID.clone(), *TEST_BLOCK_3_PTR, ops)
+            .unwrap();
+
+        let dynamic_ds_key = EntityKey {
+            subgraph_id: SUBGRAPHS_ID.clone(),
+            entity_type: String::from(DynamicEthereumContractDataSourceEntity::TYPENAME),
+            entity_id: String::from("dynamic-data-source"),
+        };
+        let entity = store
+            .get(dynamic_ds_key)
+            .unwrap()
+            .expect("dynamic data source entity wasn't written to store");
+        let stored_context = match entity.get("context") {
+            Some(Value::String(json)) => {
+                serde_json::from_str::<Entity>(json).expect("invalid context JSON")
+            }
+            _ => panic!("dynamic data source context wasn't written to store"),
+        };
+        assert_eq!(context, stored_context);
+
+        Ok(())
+    })
+}
+
 #[test]
 fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
     run_test(|store| {
@@ -1896,6 +2136,8 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            graft: None,
+            confirmations: None,
         };
 
         // Create SubgraphDeploymentEntity
@@ -1907,7 +2149,9 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
             Some(*TEST_BLOCK_0_PTR),
         )
         .create_operations(&subgraph_id);
-        store.create_subgraph_deployment(&schema, ops).unwrap();
+        store
+            .create_subgraph_deployment(&schema, ops, None)
+            .unwrap();
 
         // Create store subscriptions
         let meta_subscription =
@@ -2280,3 +2524,62 @@ fn handle_large_string_with_index() {
         Ok(())
     })
 }
+
+#[test]
+fn persisted_queries_are_found_through_subgraph_versions() {
+    run_test(|store| -> Result<(), ()> {
+        const QUERY: &str = "{ users { name } }";
+        let hash = SubgraphPersistedQueryEntity::hash(QUERY);
+
+        // Nothing is persisted for the test subgraph yet
+        assert_eq!(
+            None,
+            store.persisted_query(&TEST_SUBGRAPH_ID, &hash).unwrap()
+        );
+
+        // Make the test deployment a version of a subgraph and persist the
+        // query for that subgraph
+        let mut ops = SubgraphEntity::new(
+            SubgraphName::new("test/subgraph").unwrap(),
+            Some("testVersion".to_owned()),
+            None,
+            0,
+        )
+        .write_operations("testSubgraph");
+        ops.extend(
+            SubgraphVersionEntity::new("testSubgraph".to_owned(), TEST_SUBGRAPH_ID.clone(), 0)
+                .write_operations("testVersion"),
+        );
+        ops.extend(
+            SubgraphPersistedQueryEntity::new("testSubgraph".to_owned(), QUERY.to_owned())
+                .write_operations(),
+        );
+        store.apply_metadata_operations(ops).unwrap();
+
+        assert_eq!(
+            Some(QUERY.to_owned()),
+            store.persisted_query(&TEST_SUBGRAPH_ID, &hash).unwrap()
+        );
+        let other_hash = SubgraphPersistedQueryEntity::hash("{ users { id } }");
+        assert_eq!(
+            None,
+            store
+                .persisted_query(&TEST_SUBGRAPH_ID, &other_hash)
+                .unwrap()
+        );
+
+        // Removing the query makes it unavailable
+        store
+            .apply_metadata_operations(SubgraphPersistedQueryEntity::remove_operations(
+                "testSubgraph",
+                &hash,
+            ))
+            .unwrap();
+        assert_eq!(
+            None,
+            store.persisted_query(&TEST_SUBGRAPH_ID, &hash).unwrap()
+        );
+
+        Ok(())
+    })
+}